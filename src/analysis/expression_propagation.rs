@@ -56,8 +56,8 @@ impl ExprProperties {
                     && self.can_propagate_over_expr(location)
                     && self.can_propagate_over_expr(value)
             }
-            While(..) | ForLoop(..) | If(..) | IfElse(..) | SwitchCase(..) | Seq(..) => unreachable!(),
-            Phi(..) | Nop | Break | ReturnVoid | Unreachable => true,
+            While(..) | ForLoop(..) | If(..) | IfElse(..) | SwitchCase(..) | Seq(..) | Replaced(..) => unreachable!(),
+            Phi(..) | Nop | Offset(..) | Break | ReturnVoid | Unreachable => true,
         }
     }
 }
@@ -376,8 +376,9 @@ fn count_var_occ(stmt: &Stmt, var: Var) -> u32 {
         | I64Store8(location, value)
         | I64Store16(location, value)
         | I64Store32(location, value) => count_var_occ_expr(location, var) + count_var_occ_expr(value, var),
-        While(..) | ForLoop(..) | If(..) | IfElse(..) | SwitchCase(..) | Seq(..) => unreachable!(),
+        While(..) | ForLoop(..) | If(..) | IfElse(..) | SwitchCase(..) | Seq(..) | Replaced(..) => unreachable!(),
         Nop => 0,
+        Offset(_) => 0,
         Break => 0,
         ReturnVoid => 0,
         Unreachable => 0,
@@ -570,6 +571,7 @@ fn replace_all(use_stmt: &mut Stmt, var: Var, def_expr: &Expr) {
             replace_all_expr(value, var, def_expr);
         }
         Nop => unreachable!(),
+        Offset(..) => unreachable!(),
         Phi(..) => unreachable!(),
         Unreachable => unreachable!(),
         ReturnVoid => unreachable!(),
@@ -580,6 +582,7 @@ fn replace_all(use_stmt: &mut Stmt, var: Var, def_expr: &Expr) {
         IfElse(..) => unreachable!(),
         SwitchCase(..) => unreachable!(),
         Seq(..) => unreachable!(),
+        Replaced(..) => unreachable!(),
     }
 }
 
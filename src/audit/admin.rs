@@ -0,0 +1,264 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+
+use super::keys::KeyTracker;
+use super::path::{self, PathVisitor};
+use super::{AuditContext, AuditRule, Finding, Location, Severity};
+use crate::soroban::host;
+use crate::ssa::{Cond, Expr, Stmt, Var};
+
+const MAX_COND_LEN: usize = 80;
+
+/// Finds the paths to a contract upgrade or a write of an admin-like storage key
+/// that are not guarded by `require_auth` on the admin read from storage.
+pub struct UnprotectedAdmin;
+
+impl AuditRule for UnprotectedAdmin {
+    fn id(&self) -> &'static str {
+        "unprotected-admin"
+    }
+
+    fn description(&self) -> &'static str {
+        "Contract upgrades and admin key writes not guarded by the stored admin"
+    }
+
+    fn check(&self, ctx: &AuditContext) -> Vec<Finding> {
+        let summaries = Summaries::new(ctx);
+        let mut findings = Vec::new();
+        for func_index in ctx.spec_functions() {
+            for site in summaries.get(func_index, 0).sites.into_iter().filter(|site| !site.guarded) {
+                let mut message = format!("{} without require_auth on the stored admin", site.operation);
+                if !site.via.is_empty() {
+                    message.push_str(&format!(" (via {})", site.via.join(" -> ")));
+                }
+                let evidence = site.conditions.iter().map(|cond| format!("path condition: {}", cond)).collect();
+                let finding = ctx.finding(self.id(), Severity::High, func_index, message);
+                findings.push(finding.with_location(site.location).with_evidence(evidence));
+            }
+        }
+        findings
+    }
+}
+
+/// Whether a storage key class names an admin or owner
+fn is_admin_key(class: &str) -> bool {
+    let class = class.to_lowercase();
+    class.contains("admin") || class.contains("owner")
+}
+
+#[derive(Debug, Clone)]
+struct Site {
+    operation: String,
+    guarded: bool,
+    /// Conditions of the branches taken on the way to the operation
+    conditions: Vec<String>,
+    /// Functions called on the way to the operation
+    via: Vec<String>,
+    /// The statement performing the operation
+    location: Location,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Summary {
+    sites: Vec<Site>,
+    authorizes: bool,
+    returns_admin: bool,
+}
+
+struct Summaries<'a> {
+    ctx: &'a AuditContext,
+    cache: RefCell<HashMap<(u32, u64), Summary>>,
+    active: RefCell<HashSet<u32>>,
+}
+
+impl<'a> Summaries<'a> {
+    fn new(ctx: &'a AuditContext) -> Self {
+        Summaries {
+            ctx,
+            cache: RefCell::new(HashMap::new()),
+            active: RefCell::new(HashSet::new()),
+        }
+    }
+
+    /// Summary of a function entered without admin authorization,
+    /// `admin_params` marks the parameters that hold the stored admin
+    fn get(&self, func_index: u32, admin_params: u64) -> Summary {
+        if let Some(summary) = self.cache.borrow().get(&(func_index, admin_params)) {
+            return summary.clone();
+        }
+        let code = match self.ctx.code(func_index) {
+            Some(code) => code,
+            None => return Summary::default(),
+        };
+        if !self.active.borrow_mut().insert(func_index) {
+            return Summary::default();
+        }
+
+        let state = AdminState {
+            keys: KeyTracker::default(),
+            admins: (0..64).filter(|i| admin_params & (1 << i) != 0).collect(),
+            guarded: false,
+            conditions: Vec::new(),
+        };
+        let mut visitor = AdminVisitor {
+            summaries: self,
+            func_index,
+            offset: None,
+            sites: Vec::new(),
+            returns_admin: false,
+        };
+        let exit = path::walk(&mut visitor, code, state);
+        let summary = Summary {
+            sites: visitor.sites,
+            authorizes: exit.map_or(true, |state| state.guarded),
+            returns_admin: visitor.returns_admin,
+        };
+
+        self.active.borrow_mut().remove(&func_index);
+        self.cache.borrow_mut().insert((func_index, admin_params), summary.clone());
+        summary
+    }
+}
+
+#[derive(Debug, Clone)]
+struct AdminState {
+    keys: KeyTracker,
+    /// Locals that hold the admin read from storage
+    admins: HashSet<u32>,
+    guarded: bool,
+    conditions: Vec<String>,
+}
+
+struct AdminVisitor<'s, 'a> {
+    summaries: &'s Summaries<'a>,
+    func_index: u32,
+    /// Offset of the statement being visited
+    offset: Option<u32>,
+    sites: Vec<Site>,
+    returns_admin: bool,
+}
+
+impl<'s, 'a> AdminVisitor<'s, 'a> {
+    fn is_admin(&self, expr: &Expr, state: &AdminState) -> bool {
+        match expr {
+            Expr::GetLocal(var) => state.admins.contains(&var.index),
+            Expr::Call(func_index, args) => match self.summaries.ctx.host_fn(*func_index) {
+                Some(host::GET_CONTRACT_DATA) => {
+                    let class = args.first().and_then(|key| state.keys.class_of(key));
+                    class.map_or(false, |class| is_admin_key(&class))
+                }
+                Some(_) => false,
+                None => {
+                    let mask = self.admin_mask(args, state);
+                    self.summaries.get(*func_index, mask).returns_admin
+                }
+            },
+            _ => false,
+        }
+    }
+
+    fn admin_mask(&self, args: &[Expr], state: &AdminState) -> u64 {
+        args.iter()
+            .take(64)
+            .enumerate()
+            .filter(|(_, arg)| self.is_admin(arg, state))
+            .fold(0, |mask, (i, _)| mask | (1 << i))
+    }
+
+    fn add_site(&mut self, operation: String, state: &AdminState) {
+        self.sites.push(Site {
+            operation,
+            guarded: state.guarded,
+            conditions: state.conditions.clone(),
+            via: Vec::new(),
+            location: self.summaries.ctx.statement_location(self.func_index, self.offset),
+        });
+    }
+}
+
+impl<'s, 'a> PathVisitor for AdminVisitor<'s, 'a> {
+    type State = AdminState;
+
+    fn join(&mut self, mut a: AdminState, b: AdminState) -> AdminState {
+        a.keys.join(&b.keys);
+        a.admins.retain(|var| b.admins.contains(var));
+        a.guarded &= b.guarded;
+        let common = a.conditions.iter().zip(&b.conditions).take_while(|(x, y)| x == y).count();
+        a.conditions.truncate(common);
+        a
+    }
+
+    fn visit_assign(&mut self, var: Var, expr: &Expr, state: &mut AdminState) {
+        state.keys.assign(var, expr);
+        if self.is_admin(expr, state) {
+            state.admins.insert(var.index);
+        } else {
+            state.admins.remove(&var.index);
+        }
+    }
+
+    fn visit_store(&mut self, stmt: &Stmt, state: &mut AdminState) {
+        state.keys.store(stmt);
+    }
+
+    fn visit_offset(&mut self, offset: u32, _state: &mut AdminState) {
+        self.offset = Some(offset);
+    }
+
+    fn visit_branch(&mut self, cond: &Cond, taken: bool, state: &mut AdminState) {
+        if *cond == Cond::True {
+            return;
+        }
+        let text = self.summaries.ctx.code_str(cond, self.func_index, MAX_COND_LEN);
+        state.conditions.push(if taken { text } else { format!("!({})", text) });
+    }
+
+    fn visit_call(&mut self, func_index: u32, args: &[Expr], state: &mut AdminState) {
+        let ctx = self.summaries.ctx;
+        match ctx.host_fn(func_index) {
+            Some(name) if host::is_require_auth(name) => {
+                if args.first().map_or(false, |addr| self.is_admin(addr, state)) {
+                    state.guarded = true;
+                }
+            }
+            Some(host::UPDATE_CONTRACT_WASM) => {
+                self.add_site(String::from("upgrades the contract wasm"), state);
+            }
+            Some(name) if host::is_storage_write(name) => {
+                if let Some(class) = args.first().and_then(|key| state.keys.class_of(key)) {
+                    if is_admin_key(&class) {
+                        self.add_site(format!("writes admin storage key `{}`", class), state);
+                    }
+                }
+            }
+            Some(_) => (),
+            None => {
+                let mask = self.admin_mask(args, state);
+                let summary = self.summaries.get(func_index, mask);
+                let callee = ctx.func(func_index).name().to_string();
+                for site in summary.sites {
+                    let mut conditions = state.conditions.clone();
+                    conditions.extend(site.conditions);
+                    let mut via = vec![callee.clone()];
+                    via.extend(site.via);
+                    self.sites.push(Site {
+                        operation: site.operation,
+                        guarded: state.guarded || site.guarded,
+                        conditions,
+                        via,
+                        location: site.location,
+                    });
+                }
+                if summary.authorizes {
+                    state.guarded = true;
+                }
+            }
+        }
+    }
+
+    fn visit_return(&mut self, value: Option<&Expr>, state: &mut AdminState) {
+        if value.map_or(false, |value| self.is_admin(value, state)) {
+            self.returns_admin = true;
+        }
+    }
+}
@@ -0,0 +1,518 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use super::keys::KeyTracker;
+use super::path::{self, PathVisitor};
+use super::{AuditContext, AuditRule, Finding, Location, Severity};
+use crate::soroban::host;
+use crate::ssa::cond::MappedExpr;
+use crate::ssa::{Cond, Expr, Stmt, Var};
+
+const AMOUNT_TYPES: [&str; 2] = ["i128", "u64"];
+const MAX_OP_LEN: usize = 60;
+
+/// Flags wrapping `I64Add`, `I64Sub` and `I64Mul` on amounts whose result reaches a storage write
+/// or the return value of an exported function without an overflow check.
+/// Amounts are the `i128` and `u64` parameters and values read from contract storage under a key
+/// that an amount is written to somewhere in the module.
+/// A branch counts as a check when it compares the result with an operand, like `a + b < a`,
+/// or when its other side traps on an overflow flag of the result and an operand, like the panic
+/// of checked arithmetic on `((r ^ a) & (r ^ b)) < 0` or on the carry `lo < a_lo` of an i128.
+pub struct UncheckedArithmetic;
+
+impl AuditRule for UncheckedArithmetic {
+    fn id(&self) -> &'static str {
+        "unchecked-arithmetic"
+    }
+
+    fn description(&self) -> &'static str {
+        "Wrapping arithmetic on amounts that is stored or returned without an overflow check"
+    }
+
+    fn check(&self, ctx: &AuditContext) -> Vec<Finding> {
+        let summaries = Summaries::new(ctx);
+        let mut findings = Vec::new();
+        for func_index in ctx.spec_functions() {
+            let amount_params = AMOUNT_TYPES
+                .iter()
+                .fold(0, |mask, type_str| mask | ctx.params_of_type(func_index, type_str));
+            let summary = summaries.get(func_index, amount_params);
+            let mut messages = summary.findings;
+            for op in summary.returns_pending {
+                if let Op::Arith(op) = op {
+                    let location = summary.op_locations[&op].clone();
+                    let message = format!("`{}` can overflow and is returned without an overflow check", op);
+                    messages.entry(message).or_insert(location);
+                }
+            }
+            for (message, location) in messages {
+                findings.push(ctx.finding(self.id(), Severity::Medium, func_index, message).with_location(location));
+            }
+        }
+        findings
+    }
+}
+
+/// An unchecked operation a value depends on
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Op {
+    /// The value of a parameter of the function, used to find parameters that reach a sink
+    Param(u32),
+    /// A wrapping operation on an amount
+    Arith(String),
+}
+
+#[derive(Debug, Clone, Default)]
+struct Summary {
+    /// Whether the function may return an amount
+    returns_amount: bool,
+    /// Unchecked operations the return value depends on
+    returns_pending: BTreeSet<Op>,
+    /// Parameters that reach a storage write without a check
+    stored_params: u64,
+    /// Where the operations of this function and its callees are computed
+    op_locations: HashMap<String, Location>,
+    findings: BTreeMap<String, Location>,
+}
+
+/// Function summaries keyed by function and amount parameters
+struct Summaries<'a> {
+    ctx: &'a AuditContext,
+    /// Classes of the storage keys that amounts are written to
+    amount_keys: HashSet<String>,
+    cache: RefCell<HashMap<(u32, u64), Summary>>,
+    active: RefCell<HashSet<u32>>,
+}
+
+impl<'a> Summaries<'a> {
+    fn new(ctx: &'a AuditContext) -> Self {
+        Summaries {
+            ctx,
+            amount_keys: amount_keys(ctx),
+            cache: RefCell::new(HashMap::new()),
+            active: RefCell::new(HashSet::new()),
+        }
+    }
+
+    fn get(&self, func_index: u32, amount_params: u64) -> Summary {
+        if let Some(summary) = self.cache.borrow().get(&(func_index, amount_params)) {
+            return summary.clone();
+        }
+        let code = match self.ctx.code(func_index) {
+            Some(code) => code,
+            None => return Summary::default(),
+        };
+        if !self.active.borrow_mut().insert(func_index) {
+            return Summary::default();
+        }
+
+        let param_count = self.ctx.func(func_index).param_count();
+        let state = ArithState {
+            amounts: (0..64).filter(|i| amount_params & (1 << i) != 0).collect(),
+            pending: (0..param_count)
+                .map(|i| (i, BTreeSet::from([Op::Param(i)])))
+                .collect(),
+            operands: HashMap::new(),
+            flags: HashMap::new(),
+            keys: KeyTracker::default(),
+        };
+        let mut visitor = ArithVisitor {
+            summaries: self,
+            func_index,
+            offset: None,
+            op_locations: RefCell::new(HashMap::new()),
+            summary: Summary::default(),
+        };
+        path::walk(&mut visitor, code, state);
+        let mut summary = visitor.summary;
+        summary.op_locations = visitor.op_locations.into_inner();
+
+        self.active.borrow_mut().remove(&func_index);
+        self.cache.borrow_mut().insert((func_index, amount_params), summary.clone());
+        summary
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ArithState {
+    /// Locals that hold amounts
+    amounts: HashSet<u32>,
+    /// Unchecked operations the value of a local depends on
+    pending: HashMap<u32, BTreeSet<Op>>,
+    /// Operands of the arithmetic a local was assigned, including those of nested operations
+    operands: HashMap<u32, Vec<Expr>>,
+    /// Locals holding an overflow flag, with the operations it checks
+    flags: HashMap<u32, BTreeSet<Op>>,
+    keys: KeyTracker,
+}
+
+struct ArithVisitor<'s, 'a> {
+    summaries: &'s Summaries<'a>,
+    func_index: u32,
+    /// Offset of the statement being visited
+    offset: Option<u32>,
+    op_locations: RefCell<HashMap<String, Location>>,
+    summary: Summary,
+}
+
+impl<'s, 'a> ArithVisitor<'s, 'a> {
+    fn is_amount(&self, expr: &Expr, state: &ArithState) -> bool {
+        match expr {
+            Expr::GetLocal(var) => state.amounts.contains(&var.index),
+            Expr::Call(func_index, args) => match self.summaries.ctx.host_fn(*func_index) {
+                Some(host::GET_CONTRACT_DATA) => args
+                    .first()
+                    .and_then(|key| state.keys.class_of(key))
+                    .map_or(false, |class| self.summaries.amount_keys.contains(&class)),
+                Some(name) if is_conversion(name) => args.iter().any(|arg| self.is_amount(arg, state)),
+                Some(_) => false,
+                None => {
+                    let mask = self.amount_mask(args, state);
+                    self.summaries.get(*func_index, mask).returns_amount
+                }
+            },
+            _ => expr.children().into_iter().any(|child| self.is_amount(child, state)),
+        }
+    }
+
+    fn amount_mask(&self, args: &[Expr], state: &ArithState) -> u64 {
+        args.iter()
+            .take(64)
+            .enumerate()
+            .filter(|(_, arg)| self.is_amount(arg, state))
+            .fold(0, |mask, (i, _)| mask | (1 << i))
+    }
+
+    /// The unchecked operations the value of `expr` depends on
+    fn pending_of(&self, expr: &Expr, state: &ArithState) -> BTreeSet<Op> {
+        let mut result = BTreeSet::new();
+        match expr {
+            Expr::GetLocal(var) => {
+                if let Some(ops) = state.pending.get(&var.index) {
+                    result.extend(ops.iter().cloned());
+                }
+            }
+            Expr::Call(func_index, args) if self.summaries.ctx.host_fn(*func_index).is_none() => {
+                let mask = self.amount_mask(args, state);
+                let summary = self.summaries.get(*func_index, mask);
+                for op in summary.returns_pending {
+                    if let Op::Arith(op) = &op {
+                        let location = summary.op_locations[op].clone();
+                        self.op_locations.borrow_mut().entry(op.clone()).or_insert(location);
+                    }
+                    match op {
+                        Op::Param(i) => {
+                            if let Some(arg) = args.get(i as usize) {
+                                result.extend(self.pending_of(arg, state));
+                            }
+                        }
+                        op => {
+                            result.insert(op);
+                        }
+                    }
+                }
+            }
+            Expr::I64Add(a, b) | Expr::I64Sub(a, b) | Expr::I64Mul(a, b) => {
+                result.extend(self.pending_of(a, state));
+                result.extend(self.pending_of(b, state));
+                if self.is_amount(a, state) || self.is_amount(b, state) {
+                    let op = self.summaries.ctx.code_str(expr, self.func_index, MAX_OP_LEN);
+                    let location = self.summaries.ctx.statement_location(self.func_index, self.offset);
+                    self.op_locations.borrow_mut().entry(op.clone()).or_insert(location);
+                    result.insert(Op::Arith(op));
+                }
+            }
+            _ => {
+                for child in expr.children() {
+                    result.extend(self.pending_of(child, state));
+                }
+            }
+        }
+        result
+    }
+
+    /// The operations checked by the overflow flags `expr` reads or computes, a flag being
+    /// a comparison that involves an arithmetic result together with one of its operands
+    fn flag_ops(&self, expr: &Expr, state: &ArithState) -> BTreeSet<Op> {
+        let mut result = BTreeSet::new();
+        for expr in subexprs(expr) {
+            if let Expr::GetLocal(var) = expr {
+                if let Some(ops) = state.flags.get(&var.index) {
+                    result.extend(ops.iter().cloned());
+                }
+            }
+            if let Some((a, b)) = path::cmp_operands(expr) {
+                result.extend(self.compared_ops(a, b, state));
+            }
+        }
+        result
+    }
+
+    /// The operations of the results that a comparison involves together with one of their operands
+    fn compared_ops(&self, a: &Expr, b: &Expr, state: &ArithState) -> BTreeSet<Op> {
+        let mut compared = subexprs(a);
+        compared.extend(subexprs(b));
+        let mut result = BTreeSet::new();
+        for expr in &compared {
+            let operands = match expr {
+                Expr::GetLocal(var) => state.operands.get(&var.index),
+                _ => None,
+            };
+            if operands.map_or(false, |operands| operands.iter().any(|op| compared.contains(&op))) {
+                result.extend(self.pending_of(expr, state));
+            }
+        }
+        result
+    }
+
+    /// Record the unchecked operations that reach a sink
+    fn sink(&mut self, ops: BTreeSet<Op>, sink: &str) {
+        for op in ops {
+            match op {
+                Op::Param(i) => self.summary.stored_params |= 1 << i.min(63),
+                Op::Arith(op) => {
+                    let location = self.op_locations.borrow()[&op].clone();
+                    let message = format!("`{}` can overflow and is {} without an overflow check", op, sink);
+                    self.summary.findings.entry(message).or_insert(location);
+                }
+            }
+        }
+    }
+}
+
+impl<'s, 'a> PathVisitor for ArithVisitor<'s, 'a> {
+    type State = ArithState;
+
+    fn join(&mut self, mut a: ArithState, b: ArithState) -> ArithState {
+        a.amounts.extend(b.amounts);
+        for (var, ops) in b.pending {
+            a.pending.entry(var).or_default().extend(ops);
+        }
+        a.operands.retain(|var, ops| b.operands.get(var) == Some(ops));
+        a.flags.retain(|var, ops| b.flags.get(var) == Some(ops));
+        a.keys.join(&b.keys);
+        a
+    }
+
+    fn visit_assign(&mut self, var: Var, expr: &Expr, state: &mut ArithState) {
+        if self.is_amount(expr, state) {
+            state.amounts.insert(var.index);
+        } else {
+            state.amounts.remove(&var.index);
+        }
+        let ops = self.pending_of(expr, state);
+        state.pending.insert(var.index, ops);
+        let flag = self.flag_ops(expr, state);
+        if flag.is_empty() {
+            state.flags.remove(&var.index);
+        } else {
+            state.flags.insert(var.index, flag);
+        }
+        if is_arith(expr) {
+            state.operands.insert(var.index, operands(expr));
+        } else {
+            state.operands.remove(&var.index);
+        }
+        state.keys.assign(var, expr);
+    }
+
+    fn visit_store(&mut self, stmt: &Stmt, state: &mut ArithState) {
+        state.keys.store(stmt);
+    }
+
+    fn visit_offset(&mut self, offset: u32, _state: &mut ArithState) {
+        self.offset = Some(offset);
+    }
+
+    /// Comparing a result with one of its operands checks it, like `a + b < a` for an unsigned add
+    fn visit_branch(&mut self, cond: &Cond, _taken: bool, state: &mut ArithState) {
+        let checked: Vec<&Expr> = comparisons(cond)
+            .into_iter()
+            .flat_map(|(a, b)| [(a, b), (b, a)])
+            .filter(|(result, operand)| match result {
+                Expr::GetLocal(var) => state.operands.get(&var.index).map_or(false, |ops| ops.contains(operand)),
+                _ => false,
+            })
+            .map(|(result, _)| result)
+            .collect();
+        let checked: BTreeSet<Op> = checked.into_iter().flat_map(|expr| self.pending_of(expr, state)).collect();
+        check(checked, state);
+    }
+
+    /// Passing a branch whose other side traps on an overflow flag checks the operations the flag
+    /// is computed from, including those of the low half of an i128 that reaches the high half
+    /// through the carry. A trap on any other condition, like `a + b == 0`, is no overflow check.
+    fn visit_guard(&mut self, cond: &Cond, _taken: bool, state: &mut ArithState) {
+        let mut checked = BTreeSet::new();
+        for expr in path::cond_exprs(cond) {
+            checked.extend(self.flag_ops(expr, state));
+        }
+        for (a, b) in comparisons(cond) {
+            checked.extend(self.compared_ops(a, b, state));
+        }
+        check(checked, state);
+    }
+
+    fn visit_call(&mut self, func_index: u32, args: &[Expr], state: &mut ArithState) {
+        let ctx = self.summaries.ctx;
+        match ctx.host_fn(func_index) {
+            Some(host::PUT_CONTRACT_DATA) => {
+                if let Some(value) = args.get(1) {
+                    let ops = self.pending_of(value, state);
+                    self.sink(ops, "stored");
+                }
+            }
+            Some(_) => (),
+            None => {
+                let mask = self.amount_mask(args, state);
+                let summary = self.summaries.get(func_index, mask);
+                for (message, location) in summary.findings {
+                    self.summary.findings.entry(message).or_insert(location);
+                }
+                let callee = ctx.func(func_index).name().to_string();
+                for (i, arg) in args.iter().enumerate().take(64) {
+                    if summary.stored_params & (1 << i) != 0 {
+                        let ops = self.pending_of(arg, state);
+                        self.sink(ops, &format!("stored by `{}`", callee));
+                    }
+                }
+            }
+        }
+    }
+
+    fn visit_return(&mut self, value: Option<&Expr>, state: &mut ArithState) {
+        if let Some(value) = value {
+            if self.is_amount(value, state) {
+                self.summary.returns_amount = true;
+            }
+            let ops = self.pending_of(value, state);
+            self.summary.returns_pending.extend(ops);
+        }
+    }
+}
+
+fn check(checked: BTreeSet<Op>, state: &mut ArithState) {
+    if checked.is_empty() {
+        return;
+    }
+    for ops in state.pending.values_mut() {
+        ops.retain(|op| !checked.contains(op));
+    }
+}
+
+/// The operands of the comparisons a condition is built from
+fn comparisons(cond: &Cond) -> Vec<(&Expr, &Expr)> {
+    match cond {
+        Cond::Not(cond) => comparisons(cond),
+        Cond::And(a, b) | Cond::Or(a, b) => {
+            let mut result = comparisons(a);
+            result.extend(comparisons(b));
+            result
+        }
+        Cond::Cmp(MappedExpr::Expr(a), _, MappedExpr::Expr(b)) => vec![(a.as_ref(), b.as_ref())],
+        Cond::Expr(MappedExpr::Expr(expr)) => path::cmp_operands(expr).into_iter().collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn is_arith(expr: &Expr) -> bool {
+    matches!(expr, Expr::I64Add(..) | Expr::I64Sub(..) | Expr::I64Mul(..))
+}
+
+/// The operands of an operation and of the operations nested in it,
+/// like `a_hi`, `b_hi` and the carry of `a_hi + b_hi + carry`
+fn operands(expr: &Expr) -> Vec<Expr> {
+    let mut result = Vec::new();
+    for child in expr.children() {
+        result.push(child.clone());
+        if is_arith(child) {
+            result.extend(operands(child));
+        }
+    }
+    result
+}
+
+/// An expression and all expressions nested in it
+fn subexprs(expr: &Expr) -> Vec<&Expr> {
+    let mut result = vec![expr];
+    for child in expr.children() {
+        result.extend(subexprs(child));
+    }
+    result
+}
+
+/// Host functions that convert amounts between `Val`s and their parts, like `obj_to_i128_lo64`
+fn is_conversion(name: &str) -> bool {
+    name.starts_with("obj_to_") || name.starts_with("obj_from_")
+}
+
+/// The classes of the storage keys that an amount parameter of a spec function is written to
+fn amount_keys(ctx: &AuditContext) -> HashSet<String> {
+    let mut visitor = AmountKeys {
+        ctx,
+        keys: HashSet::new(),
+    };
+    for func_index in ctx.spec_functions() {
+        if let Some(code) = ctx.code(func_index) {
+            let params = AMOUNT_TYPES
+                .iter()
+                .fold(0, |mask, type_str| mask | ctx.params_of_type(func_index, type_str));
+            let amounts = (0..64).filter(|i| params & (1 << i) != 0).collect();
+            path::walk(&mut visitor, code, (amounts, KeyTracker::default()));
+        }
+    }
+    visitor.keys
+}
+
+struct AmountKeys<'a> {
+    ctx: &'a AuditContext,
+    keys: HashSet<String>,
+}
+
+impl<'a> AmountKeys<'a> {
+    fn is_amount(&self, expr: &Expr, amounts: &HashSet<u32>) -> bool {
+        match expr {
+            Expr::GetLocal(var) => amounts.contains(&var.index),
+            Expr::Call(func_index, args) => match self.ctx.host_fn(*func_index) {
+                Some(name) if is_conversion(name) => args.iter().any(|arg| self.is_amount(arg, amounts)),
+                _ => false,
+            },
+            _ => expr.children().into_iter().any(|child| self.is_amount(child, amounts)),
+        }
+    }
+}
+
+impl<'a> PathVisitor for AmountKeys<'a> {
+    /// Locals computed from amount parameters and the storage keys built so far
+    type State = (HashSet<u32>, KeyTracker);
+
+    fn join(&mut self, (mut amounts, mut keys): Self::State, b: Self::State) -> Self::State {
+        amounts.extend(b.0);
+        keys.join(&b.1);
+        (amounts, keys)
+    }
+
+    fn visit_store(&mut self, stmt: &Stmt, state: &mut Self::State) {
+        state.1.store(stmt);
+    }
+
+    fn visit_assign(&mut self, var: Var, expr: &Expr, state: &mut Self::State) {
+        if self.is_amount(expr, &state.0) {
+            state.0.insert(var.index);
+        } else {
+            state.0.remove(&var.index);
+        }
+        state.1.assign(var, expr);
+    }
+
+    fn visit_call(&mut self, func_index: u32, args: &[Expr], state: &mut Self::State) {
+        if self.ctx.host_fn(func_index) != Some(host::PUT_CONTRACT_DATA) {
+            return;
+        }
+        if let (Some(key), Some(value)) = (args.first(), args.get(1)) {
+            if self.is_amount(value, &state.0) {
+                self.keys.extend(state.1.class_of(key));
+            }
+        }
+    }
+}
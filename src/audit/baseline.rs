@@ -0,0 +1,111 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+
+use serde::{Deserialize, Serialize};
+
+use super::{AuditContext, Finding};
+use crate::fmt::CodeWriter;
+use crate::soroban::normalize::{fnv1a, normalize_code, FNV_OFFSET};
+
+/// Suppresses every rule of a function in `Baseline::suppress`
+const ALL_RULES: &str = "*";
+
+/// Findings that were accepted before and rules that are suppressed per exported function.
+///
+/// Recorded findings are matched by fingerprint, so each recorded entry hides one finding
+/// with the same fingerprint.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Baseline {
+    #[serde(default)]
+    pub findings: Vec<BaselineEntry>,
+    /// Rule ids to suppress keyed by exported function name, `*` suppresses all rules
+    #[serde(default)]
+    pub suppress: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BaselineEntry {
+    pub fingerprint: String,
+    pub rule_id: String,
+    pub function: String,
+    /// Message of the finding when it was recorded, for reviewers of the baseline
+    pub message: String,
+}
+
+/// Hash of the decompiled code of a function that does not change when the contract is rebuilt.
+/// Indices of local functions and large constants like memory addresses are replaced,
+/// since they move when unrelated code changes.
+fn normalized_ir_hash(ctx: &AuditContext, func_index: u32) -> u64 {
+    let code = match ctx.code(func_index) {
+        Some(code) => code,
+        None => return 0,
+    };
+    let text = CodeWriter::formatter(ctx.wasm().clone(), func_index).string_func(code);
+    fnv1a(normalize_code(&text).as_bytes(), FNV_OFFSET)
+}
+
+/// Fingerprint of a finding made of the exported function name, the rule id
+/// and the normalized IR of the function the finding is located in.
+/// That function is a callee of the exported one when the offending statement is in the callee,
+/// so code moving around the call doesn't change the fingerprint. The offset within the function
+/// is left out since it moves with any change to the code section.
+pub fn fingerprint(ctx: &AuditContext, finding: &Finding) -> String {
+    let mut hash = fnv1a(finding.function.as_bytes(), FNV_OFFSET);
+    hash = fnv1a(&[0], hash);
+    hash = fnv1a(finding.rule_id.as_bytes(), hash);
+    hash = fnv1a(&normalized_ir_hash(ctx, finding.location.func_index).to_le_bytes(), hash);
+    format!("{:016x}", hash)
+}
+
+impl Baseline {
+    pub fn load(path: &str) -> Result<Baseline, Box<dyn Error>> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    pub fn write_to_file(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+
+    /// Replace the recorded findings, suppressions are kept
+    pub fn record(&mut self, ctx: &AuditContext, findings: &[Finding]) {
+        self.findings = findings
+            .iter()
+            .map(|finding| BaselineEntry {
+                fingerprint: fingerprint(ctx, finding),
+                rule_id: finding.rule_id.clone(),
+                function: finding.function.clone(),
+                message: finding.message.clone(),
+            })
+            .collect();
+    }
+
+    pub fn is_suppressed(&self, finding: &Finding) -> bool {
+        self.suppress
+            .get(&finding.function)
+            .map_or(false, |rules| rules.iter().any(|r| r == ALL_RULES || *r == finding.rule_id))
+    }
+
+    /// Keep only the findings that are neither suppressed nor recorded in the baseline
+    pub fn filter(&self, ctx: &AuditContext, findings: Vec<Finding>) -> Vec<Finding> {
+        let mut recorded: HashMap<&str, usize> = HashMap::new();
+        for entry in &self.findings {
+            *recorded.entry(&entry.fingerprint).or_default() += 1;
+        }
+        findings
+            .into_iter()
+            .filter(|finding| !self.is_suppressed(finding))
+            .filter(|finding| match recorded.get_mut(fingerprint(ctx, finding).as_str()) {
+                Some(count) if *count > 0 => {
+                    *count -= 1;
+                    false
+                }
+                _ => true,
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,78 @@
+use std::collections::{BTreeSet, HashSet};
+
+use parity_wasm::elements::Instruction;
+
+use crate::wasm_wrapper::wasm::{Instance, TableElement};
+
+/// Calls between the functions of a module, taken from the wasm instructions.
+/// Indirect calls are resolved to every table entry with a matching signature.
+#[derive(Debug, Clone, Default)]
+pub struct CallGraph {
+    callees: Vec<BTreeSet<u32>>,
+    callers: Vec<BTreeSet<u32>>,
+}
+
+impl CallGraph {
+    pub fn build(wasm: &Instance) -> Self {
+        let module = wasm.module();
+        let func_count = module.functions().len();
+        let mut graph = CallGraph {
+            callees: vec![BTreeSet::new(); func_count],
+            callers: vec![BTreeSet::new(); func_count],
+        };
+
+        let table_funcs: Vec<u32> = wasm
+            .tables()
+            .iter()
+            .flat_map(|table| table.elements.iter())
+            .filter_map(|element| match element {
+                TableElement::Func(index) => Some(*index),
+                TableElement::Null => None,
+            })
+            .collect();
+
+        for (caller, func) in module.functions().iter().enumerate() {
+            for instr in func.instructions() {
+                match instr {
+                    Instruction::Call(callee) => graph.add_edge(caller as u32, *callee),
+                    Instruction::CallIndirect(type_ref, _) => {
+                        for &callee in &table_funcs {
+                            if module.get_func(callee).map_or(false, |f| f.type_ref() == *type_ref) {
+                                graph.add_edge(caller as u32, callee);
+                            }
+                        }
+                    }
+                    _ => (),
+                }
+            }
+        }
+        graph
+    }
+
+    fn add_edge(&mut self, caller: u32, callee: u32) {
+        if (callee as usize) < self.callees.len() {
+            self.callees[caller as usize].insert(callee);
+            self.callers[callee as usize].insert(caller);
+        }
+    }
+
+    pub fn callees(&self, func_index: u32) -> impl Iterator<Item = u32> + '_ {
+        self.callees[func_index as usize].iter().copied()
+    }
+
+    pub fn callers(&self, func_index: u32) -> impl Iterator<Item = u32> + '_ {
+        self.callers[func_index as usize].iter().copied()
+    }
+
+    /// All functions reachable from `func_index`, including itself
+    pub fn reachable_from(&self, func_index: u32) -> HashSet<u32> {
+        let mut result = HashSet::new();
+        let mut todo = vec![func_index];
+        while let Some(func) = todo.pop() {
+            if result.insert(func) {
+                todo.extend(self.callees(func));
+            }
+        }
+        result
+    }
+}
@@ -0,0 +1,288 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use super::keys::KeyTracker;
+use super::path::{self, PathVisitor};
+use super::{AuditContext, AuditRule, Finding, Location, Severity};
+use crate::soroban::host;
+use crate::ssa::{Cond, Expr, Stmt, Var};
+
+/// Orders cross-contract calls against storage writes on every path and flags writes that
+/// happen after an external call, since the called contract sees and may act on the old state.
+/// Also flags calls whose target is an `Address` argument that was not validated, i.e. compared
+/// with `obj_cmp` to an address that is not an argument itself on a branch whose other side traps.
+pub struct ExternalCall;
+
+impl AuditRule for ExternalCall {
+    fn id(&self) -> &'static str {
+        "external-call"
+    }
+
+    fn description(&self) -> &'static str {
+        "Storage writes after cross-contract calls and calls to unvalidated addresses"
+    }
+
+    fn check(&self, ctx: &AuditContext) -> Vec<Finding> {
+        let summaries = Summaries::new(ctx);
+        let mut findings = Vec::new();
+        for func_index in ctx.spec_functions() {
+            let address_params = ctx.params_of_type(func_index, host::ADDRESS_TYPE);
+            for ((severity, message), location) in summaries.get(func_index, address_params).findings {
+                findings.push(ctx.finding(self.id(), severity, func_index, message).with_location(location));
+            }
+        }
+        findings
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Summary {
+    /// External calls made on some path
+    calls: BTreeSet<String>,
+    /// Storage entries written on some path, with the first write
+    writes: BTreeMap<String, Location>,
+    /// Unvalidated address parameters that are validated on every path
+    validates: u64,
+    findings: BTreeMap<(Severity, String), Location>,
+}
+
+/// Function summaries keyed by function and unvalidated address parameters
+struct Summaries<'a> {
+    ctx: &'a AuditContext,
+    cache: RefCell<HashMap<(u32, u64), Summary>>,
+    active: RefCell<HashSet<u32>>,
+}
+
+impl<'a> Summaries<'a> {
+    fn new(ctx: &'a AuditContext) -> Self {
+        Summaries {
+            ctx,
+            cache: RefCell::new(HashMap::new()),
+            active: RefCell::new(HashSet::new()),
+        }
+    }
+
+    fn get(&self, func_index: u32, address_params: u64) -> Summary {
+        if let Some(summary) = self.cache.borrow().get(&(func_index, address_params)) {
+            return summary.clone();
+        }
+        let code = match self.ctx.code(func_index) {
+            Some(code) => code,
+            None => return Summary::default(),
+        };
+        if !self.active.borrow_mut().insert(func_index) {
+            return Summary::default();
+        }
+
+        let state = CallState {
+            calls: BTreeSet::new(),
+            unvalidated: (0..64).filter(|i| address_params & (1 << i) != 0).collect(),
+            comparisons: HashMap::new(),
+            keys: KeyTracker::default(),
+        };
+        let mut visitor = CallVisitor {
+            summaries: self,
+            func_index,
+            offset: None,
+            summary: Summary::default(),
+        };
+        if let Some(exit) = path::walk(&mut visitor, code, state) {
+            visitor.summary.validates = (0..64)
+                .filter(|i| address_params & (1 << i) != 0 && !exit.unvalidated.contains(i))
+                .fold(0, |mask, i| mask | (1 << i));
+        }
+        let summary = visitor.summary;
+
+        self.active.borrow_mut().remove(&func_index);
+        self.cache.borrow_mut().insert((func_index, address_params), summary.clone());
+        summary
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CallState {
+    /// External calls made so far on this path
+    calls: BTreeSet<String>,
+    /// Locals that hold an address argument that was not validated
+    unvalidated: HashSet<u32>,
+    /// Locals that hold the result of `obj_cmp` on the two values
+    comparisons: HashMap<u32, [Expr; 2]>,
+    keys: KeyTracker,
+}
+
+struct CallVisitor<'s, 'a> {
+    summaries: &'s Summaries<'a>,
+    func_index: u32,
+    /// Offset of the statement being visited
+    offset: Option<u32>,
+    summary: Summary,
+}
+
+impl<'s, 'a> CallVisitor<'s, 'a> {
+    fn is_unvalidated(&self, expr: &Expr, state: &CallState) -> bool {
+        match expr {
+            Expr::GetLocal(var) => state.unvalidated.contains(&var.index),
+            Expr::Call(..) => false,
+            _ => expr.children().into_iter().any(|child| self.is_unvalidated(child, state)),
+        }
+    }
+
+    fn unvalidated_mask(&self, args: &[Expr], state: &CallState) -> u64 {
+        args.iter()
+            .take(64)
+            .enumerate()
+            .filter(|(_, arg)| self.is_unvalidated(arg, state))
+            .fold(0, |mask, (i, _)| mask | (1 << i))
+    }
+
+    /// Mark the addresses `expr` is built from as validated
+    fn validate(&self, expr: &Expr, state: &mut CallState) {
+        match expr {
+            Expr::GetLocal(var) => {
+                state.unvalidated.remove(&var.index);
+            }
+            Expr::Call(..) => (),
+            _ => {
+                for child in expr.children() {
+                    self.validate(child, state);
+                }
+            }
+        }
+    }
+
+    /// The operands of the `obj_cmp` calls an expression depends on
+    fn compared<'e>(&self, expr: &'e Expr, state: &'e CallState, result: &mut Vec<&'e [Expr]>) {
+        match expr {
+            Expr::GetLocal(var) => result.extend(state.comparisons.get(&var.index).map(|args| &args[..])),
+            Expr::Call(func_index, args) if self.summaries.ctx.host_fn(*func_index) == Some(host::OBJ_CMP) => {
+                result.push(args)
+            }
+            _ => {
+                for child in expr.children() {
+                    self.compared(child, state, result);
+                }
+            }
+        }
+    }
+
+    fn location(&self) -> Location {
+        self.summaries.ctx.statement_location(self.func_index, self.offset)
+    }
+
+    fn write(&mut self, entry: String, via: Option<&str>, location: Location, state: &CallState) {
+        if !state.calls.is_empty() {
+            let calls = state.calls.iter().cloned().collect::<Vec<_>>().join(", ");
+            let via = via.map_or(String::new(), |via| format!(" in `{}`", via));
+            let message = format!("writes storage entry `{}`{} after the external call to {}", entry, via, calls);
+            self.summary.findings.entry((Severity::Medium, message)).or_insert(location.clone());
+        }
+        self.summary.writes.entry(entry).or_insert(location);
+    }
+}
+
+impl<'s, 'a> PathVisitor for CallVisitor<'s, 'a> {
+    type State = CallState;
+
+    fn join(&mut self, mut a: CallState, b: CallState) -> CallState {
+        a.calls.extend(b.calls);
+        a.unvalidated.extend(b.unvalidated);
+        a.comparisons.retain(|var, args| b.comparisons.get(var) == Some(args));
+        a.keys.join(&b.keys);
+        a
+    }
+
+    fn visit_assign(&mut self, var: Var, expr: &Expr, state: &mut CallState) {
+        state.keys.assign(var, expr);
+        if self.is_unvalidated(expr, state) {
+            state.unvalidated.insert(var.index);
+        } else {
+            state.unvalidated.remove(&var.index);
+        }
+        match expr {
+            Expr::Call(func_index, args) if self.summaries.ctx.host_fn(*func_index) == Some(host::OBJ_CMP) => {
+                if let [a, b] = &args[..] {
+                    state.comparisons.insert(var.index, [a.clone(), b.clone()]);
+                }
+            }
+            _ => {
+                state.comparisons.remove(&var.index);
+            }
+        }
+    }
+
+    /// An address compared to a trusted one is validated once the path passes a check that traps otherwise
+    fn visit_guard(&mut self, cond: &Cond, _taken: bool, state: &mut CallState) {
+        let mut compared = Vec::new();
+        for expr in path::cond_exprs(cond) {
+            self.compared(expr, state, &mut compared);
+        }
+        let mut validated = Vec::new();
+        for args in compared {
+            if let [a, b] = args {
+                if !self.is_unvalidated(b, state) {
+                    validated.push(a.clone());
+                }
+                if !self.is_unvalidated(a, state) {
+                    validated.push(b.clone());
+                }
+            }
+        }
+        for expr in &validated {
+            self.validate(expr, state);
+        }
+    }
+
+    fn visit_store(&mut self, stmt: &Stmt, state: &mut CallState) {
+        state.keys.store(stmt);
+    }
+
+    fn visit_offset(&mut self, offset: u32, _state: &mut CallState) {
+        self.offset = Some(offset);
+    }
+
+    fn visit_call(&mut self, func_index: u32, args: &[Expr], state: &mut CallState) {
+        let ctx = self.summaries.ctx;
+        match ctx.host_fn(func_index) {
+            Some(name) if host::is_contract_call(name) => {
+                let call = match args.get(1).and_then(host::symbol) {
+                    Some(function) => format!("`{}`", function),
+                    None => String::from("an unknown function"),
+                };
+                if args.first().map_or(false, |contract| self.is_unvalidated(contract, state)) {
+                    let message = format!(
+                        "calls {} on a contract address taken from an unvalidated argument",
+                        call
+                    );
+                    let location = self.location();
+                    self.summary.findings.entry((Severity::High, message)).or_insert(location);
+                }
+                state.calls.insert(call.clone());
+                self.summary.calls.insert(call);
+            }
+            Some(name) if host::is_storage_write(name) => {
+                let entry = args.first().and_then(|key| state.keys.class_of(key));
+                let location = self.location();
+                self.write(entry.unwrap_or_else(|| String::from("unknown")), None, location, state);
+            }
+            Some(_) => (),
+            None => {
+                let mask = self.unvalidated_mask(args, state);
+                let summary = self.summaries.get(func_index, mask);
+                let callee = ctx.func(func_index).name().to_string();
+                for (finding, location) in summary.findings {
+                    self.summary.findings.entry(finding).or_insert(location);
+                }
+                for (entry, location) in summary.writes {
+                    self.write(entry, Some(&callee), location, state);
+                }
+                for (i, arg) in args.iter().enumerate().take(64) {
+                    if summary.validates & (1 << i) != 0 {
+                        self.validate(arg, state);
+                    }
+                }
+                state.calls.extend(summary.calls.iter().cloned());
+                self.summary.calls.extend(summary.calls);
+            }
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+
+use z3::ast::Ast;
+
+use crate::analysis::used_vars;
+use crate::ssa::smt::{self, Translator};
+use crate::ssa::{Cond, Expr, Var};
+use crate::wasm_wrapper::wasm_adapter::Module;
+
+/// Locals without a width hint from their use are assumed to hold `Val`s
+const DEFAULT_WIDTH: u32 = 64;
+
+/// Whether the path to a finding can be taken
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Feasibility {
+    /// The path can be taken with the given model of locals, loads and calls as a witness
+    Feasible(String),
+    Infeasible,
+    /// Z3 gave up
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Fact {
+    /// A local gets a new version with the value of the expression
+    Assign(u32, u32, Expr),
+    Branch(Cond, bool),
+}
+
+/// A fact with the versions of the locals and memory at the point it was recorded
+#[derive(Debug, Clone, PartialEq)]
+struct VersionedFact {
+    fact: Fact,
+    versions: HashMap<u32, u32>,
+    memory: u32,
+}
+
+/// The assignments and branch conditions along a path, to ask Z3 whether the path is feasible.
+///
+/// Every assignment gives the local a new version, so a condition on a local always refers
+/// to the value it had when the branch was taken. Stores and calls give memory a new version.
+/// Loop bodies are walked once, so the locals a loop assigns and memory are havocked when
+/// it is entered and the first iteration does not pin them to their values before the loop.
+#[derive(Debug, Clone, Default)]
+pub struct PathConstraints {
+    versions: HashMap<u32, u32>,
+    /// Last version handed out, shared by all locals so that versions never repeat
+    last_version: u32,
+    memory: u32,
+    facts: Vec<VersionedFact>,
+}
+
+impl PathConstraints {
+    fn record(&mut self, fact: Fact, vars: impl IntoIterator<Item = Var>) {
+        let versions = vars
+            .into_iter()
+            .filter_map(|var| Some((var.index, *self.versions.get(&var.index)?)))
+            .collect();
+        self.facts.push(VersionedFact {
+            fact,
+            versions,
+            memory: self.memory,
+        });
+    }
+
+    pub fn assign(&mut self, var: Var, expr: &Expr) {
+        self.last_version += 1;
+        let fact = Fact::Assign(var.index, self.last_version, expr.clone());
+        self.record(fact, used_vars::find(expr));
+        self.versions.insert(var.index, self.last_version);
+    }
+
+    pub fn branch(&mut self, cond: &Cond, taken: bool) {
+        if *cond == Cond::True {
+            return;
+        }
+        self.record(Fact::Branch(cond.clone(), taken), cond.find_vars());
+    }
+
+    /// The locals get new versions without a value, like after an unknown number of loop iterations
+    pub fn havoc(&mut self, locals: impl IntoIterator<Item = u32>) {
+        for index in locals {
+            self.last_version += 1;
+            self.versions.insert(index, self.last_version);
+        }
+    }
+
+    /// Memory or host state may have changed
+    pub fn clobber_memory(&mut self) {
+        self.memory += 1;
+    }
+
+    /// Keep the facts both paths share. Locals assigned differently get a new, unconstrained version.
+    pub fn join(&mut self, other: &PathConstraints) {
+        let common = self
+            .facts
+            .iter()
+            .zip(&other.facts)
+            .take_while(|(a, b)| a == b)
+            .count();
+        self.facts.truncate(common);
+        self.last_version = self.last_version.max(other.last_version);
+        let mut changed: Vec<u32> = self
+            .versions
+            .iter()
+            .filter(|(index, version)| other.versions.get(index) != Some(version))
+            .map(|(index, _)| *index)
+            .collect();
+        changed.extend(other.versions.keys().filter(|index| !self.versions.contains_key(index)));
+        for index in changed {
+            self.last_version += 1;
+            self.versions.insert(index, self.last_version);
+        }
+        if self.memory != other.memory {
+            self.memory = self.memory.max(other.memory) + 1;
+        }
+    }
+
+    /// Check the path with the `extra` condition at its end
+    pub fn check(&self, module: &Module, extra: Option<&Cond>) -> Feasibility {
+        // assignments alone only define new versions and always have a model
+        if extra.is_none() && !self.facts.iter().any(|fact| matches!(fact.fact, Fact::Branch(..))) {
+            return Feasibility::Feasible(String::new());
+        }
+        let ctx = z3::Context::new(&z3::Config::new());
+        let solver = z3::Solver::new(&ctx);
+        let mut translator = Translator::new(&ctx, module);
+        for fact in &self.facts {
+            translator.versions = fact.versions.clone();
+            translator.memory = fact.memory;
+            match &fact.fact {
+                Fact::Assign(index, version, expr) => {
+                    let width = smt::width_of(expr).unwrap_or(DEFAULT_WIDTH);
+                    let value = translator.bv(expr, width);
+                    translator.versions.insert(*index, *version);
+                    solver.assert(&translator.local(*index, width)._eq(&value));
+                }
+                Fact::Branch(cond, taken) => {
+                    let cond = translator.cond(cond);
+                    solver.assert(&if *taken { cond } else { cond.not() });
+                }
+            }
+        }
+        if let Some(extra) = extra {
+            translator.versions = self.versions.clone();
+            translator.memory = self.memory;
+            solver.assert(&translator.cond(extra));
+        }
+        match solver.check() {
+            z3::SatResult::Sat => {
+                let witness = solver.get_model().map(|model| model.to_string()).unwrap_or_default();
+                Feasibility::Feasible(witness.split_whitespace().collect::<Vec<_>>().join(" "))
+            }
+            z3::SatResult::Unsat => Feasibility::Infeasible,
+            z3::SatResult::Unknown => Feasibility::Unknown,
+        }
+    }
+
+    pub fn is_feasible(&self, module: &Module) -> bool {
+        self.check(module, None) != Feasibility::Infeasible
+    }
+}
@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+
+use crate::soroban::host;
+use crate::ssa::{Expr, Stmt, Var};
+
+/// Tracks the class of storage keys along a path.
+///
+/// The class of a key is the symbol it is built from, e.g. `Balance` for `DataKey::Balance(addr)`.
+/// Enum keys are built in linear memory before the host call, so symbols stored to memory are
+/// remembered by the local the address is based on, and a key built from that memory gets its class.
+/// Keys the symbol does not flow into have no class.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyTracker {
+    /// Locals holding a symbol or a value built from one
+    locals: HashMap<u32, String>,
+    /// Locals pointing to memory that a symbol was stored to
+    memory: HashMap<u32, String>,
+}
+
+impl KeyTracker {
+    /// Keep only what is known on both paths
+    pub fn join(&mut self, other: &KeyTracker) {
+        self.locals.retain(|var, class| other.locals.get(var) == Some(class));
+        self.memory.retain(|var, class| other.memory.get(var) == Some(class));
+    }
+
+    pub fn assign(&mut self, var: Var, expr: &Expr) {
+        match self.symbol_in(expr) {
+            Some(class) => {
+                self.locals.insert(var.index, class);
+            }
+            None => {
+                self.locals.remove(&var.index);
+            }
+        }
+        self.memory.remove(&var.index);
+    }
+
+    pub fn store(&mut self, stmt: &Stmt) {
+        if let Stmt::I64Store(location, value) = stmt {
+            if let (Some(base), Some(class)) = (base_local(location), self.symbol_in(value)) {
+                self.memory.insert(base, class);
+            }
+        }
+    }
+
+    /// The class of the key passed to a storage host function
+    pub fn class_of(&self, key: &Expr) -> Option<String> {
+        self.symbol_in(key)
+    }
+
+    fn symbol_in(&self, expr: &Expr) -> Option<String> {
+        if let Some(symbol) = host::symbol(expr) {
+            return Some(symbol);
+        }
+        if let Expr::GetLocal(var) = expr {
+            return self.locals.get(&var.index).cloned();
+        }
+        if let Expr::Call(_, args) = expr {
+            let memory = args.iter().filter_map(base_local).find_map(|base| self.memory.get(&base));
+            if let Some(class) = memory {
+                return Some(class.clone());
+            }
+        }
+        expr.children().into_iter().find_map(|child| self.symbol_in(child))
+    }
+}
+
+/// The local an address is computed from, e.g. `var_5` for `var_5 + 8`
+/// or the `U32Val` `(var_5 + 8) as u64 << 32 | 4` passed to host functions
+fn base_local(expr: &Expr) -> Option<u32> {
+    match expr {
+        Expr::GetLocal(var) => Some(var.index),
+        Expr::I64ExtendUI32(expr) | Expr::I32WrapI64(expr) => base_local(expr),
+        Expr::I32Add(a, b) | Expr::I64Shl(a, b) | Expr::I64Or(a, b) => match (a.as_ref(), b.as_ref()) {
+            (base, Expr::I32Const(_) | Expr::I64Const(_)) | (Expr::I32Const(_) | Expr::I64Const(_), base) => {
+                base_local(base)
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
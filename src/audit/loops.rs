@@ -0,0 +1,406 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use super::keys::KeyTracker;
+use super::path::{self, PathVisitor};
+use super::{AuditContext, AuditRule, Finding, Location, Severity};
+use crate::soroban::host;
+use crate::ssa::cond::MappedExpr;
+use crate::ssa::{Cond, Expr, Stmt, Var};
+
+const VEC_LEN: &str = "vec_len";
+const MAP_LEN: &str = "map_len";
+const COLLECTION_TYPES: [&str; 2] = ["soroban_sdk::Vec<", "soroban_sdk::Map<"];
+const MAX_COND_LEN: usize = 60;
+
+/// Flags loops whose exit condition depends on the length of a collection passed as an argument
+/// or read from storage and that have no constant upper bound, i.e. no exit taken on every iteration
+/// that compares an induction variable with a constant. Every iteration costs CPU budget,
+/// so a large enough collection makes the function fail.
+pub struct UnboundedLoop;
+
+impl AuditRule for UnboundedLoop {
+    fn id(&self) -> &'static str {
+        "unbounded-loop"
+    }
+
+    fn description(&self) -> &'static str {
+        "Loops over the length of argument or storage collections without a constant bound"
+    }
+
+    fn check(&self, ctx: &AuditContext) -> Vec<Finding> {
+        let summaries = Summaries::new(ctx);
+        let mut findings = Vec::new();
+        for func_index in ctx.spec_functions() {
+            let params = ctx.func(func_index).spec_fn().map_or(&[][..], |spec| spec.inputs());
+            let mut messages = BTreeMap::new();
+            for ((origin, cond), location) in summaries.get(func_index).loops {
+                let origin = match origin {
+                    Origin::Storage(class) => format!("storage entry `{}`", class),
+                    Origin::Param(i) => match params.get(i as usize) {
+                        Some(param) if is_collection_type(param.type_ident().type_str()) => {
+                            format!("argument `{}: {}`", param.name(), param.type_ident().type_str())
+                        }
+                        _ => continue,
+                    },
+                };
+                let message = format!(
+                    "loop `{}` has no constant upper bound and iterates over the length of {}",
+                    cond, origin
+                );
+                messages.entry(message).or_insert(location);
+            }
+            for (message, location) in messages {
+                findings.push(ctx.finding(self.id(), Severity::Medium, func_index, message).with_location(location));
+            }
+        }
+        findings
+    }
+}
+
+fn is_collection_type(type_str: &str) -> bool {
+    COLLECTION_TYPES.iter().any(|prefix| type_str.starts_with(prefix))
+}
+
+fn is_const(expr: &Expr) -> bool {
+    matches!(expr, Expr::I32Const(_) | Expr::I64Const(_))
+}
+
+fn is_mapped_const(expr: &MappedExpr) -> bool {
+    match expr {
+        MappedExpr::Const(_) => true,
+        MappedExpr::Expr(expr) => is_const(expr),
+        MappedExpr::Mapped(_) => false,
+    }
+}
+
+fn is_induction(expr: &Expr, inductions: &HashSet<u32>) -> bool {
+    match expr {
+        Expr::GetLocal(var) => inductions.contains(&var.index),
+        Expr::I32WrapI64(expr) | Expr::I64ExtendUI32(expr) | Expr::I64ExtendSI32(expr) => {
+            is_induction(expr, inductions)
+        }
+        _ => false,
+    }
+}
+
+fn is_mapped_induction(expr: &MappedExpr, inductions: &HashSet<u32>) -> bool {
+    match expr {
+        MappedExpr::Expr(expr) => is_induction(expr, inductions),
+        _ => false,
+    }
+}
+
+/// Whether `cond` stops the loop once an induction variable passes a constant.
+/// The loop is left when `cond` is `exit_when`, so a bound in one operand of an `and`
+/// suffices for the loop condition while both operands need one for the condition of a break.
+fn has_constant_bound(cond: &Cond, exit_when: bool, inductions: &HashSet<u32>) -> bool {
+    match cond {
+        Cond::Not(cond) => has_constant_bound(cond, !exit_when, inductions),
+        Cond::And(a, b) | Cond::Or(a, b) => {
+            let a = has_constant_bound(a, exit_when, inductions);
+            let b = has_constant_bound(b, exit_when, inductions);
+            if matches!(cond, Cond::And(..)) != exit_when {
+                a || b
+            } else {
+                a && b
+            }
+        }
+        Cond::Cmp(a, _, b) => {
+            (is_mapped_const(a) && is_mapped_induction(b, inductions))
+                || (is_mapped_const(b) && is_mapped_induction(a, inductions))
+        }
+        Cond::Expr(MappedExpr::Expr(expr)) => match path::cmp_operands(expr) {
+            Some((a, b)) => {
+                (is_const(a) && is_induction(b, inductions)) || (is_const(b) && is_induction(a, inductions))
+            }
+            None => false,
+        },
+        _ => false,
+    }
+}
+
+/// The locals stepped by a constant in the loop, like `i` in `i = i + 1`
+fn inductions(stmt: &Stmt) -> HashSet<u32> {
+    let mut result = HashSet::new();
+    match stmt {
+        Stmt::ForLoop(var, _, _, _, body) => {
+            result.insert(var.index);
+            add_inductions(body, &mut result);
+        }
+        Stmt::While(_, body, _) => add_inductions(body, &mut result),
+        _ => (),
+    }
+    result
+}
+
+fn add_inductions(code: &[Stmt], result: &mut HashSet<u32>) {
+    for stmt in code {
+        match stmt {
+            Stmt::SetLocal(var, Expr::I32Add(a, b) | Expr::I32Sub(a, b) | Expr::I64Add(a, b) | Expr::I64Sub(a, b)) => {
+                let is_var = |expr: &Expr| matches!(expr, Expr::GetLocal(v) if v.index == var.index);
+                let stepped = |a: &Expr, b: &Expr| is_var(a) && is_const(b);
+                if stepped(a, b) || stepped(b, a) {
+                    result.insert(var.index);
+                }
+            }
+            Stmt::Seq(code) | Stmt::If(_, code) => add_inductions(code, result),
+            Stmt::IfElse(_, a, b) => {
+                add_inductions(a, result);
+                add_inductions(b, result);
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Where a collection comes from
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Origin {
+    /// A parameter of the function the loop is in or one of its callers
+    Param(u32),
+    /// A storage entry of the given key class
+    Storage(String),
+}
+
+#[derive(Debug, Clone, Default)]
+struct Summary {
+    returns_collection: BTreeSet<Origin>,
+    returns_length: BTreeSet<Origin>,
+    /// Unbounded loops with the origin of their collection and their condition
+    loops: BTreeMap<(Origin, String), Location>,
+}
+
+struct Summaries<'a> {
+    ctx: &'a AuditContext,
+    cache: RefCell<HashMap<u32, Summary>>,
+    active: RefCell<HashSet<u32>>,
+}
+
+impl<'a> Summaries<'a> {
+    fn new(ctx: &'a AuditContext) -> Self {
+        Summaries {
+            ctx,
+            cache: RefCell::new(HashMap::new()),
+            active: RefCell::new(HashSet::new()),
+        }
+    }
+
+    fn get(&self, func_index: u32) -> Summary {
+        if let Some(summary) = self.cache.borrow().get(&func_index) {
+            return summary.clone();
+        }
+        let code = match self.ctx.code(func_index) {
+            Some(code) => code,
+            None => return Summary::default(),
+        };
+        if !self.active.borrow_mut().insert(func_index) {
+            return Summary::default();
+        }
+
+        let param_count = self.ctx.func(func_index).param_count();
+        let state = LoopState {
+            collections: (0..param_count)
+                .map(|i| (i, BTreeSet::from([Origin::Param(i)])))
+                .collect(),
+            lengths: HashMap::new(),
+            keys: KeyTracker::default(),
+        };
+        let mut visitor = LoopVisitor {
+            summaries: self,
+            func_index,
+            offset: None,
+            summary: Summary::default(),
+            exits: HashMap::new(),
+        };
+        path::walk(&mut visitor, code, state);
+        let summary = visitor.summary;
+
+        self.active.borrow_mut().remove(&func_index);
+        self.cache.borrow_mut().insert(func_index, summary.clone());
+        summary
+    }
+}
+
+#[derive(Debug, Clone)]
+struct LoopState {
+    /// Origins of the collections held by locals
+    collections: HashMap<u32, BTreeSet<Origin>>,
+    /// Origins of the collections whose length is held by locals
+    lengths: HashMap<u32, BTreeSet<Origin>>,
+    keys: KeyTracker,
+}
+
+struct LoopVisitor<'s, 'a> {
+    summaries: &'s Summaries<'a>,
+    func_index: u32,
+    /// Offset of the statement being visited
+    offset: Option<u32>,
+    summary: Summary,
+    /// Exit conditions of the loops without a constant bound with the loop, checked when a path
+    /// reaches them. They are keyed by their address in the code, so that a branch elsewhere
+    /// with the same condition is not taken for the exit.
+    exits: HashMap<*const Cond, Location>,
+}
+
+impl<'s, 'a> LoopVisitor<'s, 'a> {
+    /// Map the origins of a callee to the arguments of the call
+    fn map_origins(&self, origins: BTreeSet<Origin>, args: &[Expr], state: &LoopState) -> BTreeSet<Origin> {
+        let mut result = BTreeSet::new();
+        for origin in origins {
+            match origin {
+                Origin::Param(i) => {
+                    if let Some(arg) = args.get(i as usize) {
+                        result.extend(self.collection_of(arg, state));
+                    }
+                }
+                origin => {
+                    result.insert(origin);
+                }
+            }
+        }
+        result
+    }
+
+    fn collection_of(&self, expr: &Expr, state: &LoopState) -> BTreeSet<Origin> {
+        match expr {
+            Expr::GetLocal(var) => state.collections.get(&var.index).cloned().unwrap_or_default(),
+            Expr::Call(func_index, args) => match self.summaries.ctx.host_fn(*func_index) {
+                Some(host::GET_CONTRACT_DATA) => {
+                    let class = args.first().and_then(|key| state.keys.class_of(key));
+                    BTreeSet::from([Origin::Storage(class.unwrap_or_else(|| String::from("unknown")))])
+                }
+                Some(_) => BTreeSet::new(),
+                None => {
+                    let origins = self.summaries.get(*func_index).returns_collection;
+                    self.map_origins(origins, args, state)
+                }
+            },
+            _ => BTreeSet::new(),
+        }
+    }
+
+    /// Origins of the collections whose length `expr` is computed from
+    fn length_of(&self, expr: &Expr, state: &LoopState) -> BTreeSet<Origin> {
+        match expr {
+            Expr::GetLocal(var) => state.lengths.get(&var.index).cloned().unwrap_or_default(),
+            Expr::Call(func_index, args) => match self.summaries.ctx.host_fn(*func_index) {
+                Some(VEC_LEN | MAP_LEN) => args.first().map_or(BTreeSet::new(), |arg| self.collection_of(arg, state)),
+                Some(_) => BTreeSet::new(),
+                None => {
+                    let origins = self.summaries.get(*func_index).returns_length;
+                    self.map_origins(origins, args, state)
+                }
+            },
+            _ => expr
+                .children()
+                .into_iter()
+                .flat_map(|child| self.length_of(child, state))
+                .collect(),
+        }
+    }
+
+    fn check_cond(&mut self, cond: &Cond, location: Location, state: &LoopState) {
+        let origins: BTreeSet<Origin> = path::cond_exprs(cond)
+            .into_iter()
+            .flat_map(|expr| self.length_of(expr, state))
+            .collect();
+        if origins.is_empty() {
+            return;
+        }
+        let text = self.summaries.ctx.code_str(cond, self.func_index, MAX_COND_LEN);
+        for origin in origins {
+            self.summary.loops.entry((origin, text.clone())).or_insert(location.clone());
+        }
+    }
+}
+
+/// The conditions a loop is left on with the value they exit on: the loop condition
+/// and the conditions of `if` statements at the top of the body that end with a break
+fn exit_conds(stmt: &Stmt) -> Vec<(&Cond, bool)> {
+    let (cond, body) = match stmt {
+        Stmt::While(cond, body, _) => (cond, body),
+        Stmt::ForLoop(_, _, cond, _, body) => (cond, body),
+        _ => return Vec::new(),
+    };
+    let mut conds = vec![(cond, false)];
+    for stmt in body {
+        if let Stmt::If(cond, then_body) = stmt {
+            if matches!(then_body.last(), Some(Stmt::Break)) {
+                conds.push((cond, true));
+            }
+        }
+    }
+    conds
+}
+
+impl<'s, 'a> PathVisitor for LoopVisitor<'s, 'a> {
+    type State = LoopState;
+
+    fn join(&mut self, mut a: LoopState, b: LoopState) -> LoopState {
+        for (var, origins) in b.collections {
+            a.collections.entry(var).or_default().extend(origins);
+        }
+        for (var, origins) in b.lengths {
+            a.lengths.entry(var).or_default().extend(origins);
+        }
+        a.keys.join(&b.keys);
+        a
+    }
+
+    fn visit_assign(&mut self, var: Var, expr: &Expr, state: &mut LoopState) {
+        state.keys.assign(var, expr);
+        let collections = self.collection_of(expr, state);
+        let lengths = self.length_of(expr, state);
+        state.collections.insert(var.index, collections);
+        state.lengths.insert(var.index, lengths);
+    }
+
+    fn visit_store(&mut self, stmt: &Stmt, state: &mut LoopState) {
+        state.keys.store(stmt);
+    }
+
+    fn visit_offset(&mut self, offset: u32, _state: &mut LoopState) {
+        self.offset = Some(offset);
+    }
+
+    fn visit_call(&mut self, func_index: u32, args: &[Expr], state: &mut LoopState) {
+        if self.summaries.ctx.host_fn(func_index).is_some() {
+            return;
+        }
+        let loops = self.summaries.get(func_index).loops;
+        for ((origin, cond), location) in loops {
+            let args_origins = self.map_origins(BTreeSet::from([origin]), args, state);
+            for origin in args_origins {
+                self.summary.loops.entry((origin, cond.clone())).or_insert(location.clone());
+            }
+        }
+    }
+
+    /// A loop without a constant bound is checked at its exits, where the lengths computed
+    /// in the body are known
+    fn visit_loop(&mut self, stmt: &Stmt, _state: &mut LoopState) {
+        let inductions = inductions(stmt);
+        let conds = exit_conds(stmt);
+        if conds.iter().any(|(cond, exit_when)| has_constant_bound(cond, *exit_when, &inductions)) {
+            return;
+        }
+        let location = self.summaries.ctx.statement_location(self.func_index, self.offset);
+        self.exits.extend(conds.into_iter().map(|(cond, _)| (cond as *const Cond, location.clone())));
+    }
+
+    fn visit_branch(&mut self, cond: &Cond, _taken: bool, state: &mut LoopState) {
+        if let Some(location) = self.exits.get(&(cond as *const Cond)).cloned() {
+            self.check_cond(cond, location, state);
+        }
+    }
+
+    fn visit_return(&mut self, value: Option<&Expr>, state: &mut LoopState) {
+        if let Some(value) = value {
+            let collections = self.collection_of(value, state);
+            let lengths = self.length_of(value, state);
+            self.summary.returns_collection.extend(collections);
+            self.summary.returns_length.extend(lengths);
+        }
+    }
+}
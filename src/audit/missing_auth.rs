@@ -0,0 +1,329 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+
+use super::feasibility::{Feasibility, PathConstraints};
+use super::keys::KeyTracker;
+use super::path::{self, PathVisitor};
+use super::{AuditContext, AuditRule, Finding, Location, Severity};
+use crate::soroban::host;
+use crate::ssa::{Cond, Expr, Stmt, Var};
+
+const TRANSFER: &str = "transfer";
+
+/// Flags exported functions that write contract storage or transfer tokens on a path
+/// that did not call `require_auth` on an address before.
+/// Addresses are the `Address` parameters of the function and values read from contract storage
+/// under a key that an `Address` parameter is written to somewhere in the module,
+/// so that the usual `admin.require_auth()` on a stored admin counts as authorization.
+/// Paths that Z3 proves infeasible are skipped, feasible ones come with a model as witness.
+pub struct MissingAuth;
+
+impl AuditRule for MissingAuth {
+    fn id(&self) -> &'static str {
+        "missing-auth"
+    }
+
+    fn description(&self) -> &'static str {
+        "Storage writes and token transfers without `require_auth`"
+    }
+
+    fn check(&self, ctx: &AuditContext) -> Vec<Finding> {
+        let summaries = Summaries::new(ctx);
+        let mut findings = Vec::new();
+        for func_index in ctx.spec_functions() {
+            let address_params = ctx.params_of_type(func_index, host::ADDRESS_TYPE);
+            let summary = summaries.get(func_index, address_params);
+            if let Some(violation) = summary.violation {
+                let finding = ctx.finding(self.id(), Severity::High, func_index, violation.message);
+                findings.push(finding.with_location(violation.location).with_evidence(violation.witnesses));
+            }
+        }
+        findings
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Violation {
+    message: String,
+    /// The unauthorized effect, in the innermost function
+    location: Location,
+    /// Models of the paths to the effect, from the outermost function inwards
+    witnesses: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Summary {
+    /// The first unauthorized effect on a feasible path
+    violation: Option<Violation>,
+    /// Whether every path through the function calls `require_auth`
+    authorizes: bool,
+    /// Whether the function may return an address
+    returns_address: bool,
+}
+
+/// Function summaries for an unauthorized caller, keyed by function and address parameters
+struct Summaries<'a> {
+    ctx: &'a AuditContext,
+    /// Classes of the storage keys that addresses are written to
+    address_keys: HashSet<String>,
+    cache: RefCell<HashMap<(u32, u64), Summary>>,
+    active: RefCell<HashSet<u32>>,
+}
+
+impl<'a> Summaries<'a> {
+    fn new(ctx: &'a AuditContext) -> Self {
+        Summaries {
+            ctx,
+            address_keys: address_keys(ctx),
+            cache: RefCell::new(HashMap::new()),
+            active: RefCell::new(HashSet::new()),
+        }
+    }
+
+    fn get(&self, func_index: u32, address_params: u64) -> Summary {
+        if let Some(summary) = self.cache.borrow().get(&(func_index, address_params)) {
+            return summary.clone();
+        }
+        let code = match self.ctx.code(func_index) {
+            Some(code) => code,
+            None => return Summary::default(),
+        };
+        // recursive calls are assumed to have no effect
+        if !self.active.borrow_mut().insert(func_index) {
+            return Summary::default();
+        }
+
+        let state = AuthState {
+            authorized: false,
+            addresses: (0..64).filter(|i| address_params & (1 << i) != 0).collect(),
+            keys: KeyTracker::default(),
+            path: PathConstraints::default(),
+        };
+        let mut visitor = AuthVisitor {
+            summaries: self,
+            func_index,
+            offset: None,
+            violation: None,
+            returns_address: false,
+        };
+        let exit = path::walk(&mut visitor, code, state);
+        let summary = Summary {
+            violation: visitor.violation,
+            authorizes: exit.map_or(true, |state| state.authorized),
+            returns_address: visitor.returns_address,
+        };
+
+        self.active.borrow_mut().remove(&func_index);
+        self.cache.borrow_mut().insert((func_index, address_params), summary.clone());
+        summary
+    }
+}
+
+#[derive(Debug, Clone)]
+struct AuthState {
+    authorized: bool,
+    /// Locals that hold an address
+    addresses: HashSet<u32>,
+    keys: KeyTracker,
+    path: PathConstraints,
+}
+
+struct AuthVisitor<'s, 'a> {
+    summaries: &'s Summaries<'a>,
+    func_index: u32,
+    /// Offset of the statement being visited
+    offset: Option<u32>,
+    violation: Option<Violation>,
+    returns_address: bool,
+}
+
+impl<'s, 'a> AuthVisitor<'s, 'a> {
+    fn is_address(&self, expr: &Expr, state: &AuthState) -> bool {
+        match expr {
+            Expr::GetLocal(var) => state.addresses.contains(&var.index),
+            Expr::Call(func_index, args) => match self.summaries.ctx.host_fn(*func_index) {
+                Some(host::GET_CONTRACT_DATA) => args
+                    .first()
+                    .and_then(|key| state.keys.class_of(key))
+                    .map_or(false, |class| self.summaries.address_keys.contains(&class)),
+                Some(_) => false,
+                None => {
+                    let mask = self.address_mask(args, state);
+                    self.summaries.get(*func_index, mask).returns_address
+                }
+            },
+            _ => false,
+        }
+    }
+
+    fn address_mask(&self, args: &[Expr], state: &AuthState) -> u64 {
+        args.iter()
+            .take(64)
+            .enumerate()
+            .filter(|(_, arg)| self.is_address(arg, state))
+            .fold(0, |mask, (i, _)| mask | (1 << i))
+    }
+
+    /// Record the first violation whose path is not proven infeasible, `inner` is the violation of a callee
+    fn report(&mut self, message: String, inner: Option<Violation>, state: &AuthState) {
+        if self.violation.is_some() {
+            return;
+        }
+        let mut witnesses = match state.path.check(self.summaries.ctx.module(), None) {
+            Feasibility::Infeasible => return,
+            Feasibility::Feasible(model) if !model.is_empty() => vec![format!("witness: {}", model)],
+            Feasibility::Feasible(_) | Feasibility::Unknown => Vec::new(),
+        };
+        let location = match inner {
+            Some(inner) => {
+                witnesses.extend(inner.witnesses);
+                inner.location
+            }
+            None => self.summaries.ctx.statement_location(self.func_index, self.offset),
+        };
+        self.violation = Some(Violation {
+            message,
+            location,
+            witnesses,
+        });
+    }
+}
+
+impl<'s, 'a> PathVisitor for AuthVisitor<'s, 'a> {
+    type State = AuthState;
+
+    fn join(&mut self, mut a: AuthState, b: AuthState) -> AuthState {
+        a.authorized &= b.authorized;
+        a.addresses.extend(b.addresses);
+        a.keys.join(&b.keys);
+        a.path.join(&b.path);
+        a
+    }
+
+    fn visit_branch(&mut self, cond: &Cond, taken: bool, state: &mut AuthState) {
+        state.path.branch(cond, taken);
+    }
+
+    fn visit_store(&mut self, stmt: &Stmt, state: &mut AuthState) {
+        state.path.clobber_memory();
+        state.keys.store(stmt);
+    }
+
+    fn visit_assign(&mut self, var: Var, expr: &Expr, state: &mut AuthState) {
+        state.path.assign(var, expr);
+        if self.is_address(expr, state) {
+            state.addresses.insert(var.index);
+        } else {
+            state.addresses.remove(&var.index);
+        }
+        state.keys.assign(var, expr);
+    }
+
+    fn visit_call(&mut self, func_index: u32, args: &[Expr], state: &mut AuthState) {
+        // the result of the call is translated with the memory before the call
+        state.path.clobber_memory();
+        if state.authorized {
+            return;
+        }
+        let ctx = self.summaries.ctx;
+        match ctx.host_fn(func_index) {
+            Some(name) if host::is_require_auth(name) => {
+                if args.first().map_or(false, |addr| self.is_address(addr, state)) {
+                    state.authorized = true;
+                }
+            }
+            Some(name) if host::is_storage_write(name) => {
+                self.report(format!("calls {} without require_auth", name), None, state);
+            }
+            Some(name) if host::is_contract_call(name) => {
+                if args.get(1).and_then(host::symbol).as_deref() == Some(TRANSFER) {
+                    let message = format!("transfers tokens through {} without require_auth", name);
+                    self.report(message, None, state);
+                }
+            }
+            Some(_) => (),
+            None => {
+                let mask = self.address_mask(args, state);
+                let summary = self.summaries.get(func_index, mask);
+                if let Some(violation) = summary.violation {
+                    let message = format!("calls {} which {}", ctx.func(func_index).name(), violation.message);
+                    self.report(message, Some(violation), state);
+                }
+                if summary.authorizes {
+                    state.authorized = true;
+                }
+            }
+        }
+    }
+
+    /// The body is walked once, so what it changes is unknown when it is entered
+    fn visit_loop(&mut self, stmt: &Stmt, state: &mut AuthState) {
+        state.path.havoc(path::loop_carried(stmt));
+        state.path.clobber_memory();
+    }
+
+    fn visit_offset(&mut self, offset: u32, _state: &mut AuthState) {
+        self.offset = Some(offset);
+    }
+
+    fn visit_return(&mut self, value: Option<&Expr>, state: &mut AuthState) {
+        if value.map_or(false, |value| self.is_address(value, state)) {
+            self.returns_address = true;
+        }
+    }
+}
+
+/// The classes of the storage keys that an `Address` parameter of a spec function is written to
+fn address_keys(ctx: &AuditContext) -> HashSet<String> {
+    let mut visitor = AddressKeys {
+        ctx,
+        keys: HashSet::new(),
+    };
+    for func_index in ctx.spec_functions() {
+        if let Some(code) = ctx.code(func_index) {
+            let params = ctx.params_of_type(func_index, host::ADDRESS_TYPE);
+            let addresses = (0..64).filter(|i| params & (1 << i) != 0).collect();
+            path::walk(&mut visitor, code, (addresses, KeyTracker::default()));
+        }
+    }
+    visitor.keys
+}
+
+struct AddressKeys<'a> {
+    ctx: &'a AuditContext,
+    keys: HashSet<String>,
+}
+
+impl<'a> PathVisitor for AddressKeys<'a> {
+    /// Locals that hold an address parameter and the storage keys built so far
+    type State = (HashSet<u32>, KeyTracker);
+
+    fn join(&mut self, (mut addresses, mut keys): Self::State, b: Self::State) -> Self::State {
+        addresses.extend(b.0);
+        keys.join(&b.1);
+        (addresses, keys)
+    }
+
+    fn visit_store(&mut self, stmt: &Stmt, state: &mut Self::State) {
+        state.1.store(stmt);
+    }
+
+    fn visit_assign(&mut self, var: Var, expr: &Expr, state: &mut Self::State) {
+        match expr {
+            Expr::GetLocal(source) if state.0.contains(&source.index) => state.0.insert(var.index),
+            _ => state.0.remove(&var.index),
+        };
+        state.1.assign(var, expr);
+    }
+
+    fn visit_call(&mut self, func_index: u32, args: &[Expr], state: &mut Self::State) {
+        if self.ctx.host_fn(func_index) != Some(host::PUT_CONTRACT_DATA) {
+            return;
+        }
+        if let (Some(key), Some(Expr::GetLocal(value))) = (args.first(), args.get(1)) {
+            if state.0.contains(&value.index) {
+                self.keys.extend(state.1.class_of(key));
+            }
+        }
+    }
+}
@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::fmt::{CodeDisplay, WriterOptions};
+use crate::soroban::host;
+use crate::ssa::Stmt;
+use crate::wasm_wrapper::wasm::Instance;
+use crate::soroban::FunctionInfo;
+use crate::wasm_wrapper::wasm_adapter::{Function, Module};
+
+pub mod baseline;
+pub mod call_graph;
+pub mod feasibility;
+pub mod keys;
+pub mod path;
+pub mod registry;
+pub mod report;
+
+mod admin;
+mod arith;
+mod external_call;
+mod loops;
+mod missing_auth;
+mod ttl;
+
+pub use call_graph::CallGraph;
+pub use registry::{RuleFilter, RuleRegistry};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    #[default]
+    Info,
+    Low,
+    Medium,
+    High,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Severity::Info => "info",
+            Severity::Low => "low",
+            Severity::Medium => "medium",
+            Severity::High => "high",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for Severity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "info" => Ok(Severity::Info),
+            "low" => Ok(Severity::Low),
+            "medium" => Ok(Severity::Medium),
+            "high" => Ok(Severity::High),
+            _ => Err(format!("Unknown severity `{}`", s)),
+        }
+    }
+}
+
+/// Where in the module a finding points to
+#[derive(Debug, Clone, Serialize)]
+pub struct Location {
+    /// Name of the function containing the problem
+    pub function: String,
+    pub func_index: u32,
+    /// Byte offset relative to the start of the code section, like in source maps
+    pub offset: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+    pub rule_id: String,
+    pub severity: Severity,
+    /// Name of the exported function the finding was reported for
+    pub function: String,
+    pub location: Location,
+    pub message: String,
+    /// Facts that support the finding, e.g. the conditions of the path it was found on
+    pub evidence: Vec<String>,
+}
+
+impl Finding {
+    pub fn with_location(mut self, location: Location) -> Self {
+        self.location = location;
+        self
+    }
+
+    pub fn with_evidence(mut self, evidence: Vec<String>) -> Self {
+        self.evidence = evidence;
+        self
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}] {}: {}: {}", self.severity, self.rule_id, self.function, self.message)?;
+        for evidence in &self.evidence {
+            write!(f, "\n    {}", evidence)?;
+        }
+        Ok(())
+    }
+}
+
+/// Everything a detector needs to know about the audited module
+pub struct AuditContext {
+    wasm: Rc<Instance>,
+    call_graph: CallGraph,
+    code: HashMap<u32, Vec<Stmt>>,
+}
+
+impl AuditContext {
+    /// Decompile all functions of the module into their structured IR,
+    /// with offset annotations so that findings can point to the statement they are about
+    pub fn new(wasm: Rc<Instance>) -> Self {
+        let options = WriterOptions { offsets: true };
+        let call_graph = CallGraph::build(&wasm);
+        let code = wasm
+            .module()
+            .functions()
+            .iter()
+            .enumerate()
+            .filter(|(_, func)| !func.is_imported())
+            .filter_map(|(i, _)| {
+                let code = wasm.decompile_function_ir(i as u32, options).ok()?;
+                Some((i as u32, code))
+            })
+            .collect();
+        AuditContext { wasm, call_graph, code }
+    }
+
+    /// Audit the given IR instead of the decompiled functions, e.g. hand-written code in tests
+    pub fn with_code(wasm: Rc<Instance>, code: HashMap<u32, Vec<Stmt>>) -> Self {
+        let call_graph = CallGraph::build(&wasm);
+        AuditContext { wasm, call_graph, code }
+    }
+
+    pub fn wasm(&self) -> &Rc<Instance> {
+        &self.wasm
+    }
+
+    pub fn module(&self) -> &Module {
+        self.wasm.module()
+    }
+
+    pub fn func(&self, func_index: u32) -> &Function {
+        self.module().func(func_index)
+    }
+
+    pub fn call_graph(&self) -> &CallGraph {
+        &self.call_graph
+    }
+
+    /// The structured IR of a function defined in the module
+    pub fn code(&self, func_index: u32) -> Option<&[Stmt]> {
+        self.code.get(&func_index).map(Vec::as_slice)
+    }
+
+    /// The host function name of an imported function, see `host::host_fn`
+    pub fn host_fn(&self, func_index: u32) -> Option<&str> {
+        host::host_fn(self.module(), func_index)
+    }
+
+    /// The functions described by the contract spec
+    pub fn spec(&self) -> &[FunctionInfo] {
+        self.module().spec_fns()
+    }
+
+    /// Indices of the exported functions that are described by the contract spec
+    pub fn spec_functions(&self) -> Vec<u32> {
+        self.module()
+            .functions()
+            .iter()
+            .enumerate()
+            .filter(|(_, func)| !func.is_imported() && func.has_spec())
+            .map(|(i, _)| i as u32)
+            .collect()
+    }
+
+    /// Bit mask of the parameters of a spec function that have the given spec type
+    pub fn params_of_type(&self, func_index: u32, type_str: &str) -> u64 {
+        let mut mask = 0;
+        if let Some(spec) = self.func(func_index).spec_fn() {
+            for (i, param) in spec.inputs().iter().enumerate().take(64) {
+                if param.type_ident().type_str() == type_str {
+                    mask |= 1 << i;
+                }
+            }
+        }
+        mask
+    }
+
+    /// Print a piece of IR of a function on one line, shortened to `max_len` characters
+    pub fn code_str(&self, code: &impl CodeDisplay, func_index: u32, max_len: usize) -> String {
+        let text = code.create_str(self.wasm.clone(), func_index);
+        let mut text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        if text.chars().count() > max_len {
+            text = text.chars().take(max_len).collect::<String>() + "...";
+        }
+        text
+    }
+
+    /// The start of a function
+    pub fn location(&self, func_index: u32) -> Location {
+        self.statement_location(func_index, None)
+    }
+
+    /// The statement at `offset` in a function, the function's start if the offset is unknown
+    pub fn statement_location(&self, func_index: u32, offset: Option<u32>) -> Location {
+        let func = self.func(func_index);
+        Location {
+            function: func.name().to_string(),
+            func_index,
+            offset: offset.or_else(|| func.instr_offset(0)),
+        }
+    }
+
+    /// A finding for an exported function, located at its start until `Finding::with_location` is used
+    pub fn finding(&self, rule_id: &str, severity: Severity, func_index: u32, message: String) -> Finding {
+        Finding {
+            rule_id: rule_id.to_string(),
+            severity,
+            function: self.func(func_index).name().to_string(),
+            location: self.location(func_index),
+            message,
+            evidence: Vec::new(),
+        }
+    }
+}
+
+/// A check that looks for one kind of problem in a module.
+///
+/// Rules get the module, its spec, the call graph and the structured IR of every function
+/// through the `AuditContext`. Rules defined outside this crate are added with
+/// `RuleRegistry::register` or loaded from a library, see `export_rules!`.
+pub trait AuditRule {
+    /// Unique id used to enable or disable the rule, e.g. `missing-auth`
+    fn id(&self) -> &'static str;
+    /// One line summary shown by `audit --list-rules`
+    fn description(&self) -> &'static str;
+    fn check(&self, ctx: &AuditContext) -> Vec<Finding>;
+}
+
+/// The rules built into the auditor
+pub fn builtin_rules() -> Vec<Box<dyn AuditRule>> {
+    vec![
+        Box::new(missing_auth::MissingAuth),
+        Box::new(ttl::MissingTtlExtension),
+        Box::new(admin::UnprotectedAdmin),
+        Box::new(arith::UncheckedArithmetic),
+        Box::new(external_call::ExternalCall),
+        Box::new(loops::UnboundedLoop),
+    ]
+}
+
+/// Run all built-in rules on the module
+pub fn audit(wasm: Rc<Instance>) -> Vec<Finding> {
+    let ctx = AuditContext::new(wasm);
+    RuleRegistry::with_builtin_rules().run(&ctx, &RuleFilter::default())
+}
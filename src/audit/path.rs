@@ -0,0 +1,343 @@
+use std::collections::HashSet;
+
+use crate::ssa::cond::MappedExpr;
+use crate::ssa::{Cond, Expr, LoopKind, Stmt, Var};
+
+/// Callbacks for a walk over the paths of structured code.
+///
+/// The walker keeps one state per path. When paths meet again after an `if`,
+/// a `switch` or a loop, their states are merged with `join`.
+pub trait PathVisitor {
+    type State: Clone;
+
+    /// Merge the states of two paths that meet again
+    fn join(&mut self, a: Self::State, b: Self::State) -> Self::State;
+
+    /// Called for every expression node after its children, in evaluation order
+    fn visit_expr(&mut self, _expr: &Expr, _state: &mut Self::State) {}
+
+    /// Called for every direct call after its arguments have been evaluated
+    fn visit_call(&mut self, _func_index: u32, _args: &[Expr], _state: &mut Self::State) {}
+
+    fn visit_assign(&mut self, _var: Var, _expr: &Expr, _state: &mut Self::State) {}
+
+    /// Called for every store statement after address and value have been evaluated
+    fn visit_store(&mut self, _stmt: &Stmt, _state: &mut Self::State) {}
+
+    /// Called when a path enters the branch of `cond` that is `taken`
+    fn visit_branch(&mut self, _cond: &Cond, _taken: bool, _state: &mut Self::State) {}
+
+    /// Called after `visit_branch` when the other side of the branch traps,
+    /// e.g. the path that passed the overflow check of checked arithmetic
+    fn visit_guard(&mut self, _cond: &Cond, _taken: bool, _state: &mut Self::State) {}
+
+    /// Called before the body of a loop is entered
+    fn visit_loop(&mut self, _stmt: &Stmt, _state: &mut Self::State) {}
+
+    /// Called for the offset annotation before a statement, see `WriterOptions::offsets`
+    fn visit_offset(&mut self, _offset: u32, _state: &mut Self::State) {}
+
+    /// Called when a path leaves the function through a return
+    fn visit_return(&mut self, _value: Option<&Expr>, _state: &mut Self::State) {}
+}
+
+/// Walk all paths of `code` starting with `state`.
+/// Returns the joined state of all paths that leave the code normally or through a return,
+/// or `None` if every path traps.
+pub fn walk<V: PathVisitor>(visitor: &mut V, code: &[Stmt], state: V::State) -> Option<V::State> {
+    let mut walker = Walker {
+        visitor,
+        returns: None,
+        breaks: Vec::new(),
+    };
+    let end = walker.walk_block(code, Some(state));
+    let returns = walker.returns.take();
+    walker.join_opt(end, returns)
+}
+
+/// The locals a loop assigns, including its induction variable and phis, whose values
+/// differ between iterations
+pub fn loop_carried(stmt: &Stmt) -> HashSet<u32> {
+    let mut result = HashSet::new();
+    add_assigned(std::slice::from_ref(stmt), &mut result);
+    result
+}
+
+fn add_assigned(code: &[Stmt], result: &mut HashSet<u32>) {
+    for stmt in code {
+        match stmt {
+            Stmt::SetLocal(var, _) | Stmt::Phi(var, _) => {
+                result.insert(var.index);
+            }
+            Stmt::ForLoop(var, _, _, _, body) => {
+                result.insert(var.index);
+                add_assigned(body, result);
+            }
+            Stmt::While(_, code, _) | Stmt::Seq(code) | Stmt::If(_, code) => add_assigned(code, result),
+            Stmt::IfElse(_, a, b) => {
+                add_assigned(a, result);
+                add_assigned(b, result);
+            }
+            Stmt::SwitchCase(_, cases, default) => {
+                for (_, case) in cases {
+                    add_assigned(std::slice::from_ref(case), result);
+                }
+                if let Some(default) = default {
+                    add_assigned(std::slice::from_ref(default.as_ref()), result);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Whether the code ends in a trap, like the panic branch of a check
+pub fn traps(code: &[Stmt]) -> bool {
+    matches!(code.last(), Some(Stmt::Unreachable))
+}
+
+/// The operands of a comparison expression like `a < b`
+pub fn cmp_operands(expr: &Expr) -> Option<(&Expr, &Expr)> {
+    match expr {
+        Expr::I32Eq(a, b)
+        | Expr::I32Ne(a, b)
+        | Expr::I32LtS(a, b)
+        | Expr::I32LtU(a, b)
+        | Expr::I32GtS(a, b)
+        | Expr::I32GtU(a, b)
+        | Expr::I32LeS(a, b)
+        | Expr::I32LeU(a, b)
+        | Expr::I32GeS(a, b)
+        | Expr::I32GeU(a, b)
+        | Expr::I64Eq(a, b)
+        | Expr::I64Ne(a, b)
+        | Expr::I64LtS(a, b)
+        | Expr::I64LtU(a, b)
+        | Expr::I64GtS(a, b)
+        | Expr::I64GtU(a, b)
+        | Expr::I64LeS(a, b)
+        | Expr::I64LeU(a, b)
+        | Expr::I64GeS(a, b)
+        | Expr::I64GeU(a, b) => Some((a, b)),
+        _ => None,
+    }
+}
+
+/// The expressions a condition is built from
+pub fn cond_exprs(cond: &Cond) -> Vec<&Expr> {
+    let mut result = Vec::new();
+    add_cond_exprs(cond, &mut result);
+    result
+}
+
+fn add_cond_exprs<'a>(cond: &'a Cond, result: &mut Vec<&'a Expr>) {
+    match cond {
+        Cond::True | Cond::False => (),
+        Cond::Not(cond) => add_cond_exprs(cond, result),
+        Cond::And(a, b) | Cond::Or(a, b) => {
+            add_cond_exprs(a, result);
+            add_cond_exprs(b, result);
+        }
+        Cond::Cmp(a, _, b) => {
+            add_mapped_expr(a, result);
+            add_mapped_expr(b, result);
+        }
+        Cond::Expr(expr) => add_mapped_expr(expr, result),
+    }
+}
+
+fn add_mapped_expr<'a>(expr: &'a MappedExpr, result: &mut Vec<&'a Expr>) {
+    if let MappedExpr::Expr(expr) = expr {
+        result.push(expr);
+    }
+}
+
+struct Walker<'v, V: PathVisitor> {
+    visitor: &'v mut V,
+    returns: Option<V::State>,
+    breaks: Vec<Option<V::State>>,
+}
+
+impl<'v, V: PathVisitor> Walker<'v, V> {
+    fn join_opt(&mut self, a: Option<V::State>, b: Option<V::State>) -> Option<V::State> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(self.visitor.join(a, b)),
+            (a, None) => a,
+            (None, b) => b,
+        }
+    }
+
+    fn eval(&mut self, expr: &Expr, state: &mut V::State) {
+        for child in expr.children() {
+            self.eval(child, state);
+        }
+        self.visitor.visit_expr(expr, state);
+        if let Expr::Call(func_index, args) = expr {
+            self.visitor.visit_call(*func_index, args, state);
+        }
+    }
+
+    fn eval_cond(&mut self, cond: &Cond, state: &mut V::State) {
+        for expr in cond_exprs(cond) {
+            self.eval(expr, state);
+        }
+    }
+
+    fn branch(&mut self, cond: &Cond, taken: bool, state: &Option<V::State>) -> Option<V::State> {
+        let mut state = state.clone()?;
+        self.visitor.visit_branch(cond, taken, &mut state);
+        Some(state)
+    }
+
+    /// Enter the branch of `cond` that is `taken` when the other side is `other`
+    fn branch_to(&mut self, cond: &Cond, taken: bool, other: &[Stmt], state: &Option<V::State>) -> Option<V::State> {
+        let mut state = self.branch(cond, taken, state)?;
+        if traps(other) {
+            self.visitor.visit_guard(cond, taken, &mut state);
+        }
+        Some(state)
+    }
+
+    fn walk_block(&mut self, code: &[Stmt], mut state: Option<V::State>) -> Option<V::State> {
+        for stmt in code {
+            if state.is_none() {
+                break;
+            }
+            state = self.walk_stmt(stmt, state);
+        }
+        state
+    }
+
+    fn walk_stmt(&mut self, stmt: &Stmt, state: Option<V::State>) -> Option<V::State> {
+        use Stmt::*;
+        let mut state = state?;
+        match stmt {
+            Nop | Phi(..) | Replaced(_) => (),
+            Offset(pos) => self.visitor.visit_offset(pos.offset, &mut state),
+            Unreachable => return None,
+            Expr(expr) | Branch(expr) | SetGlobal(_, expr) => self.eval(expr, &mut state),
+            SetLocal(var, expr) => {
+                self.eval(expr, &mut state);
+                self.visitor.visit_assign(*var, expr, &mut state);
+            }
+            I32Store(location, value)
+            | I64Store(location, value)
+            | F32Store(location, value)
+            | F64Store(location, value)
+            | I32Store8(location, value)
+            | I32Store16(location, value)
+            | I64Store8(location, value)
+            | I64Store16(location, value)
+            | I64Store32(location, value) => {
+                self.eval(location, &mut state);
+                self.eval(value, &mut state);
+                self.visitor.visit_store(stmt, &mut state);
+            }
+            Return(expr) => {
+                self.eval(expr, &mut state);
+                self.visitor.visit_return(Some(expr), &mut state);
+                let returns = self.returns.take();
+                self.returns = self.join_opt(returns, Some(state));
+                return None;
+            }
+            ReturnVoid => {
+                self.visitor.visit_return(None, &mut state);
+                let returns = self.returns.take();
+                self.returns = self.join_opt(returns, Some(state));
+                return None;
+            }
+            Break => {
+                if let Some(breaks) = self.breaks.pop() {
+                    let joined = self.join_opt(breaks, Some(state));
+                    self.breaks.push(joined);
+                }
+                return None;
+            }
+            Seq(code) => return self.walk_block(code, Some(state)),
+            If(cond, body) => {
+                self.eval_cond(cond, &mut state);
+                let state = Some(state);
+                let then_state = self.branch(cond, true, &state);
+                let then_state = self.walk_block(body, then_state);
+                let else_state = self.branch_to(cond, false, body, &state);
+                return self.join_opt(then_state, else_state);
+            }
+            IfElse(cond, then_body, else_body) => {
+                self.eval_cond(cond, &mut state);
+                let state = Some(state);
+                let then_state = self.branch_to(cond, true, else_body, &state);
+                let then_state = self.walk_block(then_body, then_state);
+                let else_state = self.branch_to(cond, false, then_body, &state);
+                let else_state = self.walk_block(else_body, else_state);
+                return self.join_opt(then_state, else_state);
+            }
+            SwitchCase(expr, cases, default) => {
+                if let MappedExpr::Expr(expr) = expr {
+                    self.eval(expr, &mut state);
+                }
+                let mut result = None;
+                for (_, case) in cases {
+                    let case_state = self.walk_stmt(case, Some(state.clone()));
+                    result = self.join_opt(result, case_state);
+                }
+                let default_state = match default {
+                    Some(default) => self.walk_stmt(default, Some(state)),
+                    None => Some(state),
+                };
+                return self.join_opt(result, default_state);
+            }
+            While(cond, body, kind) => {
+                self.visitor.visit_loop(stmt, &mut state);
+                return self.walk_loop(cond, body, None, *kind, state);
+            }
+            ForLoop(var, init, cond, step, body) => {
+                if let Some(init) = init {
+                    self.eval(init, &mut state);
+                    self.visitor.visit_assign(*var, init, &mut state);
+                }
+                self.visitor.visit_loop(stmt, &mut state);
+                return self.walk_loop(cond, body, Some((*var, step)), LoopKind::While, state);
+            }
+        }
+        Some(state)
+    }
+
+    /// Walk the body of a loop once. The loop is left after zero iterations,
+    /// after one iteration or through a break.
+    fn walk_loop(
+        &mut self,
+        cond: &Cond,
+        body: &[Stmt],
+        step: Option<(Var, &Expr)>,
+        kind: LoopKind,
+        mut state: V::State,
+    ) -> Option<V::State> {
+        let endless = *cond == Cond::True;
+        self.breaks.push(None);
+
+        let (skip_state, body_state) = match kind {
+            LoopKind::While => {
+                self.eval_cond(cond, &mut state);
+                let state = Some(state);
+                let skip_state = if endless { None } else { self.branch(cond, false, &state) };
+                let body_state = if endless { state } else { self.branch(cond, true, &state) };
+                (skip_state, body_state)
+            }
+            LoopKind::DoWhile => (None, Some(state)),
+        };
+
+        let mut end_state = self.walk_block(body, body_state);
+        if let (Some((var, step)), Some(state)) = (step, end_state.as_mut()) {
+            self.eval(step, state);
+            self.visitor.visit_assign(var, step, state);
+        }
+        if let (LoopKind::DoWhile, Some(state)) = (kind, end_state.as_mut()) {
+            self.eval_cond(cond, state);
+        }
+        let exit_state = if endless { None } else { self.branch(cond, false, &end_state) };
+
+        let breaks = self.breaks.pop().flatten();
+        let exit_state = self.join_opt(exit_state, breaks);
+        self.join_opt(skip_state, exit_state)
+    }
+}
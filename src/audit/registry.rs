@@ -0,0 +1,173 @@
+use std::ffi::CStr;
+use std::fmt;
+use std::os::raw::c_char;
+use std::path::Path;
+
+use libloading::Library;
+
+use super::{builtin_rules, AuditContext, AuditRule, Finding, Severity};
+
+/// Rule libraries must be built against the same version of the auditor
+pub const RULES_API_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The auditor version and the compiler it was built with, nul terminated.
+/// Rules are passed across the library boundary as Rust trait objects, whose layout is only
+/// stable for the same compiler and the same auditor sources, so both have to match.
+pub const RULES_ABI: &str = concat!(env!("CARGO_PKG_VERSION"), " ", env!("AUDITOR_RUSTC_VERSION"), "\0");
+
+const ABI_SYMBOL: &[u8] = b"auditor_rules_abi\0";
+const REGISTER_SYMBOL: &[u8] = b"auditor_register_rules\0";
+
+/// `RULES_ABI` without the nul terminator
+pub fn rules_abi() -> &'static str {
+    RULES_ABI.trim_end_matches('\0')
+}
+
+/// Export the rules of a library crate so that `audit --rules-lib` can load them.
+///
+/// The crate is built as a `cdylib` and passes a function that adds its rules to the registry.
+/// The registry and the rules use the Rust ABI, which is not stable, so the library must be built
+/// against the same auditor version with the same `rustc`. The library exports `auditor_rules_abi`
+/// with the C ABI, and `load_library` compares it with `RULES_ABI` before calling into Rust code:
+///
+/// ```ignore
+/// fn register(registry: &mut auditor::audit::RuleRegistry) {
+///     registry.register(Box::new(MyRule));
+/// }
+///
+/// auditor::export_rules!(register);
+/// ```
+#[macro_export]
+macro_rules! export_rules {
+    ($register:path) => {
+        #[no_mangle]
+        pub extern "C" fn auditor_rules_abi() -> *const ::std::os::raw::c_char {
+            $crate::audit::registry::RULES_ABI.as_ptr() as *const ::std::os::raw::c_char
+        }
+
+        #[no_mangle]
+        pub fn auditor_register_rules(registry: &mut $crate::audit::RuleRegistry) {
+            $register(registry)
+        }
+    };
+}
+
+#[derive(Debug)]
+pub enum RegistryError {
+    Library(libloading::Error),
+    VersionMismatch(String),
+    UnknownRule(String),
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Library(error) => write!(f, "Error while loading rule library: {}", error),
+            Self::VersionMismatch(version) => write!(
+                f,
+                "Rule library was built for auditor {}, expected {} (the auditor version and rustc must match)",
+                version,
+                rules_abi()
+            ),
+            Self::UnknownRule(id) => write!(f, "Unknown rule `{}`", id),
+        }
+    }
+}
+
+impl From<libloading::Error> for RegistryError {
+    fn from(error: libloading::Error) -> Self {
+        Self::Library(error)
+    }
+}
+
+/// Which rules run and which findings are kept
+#[derive(Debug, Clone, Default)]
+pub struct RuleFilter {
+    /// Ids of the rules to run, all rules if empty
+    pub enabled: Vec<String>,
+    pub disabled: Vec<String>,
+    /// Findings below this severity are dropped
+    pub min_severity: Severity,
+}
+
+impl RuleFilter {
+    pub fn is_enabled(&self, id: &str) -> bool {
+        (self.enabled.is_empty() || self.enabled.iter().any(|e| e == id)) && !self.disabled.iter().any(|d| d == id)
+    }
+}
+
+/// The audit rules that can run on a module
+#[derive(Default)]
+pub struct RuleRegistry {
+    rules: Vec<Box<dyn AuditRule>>,
+    // keeps the code of rules loaded from libraries alive, declared after `rules` so it is dropped last
+    libraries: Vec<Library>,
+}
+
+impl RuleRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_builtin_rules() -> Self {
+        let mut registry = Self::new();
+        for rule in builtin_rules() {
+            registry.register(rule);
+        }
+        registry
+    }
+
+    /// Add a rule. A rule with the same id as an existing one replaces it.
+    pub fn register(&mut self, rule: Box<dyn AuditRule>) {
+        match self.rules.iter().position(|r| r.id() == rule.id()) {
+            Some(i) => self.rules[i] = rule,
+            None => self.rules.push(rule),
+        }
+    }
+
+    pub fn rules(&self) -> &[Box<dyn AuditRule>] {
+        &self.rules
+    }
+
+    pub fn get(&self, id: &str) -> Option<&dyn AuditRule> {
+        self.rules.iter().find(|rule| rule.id() == id).map(|rule| rule.as_ref())
+    }
+
+    /// Load the rules of a library built with `export_rules!`.
+    /// Returns the number of rules added.
+    pub fn load_library(&mut self, path: &Path) -> Result<usize, RegistryError> {
+        // SAFETY: the initialization code of the library is trusted like any other rule.
+        // The ABI string is read through the C ABI, the Rust ABI register function
+        // is only called once the library is known to be built like the auditor.
+        let library = unsafe { Library::new(path) }?;
+        let abi: extern "C" fn() -> *const c_char = *unsafe { library.get(ABI_SYMBOL) }?;
+        let version = unsafe { CStr::from_ptr(abi()) }.to_string_lossy().into_owned();
+        if version != rules_abi() {
+            return Err(RegistryError::VersionMismatch(version));
+        }
+        let register: fn(&mut RuleRegistry) = *unsafe { library.get(REGISTER_SYMBOL) }?;
+
+        let count = self.rules.len();
+        register(self);
+        self.libraries.push(library);
+        Ok(self.rules.len() - count)
+    }
+
+    /// Check that the filter only names registered rules
+    pub fn check_filter(&self, filter: &RuleFilter) -> Result<(), RegistryError> {
+        match filter.enabled.iter().chain(&filter.disabled).find(|id| self.get(id).is_none()) {
+            Some(id) => Err(RegistryError::UnknownRule(id.clone())),
+            None => Ok(()),
+        }
+    }
+
+    /// Run the enabled rules and keep the findings of at least the minimum severity
+    pub fn run(&self, ctx: &AuditContext, filter: &RuleFilter) -> Vec<Finding> {
+        self.rules
+            .iter()
+            .filter(|rule| filter.is_enabled(rule.id()))
+            .flat_map(|rule| rule.check(ctx))
+            .filter(|finding| finding.severity >= filter.min_severity)
+            .collect()
+    }
+}
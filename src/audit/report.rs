@@ -0,0 +1,199 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use serde_json::{json, Value};
+
+use super::{AuditContext, AuditRule, Finding, Location, Severity};
+use crate::fmt::{CodeWriter, WriterOptions};
+use crate::source_map::parse_offset;
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+const TOOL_NAME: &str = "soroban-auditor";
+const MAX_SNIPPET_LINES: usize = 40;
+
+const SEVERITIES: [Severity; 4] = [Severity::High, Severity::Medium, Severity::Low, Severity::Info];
+
+/// The SARIF level of a severity
+const fn sarif_level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::High => "error",
+        Severity::Medium => "warning",
+        Severity::Low | Severity::Info => "note",
+    }
+}
+
+/// Build a SARIF 2.1.0 log of the findings for the wasm binary at `file`.
+///
+/// SARIF byte offsets are relative to the artifact, but wasm offsets are relative to the
+/// code section, so they are given as a property of the function's logical location.
+pub fn to_sarif(rules: &[Box<dyn AuditRule>], findings: &[Finding], file: &str) -> Value {
+    let sarif_rules: Vec<Value> = rules
+        .iter()
+        .map(|rule| {
+            json!({
+                "id": rule.id(),
+                "shortDescription": { "text": rule.description() },
+            })
+        })
+        .collect();
+    let results: Vec<Value> = findings
+        .iter()
+        .map(|finding| {
+            let mut result = json!({
+                "ruleId": finding.rule_id,
+                "level": sarif_level(finding.severity),
+                "message": { "text": finding.message },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": file },
+                    },
+                    "logicalLocations": [{
+                        "name": finding.location.function,
+                        "kind": "function",
+                        "properties": {
+                            "funcIndex": finding.location.func_index,
+                            "wasmOffset": finding.location.offset,
+                        },
+                    }],
+                }],
+                "properties": {
+                    "severity": finding.severity,
+                    "exportedFunction": finding.function,
+                    "evidence": finding.evidence,
+                },
+            });
+            if let Some(index) = rules.iter().position(|rule| rule.id() == finding.rule_id) {
+                result["ruleIndex"] = json!(index);
+            }
+            result
+        })
+        .collect();
+
+    json!({
+        "$schema": SARIF_SCHEMA,
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": TOOL_NAME,
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": sarif_rules,
+                },
+            },
+            "artifacts": [{ "location": { "uri": file } }],
+            "results": results,
+        }],
+    })
+}
+
+/// The decompiled code of the function a finding points to, shortened to `MAX_SNIPPET_LINES`
+/// around the statement at the finding's offset, which is marked with a comment
+fn snippet(ctx: &AuditContext, location: &Location) -> Option<String> {
+    let code = ctx.code(location.func_index)?;
+    let options = WriterOptions { offsets: true };
+    let mut writer = CodeWriter::formatter(ctx.wasm().clone(), location.func_index).with_options(options);
+    let text = writer.string_func(code);
+
+    // the offset annotations are dropped, the one of the finding marks the statement below it
+    let mut lines = Vec::new();
+    let mut target = None;
+    let mut found = false;
+    for line in text.lines() {
+        if let Some(offset) = parse_offset(line) {
+            found |= Some(offset) == location.offset;
+            continue;
+        }
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        if found && target.is_none() && !trimmed.starts_with("//") {
+            target = Some(lines.len());
+        }
+        lines.push(line.to_string());
+    }
+    if let Some(target) = target {
+        lines[target].push_str(" // <- finding");
+    }
+
+    let start = target
+        .unwrap_or(0)
+        .saturating_sub(MAX_SNIPPET_LINES / 2)
+        .min(lines.len().saturating_sub(MAX_SNIPPET_LINES));
+    let end = (start + MAX_SNIPPET_LINES).min(lines.len());
+    let mut snippet = Vec::new();
+    if start > 0 {
+        snippet.push(String::from("// ..."));
+    }
+    snippet.extend_from_slice(&lines[start..end]);
+    if end < lines.len() {
+        snippet.push(String::from("// ..."));
+    }
+    Some(snippet.join("\n"))
+}
+
+/// Build a Markdown report with a summary table per exported function
+/// and the decompiled code of every finding
+pub fn to_markdown(ctx: &AuditContext, findings: &[Finding], file: &str) -> String {
+    let mut report = String::new();
+    writeln!(report, "# Audit report for `{}`\n", file).unwrap();
+
+    let mut counts: BTreeMap<&str, [usize; 4]> = ctx
+        .spec_functions()
+        .into_iter()
+        .map(|func_index| (ctx.func(func_index).name(), [0; 4]))
+        .collect();
+    for finding in findings {
+        let column = SEVERITIES.iter().position(|s| *s == finding.severity).unwrap();
+        counts.entry(&finding.function).or_insert([0; 4])[column] += 1;
+    }
+    writeln!(report, "## Summary\n").unwrap();
+    writeln!(report, "| Function | High | Medium | Low | Info |").unwrap();
+    writeln!(report, "|---|---:|---:|---:|---:|").unwrap();
+    for (function, counts) in &counts {
+        writeln!(
+            report,
+            "| `{}` | {} | {} | {} | {} |",
+            function,
+            counts[0],
+            counts[1],
+            counts[2],
+            counts[3]
+        )
+        .unwrap();
+    }
+    writeln!(report, "\n{} finding(s)\n", findings.len()).unwrap();
+
+    if findings.is_empty() {
+        return report;
+    }
+    writeln!(report, "## Findings").unwrap();
+    for (i, finding) in findings.iter().enumerate() {
+        writeln!(
+            report,
+            "\n### {}. {} in `{}` ({})\n",
+            i + 1,
+            finding.rule_id,
+            finding.function,
+            finding.severity
+        )
+        .unwrap();
+        writeln!(report, "{}\n", finding.message).unwrap();
+        for evidence in &finding.evidence {
+            writeln!(report, "- {}", evidence).unwrap();
+        }
+        if !finding.evidence.is_empty() {
+            writeln!(report).unwrap();
+        }
+        let location = &finding.location;
+        match location.offset {
+            Some(offset) => writeln!(report, "Location: `{}` at code offset 0x{:x}\n", location.function, offset),
+            None => writeln!(report, "Location: `{}`\n", location.function),
+        }
+        .unwrap();
+        if let Some(snippet) = snippet(ctx, location) {
+            writeln!(report, "```rust\n{}\n```", snippet).unwrap();
+        }
+    }
+    report
+}
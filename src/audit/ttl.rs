@@ -0,0 +1,246 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use super::keys::KeyTracker;
+use super::path::{self, PathVisitor};
+use super::{AuditContext, AuditRule, Finding, Location, Severity};
+use crate::soroban::host::{self, StorageType};
+use crate::ssa::expr::DAY_IN_LEDGERS;
+use crate::ssa::{Expr, Stmt, Var};
+
+/// Used for extensions of keys whose class could not be determined
+const ANY_KEY: &str = "*";
+
+/// Flags persistent and instance storage writes that are not followed by a TTL extension
+/// of the same key class, and TTL extensions with suspiciously small constants.
+/// Extending the instance covers every instance entry, no matter where on the path it happens.
+pub struct MissingTtlExtension;
+
+impl AuditRule for MissingTtlExtension {
+    fn id(&self) -> &'static str {
+        "missing-ttl-extension"
+    }
+
+    fn description(&self) -> &'static str {
+        "Persistent and instance storage writes without a TTL extension"
+    }
+
+    fn check(&self, ctx: &AuditContext) -> Vec<Finding> {
+        let summaries = Summaries::new(ctx);
+        let mut findings = Vec::new();
+        for func_index in ctx.spec_functions() {
+            let summary = summaries.get(func_index);
+            for ((storage, class), location) in summary.pending {
+                if storage == StorageType::Instance && summary.instance_extended {
+                    continue;
+                }
+                let message = format!(
+                    "writes {} storage entry `{}` without extending its TTL, the entry can expire",
+                    storage.name(),
+                    class
+                );
+                findings.push(ctx.finding(self.id(), Severity::Medium, func_index, message).with_location(location));
+            }
+            for (warning, location) in summary.warnings {
+                findings.push(ctx.finding(self.id(), Severity::Low, func_index, warning).with_location(location));
+            }
+        }
+        findings
+    }
+}
+
+type Entry = (StorageType, String);
+
+#[derive(Debug, Clone, Default)]
+struct Summary {
+    /// Entries that are written without a following extension on some path, with the write
+    pending: BTreeMap<Entry, Location>,
+    /// Entries extended on every path
+    extended: BTreeSet<Entry>,
+    /// Whether the instance is extended on every path
+    instance_extended: bool,
+    warnings: BTreeMap<String, Location>,
+}
+
+struct Summaries<'a> {
+    ctx: &'a AuditContext,
+    cache: RefCell<HashMap<u32, Summary>>,
+    active: RefCell<HashSet<u32>>,
+}
+
+impl<'a> Summaries<'a> {
+    fn new(ctx: &'a AuditContext) -> Self {
+        Summaries {
+            ctx,
+            cache: RefCell::new(HashMap::new()),
+            active: RefCell::new(HashSet::new()),
+        }
+    }
+
+    fn get(&self, func_index: u32) -> Summary {
+        if let Some(summary) = self.cache.borrow().get(&func_index) {
+            return summary.clone();
+        }
+        let code = match self.ctx.code(func_index) {
+            Some(code) => code,
+            None => return Summary::default(),
+        };
+        if !self.active.borrow_mut().insert(func_index) {
+            return Summary::default();
+        }
+
+        let mut visitor = TtlVisitor {
+            summaries: self,
+            func_index,
+            offset: None,
+            warnings: BTreeMap::new(),
+        };
+        let exit = path::walk(&mut visitor, code, TtlState::default());
+        let summary = match exit {
+            Some(state) => Summary {
+                pending: state.pending,
+                extended: state.extended,
+                instance_extended: state.instance_extended,
+                warnings: visitor.warnings,
+            },
+            None => Summary {
+                warnings: visitor.warnings,
+                ..Summary::default()
+            },
+        };
+
+        self.active.borrow_mut().remove(&func_index);
+        self.cache.borrow_mut().insert(func_index, summary.clone());
+        summary
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct TtlState {
+    pending: BTreeMap<Entry, Location>,
+    extended: BTreeSet<Entry>,
+    instance_extended: bool,
+    keys: KeyTracker,
+}
+
+impl TtlState {
+    fn extend(&mut self, storage: StorageType, class: String) {
+        if class == ANY_KEY {
+            self.pending.retain(|(s, _), _| *s != storage);
+        } else {
+            self.pending.remove(&(storage, class.clone()));
+        }
+        self.extended.insert((storage, class));
+    }
+
+    fn extend_instance(&mut self) {
+        self.instance_extended = true;
+        self.pending.retain(|(s, _), _| *s != StorageType::Instance);
+    }
+}
+
+struct TtlVisitor<'s, 'a> {
+    summaries: &'s Summaries<'a>,
+    func_index: u32,
+    /// Offset of the statement being visited
+    offset: Option<u32>,
+    warnings: BTreeMap<String, Location>,
+}
+
+impl<'s, 'a> TtlVisitor<'s, 'a> {
+    fn location(&self) -> Location {
+        self.summaries.ctx.statement_location(self.func_index, self.offset)
+    }
+
+    fn warn(&mut self, warning: String) {
+        let location = self.location();
+        self.warnings.entry(warning).or_insert(location);
+    }
+
+    fn check_constants(&mut self, name: &str, threshold: Option<&Expr>, extend_to: Option<&Expr>) {
+        let threshold = threshold.and_then(host::u32_val);
+        let extend_to = extend_to.and_then(host::u32_val);
+        if let Some(extend_to) = extend_to {
+            if extend_to < DAY_IN_LEDGERS {
+                self.warn(format!(
+                    "{} extends the TTL to only {} ledgers, less than a day",
+                    name, extend_to
+                ));
+            }
+        }
+        if let (Some(threshold), Some(extend_to)) = (threshold, extend_to) {
+            if threshold > extend_to {
+                self.warn(format!(
+                    "{} uses a threshold of {} above the extension to {} ledgers",
+                    name, threshold, extend_to
+                ));
+            }
+        }
+    }
+}
+
+impl<'s, 'a> PathVisitor for TtlVisitor<'s, 'a> {
+    type State = TtlState;
+
+    fn join(&mut self, mut a: TtlState, b: TtlState) -> TtlState {
+        for (entry, location) in b.pending {
+            a.pending.entry(entry).or_insert(location);
+        }
+        a.extended = a.extended.intersection(&b.extended).cloned().collect();
+        a.instance_extended &= b.instance_extended;
+        a.keys.join(&b.keys);
+        a
+    }
+
+    fn visit_assign(&mut self, var: Var, expr: &Expr, state: &mut TtlState) {
+        state.keys.assign(var, expr);
+    }
+
+    fn visit_offset(&mut self, offset: u32, _state: &mut TtlState) {
+        self.offset = Some(offset);
+    }
+
+    fn visit_store(&mut self, stmt: &Stmt, state: &mut TtlState) {
+        state.keys.store(stmt);
+    }
+
+    fn visit_call(&mut self, func_index: u32, args: &[Expr], state: &mut TtlState) {
+        match self.summaries.ctx.host_fn(func_index) {
+            Some(host::PUT_CONTRACT_DATA) => {
+                let storage = args.get(2).and_then(StorageType::from_expr);
+                if let Some(storage @ (StorageType::Persistent | StorageType::Instance)) = storage {
+                    let class = args.first().and_then(|key| state.keys.class_of(key));
+                    let class = class.unwrap_or_else(|| String::from("unknown"));
+                    state.pending.insert((storage, class), self.location());
+                }
+            }
+            Some(host::EXTEND_CONTRACT_DATA_TTL) => {
+                if let Some(storage) = args.get(1).and_then(StorageType::from_expr) {
+                    let class = args.first().and_then(|key| state.keys.class_of(key));
+                    state.extend(storage, class.unwrap_or_else(|| String::from(ANY_KEY)));
+                }
+                self.check_constants(host::EXTEND_CONTRACT_DATA_TTL, args.get(2), args.get(3));
+            }
+            Some(host::EXTEND_INSTANCE_TTL) => {
+                state.extend_instance();
+                self.check_constants(host::EXTEND_INSTANCE_TTL, args.first(), args.get(1));
+            }
+            Some(_) => (),
+            None => {
+                let summary = self.summaries.get(func_index);
+                for (storage, class) in summary.extended {
+                    state.extend(storage, class);
+                }
+                if summary.instance_extended {
+                    state.extend_instance();
+                }
+                for (entry, location) in summary.pending {
+                    state.pending.entry(entry).or_insert(location);
+                }
+                for (warning, location) in summary.warnings {
+                    self.warnings.entry(warning).or_insert(location);
+                }
+            }
+        }
+    }
+}
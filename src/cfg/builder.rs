@@ -7,7 +7,7 @@ use slab::Slab;
 
 use super::{BasicBlock, Cfg, Edge, EdgeCond, EdgeType, NodeId};
 
-use crate::ssa::{Expr, Stmt, Var};
+use crate::ssa::{Expr, SourcePos, Stmt, Var};
 
 #[derive(Debug)]
 pub enum CfgBuildError {
@@ -83,10 +83,13 @@ struct CfgBuilder {
     unreachable: bool,
     result_vars: ResultVars,
     next_tmp_var_index: u32,
+    record_offsets: bool,
+    curr_pos: Option<SourcePos>,
+    pos_recorded: bool,
 }
 
 impl CfgBuilder {
-    fn new() -> Self {
+    fn new(record_offsets: bool) -> Self {
         let mut nodes = Slab::new();
         let curr_id = nodes.insert(BasicBlock::new());
         let next_id = nodes.insert(BasicBlock::new());
@@ -98,6 +101,9 @@ impl CfgBuilder {
             unreachable: false,
             result_vars: ResultVars::Uninit,
             next_tmp_var_index: 0,
+            record_offsets,
+            curr_pos: None,
+            pos_recorded: false,
         }
     }
 
@@ -127,10 +133,23 @@ impl CfgBuilder {
     /// Add a statement to the current node
     fn push_code(&mut self, stmt: Stmt) {
         if !self.unreachable {
+            if let (true, false, Some(pos)) = (self.record_offsets, self.pos_recorded, self.curr_pos) {
+                self.nodes[self.curr_id].code.push(Stmt::Offset(pos));
+                self.pos_recorded = true;
+            }
             self.nodes[self.curr_id].code.push(stmt);
         }
     }
 
+    /// Set the instruction that produces the following statements
+    fn set_pos(&mut self, func: &Function, instr: usize) {
+        self.curr_pos = func.instr_offset(instr).map(|offset| SourcePos {
+            instr: instr as u32,
+            offset,
+        });
+        self.pos_recorded = false;
+    }
+
     /// Push an expression onto the value stack
     fn push(&mut self, expr: Expr) {
         if !self.unreachable {
@@ -268,9 +287,11 @@ impl CfgBuilder {
         let nxt_var_idx = self.result_vars.init(nxt_var_idx as u32);
         self.next_tmp_var_index = nxt_var_idx;
 
-        for instr in code {
+        for (i, instr) in code.iter().enumerate() {
             use self::Instruction::*;
 
+            self.set_pos(func, i);
+
             match instr {
                 Nop => (),
                 Block(block_type) => {
@@ -805,6 +826,7 @@ impl CfgBuilder {
 pub fn build(
     wasm: Rc<wasm::Instance>, 
     func_index: u32, 
+    record_offsets: bool,
 ) -> Result<Cfg, CfgBuildError> {
-    CfgBuilder::new().build(wasm, func_index)
+    CfgBuilder::new(record_offsets).build(wasm, func_index)
 }
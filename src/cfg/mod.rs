@@ -97,6 +97,7 @@ impl Cfg {
             .map(|(i, bb)| {
                 let instrs = (&bb.code[..])
                     .create_str(Rc::clone(&self.wasm), self.func_index)
+                    .replace('"', "\\\"")
                     .replace("\n", "\\n");
                 if bb.code.is_empty() {
                     format!("\t{}", i)
@@ -132,7 +133,12 @@ impl Cfg {
     }
 
     pub fn build(wasm: Rc<wasm::Instance>, func_index: u32) -> Result<Cfg, CfgBuildError> {
-        builder::build(wasm, func_index)
+        builder::build(wasm, func_index, false)
+    }
+
+    /// Build the cfg and mark each statement with the wasm instruction it originates from
+    pub fn build_with_offsets(wasm: Rc<wasm::Instance>, func_index: u32) -> Result<Cfg, CfgBuildError> {
+        builder::build(wasm, func_index, true)
     }
 
     pub fn region_successors(&self, region_nodes: &HashSet<NodeId>) -> HashSet<NodeId> {
@@ -0,0 +1,229 @@
+//! Clone detection across many contracts.
+//!
+//! Every exported function is fingerprinted like in [`crate::identify`]. Two contracts are
+//! versions of each other when most of their exported functions match by name and fingerprint,
+//! and contracts are grouped transitively. Within a group the most common version of each
+//! function is taken as the original, so the report shows where each fork diverges from it.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::rc::Rc;
+
+use serde::Serialize;
+
+use crate::identify::{self, Fingerprint};
+use crate::wasm_wrapper::wasm::Instance;
+
+/// Lowest share of matching exported functions of two contracts to be clustered together
+pub const MIN_CONTRACT_SIMILARITY: f64 = 0.5;
+
+/// The exported functions of one contract
+#[derive(Debug, Clone)]
+pub struct ContractFingerprints {
+    pub contract: String,
+    pub functions: BTreeMap<String, Fingerprint>,
+}
+
+impl ContractFingerprints {
+    /// Fingerprint the exported functions of the contract, functions that can not be decompiled
+    /// are left out
+    pub fn new(contract: &str, wasm: &Rc<Instance>) -> Self {
+        let module = wasm.module();
+        let functions = (0..module.functions().len() as u32)
+            .filter(|i| module.is_exported(*i) && !module.func(*i).is_imported())
+            .filter_map(|i| Some((module.func(i).name().to_string(), identify::fingerprint(wasm, i)?)))
+            .collect();
+        ContractFingerprints {
+            contract: contract.to_string(),
+            functions,
+        }
+    }
+
+    /// Share of the exported functions of both contracts that match in name and fingerprint
+    pub fn similarity(&self, other: &ContractFingerprints, max_distance: usize) -> f64 {
+        let matched = self
+            .functions
+            .iter()
+            .filter(|(name, fingerprint)| {
+                other
+                    .functions
+                    .get(*name)
+                    .and_then(|other| fingerprint.distance(other))
+                    .is_some_and(|distance| distance <= max_distance)
+            })
+            .count();
+        let shared = self
+            .functions
+            .keys()
+            .filter(|name| other.functions.contains_key(*name))
+            .count();
+        let total = self.functions.len() + other.functions.len() - shared;
+        if total == 0 {
+            return 0.0;
+        }
+        matched as f64 / total as f64
+    }
+}
+
+/// One version of a function and the contracts that contain it
+#[derive(Debug, Clone, Serialize)]
+pub struct FunctionVersion {
+    #[serde(flatten)]
+    pub fingerprint: Fingerprint,
+    pub contracts: Vec<String>,
+}
+
+/// The versions of an exported function in a cluster, the most common first
+#[derive(Debug, Clone, Serialize)]
+pub struct FunctionCluster {
+    pub name: String,
+    pub versions: Vec<FunctionVersion>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DivergenceKind {
+    /// The function differs from the most common version
+    Changed,
+    /// Most contracts of the cluster export the function, this one does not
+    Missing,
+    /// Few contracts of the cluster export the function, this one does
+    Added,
+}
+
+impl fmt::Display for DivergenceKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = match self {
+            Self::Changed => "~",
+            Self::Missing => "-",
+            Self::Added => "+",
+        };
+        write!(f, "{}", sign)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Divergence {
+    pub contract: String,
+    pub function: String,
+    pub kind: DivergenceKind,
+    /// TLSH distance to the most common version of a changed function, `None` if the code is too
+    /// short to hash
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub distance: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ContractCluster {
+    pub contracts: Vec<String>,
+    pub functions: Vec<FunctionCluster>,
+    pub divergences: Vec<Divergence>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ClusterReport {
+    /// Groups of at least two similar contracts, the largest first
+    pub clusters: Vec<ContractCluster>,
+    /// Contracts that are not similar to any other
+    pub singletons: Vec<String>,
+}
+
+fn find(parents: &mut [usize], i: usize) -> usize {
+    if parents[i] != i {
+        parents[i] = find(parents, parents[i]);
+    }
+    parents[i]
+}
+
+/// Group the contracts whose similarity is at least `MIN_CONTRACT_SIMILARITY`, transitively
+pub fn cluster(contracts: &[ContractFingerprints], max_distance: usize) -> ClusterReport {
+    let mut parents: Vec<usize> = (0..contracts.len()).collect();
+    for (i, a) in contracts.iter().enumerate() {
+        for (j, b) in contracts.iter().enumerate().skip(i + 1) {
+            if a.similarity(b, max_distance) >= MIN_CONTRACT_SIMILARITY {
+                let (root_a, root_b) = (find(&mut parents, i), find(&mut parents, j));
+                parents[root_b] = root_a;
+            }
+        }
+    }
+    let mut groups: BTreeMap<usize, Vec<&ContractFingerprints>> = BTreeMap::new();
+    for (i, contract) in contracts.iter().enumerate() {
+        groups.entry(find(&mut parents, i)).or_default().push(contract);
+    }
+
+    let mut report = ClusterReport::default();
+    for members in groups.into_values() {
+        match members.as_slice() {
+            [contract] => report.singletons.push(contract.contract.clone()),
+            _ => report.clusters.push(contract_cluster(&members)),
+        }
+    }
+    report
+        .clusters
+        .sort_by_key(|cluster| std::cmp::Reverse(cluster.contracts.len()));
+    report
+}
+
+/// Group the versions of each exported function by structure and compare every member with the
+/// most common ones
+fn contract_cluster(members: &[&ContractFingerprints]) -> ContractCluster {
+    let mut names: Vec<&String> = members.iter().flat_map(|member| member.functions.keys()).collect();
+    names.sort();
+    names.dedup();
+
+    let mut functions = Vec::new();
+    let mut divergences = Vec::new();
+    for name in names {
+        let mut versions: Vec<FunctionVersion> = Vec::new();
+        for member in members {
+            let fingerprint = match member.functions.get(name) {
+                Some(fingerprint) => fingerprint,
+                None => continue,
+            };
+            let version = versions
+                .iter_mut()
+                .find(|version| version.fingerprint.structure == fingerprint.structure);
+            match version {
+                Some(version) => version.contracts.push(member.contract.clone()),
+                None => versions.push(FunctionVersion {
+                    fingerprint: fingerprint.clone(),
+                    contracts: vec![member.contract.clone()],
+                }),
+            }
+        }
+        // Stable, so ties go to the version of the first contract
+        versions.sort_by_key(|version| std::cmp::Reverse(version.contracts.len()));
+
+        let exported_by: usize = versions.iter().map(|version| version.contracts.len()).sum();
+        let common = exported_by * 2 > members.len();
+        for member in members {
+            let divergence = match (member.functions.get(name), common) {
+                (None, true) => Some((DivergenceKind::Missing, None)),
+                (Some(_), false) => Some((DivergenceKind::Added, None)),
+                (Some(fingerprint), true) if !versions[0].contracts.contains(&member.contract) => {
+                    let distance = versions[0].fingerprint.distance(fingerprint);
+                    Some((DivergenceKind::Changed, distance))
+                }
+                _ => None,
+            };
+            if let Some((kind, distance)) = divergence {
+                divergences.push(Divergence {
+                    contract: member.contract.clone(),
+                    function: name.clone(),
+                    kind,
+                    distance,
+                });
+            }
+        }
+        functions.push(FunctionCluster {
+            name: name.clone(),
+            versions,
+        });
+    }
+    divergences.sort_by(|a, b| (&a.contract, &a.function).cmp(&(&b.contract, &b.function)));
+    ContractCluster {
+        contracts: members.iter().map(|member| member.contract.clone()).collect(),
+        functions,
+        divergences,
+    }
+}
@@ -0,0 +1,471 @@
+//! What changed between two versions of a contract.
+//!
+//! Functions of the old binary are matched with functions of the new one in stages: exported
+//! functions by export name, then spec functions by their signature, then local functions by an
+//! identical body and finally by the most similar mix of instructions. Calls are compared through
+//! this matching, so local functions that merely moved to another index are not reported.
+
+pub mod text;
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use parity_wasm::elements::{Instruction, Internal};
+use regex::{Captures, Regex};
+use serde::Serialize;
+
+use crate::fmt::WriterOptions;
+use crate::soroban::normalize::Segments;
+use crate::wasm_wrapper::wasm::Instance;
+use crate::wasm_wrapper::wasm_adapter::{Function, Module};
+
+/// Lowest instruction similarity of two local functions to be taken as versions of each other
+const MIN_SIMILARITY: f64 = 0.7;
+
+/// Unchanged lines around the changes in body diffs
+const CONTEXT_LINES: usize = 3;
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Include unified diffs of the decompiled bodies of changed functions
+    pub bodies: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options { bodies: true }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = match self {
+            Self::Added => "+",
+            Self::Removed => "-",
+            Self::Changed => "~",
+        };
+        write!(f, "{}", sign)
+    }
+}
+
+/// How a function of the old binary was found in the new one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchKind {
+    ExportName,
+    Signature,
+    IdenticalBody,
+    Similarity,
+}
+
+impl fmt::Display for MatchKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::ExportName => "export name",
+            Self::Signature => "spec signature",
+            Self::IdenticalBody => "identical body",
+            Self::Similarity => "similarity",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FunctionRef {
+    pub index: u32,
+    pub name: String,
+    pub signature: String,
+}
+
+impl FunctionRef {
+    fn new(module: &Module, index: u32) -> Self {
+        let func = module.func(index);
+        let signature = match func.spec_fn() {
+            Some(spec) if func.has_spec() => spec.to_string(),
+            _ => func.to_string(),
+        };
+        FunctionRef {
+            index,
+            name: func.name().to_string(),
+            signature,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FunctionChange {
+    pub kind: ChangeKind,
+    pub old: Option<FunctionRef>,
+    pub new: Option<FunctionRef>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub matched_by: Option<MatchKind>,
+    /// Unified diff of the decompiled bodies
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TypeChange {
+    pub kind: ChangeKind,
+    pub name: String,
+    /// Unified diff of the definitions
+    pub diff: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DiffReport {
+    pub functions: Vec<FunctionChange>,
+    pub types: Vec<TypeChange>,
+    /// Matched functions without changes
+    pub unchanged: usize,
+}
+
+impl DiffReport {
+    pub fn is_empty(&self) -> bool {
+        self.functions.is_empty() && self.types.is_empty()
+    }
+}
+
+/// Local functions, imports are part of neither side of the matching
+fn local_functions(module: &Module) -> Vec<u32> {
+    (0..module.functions().len() as u32)
+        .filter(|i| !module.func(*i).is_imported())
+        .collect()
+}
+
+fn exports(module: &Module) -> HashMap<&str, u32> {
+    module
+        .exports()
+        .iter()
+        .filter_map(|export| match export.internal() {
+            Internal::Function(index) => Some((export.field(), *index)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Spec signature of a function without the names of the function and its parameters
+fn signature_key(func: &Function) -> Option<String> {
+    let spec = func.spec_fn().filter(|_| func.has_spec())?;
+    let inputs: Vec<&str> = spec
+        .inputs()
+        .iter()
+        .map(|param| param.type_ident().type_str())
+        .collect();
+    let output = spec.output().map_or("", |output| output.type_ident().type_str());
+    Some(format!("({}) -> {}", inputs.join(", "), output))
+}
+
+fn opcode(instr: &Instruction) -> String {
+    let name = format!("{:?}", instr);
+    match name.find('(') {
+        Some(end) => name[..end].to_string(),
+        None => name,
+    }
+}
+
+/// Weighted Jaccard similarity of the opcode counts of two functions
+fn similarity(a: &BTreeMap<String, usize>, b: &BTreeMap<String, usize>) -> f64 {
+    let (mut shared, mut total) = (0, 0);
+    for (opcode, count) in a {
+        let other = b.get(opcode).copied().unwrap_or_default();
+        shared += (*count).min(other);
+        total += (*count).max(other);
+    }
+    total += b
+        .iter()
+        .filter(|(opcode, _)| !a.contains_key(*opcode))
+        .map(|(_, count)| count)
+        .sum::<usize>();
+    if total == 0 {
+        return 1.0;
+    }
+    shared as f64 / total as f64
+}
+
+struct Matcher<'a> {
+    old: &'a Module,
+    new: &'a Module,
+    old_segments: Segments,
+    new_segments: Segments,
+    /// Old function index to new function index and how they were matched
+    matches: BTreeMap<u32, (u32, MatchKind)>,
+}
+
+impl<'a> Matcher<'a> {
+    fn unmatched_old(&self) -> Vec<u32> {
+        local_functions(self.old)
+            .into_iter()
+            .filter(|i| !self.matches.contains_key(i))
+            .collect()
+    }
+
+    fn unmatched_new(&self) -> Vec<u32> {
+        let matched: Vec<u32> = self.matches.values().map(|(new, _)| *new).collect();
+        local_functions(self.new)
+            .into_iter()
+            .filter(|i| !matched.contains(i))
+            .collect()
+    }
+
+    /// Match the unmatched functions whose key is unique on both sides
+    fn match_unique_keys(
+        &mut self,
+        kind: MatchKind,
+        old_key: impl Fn(u32) -> Option<String>,
+        new_key: impl Fn(u32) -> Option<String>,
+    ) {
+        let mut keys: HashMap<String, (Vec<u32>, Vec<u32>)> = HashMap::new();
+        for i in self.unmatched_old() {
+            if let Some(key) = old_key(i) {
+                keys.entry(key).or_default().0.push(i);
+            }
+        }
+        for i in self.unmatched_new() {
+            if let Some(key) = new_key(i) {
+                keys.entry(key).or_default().1.push(i);
+            }
+        }
+        for (old, new) in keys.into_values() {
+            if let ([old], [new]) = (old.as_slice(), new.as_slice()) {
+                self.matches.insert(*old, (*new, kind));
+            }
+        }
+    }
+
+    fn match_exports(&mut self) {
+        let old_exports = exports(self.old);
+        for (name, new) in exports(self.new) {
+            if let Some(old) = old_exports.get(name) {
+                if !self.old.func(*old).is_imported() && !self.new.func(new).is_imported() {
+                    self.matches.insert(*old, (new, MatchKind::ExportName));
+                }
+            }
+        }
+    }
+
+    fn match_signatures(&mut self) {
+        let (old, new) = (self.old, self.new);
+        self.match_unique_keys(
+            MatchKind::Signature,
+            |i| signature_key(old.func(i)),
+            |i| signature_key(new.func(i)),
+        );
+    }
+
+    fn match_identical_bodies(&mut self) {
+        let (old, new) = (self.old, self.new);
+        let (old_segments, new_segments) = (self.old_segments.clone(), self.new_segments.clone());
+        // Local callees are not matched yet, so only calls of imports can be told apart
+        let body = |module: &Module, segments: &Segments, i: u32| {
+            let callee = |index: u32| match module.func(index) {
+                func if func.is_imported() => func.name().to_string(),
+                _ => String::from("local"),
+            };
+            let func = module.func(i);
+            let instructions = segments.normalize(func.instructions(), callee);
+            Some(format!("{}{}", func.func_type(), instructions.join(" ")))
+        };
+        self.match_unique_keys(
+            MatchKind::IdenticalBody,
+            |i| body(old, &old_segments, i),
+            |i| body(new, &new_segments, i),
+        );
+    }
+
+    /// Pair the remaining functions of the same type greedily by decreasing similarity
+    fn match_similar(&mut self) {
+        let histogram = |func: &Function| {
+            let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+            for instr in func.instructions() {
+                *counts.entry(opcode(instr)).or_default() += 1;
+            }
+            counts
+        };
+        let old: Vec<(u32, BTreeMap<String, usize>)> = self
+            .unmatched_old()
+            .into_iter()
+            .map(|i| (i, histogram(self.old.func(i))))
+            .collect();
+        let new: Vec<(u32, BTreeMap<String, usize>)> = self
+            .unmatched_new()
+            .into_iter()
+            .map(|i| (i, histogram(self.new.func(i))))
+            .collect();
+
+        let mut candidates = Vec::new();
+        for (old_index, old_counts) in &old {
+            for (new_index, new_counts) in &new {
+                if self.old.func(*old_index).func_type() != self.new.func(*new_index).func_type() {
+                    continue;
+                }
+                let score = similarity(old_counts, new_counts);
+                if score >= MIN_SIMILARITY {
+                    candidates.push((score, *old_index, *new_index));
+                }
+            }
+        }
+        candidates.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)).then(a.2.cmp(&b.2)));
+        let mut taken = Vec::new();
+        for (_, old_index, new_index) in candidates {
+            if self.matches.contains_key(&old_index) || taken.contains(&new_index) {
+                continue;
+            }
+            taken.push(new_index);
+            self.matches.insert(old_index, (new_index, MatchKind::Similarity));
+        }
+    }
+
+    /// Whether the matched functions differ in more than the indices of the functions they call
+    fn changed(&self, old_index: u32, new_index: u32) -> bool {
+        let callee = |module: &Module, index: u32| match module.func(index) {
+            func if func.is_imported() => func.name().to_string(),
+            _ => format!("func_{}", index),
+        };
+        let old_callee = |index: u32| match self.matches.get(&index) {
+            Some((new, _)) => callee(self.new, *new),
+            None if self.old.func(index).is_imported() => callee(self.old, index),
+            None => String::from("?"),
+        };
+        let (old, new) = (self.old.func(old_index), self.new.func(new_index));
+        old.func_type() != new.func_type()
+            || signature_key(old) != signature_key(new)
+            || old.instructions().len() != new.instructions().len()
+            || self.old_segments.normalize(old.instructions(), old_callee)
+                != self
+                    .new_segments
+                    .normalize(new.instructions(), |index| callee(self.new, index))
+    }
+}
+
+/// Decompiled body of a function, errors are part of the text so they show up in the diff
+fn decompile(wasm: &Instance, func_index: u32) -> String {
+    wasm.decompile_function_to_string(func_index, WriterOptions::default())
+        .unwrap_or_else(|e| format!("// can not be decompiled: {:?}\n", e))
+}
+
+/// Unified diff of the decompiled bodies with the local functions the old body calls renamed to
+/// their match in the new binary
+fn body_diff(old: &Instance, new: &Instance, old_index: u32, new_index: u32, matcher: &Matcher) -> String {
+    let old_code = decompile(old, old_index);
+    let old_code = Regex::new(r"\bfunc_(\d+)\b")
+        .unwrap()
+        .replace_all(&old_code, |captures: &Captures| {
+            match captures[1]
+                .parse::<u32>()
+                .ok()
+                .and_then(|index| matcher.matches.get(&index))
+            {
+                Some((new_callee, _)) => format!("func_{}", new_callee),
+                None => captures[0].to_string(),
+            }
+        })
+        .into_owned();
+    text::unified_diff(
+        &old_code,
+        &decompile(new, new_index),
+        &format!("old/{}", old.module().func(old_index).name()),
+        &format!("new/{}", new.module().func(new_index).name()),
+        CONTEXT_LINES,
+    )
+}
+
+fn type_changes(old: &Module, new: &Module) -> Vec<TypeChange> {
+    let old_types: BTreeMap<&str, &str> = old
+        .spec_types()
+        .iter()
+        .map(|spec_type| (spec_type.name.as_str(), spec_type.definition.as_str()))
+        .collect();
+    let new_types: BTreeMap<&str, &str> = new
+        .spec_types()
+        .iter()
+        .map(|spec_type| (spec_type.name.as_str(), spec_type.definition.as_str()))
+        .collect();
+    let mut names: Vec<&str> = old_types.keys().chain(new_types.keys()).copied().collect();
+    names.sort_unstable();
+    names.dedup();
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let (kind, old_definition, new_definition) = match (old_types.get(name), new_types.get(name)) {
+                (Some(old), Some(new)) if old != new => (ChangeKind::Changed, *old, *new),
+                (Some(old), None) => (ChangeKind::Removed, *old, ""),
+                (None, Some(new)) => (ChangeKind::Added, "", *new),
+                _ => return None,
+            };
+            Some(TypeChange {
+                kind,
+                name: name.to_string(),
+                diff: text::unified_diff(
+                    old_definition,
+                    new_definition,
+                    &format!("old/{}", name),
+                    &format!("new/{}", name),
+                    CONTEXT_LINES,
+                ),
+            })
+        })
+        .collect()
+}
+
+/// Match the functions of the two versions of a contract and report what differs
+pub fn compare(old: &Instance, new: &Instance, options: &Options) -> DiffReport {
+    let mut matcher = Matcher {
+        old: old.module(),
+        new: new.module(),
+        old_segments: Segments::new(old.module()),
+        new_segments: Segments::new(new.module()),
+        matches: BTreeMap::new(),
+    };
+    matcher.match_exports();
+    matcher.match_signatures();
+    matcher.match_identical_bodies();
+    matcher.match_similar();
+
+    let mut report = DiffReport {
+        types: type_changes(old.module(), new.module()),
+        ..DiffReport::default()
+    };
+    for (&old_index, &(new_index, kind)) in &matcher.matches {
+        if !matcher.changed(old_index, new_index) {
+            report.unchanged += 1;
+            continue;
+        }
+        report.functions.push(FunctionChange {
+            kind: ChangeKind::Changed,
+            old: Some(FunctionRef::new(old.module(), old_index)),
+            new: Some(FunctionRef::new(new.module(), new_index)),
+            matched_by: Some(kind),
+            diff: options
+                .bodies
+                .then(|| body_diff(old, new, old_index, new_index, &matcher)),
+        });
+    }
+    for old_index in matcher.unmatched_old() {
+        report.functions.push(FunctionChange {
+            kind: ChangeKind::Removed,
+            old: Some(FunctionRef::new(old.module(), old_index)),
+            new: None,
+            matched_by: None,
+            diff: None,
+        });
+    }
+    for new_index in matcher.unmatched_new() {
+        report.functions.push(FunctionChange {
+            kind: ChangeKind::Added,
+            old: None,
+            new: Some(FunctionRef::new(new.module(), new_index)),
+            matched_by: None,
+            diff: None,
+        });
+    }
+    report
+}
@@ -0,0 +1,80 @@
+//! Unified diffs of decompiled code.
+
+use lcs::{DiffComponent, LcsTable};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Line {
+    Unchanged,
+    Deleted,
+    Inserted,
+}
+
+/// A unified diff of `old` and `new` with `context` unchanged lines around each change, empty if
+/// they are equal
+pub fn unified_diff(old: &str, new: &str, old_label: &str, new_label: &str, context: usize) -> String {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let table = LcsTable::new(&old_lines, &new_lines);
+    let lines: Vec<(Line, &str)> = table
+        .diff()
+        .into_iter()
+        .map(|component| match component {
+            DiffComponent::Unchanged(line, _) => (Line::Unchanged, *line),
+            DiffComponent::Deletion(line) => (Line::Deleted, *line),
+            DiffComponent::Insertion(line) => (Line::Inserted, *line),
+        })
+        .collect();
+
+    let changes: Vec<usize> = (0..lines.len()).filter(|i| lines[*i].0 != Line::Unchanged).collect();
+    if changes.is_empty() {
+        return String::new();
+    }
+    // Changes closer than twice the context share a hunk
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for &i in &changes {
+        match hunks.last_mut() {
+            Some((_, end)) if i <= *end + 2 * context + 1 => *end = i,
+            _ => hunks.push((i, i)),
+        }
+    }
+
+    // Line numbers in the old and new text before each diff line
+    let mut positions = Vec::with_capacity(lines.len() + 1);
+    let (mut old_pos, mut new_pos) = (0, 0);
+    for (kind, _) in &lines {
+        positions.push((old_pos, new_pos));
+        match kind {
+            Line::Unchanged => (old_pos, new_pos) = (old_pos + 1, new_pos + 1),
+            Line::Deleted => old_pos += 1,
+            Line::Inserted => new_pos += 1,
+        }
+    }
+    positions.push((old_pos, new_pos));
+
+    let mut diff = format!("--- {}\n+++ {}\n", old_label, new_label);
+    for (first, last) in hunks {
+        let start = first.saturating_sub(context);
+        let end = (last + context + 1).min(lines.len());
+        let (old_start, new_start) = positions[start];
+        let (old_end, new_end) = positions[end];
+        let range = |start: usize, len: usize| if len == 0 { start } else { start + 1 };
+        diff.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            range(old_start, old_end - old_start),
+            old_end - old_start,
+            range(new_start, new_end - new_start),
+            new_end - new_start
+        ));
+        for (kind, line) in &lines[start..end] {
+            let marker = match kind {
+                Line::Unchanged => ' ',
+                Line::Deleted => '-',
+                Line::Inserted => '+',
+            };
+            diff.push(marker);
+            diff.push_str(line);
+            diff.push('\n');
+        }
+    }
+    diff
+}
@@ -0,0 +1,240 @@
+//! Differential testing of a reconstructed contract against the original wasm.
+//!
+//! Both binaries run on the mock host with the same random, spec-typed arguments. The calls form
+//! a sequence: every call starts from the storage the original left behind, so later functions
+//! see initialized state while each call is still compared on identical inputs.
+
+pub mod project;
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::Serialize;
+use soroban_sdk::xdr::{ScSpecEntry, ScVal};
+
+use crate::run::inputs::InputGenerator;
+use crate::run::{Contract, HostConfig, RunError, RunResult, StorageEntry};
+use crate::soroban::host::StorageType;
+
+#[derive(Debug)]
+pub enum DiffError {
+    /// The contract spec of the original could not be read
+    Spec(String),
+    /// The reconstruction does not compile
+    Build(String),
+}
+
+impl fmt::Display for DiffError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Spec(error) => write!(f, "Failed to read the contract spec: {}", error),
+            Self::Build(error) => write!(f, "Failed to build the reconstruction: {}", error),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Calls per function
+    pub cases: usize,
+    pub seed: u64,
+    /// Only compare these functions, all spec functions if empty
+    pub functions: Vec<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            cases: 16,
+            seed: 0,
+            functions: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DivergenceKind {
+    /// Only one of the two trapped
+    Trap,
+    Result,
+    Storage,
+    Events,
+}
+
+impl fmt::Display for DivergenceKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Trap => "trap",
+            Self::Result => "return value",
+            Self::Storage => "storage writes",
+            Self::Events => "events",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A call on which the reconstruction behaves differently
+#[derive(Debug, Clone, Serialize)]
+pub struct Divergence {
+    pub case: usize,
+    pub args: Vec<ScVal>,
+    pub kind: DivergenceKind,
+    pub original: serde_json::Value,
+    pub reconstruction: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FunctionReport {
+    pub name: String,
+    pub cases: usize,
+    /// Calls that trapped in both binaries
+    pub traps: usize,
+    pub divergences: Vec<Divergence>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DiffReport {
+    pub functions: Vec<FunctionReport>,
+}
+
+impl DiffReport {
+    pub fn divergences(&self) -> usize {
+        self.functions.iter().map(|function| function.divergences.len()).sum()
+    }
+}
+
+/// Call `func`, errors of the module itself count as a trap
+fn execute(contract: &Result<Contract, RunError>, func: &str, args: &[ScVal], config: HostConfig) -> RunResult {
+    contract
+        .as_ref()
+        .map_err(RunError::to_string)
+        .and_then(|contract| contract.run(func, args, config).map_err(|e| e.to_string()))
+        .unwrap_or_else(|error| RunResult {
+            error: Some(error),
+            ..RunResult::default()
+        })
+}
+
+fn json<T: Serialize>(value: &T) -> serde_json::Value {
+    serde_json::to_value(value).unwrap_or_default()
+}
+
+/// How the two results differ. Storage and events of trapped calls are rolled back, so they are
+/// only compared if neither call trapped.
+fn diff(
+    original: &RunResult,
+    reconstruction: &RunResult,
+) -> Vec<(DivergenceKind, serde_json::Value, serde_json::Value)> {
+    let mut diffs = Vec::new();
+    match (&original.error, &reconstruction.error) {
+        (None, None) => {
+            if original.result != reconstruction.result {
+                diffs.push((
+                    DivergenceKind::Result,
+                    json(&original.result),
+                    json(&reconstruction.result),
+                ));
+            }
+            if original.storage != reconstruction.storage {
+                diffs.push((
+                    DivergenceKind::Storage,
+                    json(&original.storage),
+                    json(&reconstruction.storage),
+                ));
+            }
+            if original.events != reconstruction.events {
+                diffs.push((
+                    DivergenceKind::Events,
+                    json(&original.events),
+                    json(&reconstruction.events),
+                ));
+            }
+        }
+        (Some(_), Some(_)) => (),
+        _ => {
+            let outcome = |result: &RunResult| match &result.error {
+                Some(error) => serde_json::json!({ "trapped": error }),
+                None => json(&result.result),
+            };
+            diffs.push((DivergenceKind::Trap, outcome(original), outcome(reconstruction)));
+        }
+    }
+    diffs
+}
+
+/// Run every spec function of `original` on random inputs in both binaries and compare the results
+pub fn compare(original: &[u8], reconstruction: &[u8], options: &Options) -> Result<DiffReport, DiffError> {
+    let entries = soroban_spec::read::from_wasm(original).map_err(|e| DiffError::Spec(format!("{:?}", e)))?;
+    let functions: Vec<_> = entries
+        .iter()
+        .filter_map(|entry| match entry {
+            ScSpecEntry::FunctionV0(func) => Some(func),
+            _ => None,
+        })
+        .filter(|func| {
+            let name = func.name.to_utf8_string_lossy();
+            !name.starts_with("__") && (options.functions.is_empty() || options.functions.contains(&name))
+        })
+        .collect();
+
+    let original = Contract::new(original);
+    let reconstruction = Contract::new(reconstruction);
+    let mut generator = InputGenerator::new(&entries, options.seed);
+    let mut storage: BTreeMap<(StorageType, ScVal), ScVal> = BTreeMap::new();
+    let mut report = DiffReport {
+        functions: functions
+            .iter()
+            .map(|func| FunctionReport {
+                name: func.name.to_utf8_string_lossy(),
+                cases: 0,
+                traps: 0,
+                divergences: Vec::new(),
+            })
+            .collect(),
+    };
+
+    for case in 0..options.cases {
+        for (func, function_report) in functions.iter().zip(&mut report.functions) {
+            let args = generator.args(func);
+            let config = HostConfig {
+                storage: storage
+                    .iter()
+                    .map(|((storage_type, key), val)| StorageEntry {
+                        storage_type: *storage_type,
+                        key: key.clone(),
+                        val: val.clone(),
+                    })
+                    .collect(),
+                ..HostConfig::default()
+            };
+            let original_result = execute(&original, &function_report.name, &args, config.clone());
+            let reconstruction_result = execute(&reconstruction, &function_report.name, &args, config);
+
+            function_report.cases += 1;
+            if original_result.error.is_some() && reconstruction_result.error.is_some() {
+                function_report.traps += 1;
+            }
+            for (kind, original_value, reconstruction_value) in diff(&original_result, &reconstruction_result) {
+                function_report.divergences.push(Divergence {
+                    case,
+                    args: args.clone(),
+                    kind,
+                    original: original_value,
+                    reconstruction: reconstruction_value,
+                });
+            }
+
+            if original_result.error.is_none() {
+                for change in original_result.storage {
+                    let key = (change.storage_type, change.key);
+                    match change.after {
+                        Some(val) => storage.insert(key, val),
+                        None => storage.remove(&key),
+                    };
+                }
+            }
+        }
+    }
+    Ok(report)
+}
@@ -0,0 +1,104 @@
+//! The decompiled contract as a cargo project that builds to wasm.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::fmt::WriterOptions;
+use crate::wasm_wrapper::wasm::Instance;
+
+use super::DiffError;
+
+const TARGET: &str = "wasm32-unknown-unknown";
+
+/// soroban-sdk version of the reconstruction when the binary records none and none was inferred
+pub const DEFAULT_SDK_VERSION: &str = "20.5.0";
+
+const MANIFEST: &str = r#"[package]
+name = "reconstruction"
+version = "0.1.0"
+edition = "2021"
+
+[lib]
+crate-type = ["cdylib"]
+
+[dependencies]
+soroban-sdk = "={sdk_version}"
+
+[profile.release]
+opt-level = "z"
+overflow-checks = true
+panic = "abort"
+codegen-units = 1
+lto = true
+"#;
+
+/// Rust source of the whole contract: the spec types and the exported functions in a
+/// `#[contractimpl]` block. Calls of local functions are inlined by the decompiler.
+pub fn reconstruct(wasm: &Instance) -> String {
+    let module = wasm.module();
+    let mut source = String::from("#![no_std]\nuse soroban_sdk::*;\n\n");
+    for spec_type in module.spec_types() {
+        source.push_str(&spec_type.definition);
+        source.push_str("\n\n");
+    }
+    source.push_str("#[contract]\npub struct Contract;\n\n#[contractimpl]\nimpl Contract {\n");
+    for (i, func) in module.functions().iter().enumerate() {
+        if func.is_imported() || !func.has_spec() || !module.is_exported(i as u32) {
+            continue;
+        }
+        match wasm.decompile_function_to_string(i as u32, WriterOptions::default()) {
+            Ok(code) => {
+                for line in code.lines() {
+                    source.push_str("    ");
+                    source.push_str(line);
+                    source.push('\n');
+                }
+            }
+            Err(_) => source.push_str(&format!("    // {} can not be decompiled\n", func.name())),
+        }
+    }
+    source.push_str("}\n");
+    source
+}
+
+/// Write a cargo project with `source` as its library. It depends on `sdk_version` of soroban-sdk,
+/// usually the one of [`BuildInfo`](crate::soroban::BuildInfo), so the SDK helpers match the original.
+pub fn write_project(dir: &Path, source: &str, sdk_version: Option<&str>) -> std::io::Result<()> {
+    let manifest = MANIFEST.replace("{sdk_version}", sdk_version.unwrap_or(DEFAULT_SDK_VERSION));
+    fs::create_dir_all(dir.join("src"))?;
+    fs::write(dir.join("Cargo.toml"), manifest)?;
+    fs::write(dir.join("src").join("lib.rs"), source)
+}
+
+/// Name of the wasm file the library of the project builds to
+fn lib_name(manifest: &Path) -> Result<String, DiffError> {
+    let manifest = fs::read_to_string(manifest).map_err(|e| DiffError::Build(e.to_string()))?;
+    let manifest: toml::Table = manifest
+        .parse()
+        .map_err(|e: toml::de::Error| DiffError::Build(e.to_string()))?;
+    let name = manifest
+        .get("lib")
+        .and_then(|lib| lib.get("name"))
+        .or_else(|| manifest.get("package").and_then(|package| package.get("name")))
+        .and_then(|name| name.as_str())
+        .ok_or_else(|| DiffError::Build(String::from("Cargo.toml has no package name")))?;
+    Ok(name.replace('-', "_"))
+}
+
+/// Build the cargo project in `dir` for wasm and return the binary
+pub fn build_project(dir: &Path) -> Result<Vec<u8>, DiffError> {
+    let manifest = dir.join("Cargo.toml");
+    let name = lib_name(&manifest)?;
+    let output = Command::new("cargo")
+        .args(["build", "--release", "--target", TARGET, "--manifest-path"])
+        .arg(&manifest)
+        .output()
+        .map_err(|e| DiffError::Build(format!("Failed to run cargo: {}", e)))?;
+    if !output.status.success() {
+        return Err(DiffError::Build(String::from_utf8_lossy(&output.stderr).into_owned()));
+    }
+    let target_dir = std::env::var_os("CARGO_TARGET_DIR").map_or_else(|| dir.join("target"), PathBuf::from);
+    let path = target_dir.join(TARGET).join("release").join(format!("{}.wasm", name));
+    fs::read(&path).map_err(|e| DiffError::Build(format!("{}: {}", path.display(), e)))
+}
@@ -60,6 +60,17 @@ impl DomTree {
     pub fn build(cfg: &Cfg) -> Self {
         DomTreeBuilder::new(cfg).build()
     }
+
+    pub fn dot_string(&self) -> String {
+        let edges = self
+            .succs
+            .iter()
+            .enumerate()
+            .flat_map(|(i, succs)| succs.iter().map(move |s| format!("\t{} -> {}", i, s)))
+            .collect::<Vec<_>>()
+            .join("\n");
+        format!("digraph G {{\n\tstart -> 0\n{}\n}}", edges)
+    }
 }
 
 struct DomTreeBuilder<'a> {
@@ -1,7 +1,6 @@
 use crate::analysis;
 use crate::cfg::{Cfg, CfgBuildError};
-use crate::soroban::sdk_linker::search_for_patterns;
-use crate::soroban::FunctionInfo;
+use crate::soroban::{self, FunctionInfo};
 use crate::ssa;
 use crate::ssa::Expr;
 use crate::structuring;
@@ -69,12 +68,24 @@ impl Output {
     }
 }
 
+/// Options that control what the decompiler emits
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WriterOptions {
+    /// Print a `// @0x..` comment with the code section offset before each statement
+    pub offsets: bool,
+}
+
 pub struct CodeWriter {
     indent: usize,
     wasm: Rc<wasm::Instance>,
     func_index: u32,
     output: Output,
     suppress_newline: bool,
+    options: WriterOptions,
+    matched_patterns: Vec<String>,
+    warnings: Vec<String>,
+    /// Basic blocks of the CFG of the last function decompiled outside of a call
+    blocks: usize,
 }
 
 impl CodeWriter {
@@ -85,6 +96,10 @@ impl CodeWriter {
             func_index,
             output: Output::str(),
             suppress_newline: false,
+            options: WriterOptions::default(),
+            matched_patterns: Vec::new(),
+            warnings: Vec::new(),
+            blocks: 0,
         }
     }
 
@@ -95,6 +110,40 @@ impl CodeWriter {
             func_index,
             output: Output::stdout(),
             suppress_newline: false,
+            options: WriterOptions::default(),
+            matched_patterns: Vec::new(),
+            warnings: Vec::new(),
+            blocks: 0,
+        }
+    }
+
+    pub fn with_options(mut self, options: WriterOptions) -> CodeWriter {
+        self.options = options;
+        self
+    }
+
+    pub const fn options(&self) -> WriterOptions {
+        self.options
+    }
+
+    /// Names of the SDK patterns that were substituted into the output
+    pub fn matched_patterns(&self) -> &[String] {
+        &self.matched_patterns
+    }
+
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// Number of basic blocks of the decompiled function
+    pub const fn blocks(&self) -> usize {
+        self.blocks
+    }
+
+    /// Add a warning, each warning is only reported once
+    pub fn warn(&mut self, warning: String) {
+        if !self.warnings.contains(&warning) {
+            self.warnings.push(warning);
         }
     }
 
@@ -127,7 +176,29 @@ impl CodeWriter {
     }
 
     pub fn string_func(&mut self, fmt: impl CodeDisplay) -> String {
-        fmt.create_str(self.wasm.clone(), self.func_index)
+        let mut writer = CodeWriter::formatter(self.wasm.clone(), self.func_index).with_options(self.options);
+        fmt.fmt_code(&mut writer);
+        self.matched_patterns.append(&mut writer.matched_patterns);
+        for warning in std::mem::take(&mut writer.warnings) {
+            self.warn(warning);
+        }
+        writer.get_output()
+    }
+
+    /// Replace the SDK code matched by the patterns of the contract's SDK version
+    pub fn link_patterns(&mut self, code: &[Stmt]) -> Vec<Stmt> {
+        let wasm = self.wasm.clone();
+        match wasm.module().patterns() {
+            Ok(patterns) => {
+                let matches = soroban::link_patterns(patterns, code.to_vec(), wasm.module(), self.func());
+                self.matched_patterns.extend(matches.matched);
+                matches.code
+            }
+            Err(err) => {
+                self.warn(format!("Error loading patterns: {}", err));
+                code.to_vec()
+            }
+        }
     }
 
     pub fn write_fmt(&mut self, args: std::fmt::Arguments) {
@@ -140,7 +211,14 @@ impl CodeWriter {
         is_call: bool,
         args: &[Expr],
     ) -> Result<Vec<Stmt>, CfgBuildError> {
-        let mut cfg = Cfg::build(self.wasm.clone(), func_index)?;
+        let mut cfg = if self.options.offsets {
+            Cfg::build_with_offsets(self.wasm.clone(), func_index)?
+        } else {
+            Cfg::build(self.wasm.clone(), func_index)?
+        };
+        if !is_call {
+            self.blocks = cfg.nodes.len();
+        }
         let mut def_use_map = ssa::transform_to_ssa(&mut cfg);
         analysis::propagate_expressions(&mut cfg, &mut def_use_map);
         analysis::eliminate_dead_code(&mut cfg, &mut def_use_map);
@@ -187,29 +265,20 @@ impl CodeWriter {
                 code_to_write.push_str(func_header.as_str());
             }
 
+            let code = self.link_patterns(code);
             let code_string = &self.string_func(&code[..]);
             let code_clean = self.clean_lines(code_string);
-
-            let enable_lsh = true;
-            if enable_lsh {
-                let replaced_body = search_for_patterns(&code_clean);
-                if let Some(replaced_body_value) = replaced_body {
-                    code_to_write.push_str(&replaced_body_value.as_str());
-                } else {
-                    code_to_write.push_str(&code_clean[..]);
-                }
-            } else {
-                code_to_write.push_str(&code_clean[..]);
-            }
+            code_to_write.push_str(&code_clean[..]);
 
             self.dedent();
             if !is_call {
                 code_to_write.push_str("\n}\n\n");
             }
 
+            // prettyplease drops comments, so keep the offset annotations as they are
             let formatted = match CodeWriter::prettify(&code_to_write) {
-                Ok(formatted) => formatted,
-                Err(_) => {
+                Ok(formatted) if !self.options.offsets => formatted,
+                _ => {
                     code_to_write
                 }
             };
@@ -280,6 +349,7 @@ impl CodeWriter {
     }
 
     pub fn write_body(&mut self, code: &[Stmt]) {
+        let code = self.link_patterns(code);
         self.write(&code[..])
     }
 
@@ -0,0 +1,59 @@
+//! Inputs kept between fuzzing sessions.
+//!
+//! Every function has a directory of JSON files, each holding the arguments of one input.
+//! Crashing inputs go to `crashes/` next to them.
+
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use soroban_sdk::xdr::ScVal;
+
+const CRASHES: &str = "crashes";
+
+#[derive(Debug, Clone)]
+pub struct Corpus {
+    dir: PathBuf,
+}
+
+impl Corpus {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Corpus { dir: dir.into() }
+    }
+
+    /// The saved inputs of a function, files that do not parse are skipped
+    pub fn load(&self, function: &str) -> io::Result<Vec<Vec<ScVal>>> {
+        let dir = self.dir.join(function);
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut paths: Vec<PathBuf> = fs::read_dir(&dir)?
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        paths.sort();
+        Ok(paths
+            .iter()
+            .filter_map(|path| serde_json::from_str(&fs::read_to_string(path).ok()?).ok())
+            .collect())
+    }
+
+    pub fn save(&self, function: &str, args: &[ScVal]) -> io::Result<()> {
+        save(&self.dir.join(function), function, args)
+    }
+
+    pub fn save_crash(&self, function: &str, args: &[ScVal]) -> io::Result<()> {
+        save(&self.dir.join(CRASHES), function, args)
+    }
+}
+
+/// Write the input to `dir`, named after its hash so that saving it again is a no-op
+fn save(dir: &Path, prefix: &str, args: &[ScVal]) -> io::Result<()> {
+    let json = serde_json::to_string(args)?;
+    let mut hasher = DefaultHasher::new();
+    json.hash(&mut hasher);
+    fs::create_dir_all(dir)?;
+    fs::write(dir.join(format!("{}-{:016x}.json", prefix, hasher.finish())), json)
+}
@@ -0,0 +1,115 @@
+//! Basic-block coverage of contract functions.
+//!
+//! Every node of a function's `Cfg` that holds code gets a probe in front of the instruction its
+//! first statement comes from. The probe sets the node's bit in a bitmap of i64 globals that are
+//! exported as `run::COVERAGE_GLOBAL` and read back after each call.
+
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use parity_wasm::elements::{
+    ExportEntry, ExportSection, GlobalEntry, GlobalSection, GlobalType, InitExpr, Instruction, Internal, Section,
+    ValueType,
+};
+
+use crate::cfg::{Cfg, NodeId};
+use crate::run::COVERAGE_GLOBAL;
+use crate::ssa::Stmt;
+use crate::wasm_wrapper::wasm::Instance;
+
+/// A block is the `Cfg` node `node` of the function `func_index`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Block {
+    pub func_index: u32,
+    pub node: NodeId,
+}
+
+/// A binary with coverage probes
+#[derive(Debug, Clone)]
+pub struct Instrumented {
+    pub code: Vec<u8>,
+    /// Block `i` is bit `i % 64` of the global `i / 64`
+    pub blocks: Vec<Block>,
+}
+
+/// The blocks of a function by the index of the instruction their probe goes in front of
+fn probe_positions(wasm: &Rc<Instance>, func_index: u32) -> BTreeMap<usize, NodeId> {
+    let mut positions = BTreeMap::new();
+    let cfg = match Cfg::build_with_offsets(wasm.clone(), func_index) {
+        Ok(cfg) => cfg,
+        Err(_) => return positions,
+    };
+    for (node, block) in cfg.nodes.iter() {
+        let instr = block.code.iter().find_map(|stmt| match stmt {
+            Stmt::Offset(pos) => Some(pos.instr as usize),
+            _ => None,
+        });
+        if let Some(instr) = instr {
+            positions.entry(instr).or_insert(node);
+        }
+    }
+    positions
+}
+
+fn probe(global: u32, bit: usize) -> [Instruction; 4] {
+    [
+        Instruction::GetGlobal(global),
+        Instruction::I64Const(1 << bit),
+        Instruction::I64Or,
+        Instruction::SetGlobal(global),
+    ]
+}
+
+/// Add coverage probes to `code`, the binary `wasm` was loaded from
+pub fn instrument(wasm: &Rc<Instance>, code: &[u8]) -> Result<Instrumented, parity_wasm::elements::Error> {
+    let module = wasm.module();
+    let mut binary: parity_wasm::elements::Module = parity_wasm::deserialize_buffer(code)?;
+    let first_global = module.globals().len() as u32;
+    let imported_funcs = module.functions().iter().filter(|func| func.is_imported()).count();
+
+    let mut blocks = Vec::new();
+    if let Some(code_section) = binary.code_section_mut() {
+        for (i, body) in code_section.bodies_mut().iter_mut().enumerate() {
+            let func_index = (imported_funcs + i) as u32;
+            let instructions = body.code_mut().elements_mut();
+            let mut probed = Vec::with_capacity(instructions.len());
+            let mut positions = probe_positions(wasm, func_index).into_iter().peekable();
+            for (instr_index, instr) in instructions.drain(..).enumerate() {
+                if let Some((_, node)) = positions.next_if(|(pos, _)| *pos == instr_index) {
+                    let id = blocks.len();
+                    blocks.push(Block { func_index, node });
+                    probed.extend(probe(first_global + (id / 64) as u32, id % 64));
+                }
+                probed.push(instr);
+            }
+            *instructions = probed;
+        }
+    }
+
+    let globals = blocks.len().div_ceil(64);
+    let entries = (0..globals).map(|_| {
+        GlobalEntry::new(
+            GlobalType::new(ValueType::I64, true),
+            InitExpr::new(vec![Instruction::I64Const(0), Instruction::End]),
+        )
+    });
+    match binary.global_section_mut() {
+        Some(section) => section.entries_mut().extend(entries),
+        None => binary.insert_section(Section::Global(GlobalSection::with_entries(entries.collect())))?,
+    }
+    let exports = (0..globals).map(|i| {
+        ExportEntry::new(
+            format!("{}{}", COVERAGE_GLOBAL, i),
+            Internal::Global(first_global + i as u32),
+        )
+    });
+    match binary.export_section_mut() {
+        Some(section) => section.entries_mut().extend(exports),
+        None => binary.insert_section(Section::Export(ExportSection::with_entries(exports.collect())))?,
+    }
+
+    Ok(Instrumented {
+        code: parity_wasm::serialize(binary)?,
+        blocks,
+    })
+}
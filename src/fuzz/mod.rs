@@ -0,0 +1,263 @@
+//! Coverage-guided fuzzing of exported contract functions.
+//!
+//! Inputs are spec-typed arguments that run on the mock host in a binary instrumented with block
+//! probes. Inputs that reach new blocks join the corpus and are mutated further. A trap that is
+//! neither an explicit contract error nor a host function the mock host lacks is a finding; its
+//! input is minimized before it is reported.
+
+pub mod corpus;
+pub mod coverage;
+
+use std::collections::HashSet;
+use std::fmt;
+use std::rc::Rc;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+use soroban_sdk::xdr::{ScError, ScSpecEntry, ScSpecFunctionV0, ScVal};
+
+use crate::run::inputs::{self, InputGenerator};
+use crate::run::{self, Contract, HostConfig, HostErrorKind, RunResult, StorageEntry};
+use crate::wasm_wrapper::wasm::Instance;
+use corpus::Corpus;
+
+/// Runs spent on minimizing one crashing input
+const MAX_SHRINK_RUNS: usize = 200;
+
+#[derive(Debug)]
+pub enum FuzzError {
+    /// The contract spec could not be read
+    Spec(String),
+    Instrument(parity_wasm::elements::Error),
+    /// The instrumented contract could not be compiled or linked
+    Load(run::RunError),
+    Corpus(std::io::Error),
+}
+
+impl fmt::Display for FuzzError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Spec(error) => write!(f, "Failed to read the contract spec: {}", error),
+            Self::Instrument(error) => write!(f, "Failed to instrument the contract: {}", error),
+            Self::Load(error) => write!(f, "Failed to load the instrumented contract: {}", error),
+            Self::Corpus(error) => write!(f, "Failed to access the corpus: {}", error),
+        }
+    }
+}
+
+impl From<std::io::Error> for FuzzError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Corpus(error)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Only fuzz these functions, all spec functions if empty
+    pub functions: Vec<String>,
+    /// Runs per function
+    pub runs: usize,
+    pub seed: u64,
+    /// Storage before every call
+    pub storage: Vec<StorageEntry>,
+    pub corpus: Option<Corpus>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            functions: Vec::new(),
+            runs: 256,
+            seed: 0,
+            storage: Vec::new(),
+            corpus: None,
+        }
+    }
+}
+
+/// A crashing input, minimized
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+    pub function: String,
+    pub args: Vec<ScVal>,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FunctionStats {
+    pub name: String,
+    pub runs: usize,
+    /// Blocks reached by calls of this function, including the functions it calls
+    pub covered: usize,
+    pub corpus: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FuzzReport {
+    pub blocks: usize,
+    pub covered: usize,
+    pub functions: Vec<FunctionStats>,
+    pub findings: Vec<Finding>,
+}
+
+/// Whether a call crashed: it trapped without failing with a contract error. Calls of host
+/// functions the mock host does not implement are a gap of the fuzzer, not of the contract.
+fn is_crash(result: &RunResult) -> bool {
+    result.error.is_some()
+        && result.host_error != Some(HostErrorKind::Unsupported)
+        && !matches!(result.failure, Some(ScVal::Error(ScError::Contract(_))))
+}
+
+/// Set the bits of `coverage` in `total`, returns whether any was new
+fn merge(total: &mut [u64], coverage: &[u64]) -> bool {
+    let mut new = false;
+    for (total, bits) in total.iter_mut().zip(coverage) {
+        new |= bits & !*total != 0;
+        *total |= bits;
+    }
+    new
+}
+
+fn count(bitmap: &[u64]) -> usize {
+    bitmap.iter().map(|bits| bits.count_ones() as usize).sum()
+}
+
+struct Fuzzer<'a> {
+    contract: Contract,
+    options: &'a Options,
+    generator: InputGenerator,
+    rng: StdRng,
+    /// Blocks reached by any call
+    total: Vec<u64>,
+}
+
+impl<'a> Fuzzer<'a> {
+    fn execute(&self, function: &str, args: &[ScVal]) -> RunResult {
+        let config = HostConfig {
+            storage: self.options.storage.clone(),
+            ..HostConfig::default()
+        };
+        self.contract.run(function, args, config).unwrap_or_else(|e| RunResult {
+            error: Some(e.to_string()),
+            ..RunResult::default()
+        })
+    }
+
+    /// Shrink the arguments one at a time for as long as the call crashes with the same error
+    fn minimize(&self, function: &str, mut args: Vec<ScVal>, error: &str) -> Vec<ScVal> {
+        let mut runs = 0;
+        let mut progress = true;
+        while progress && runs < MAX_SHRINK_RUNS {
+            progress = false;
+            for i in 0..args.len() {
+                for smaller in inputs::shrink(&args[i]) {
+                    if runs == MAX_SHRINK_RUNS {
+                        return args;
+                    }
+                    runs += 1;
+                    let mut candidate = args.clone();
+                    candidate[i] = smaller;
+                    let result = self.execute(function, &candidate);
+                    if is_crash(&result) && result.error.as_deref() == Some(error) {
+                        args = candidate;
+                        progress = true;
+                        break;
+                    }
+                }
+            }
+        }
+        args
+    }
+
+    fn fuzz_function(
+        &mut self,
+        func: &ScSpecFunctionV0,
+        findings: &mut Vec<Finding>,
+    ) -> Result<FunctionStats, FuzzError> {
+        let name = func.name.to_utf8_string_lossy();
+        let mut queue = match &self.options.corpus {
+            Some(corpus) => corpus.load(&name)?,
+            None => Vec::new(),
+        };
+        let saved = queue.len();
+        let mut covered = vec![0; self.total.len()];
+        let mut errors: HashSet<String> = HashSet::new();
+
+        for run in 0..self.options.runs {
+            // Replay the corpus first, then mutate it with the odd fresh input in between
+            let args = if run < saved {
+                queue[run].clone()
+            } else if queue.is_empty() || self.rng.gen_ratio(1, 4) {
+                self.generator.args(func)
+            } else {
+                let parent = queue[self.rng.gen_range(0..queue.len())].clone();
+                self.generator.mutate(func, &parent)
+            };
+            let result = self.execute(&name, &args);
+            merge(&mut covered, &result.coverage);
+            if merge(&mut self.total, &result.coverage) && run >= saved {
+                if let Some(corpus) = &self.options.corpus {
+                    corpus.save(&name, &args)?;
+                }
+                queue.push(args.clone());
+            }
+
+            let error = match &result.error {
+                Some(error) if is_crash(&result) && !errors.contains(error) => error.clone(),
+                _ => continue,
+            };
+            errors.insert(error.clone());
+            let args = self.minimize(&name, args, &error);
+            if let Some(corpus) = &self.options.corpus {
+                corpus.save_crash(&name, &args)?;
+            }
+            findings.push(Finding {
+                function: name.clone(),
+                args,
+                error,
+            });
+        }
+
+        Ok(FunctionStats {
+            name,
+            runs: self.options.runs,
+            covered: count(&covered),
+            corpus: queue.len(),
+        })
+    }
+}
+
+/// Fuzz the spec functions of the contract `code`, which `wasm` was loaded from
+pub fn fuzz(wasm: &Rc<Instance>, code: &[u8], options: &Options) -> Result<FuzzReport, FuzzError> {
+    let entries = soroban_spec::read::from_wasm(code).map_err(|e| FuzzError::Spec(format!("{:?}", e)))?;
+    let instrumented = coverage::instrument(wasm, code).map_err(FuzzError::Instrument)?;
+    let mut fuzzer = Fuzzer {
+        contract: Contract::new(&instrumented.code).map_err(FuzzError::Load)?,
+        options,
+        generator: InputGenerator::new(&entries, options.seed),
+        rng: StdRng::seed_from_u64(options.seed),
+        total: vec![0; instrumented.blocks.len().div_ceil(64)],
+    };
+
+    let mut functions = Vec::new();
+    let mut findings = Vec::new();
+    for entry in &entries {
+        let func = match entry {
+            ScSpecEntry::FunctionV0(func) => func,
+            _ => continue,
+        };
+        let name = func.name.to_utf8_string_lossy();
+        if name.starts_with("__") || !(options.functions.is_empty() || options.functions.contains(&name)) {
+            continue;
+        }
+        functions.push(fuzzer.fuzz_function(func, &mut findings)?);
+    }
+
+    Ok(FuzzReport {
+        blocks: instrumented.blocks.len(),
+        covered: count(&fuzzer.total),
+        functions,
+        findings,
+    })
+}
@@ -0,0 +1,134 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::str::FromStr;
+
+use crate::analysis;
+use crate::cfg::{Cfg, CfgBuildError};
+use crate::dominance::DomTree;
+use crate::ssa;
+use crate::wasm_wrapper::wasm;
+
+/// Point of the decompilation pipeline at which a graph is captured
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GraphStage {
+    /// The CFG as produced by the builder
+    Raw,
+    /// The CFG in SSA form, including phi nodes
+    Ssa,
+    /// The SSA CFG after expression propagation and dead code elimination
+    Propagated,
+    /// The dominator tree of the raw CFG
+    DomTree,
+}
+
+impl GraphStage {
+    pub const ALL: [GraphStage; 4] = [GraphStage::Raw, GraphStage::Ssa, GraphStage::Propagated, GraphStage::DomTree];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            GraphStage::Raw => "raw",
+            GraphStage::Ssa => "ssa",
+            GraphStage::Propagated => "propagated",
+            GraphStage::DomTree => "domtree",
+        }
+    }
+}
+
+impl fmt::Display for GraphStage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for GraphStage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        GraphStage::ALL
+            .iter()
+            .copied()
+            .find(|stage| stage.name() == s)
+            .ok_or_else(|| format!("unknown graph stage `{}`", s))
+    }
+}
+
+#[derive(Debug)]
+pub enum GraphError {
+    CfgBuildError(CfgBuildError),
+    IoError(io::Error),
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::CfgBuildError(CfgBuildError::NoSuchFunc) => write!(f, "No such function"),
+            Self::CfgBuildError(CfgBuildError::FuncIsImported) => write!(f, "Function is imported"),
+            Self::IoError(error) => write!(f, "Error while writing graph: {}", error),
+        }
+    }
+}
+
+impl From<CfgBuildError> for GraphError {
+    fn from(error: CfgBuildError) -> Self {
+        Self::CfgBuildError(error)
+    }
+}
+
+impl From<io::Error> for GraphError {
+    fn from(error: io::Error) -> Self {
+        Self::IoError(error)
+    }
+}
+
+/// Write one dot file per requested stage of the function into `dir`.
+/// The files are named `<func_index>_<func_name>.<stage>.dot`.
+pub fn write_graphs(
+    wasm: Rc<wasm::Instance>,
+    func_index: u32,
+    stages: &[GraphStage],
+    dir: &Path,
+) -> Result<Vec<PathBuf>, GraphError> {
+    let func_name = wasm
+        .module()
+        .get_func(func_index)
+        .ok_or(CfgBuildError::NoSuchFunc)?
+        .name()
+        .to_string();
+    let mut graphs = Vec::new();
+
+    let mut cfg = Cfg::build(wasm, func_index)?;
+    if stages.contains(&GraphStage::Raw) {
+        graphs.push((GraphStage::Raw, cfg.dot_string()));
+    }
+    if stages.contains(&GraphStage::DomTree) {
+        graphs.push((GraphStage::DomTree, DomTree::build(&cfg).dot_string()));
+    }
+
+    let mut def_use_map = ssa::transform_to_ssa(&mut cfg);
+    if stages.contains(&GraphStage::Ssa) {
+        graphs.push((GraphStage::Ssa, cfg.dot_string()));
+    }
+    if stages.contains(&GraphStage::Propagated) {
+        analysis::propagate_expressions(&mut cfg, &mut def_use_map);
+        analysis::eliminate_dead_code(&mut cfg, &mut def_use_map);
+        graphs.push((GraphStage::Propagated, cfg.dot_string()));
+    }
+
+    fs::create_dir_all(dir)?;
+    let mut paths = Vec::new();
+    for (stage, dot) in graphs {
+        let path = dir.join(format!("{}_{}.{}.dot", func_index, sanitize_file_name(&func_name), stage));
+        fs::write(&path, dot)?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+fn sanitize_file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' { c } else { '_' })
+        .collect()
+}
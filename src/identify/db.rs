@@ -0,0 +1,109 @@
+//! The persistent database of reference functions.
+
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+
+use serde::{Deserialize, Serialize};
+
+use super::Fingerprint;
+
+/// A known function of a reference contract
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Reference {
+    /// Demangled name, e.g. `soroban_sdk::address::Address::require_auth`
+    pub label: String,
+    /// File name of the contract the function was taken from
+    pub contract: String,
+    #[serde(flatten)]
+    pub fingerprint: Fingerprint,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SimilarityDb {
+    pub references: Vec<Reference>,
+}
+
+impl SimilarityDb {
+    pub fn load(path: &str) -> Result<SimilarityDb, Box<dyn Error>> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    pub fn write_to_file(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+
+    /// Add a reference unless a function with the same label and structure is known already,
+    /// returns whether it was added
+    pub fn add(&mut self, reference: Reference) -> bool {
+        let known = self.references.iter().any(|known| {
+            known.label == reference.label && known.fingerprint.structure == reference.fingerprint.structure
+        });
+        if !known {
+            self.references.push(reference);
+        }
+        !known
+    }
+}
+
+/// Decode a symbol mangled with the legacy Rust scheme, other names are returned unchanged
+pub fn demangle(name: &str) -> String {
+    let mut rest = match name.strip_prefix("_ZN") {
+        Some(rest) => rest,
+        None => return name.to_string(),
+    };
+    let mut segments = Vec::new();
+    while let Some(digits) = rest.find(|c: char| !c.is_ascii_digit()).filter(|end| *end > 0) {
+        let len: usize = match rest[..digits].parse() {
+            Ok(len) => len,
+            Err(_) => return name.to_string(),
+        };
+        let segment = match rest.get(digits..digits + len) {
+            Some(segment) => segment,
+            None => return name.to_string(),
+        };
+        segments.push(segment);
+        rest = &rest[digits + len..];
+    }
+    if rest != "E" || segments.is_empty() {
+        return name.to_string();
+    }
+    // The last segment is a hash of the crate and its dependencies
+    if let Some(last) = segments.last() {
+        if last.len() == 17 && last.starts_with('h') && last[1..].chars().all(|c| c.is_ascii_hexdigit()) {
+            segments.pop();
+        }
+    }
+    let escapes = [
+        ("$LT$", "<"),
+        ("$GT$", ">"),
+        ("$RF$", "&"),
+        ("$BP$", "*"),
+        ("$C$", ","),
+        ("$SP$", "@"),
+        ("$u20$", " "),
+        ("$u27$", "'"),
+        ("$u5b$", "["),
+        ("$u5d$", "]"),
+        ("$u7b$", "{"),
+        ("$u7d$", "}"),
+        ("$u7e$", "~"),
+        ("..", "::"),
+    ];
+    segments
+        .iter()
+        .map(|segment| {
+            let segment = segment
+                .strip_prefix('_')
+                .filter(|s| s.starts_with('$'))
+                .unwrap_or(segment);
+            escapes
+                .iter()
+                .fold(segment.to_string(), |segment, (escape, c)| segment.replace(escape, c))
+        })
+        .collect::<Vec<_>>()
+        .join("::")
+}
@@ -0,0 +1,310 @@
+//! Identification of known library functions in a contract.
+//!
+//! Every function gets two fingerprints: a TLSH of its normalized decompiled code, which stays
+//! close when the code changes a little, and a hash of its IR with variables, callees and
+//! addresses normalized, which matches functions that only differ in naming. Reference contracts
+//! built with a name section, like the SDK examples, provide the labels.
+
+pub mod db;
+
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::mem;
+use std::rc::Rc;
+use std::str::FromStr;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tlsh_fixed::Tlsh;
+
+use crate::fmt::{CodeWriter, WriterOptions};
+use crate::soroban::pattern::Capture;
+use crate::soroban::normalize::{normalize_code, Fnv, Segments, FNV_OFFSET};
+use crate::soroban::sdk_linker::get_sequence_tlsh;
+use crate::ssa::cond::MappedExpr;
+use crate::ssa::{Cond, Expr, Stmt, Var};
+use crate::wasm_wrapper::wasm::Instance;
+use crate::wasm_wrapper::wasm_adapter::Module;
+use db::{Reference, SimilarityDb};
+
+/// Default for the largest TLSH distance that still counts as a match
+pub const MAX_DISTANCE: usize = 50;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fingerprint {
+    /// `None` if the code is too short to hash
+    pub tlsh: Option<String>,
+    /// Hash of the normalized IR as hex
+    pub structure: String,
+}
+
+impl Fingerprint {
+    /// TLSH distance to `other`, 0 if the structure is identical and `None` if either is too
+    /// short to hash
+    pub fn distance(&self, other: &Fingerprint) -> Option<usize> {
+        if self.structure == other.structure {
+            return Some(0);
+        }
+        let tlsh = Tlsh::from_str(self.tlsh.as_deref()?).ok()?;
+        let other = Tlsh::from_str(other.tlsh.as_deref()?).ok()?;
+        Some(tlsh.diff(&other, false))
+    }
+}
+
+/// The closest reference of a function
+#[derive(Debug, Clone, Serialize)]
+pub struct Identification {
+    pub func_index: u32,
+    pub name: String,
+    pub label: String,
+    pub contract: String,
+    /// TLSH distance, 0 if the structure is identical
+    pub distance: usize,
+}
+
+/// Hashes the IR with variables replaced by their order of appearance, callees by their kind
+/// and addresses left out, which move with unrelated changes
+struct StructureHasher<'a> {
+    module: &'a Module,
+    segments: Segments,
+    vars: HashMap<(u32, u32), usize>,
+    hasher: Fnv,
+}
+
+impl<'a> StructureHasher<'a> {
+    fn new(module: &'a Module) -> Self {
+        StructureHasher {
+            module,
+            segments: Segments::new(module),
+            vars: HashMap::new(),
+            hasher: Fnv(FNV_OFFSET),
+        }
+    }
+
+    fn var(&mut self, var: &Var) {
+        let next = self.vars.len();
+        let order = *self.vars.entry((var.index, var.subscript)).or_insert(next);
+        order.hash(&mut self.hasher);
+    }
+
+    fn block(&mut self, code: &[Stmt]) {
+        code.len().hash(&mut self.hasher);
+        code.iter().for_each(|stmt| self.stmt(stmt));
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        mem::discriminant(stmt).hash(&mut self.hasher);
+        match stmt {
+            Stmt::Unreachable | Stmt::ReturnVoid | Stmt::Break | Stmt::Nop | Stmt::Offset(_) => (),
+            Stmt::Expr(expr) | Stmt::Return(expr) | Stmt::Branch(expr) => self.expr(expr),
+            Stmt::While(cond, body, kind) => {
+                mem::discriminant(kind).hash(&mut self.hasher);
+                self.cond(cond);
+                self.block(body);
+            }
+            Stmt::ForLoop(var, init, cond, step, body) => {
+                self.var(var);
+                if let Some(init) = init {
+                    self.expr(init);
+                }
+                self.cond(cond);
+                self.expr(step);
+                self.block(body);
+            }
+            Stmt::Seq(code) => self.block(code),
+            Stmt::Phi(var, sources) => {
+                self.var(var);
+                sources.hash(&mut self.hasher);
+            }
+            Stmt::If(cond, body) => {
+                self.cond(cond);
+                self.block(body);
+            }
+            Stmt::IfElse(cond, then_body, else_body) => {
+                self.cond(cond);
+                self.block(then_body);
+                self.block(else_body);
+            }
+            Stmt::SwitchCase(expr, cases, default) => {
+                self.mapped(expr);
+                for (values, case) in cases {
+                    values.0.hash(&mut self.hasher);
+                    self.stmt(case);
+                }
+                if let Some(default) = default {
+                    self.stmt(default);
+                }
+            }
+            Stmt::SetLocal(var, expr) => {
+                self.var(var);
+                self.expr(expr);
+            }
+            Stmt::SetGlobal(index, expr) => {
+                index.hash(&mut self.hasher);
+                self.expr(expr);
+            }
+            Stmt::I32Store(location, value)
+            | Stmt::I64Store(location, value)
+            | Stmt::F32Store(location, value)
+            | Stmt::F64Store(location, value)
+            | Stmt::I32Store8(location, value)
+            | Stmt::I32Store16(location, value)
+            | Stmt::I64Store8(location, value)
+            | Stmt::I64Store16(location, value)
+            | Stmt::I64Store32(location, value) => {
+                self.expr(location);
+                self.expr(value);
+            }
+            Stmt::Replaced(replacement) => {
+                replacement.pattern.hash(&mut self.hasher);
+                for (name, capture) in &replacement.captures {
+                    name.hash(&mut self.hasher);
+                    match capture {
+                        Capture::Expr(expr) => self.expr(expr),
+                        Capture::Cond(cond) => self.cond(cond),
+                        Capture::Stmts(code) => self.block(code),
+                    }
+                }
+            }
+        }
+    }
+
+    fn cond(&mut self, cond: &Cond) {
+        mem::discriminant(cond).hash(&mut self.hasher);
+        match cond {
+            Cond::True | Cond::False => (),
+            Cond::Not(cond) => self.cond(cond),
+            Cond::And(a, b) | Cond::Or(a, b) => {
+                self.cond(a);
+                self.cond(b);
+            }
+            Cond::Cmp(a, op, b) => {
+                mem::discriminant(op).hash(&mut self.hasher);
+                self.mapped(a);
+                self.mapped(b);
+            }
+            Cond::Expr(expr) => self.mapped(expr),
+        }
+    }
+
+    fn mapped(&mut self, expr: &MappedExpr) {
+        mem::discriminant(expr).hash(&mut self.hasher);
+        match expr {
+            MappedExpr::Expr(expr) => self.expr(expr),
+            MappedExpr::Const(value) | MappedExpr::Mapped(value) => value.hash(&mut self.hasher),
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        mem::discriminant(expr).hash(&mut self.hasher);
+        match expr {
+            Expr::Call(index, _) => {
+                let imported = self.module.get_func(*index).filter(|func| func.is_imported());
+                imported.map_or("local", |func| func.name()).hash(&mut self.hasher);
+            }
+            Expr::CallIndirect(index, args, type_ref) => {
+                type_ref.hash(&mut self.hasher);
+                // a constant slot filled by an element segment is a function pointer
+                match **index {
+                    Expr::I32Const(slot) if self.segments.is_element(slot) => "N".hash(&mut self.hasher),
+                    _ => self.expr(index),
+                }
+                args.iter().for_each(|arg| self.expr(arg));
+                return;
+            }
+            Expr::GetLocal(var) => self.var(var),
+            Expr::GetGlobal(index) => index.hash(&mut self.hasher),
+            Expr::I32Const(value) if !self.segments.is_address32(*value) => value.hash(&mut self.hasher),
+            Expr::I64Const(value) if !self.segments.is_address64(*value) => value.hash(&mut self.hasher),
+            Expr::F32Const(value) => value.hash(&mut self.hasher),
+            Expr::F64Const(value) => value.hash(&mut self.hasher),
+            _ => (),
+        }
+        for child in expr.children() {
+            self.expr(child);
+        }
+    }
+}
+
+fn structure_hash(module: &Module, code: &[Stmt]) -> u64 {
+    let mut hasher = StructureHasher::new(module);
+    hasher.block(code);
+    hasher.hasher.finish()
+}
+
+/// Fingerprint of a local function, `None` if it can not be decompiled
+pub fn fingerprint(wasm: &Rc<Instance>, func_index: u32) -> Option<Fingerprint> {
+    let code = wasm.decompile_function_ir(func_index, WriterOptions::default()).ok()?;
+    let text = CodeWriter::formatter(wasm.clone(), func_index).string_func(code.as_slice());
+    Some(Fingerprint {
+        tlsh: get_sequence_tlsh(&normalize_code(&text)).ok().map(|tlsh| tlsh.hash()),
+        structure: format!("{:016x}", structure_hash(wasm.module(), &code)),
+    })
+}
+
+/// Add the named local functions of a reference contract to the database, returns how many
+/// were new. Exported functions are labeled with the contract name, functions of the name
+/// section with their demangled name.
+pub fn index_contract(db: &mut SimilarityDb, wasm: &Rc<Instance>, contract: &str) -> usize {
+    let unnamed = Regex::new(r"^func_\d+$").unwrap();
+    let module = wasm.module();
+    let mut added = 0;
+    for (i, func) in module.functions().iter().enumerate() {
+        if func.is_imported() || unnamed.is_match(func.name()) {
+            continue;
+        }
+        let label = if module.is_exported(i as u32) {
+            format!("{}::{}", contract, func.name())
+        } else {
+            db::demangle(func.name())
+        };
+        if let Some(fingerprint) = fingerprint(wasm, i as u32) {
+            let reference = Reference {
+                label,
+                contract: contract.to_string(),
+                fingerprint,
+            };
+            added += usize::from(db.add(reference));
+        }
+    }
+    added
+}
+
+/// The reference closest to `fingerprint` within `max_distance`, identical structure first
+pub fn nearest<'a>(
+    db: &'a SimilarityDb,
+    fingerprint: &Fingerprint,
+    max_distance: usize,
+) -> Option<(&'a Reference, usize)> {
+    if let Some(reference) = db
+        .references
+        .iter()
+        .find(|reference| reference.fingerprint.structure == fingerprint.structure)
+    {
+        return Some((reference, 0));
+    }
+    db.references
+        .iter()
+        .filter_map(|reference| Some((reference, fingerprint.distance(&reference.fingerprint)?)))
+        .filter(|(_, distance)| *distance <= max_distance)
+        .min_by_key(|(_, distance)| *distance)
+}
+
+/// Find the closest reference of every internal function of the contract
+pub fn identify(wasm: &Rc<Instance>, db: &SimilarityDb, max_distance: usize) -> Vec<Identification> {
+    let module = wasm.module();
+    (0..module.functions().len() as u32)
+        .filter(|i| !module.func(*i).is_imported() && !module.is_exported(*i))
+        .filter_map(|i| {
+            let fingerprint = fingerprint(wasm, i)?;
+            let (reference, distance) = nearest(db, &fingerprint, max_distance)?;
+            Some(Identification {
+                func_index: i,
+                name: module.func(i).name().to_string(),
+                label: reference.label.clone(),
+                contract: reference.contract.clone(),
+                distance,
+            })
+        })
+        .collect()
+}
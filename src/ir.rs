@@ -0,0 +1,52 @@
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+
+use crate::fmt::WriterOptions;
+use crate::ssa::Stmt;
+use crate::wasm_wrapper::wasm::Instance;
+
+/// The structured IR of a function after decompilation, before it is printed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FunctionIr {
+    pub index: u32,
+    pub name: String,
+    pub code: Vec<Stmt>,
+}
+
+/// Decompile the given functions into their IR, skipping those that can not be decompiled
+pub fn collect(wasm: &Instance, func_indices: &[u32], options: WriterOptions) -> Vec<FunctionIr> {
+    func_indices
+        .iter()
+        .filter_map(|&index| {
+            let code = wasm.decompile_function_ir(index, options).ok()?;
+            Some(FunctionIr {
+                index,
+                name: wasm.module().func(index).name().to_string(),
+                code,
+            })
+        })
+        .collect()
+}
+
+/// Write the IR as JSON, `-` writes to stdout
+pub fn dump<P: AsRef<Path>>(functions: &[FunctionIr], path: P) -> Result<(), Box<dyn Error>> {
+    let path = path.as_ref();
+    if path == Path::new("-") {
+        let stdout = std::io::stdout();
+        let mut writer = stdout.lock();
+        serde_json::to_writer_pretty(&mut writer, functions)?;
+        writeln!(writer)?;
+    } else {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, functions)?;
+    }
+    Ok(())
+}
+
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Vec<FunctionIr>, Box<dyn Error>> {
+    let reader = BufReader::new(File::open(path)?);
+    Ok(serde_json::from_reader(reader)?)
+}
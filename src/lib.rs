@@ -1,8 +1,21 @@
 pub mod analysis;
+pub mod audit;
 pub mod cfg;
+pub mod cluster;
+pub mod diff;
+pub mod difftest;
 pub mod dominance;
 pub mod fmt;
+pub mod fuzz;
+pub mod graph;
+pub mod identify;
+pub mod ir;
+pub mod output;
+pub mod run;
+pub mod select;
 pub mod ssa;
 pub mod structuring;
+pub mod symbolic;
 pub mod wasm_wrapper;
 pub mod soroban;
+pub mod source_map;
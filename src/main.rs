@@ -1,53 +1,1442 @@
+use auditor::audit::baseline::Baseline;
+use auditor::audit::{report, AuditContext, RuleFilter, RuleRegistry, Severity};
 use auditor::cfg::CfgBuildError;
+use auditor::cluster::{self, ContractFingerprints};
+use auditor::diff::{self, ChangeKind};
+use auditor::difftest::{self, project};
+use auditor::fmt::WriterOptions;
+use auditor::fuzz::{self, corpus::Corpus};
+use auditor::graph::{self, GraphStage};
+use auditor::identify::{self, db::SimilarityDb};
+use auditor::ir;
+use auditor::output::{ModuleMetadata, ModuleOutput};
+use auditor::run::trace::{self, Trace};
+use auditor::run::{self, AuthPolicy, HostConfig, StorageEntry};
+use auditor::select::{self, FuncSelector};
+use auditor::soroban::build_info::{self, BuildInfo, SdkTable, VersionSource};
+use auditor::source_map::SourceMap;
+use auditor::symbolic::{self, Limits};
 use auditor::wasm_wrapper::wasm;
-use clap::{App, Arg};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use serde::Serialize;
+use soroban_sdk::xdr::{ScAddress, ScVal};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 fn main() {
     let args = App::new("auditor")
         .version(VERSION)
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .subcommand(
+            SubCommand::with_name("list")
+                .about("List all functions of the wasm binary")
+                .arg(
+                    Arg::with_name("file")
+                        .help("The wasm binary to inspect")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("info")
+                .about("Summarize the module and the soroban-sdk and rustc versions it was built with")
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .value_name("FORMAT")
+                        .possible_values(&["text", "json"])
+                        .default_value("text")
+                        .help("Output format of the summary"),
+                )
+                .arg(
+                    Arg::with_name("file")
+                        .help("The wasm binary to inspect")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("sdk-table")
+                .about("Print the table of SDK helper fingerprints of contracts that record their SDK version")
+                .arg(
+                    Arg::with_name("paths")
+                        .help("Contracts or directories searched for .wasm files")
+                        .required(true)
+                        .multiple(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("audit")
+                .about("Check the exported functions of a contract for security problems")
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .value_name("FORMAT")
+                        .possible_values(&["text", "json", "sarif", "markdown"])
+                        .default_value("text")
+                        .help("Output format of the findings"),
+                )
+                .arg(
+                    Arg::with_name("enable")
+                        .long("enable")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .value_name("RULE")
+                        .help("Only run the rules with the given ids"),
+                )
+                .arg(
+                    Arg::with_name("disable")
+                        .long("disable")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .value_name("RULE")
+                        .help("Do not run the rule with the given id"),
+                )
+                .arg(
+                    Arg::with_name("min-severity")
+                        .long("min-severity")
+                        .takes_value(true)
+                        .value_name("SEVERITY")
+                        .possible_values(&["info", "low", "medium", "high"])
+                        .default_value("info")
+                        .help("Only report findings of at least this severity"),
+                )
+                .arg(
+                    Arg::with_name("rules-lib")
+                        .long("rules-lib")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .value_name("LIB")
+                        .help("Load additional rules from a library built with `auditor::export_rules!`"),
+                )
+                .arg(
+                    Arg::with_name("baseline")
+                        .long("baseline")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help("Only report findings that are not recorded or suppressed in the baseline FILE"),
+                )
+                .arg(
+                    Arg::with_name("write-baseline")
+                        .long("write-baseline")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help("Record the findings in the baseline FILE, keeping its suppressions"),
+                )
+                .arg(
+                    Arg::with_name("list-rules")
+                        .long("list-rules")
+                        .help("List the available rules and exit"),
+                )
+                .arg(
+                    Arg::with_name("file")
+                        .help("The wasm binary to audit")
+                        .required_unless("list-rules"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("unreachable")
+                .about("Find inputs that make functions reach an `unreachable` instruction")
+                .arg(
+                    Arg::with_name("func")
+                        .long("func")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .value_name("SELECTOR")
+                        .help("Only explore functions matching the exact name, index or regex (default: exports)"),
+                )
+                .arg(
+                    Arg::with_name("unroll")
+                        .long("unroll")
+                        .takes_value(true)
+                        .value_name("N")
+                        .default_value("2")
+                        .help("How often each loop is unrolled"),
+                )
+                .arg(
+                    Arg::with_name("max-paths")
+                        .long("max-paths")
+                        .takes_value(true)
+                        .value_name("N")
+                        .default_value("1024")
+                        .help("Stop exploring a function after N paths"),
+                )
+                .arg(
+                    Arg::with_name("file")
+                        .help("The wasm binary to explore")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("run")
+                .about("Call an exported function of a contract with a local mock host")
+                .arg(
+                    Arg::with_name("storage")
+                        .long("storage")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help("JSON file with the storage entries before the call"),
+                )
+                .arg(
+                    Arg::with_name("authorize")
+                        .long("authorize")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .value_name("ADDRESS")
+                        .help("Only let `require_auth` succeed for the given addresses"),
+                )
+                .arg(
+                    Arg::with_name("deny-auth")
+                        .long("deny-auth")
+                        .conflicts_with("authorize")
+                        .help("Let every `require_auth` fail"),
+                )
+                .arg(
+                    Arg::with_name("ledger-sequence")
+                        .long("ledger-sequence")
+                        .takes_value(true)
+                        .value_name("N")
+                        .default_value("1")
+                        .help("Sequence number of the current ledger"),
+                )
+                .arg(
+                    Arg::with_name("timestamp")
+                        .long("timestamp")
+                        .takes_value(true)
+                        .value_name("SECONDS")
+                        .default_value("0")
+                        .help("Timestamp of the current ledger"),
+                )
+                .arg(
+                    Arg::with_name("record")
+                        .long("record")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help("Write the host calls with their call sites as a JSON trace to FILE"),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .value_name("FORMAT")
+                        .possible_values(&["text", "json"])
+                        .default_value("text")
+                        .help("Output format of the result"),
+                )
+                .arg(
+                    Arg::with_name("file")
+                        .help("The wasm binary of the contract")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("function")
+                        .help("The exported function to call")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("args")
+                        .help("The arguments as JSON ScVals, e.g. '{\"u32\":5}'")
+                        .multiple(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("replay")
+                .about("Run a contract against the host calls of a recorded trace")
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .value_name("FORMAT")
+                        .possible_values(&["text", "json"])
+                        .default_value("text")
+                        .help("Output format of the result"),
+                )
+                .arg(
+                    Arg::with_name("trace")
+                        .help("The trace written by `run --record`")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("file")
+                        .help("The wasm binary of the contract")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("index")
+                .about("Add the named functions of reference contracts to a similarity database")
+                .arg(
+                    Arg::with_name("db")
+                        .long("db")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .required(true)
+                        .help("The database, created if it does not exist"),
+                )
+                .arg(
+                    Arg::with_name("paths")
+                        .help("Wasm binaries or directories searched for them")
+                        .required(true)
+                        .multiple(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("identify")
+                .about("Find the closest known function of every internal function of a contract")
+                .arg(
+                    Arg::with_name("db")
+                        .long("db")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .required(true)
+                        .help("The database written by `index`"),
+                )
+                .arg(
+                    Arg::with_name("max-distance")
+                        .long("max-distance")
+                        .takes_value(true)
+                        .value_name("N")
+                        .default_value("50")
+                        .help("Largest TLSH distance that counts as a match"),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .value_name("FORMAT")
+                        .possible_values(&["text", "json"])
+                        .default_value("text")
+                        .help("Output format of the result"),
+                )
+                .arg(
+                    Arg::with_name("file")
+                        .help("The wasm binary of the contract")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("cluster")
+                .about("Group similar contracts and show where each fork diverges from the most common version")
+                .arg(
+                    Arg::with_name("max-distance")
+                        .long("max-distance")
+                        .takes_value(true)
+                        .value_name("N")
+                        .default_value("50")
+                        .help("Largest TLSH distance of functions that still count as the same"),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .value_name("FORMAT")
+                        .possible_values(&["text", "json"])
+                        .default_value("text")
+                        .help("Output format of the report"),
+                )
+                .arg(
+                    Arg::with_name("dir")
+                        .help("Directory searched for .wasm files")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("diff")
+                .about("Show what changed between two versions of a contract")
+                .arg(
+                    Arg::with_name("no-bodies")
+                        .long("no-bodies")
+                        .help("Only list changed functions without diffing their decompiled bodies"),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .value_name("FORMAT")
+                        .possible_values(&["text", "json"])
+                        .default_value("text")
+                        .help("Output format of the report"),
+                )
+                .arg(
+                    Arg::with_name("old")
+                        .help("The wasm binary of the old version")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("new")
+                        .help("The wasm binary of the new version")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("difftest")
+                .about("Compare the decompiled contract with the original on random inputs")
+                .arg(
+                    Arg::with_name("source")
+                        .long("source")
+                        .takes_value(true)
+                        .value_name("DIR")
+                        .help("Compare with the cargo project in DIR instead of the decompiled contract"),
+                )
+                .arg(
+                    Arg::with_name("work-dir")
+                        .long("work-dir")
+                        .takes_value(true)
+                        .value_name("DIR")
+                        .conflicts_with("source")
+                        .help("Directory the cargo project of the decompiled contract is written to"),
+                )
+                .arg(
+                    Arg::with_name("func")
+                        .long("func")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .value_name("SELECTOR")
+                        .help("Only compare functions matching the name, index or regex (default: spec functions)"),
+                )
+                .arg(
+                    Arg::with_name("cases")
+                        .long("cases")
+                        .takes_value(true)
+                        .value_name("N")
+                        .default_value("16")
+                        .help("How often each function is called"),
+                )
+                .arg(
+                    Arg::with_name("seed")
+                        .long("seed")
+                        .takes_value(true)
+                        .value_name("N")
+                        .default_value("0")
+                        .help("Seed of the random inputs"),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .value_name("FORMAT")
+                        .possible_values(&["text", "json"])
+                        .default_value("text")
+                        .help("Output format of the report"),
+                )
+                .arg(
+                    Arg::with_name("file")
+                        .help("The wasm binary of the original contract")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("fuzz")
+                .about("Fuzz the exported functions of a contract with coverage feedback")
+                .arg(
+                    Arg::with_name("func")
+                        .long("func")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .value_name("SELECTOR")
+                        .help("Only fuzz functions matching the name, index or regex (default: spec functions)"),
+                )
+                .arg(
+                    Arg::with_name("runs")
+                        .long("runs")
+                        .takes_value(true)
+                        .value_name("N")
+                        .default_value("256")
+                        .help("How often each function is called"),
+                )
+                .arg(
+                    Arg::with_name("seed")
+                        .long("seed")
+                        .takes_value(true)
+                        .value_name("N")
+                        .default_value("0")
+                        .help("Seed of the random inputs"),
+                )
+                .arg(
+                    Arg::with_name("corpus")
+                        .long("corpus")
+                        .takes_value(true)
+                        .value_name("DIR")
+                        .help("Directory the inputs reaching new blocks and the crashes are kept in"),
+                )
+                .arg(
+                    Arg::with_name("storage")
+                        .long("storage")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help("JSON file with the storage entries before each call"),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .value_name("FORMAT")
+                        .possible_values(&["text", "json"])
+                        .default_value("text")
+                        .help("Output format of the report"),
+                )
+                .arg(
+                    Arg::with_name("file")
+                        .help("The wasm binary of the contract")
+                        .required(true),
+                ),
+        )
         .arg(
             Arg::with_name("show-graph")
                 .long("show-graph")
-                .help("Print the constructed CFG in dot format before structuring"),
+                .takes_value(true)
+                .min_values(0)
+                .require_equals(true)
+                .use_delimiter(true)
+                .value_name("STAGE")
+                .possible_values(&["raw", "ssa", "propagated", "domtree"])
+                .help("Write the CFG of each function in dot format for the given stages (default: all)"),
+        )
+        .arg(
+            Arg::with_name("graph-dir")
+                .long("graph-dir")
+                .takes_value(true)
+                .value_name("DIR")
+                .default_value("graphs")
+                .help("Directory the dot files of --show-graph are written to"),
+        )
+        .arg(
+            Arg::with_name("offsets")
+                .long("offsets")
+                .help("Annotate statements with wasm bytecode offsets, the output is not reformatted to keep the comments"),
+        )
+        .arg(
+            Arg::with_name("source-map")
+                .long("source-map")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Write a JSON map from output lines to wasm bytecode offsets (implies --offsets)"),
+        )
+        .arg(
+            Arg::with_name("trace")
+                .long("trace")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Annotate host call sites with the values observed in a recorded trace (implies --offsets)"),
+        )
+        .arg(
+            Arg::with_name("dump-ir")
+                .long("dump-ir")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Write the structured IR of each function as JSON to FILE (`-` for stdout)"),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .takes_value(true)
+                .value_name("FORMAT")
+                .possible_values(&["text", "json"])
+                .default_value("text")
+                .help("Output format of the decompilation result"),
         )
         .arg(
             Arg::with_name("file")
                 .help("The wasm binary to decompile")
                 .required(true),
         )
-        .arg(Arg::with_name("function_name").help("The index of the function to decompile"))
+        .arg(
+            Arg::with_name("func")
+                .long("func")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("SELECTOR")
+                .help("Only decompile functions matching the exact name, index or regex"),
+        )
+        .arg(Arg::with_name("function_name").help("The name of the function to decompile (same as --func)"))
         .get_matches();
 
+    match args.subcommand() {
+        ("list", Some(sub_args)) => list_functions(sub_args),
+        ("info", Some(sub_args)) => show_info(sub_args),
+        ("sdk-table", Some(sub_args)) => sdk_table(sub_args),
+        ("audit", Some(sub_args)) => audit_contract(sub_args),
+        ("unreachable", Some(sub_args)) => find_unreachable(sub_args),
+        ("run", Some(sub_args)) => run_contract(sub_args),
+        ("replay", Some(sub_args)) => replay_trace(sub_args),
+        ("index", Some(sub_args)) => index_references(sub_args),
+        ("identify", Some(sub_args)) => identify_functions(sub_args),
+        ("cluster", Some(sub_args)) => cluster_contracts(sub_args),
+        ("diff", Some(sub_args)) => diff_contracts(sub_args),
+        ("difftest", Some(sub_args)) => diff_test(sub_args),
+        ("fuzz", Some(sub_args)) => fuzz_contract(sub_args),
+        _ => decompile(&args),
+    }
+}
+
+fn list_functions(args: &ArgMatches) {
+    let wasm = wasm::Instance::load_file(args.value_of("file").unwrap());
+    let module = wasm.module();
+    for (i, func) in module.functions().iter().enumerate() {
+        let kind = if func.is_imported() {
+            "import"
+        } else if module.is_exported(i as u32) {
+            "export"
+        } else {
+            "local"
+        };
+        let spec = match func.spec_fn() {
+            Some(spec) if func.has_spec() => spec.to_string(),
+            _ => String::from("-"),
+        };
+        println!(
+            "{:>5}  {:<6}  {:<40}  {:<40}  {:<60}  {}",
+            i,
+            kind,
+            func.name(),
+            func.func_type(),
+            spec,
+            func.instructions().len()
+        );
+    }
+}
+
+fn show_info(args: &ArgMatches) {
+    let wasm = wasm::Instance::load_file(args.value_of("file").unwrap());
+    let metadata = ModuleMetadata::new(wasm.module());
+
+    if args.value_of("format") == Some("json") {
+        match serde_json::to_string_pretty(&metadata) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("Failed to serialize the summary: {}", e),
+        }
+        return;
+    }
+    let build = &metadata.build;
+    let sdk = match (&build.sdk_version, build.source) {
+        (Some(version), VersionSource::Fingerprints) => {
+            format!("{} (inferred from {} helpers)", version, build.matched_helpers)
+        }
+        (Some(version), _) => match &build.sdk_commit {
+            Some(commit) => format!("{} ({})", version, commit),
+            None => version.clone(),
+        },
+        (None, _) => String::from("unknown"),
+    };
+    println!("Functions:       {} ({} imported)", metadata.functions, metadata.imported_functions);
+    println!("Exports:         {}", metadata.exports.join(", "));
+    println!("Globals:         {}", metadata.globals);
+    println!("Memories:        {}", metadata.memories);
+    println!("Custom sections: {}", metadata.custom_sections.join(", "));
+    println!("soroban-sdk:     {}", sdk);
+    println!("rustc:           {}", build.rustc_version.as_deref().unwrap_or("unknown"));
+    for entry in &build.meta {
+        if entry.key != build_info::SDK_VERSION_KEY && entry.key != build_info::RUSTC_VERSION_KEY {
+            println!("{:<16} {}", format!("{}:", entry.key), entry.value);
+        }
+    }
+}
+
+fn sdk_table(args: &ArgMatches) {
+    let mut files = Vec::new();
+    for path in args.values_of("paths").into_iter().flatten() {
+        if let Err(e) = wasm_files(Path::new(path), &mut files) {
+            eprintln!("Failed to read {}: {}", path, e);
+            std::process::exit(1);
+        }
+    }
+    let mut contracts = Vec::new();
+    for file in &files {
+        let module: parity_wasm::elements::Module = match parity_wasm::deserialize_file(file) {
+            Ok(module) => module,
+            Err(e) => {
+                eprintln!("Skipping {}: {}", file.display(), e);
+                continue;
+            }
+        };
+        match BuildInfo::from_meta(&module).sdk_version {
+            Some(version) => contracts.push((version, build_info::helper_fingerprints(&module))),
+            None => eprintln!("Skipping {}: no SDK version in the metadata", file.display()),
+        }
+    }
+    let table = SdkTable::from_contracts(contracts.iter().map(|(version, helpers)| (version.as_str(), helpers.clone())));
+    match toml::to_string(&table) {
+        Ok(table) => print!("{}", table),
+        Err(e) => {
+            eprintln!("Failed to serialize the table: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn audit_contract(args: &ArgMatches) {
+    let mut registry = RuleRegistry::with_builtin_rules();
+    for path in args.values_of("rules-lib").into_iter().flatten() {
+        if let Err(e) = registry.load_library(Path::new(path)) {
+            eprintln!("{}: {}", path, e);
+            std::process::exit(1);
+        }
+    }
+    if args.is_present("list-rules") {
+        for rule in registry.rules() {
+            println!("{:<24}  {}", rule.id(), rule.description());
+        }
+        return;
+    }
+
+    let filter = RuleFilter {
+        enabled: args.values_of("enable").into_iter().flatten().map(str::to_string).collect(),
+        disabled: args.values_of("disable").into_iter().flatten().map(str::to_string).collect(),
+        min_severity: args.value_of("min-severity").unwrap().parse::<Severity>().unwrap(),
+    };
+    if let Err(e) = registry.check_filter(&filter) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+
     let file_path = args.value_of("file").unwrap();
     let wasm = wasm::Instance::load_file(file_path);
-    let _show_graph = args.is_present("show-graph");
-
-    if let Some(func_name) = args.value_of("function_name") {
-        for (i, func) in wasm.module().functions().iter().enumerate() {
-            if func.name() == func_name {
-                let func_index = i as u32;
-                match wasm.decompile_function(func_index) {
-                    Ok(()) => (),
-                    Err(CfgBuildError::NoSuchFunc) => eprintln!("No function with index {}", func_index),
-                    Err(CfgBuildError::FuncIsImported) => {
-                        eprintln!("Function {} is imported and can not be decompiled", func_index)
-                    }
-                }
+    let ctx = AuditContext::new(wasm);
+    let mut findings = registry.run(&ctx, &filter);
+
+    if let Some(path) = args.value_of("write-baseline") {
+        let mut baseline = if Path::new(path).exists() {
+            load_baseline(path)
+        } else {
+            Baseline::default()
+        };
+        findings.retain(|finding| !baseline.is_suppressed(finding));
+        baseline.record(&ctx, &findings);
+        match baseline.write_to_file(path) {
+            Ok(()) => eprintln!("Recorded {} finding(s) in {}", findings.len(), path),
+            Err(e) => eprintln!("Failed to write the baseline: {}", e),
+        }
+    }
+    if let Some(path) = args.value_of("baseline") {
+        findings = load_baseline(path).filter(&ctx, findings);
+    }
+
+    match args.value_of("format") {
+        Some("json") => match serde_json::to_string_pretty(&findings) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("Failed to serialize the findings: {}", e),
+        },
+        Some("sarif") => {
+            let sarif = report::to_sarif(registry.rules(), &findings, file_path);
+            match serde_json::to_string_pretty(&sarif) {
+                Ok(json) => println!("{}", json),
+                Err(e) => eprintln!("Failed to serialize the findings: {}", e),
+            }
+        }
+        Some("markdown") => print!("{}", report::to_markdown(&ctx, &findings, file_path)),
+        _ => {
+            for finding in &findings {
+                println!("{}", finding);
+            }
+            eprintln!("{} finding(s)", findings.len());
+        }
+    }
+}
+
+fn load_baseline(path: &str) -> Baseline {
+    match Baseline::load(path) {
+        Ok(baseline) => baseline,
+        Err(e) => {
+            eprintln!("Failed to read the baseline {}: {}", path, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn find_unreachable(args: &ArgMatches) {
+    let wasm = wasm::Instance::load_file(args.value_of("file").unwrap());
+    let limits = Limits {
+        max_unroll: parse_number(args, "unroll"),
+        max_paths: parse_number(args, "max-paths"),
+    };
+    let selectors: Vec<FuncSelector> = args.values_of("func").into_iter().flatten().map(FuncSelector::new).collect();
+    let func_indices: Vec<u32> = if !selectors.is_empty() {
+        match select::select_functions(wasm.module(), &selectors) {
+            Ok(indices) => indices,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
             }
         }
     } else {
-        for (i, func) in wasm.module().functions().iter().enumerate() {
-            if !func.is_imported() {
-                match wasm.decompile_function(i as u32) {
-                    Ok(()) => (),
-                    Err(CfgBuildError::NoSuchFunc) => eprintln!("No function with index {}", i),
-                    Err(CfgBuildError::FuncIsImported) => {
-                        eprintln!("Function {} is imported and can not be decompiled", i)
-                    }
+        (0..wasm.module().functions().len() as u32)
+            .filter(|i| wasm.module().is_exported(*i))
+            .collect()
+    };
+
+    for func_index in func_indices {
+        let name = wasm.module().func(func_index).name().to_string();
+        let exploration = match symbolic::find_unreachable(wasm.clone(), func_index, limits) {
+            Ok(exploration) => exploration,
+            Err(_) => {
+                eprintln!("Function {} ({}) can not be explored", name, func_index);
+                continue;
+            }
+        };
+        for input in &exploration.unreachable {
+            let args: Vec<String> = input.args.iter().map(|arg| format!("0x{:x}", arg)).collect();
+            match input.offset {
+                Some(offset) => print!("{} at code offset 0x{:x}", name, offset),
+                None => print!("{} in block {}", name, input.node),
+            }
+            println!(": {}({})", name, args.join(", "));
+        }
+        if exploration.truncated {
+            eprintln!("{}: explored {} path(s), limits reached", name, exploration.paths);
+        }
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(args: &ArgMatches, name: &str) -> T {
+    match args.value_of(name).unwrap().parse() {
+        Ok(value) => value,
+        Err(_) => {
+            eprintln!("--{} expects a number", name);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn run_contract(args: &ArgMatches) {
+    let file_path = args.value_of("file").unwrap();
+    let code = match std::fs::read(file_path) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", file_path, e);
+            std::process::exit(1);
+        }
+    };
+    let call_args: Vec<ScVal> = args
+        .values_of("args")
+        .into_iter()
+        .flatten()
+        .map(|arg| match serde_json::from_str(arg) {
+            Ok(value) => value,
+            Err(e) => {
+                eprintln!("Invalid argument {}: {}", arg, e);
+                std::process::exit(1);
+            }
+        })
+        .collect();
+
+    let mut config = HostConfig {
+        ledger_sequence: parse_number(args, "ledger-sequence"),
+        ledger_timestamp: parse_number(args, "timestamp"),
+        ..HostConfig::default()
+    };
+    if args.is_present("deny-auth") {
+        config.auth = AuthPolicy::Only(Vec::new());
+    } else if let Some(addresses) = args.values_of("authorize") {
+        let addresses = addresses.map(|address| match address.parse::<ScAddress>() {
+            Ok(address) => address,
+            Err(_) => {
+                eprintln!("Invalid address {}", address);
+                std::process::exit(1);
+            }
+        });
+        config.auth = AuthPolicy::Only(addresses.collect());
+    }
+    if let Some(path) = args.value_of("storage") {
+        config.storage = load_storage(path);
+    }
+
+    let function = args.value_of("function").unwrap();
+    let record_path = args.value_of("record");
+    let code = match record_path {
+        Some(_) => match trace::instrument(&wasm::Instance::load_file(file_path), &code) {
+            Ok(code) => code,
+            Err(e) => {
+                eprintln!("Failed to instrument {}: {}", file_path, e);
+                std::process::exit(1);
+            }
+        },
+        None => code,
+    };
+    let result = match run::run(&code, function, &call_args, config) {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Some(path) = record_path {
+        if let Err(e) = Trace::new(function, &call_args, &result).write_to_file(path) {
+            eprintln!("Failed to write the trace to {}: {}", path, e);
+        }
+    }
+    if args.value_of("format") == Some("json") {
+        match serde_json::to_string_pretty(&result) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("Failed to serialize the result: {}", e),
+        }
+        return;
+    }
+
+    match (&result.result, &result.error) {
+        (Some(value), _) => println!("Result: {}", to_json(value)),
+        (None, Some(error)) => println!("Trapped: {}", error),
+        (None, None) => (),
+    }
+    println!("Storage changes:");
+    for change in &result.storage {
+        let before = change.before.as_ref().map_or(String::from("-"), to_json);
+        let after = change.after.as_ref().map_or(String::from("-"), to_json);
+        println!("  {:?} {}: {} -> {}", change.storage_type, to_json(&change.key), before, after);
+    }
+    println!("Events:");
+    for event in &result.events {
+        println!("  {} {}", to_json(&event.topics), to_json(&event.data));
+    }
+    if !result.logs.is_empty() {
+        println!("Logs:");
+        for log in &result.logs {
+            println!("  {}", log);
+        }
+    }
+    println!("Host calls:");
+    for call in &result.trace {
+        let call_args: Vec<String> = call.args.iter().map(|arg| arg.to_string()).collect();
+        match &call.result {
+            Some(value) => println!("  {}({}) -> {}", call.function, call_args.join(", "), value),
+            None => println!("  {}({}) trapped", call.function, call_args.join(", ")),
+        }
+    }
+}
+
+fn replay_trace(args: &ArgMatches) {
+    let file_path = args.value_of("file").unwrap();
+    let code = match std::fs::read(file_path) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", file_path, e);
+            std::process::exit(1);
+        }
+    };
+    let trace_path = args.value_of("trace").unwrap();
+    let trace = load_trace(trace_path);
+    let result = match trace::replay(&code, &trace) {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if args.value_of("format") == Some("json") {
+        match serde_json::to_string_pretty(&result) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("Failed to serialize the result: {}", e),
+        }
+    } else {
+        println!("Replayed {} of {} host calls", result.calls, trace.calls.len());
+        for log in &trace.logs {
+            println!("  recorded log: {}", log);
+        }
+        if let Some(mismatch) = &result.mismatch {
+            println!("Call {} differs from the trace:", mismatch.index);
+            match &mismatch.expected {
+                Some(call) => println!("  expected {}.{}{:?}", call.module, call.function, call.raw_args),
+                None => println!("  expected no more calls"),
+            }
+            match &mismatch.actual {
+                Some(call) => println!("  actual   {}.{}{:?}", call.module, call.function, call.raw_args),
+                None => println!("  actual   no more calls"),
+            }
+            if let (Some(expected), Some(actual)) = (&mismatch.expected, &mismatch.actual) {
+                let mut reads = expected.memory_reads.iter().zip(&actual.memory_reads);
+                if let Some((before, now)) = reads.find(|(before, now)| before != now) {
+                    println!("  reads {:?} at 0x{:x}, recorded {:?}", now.bytes, now.pos, before.bytes);
                 }
             }
         }
+        if !result.result_matches {
+            let recorded = match (&trace.result, &trace.error) {
+                (_, Some(error)) => format!("trapped: {}", error),
+                (Some(value), None) => to_json(value),
+                (None, None) => String::from("-"),
+            };
+            println!("The result differs from the recorded {}", recorded);
+        }
+        if let Some(error) = &result.error {
+            println!("Trapped: {}", error);
+        }
+    }
+    if !result.is_faithful() {
+        std::process::exit(2);
+    }
+}
+
+fn load_trace(path: &str) -> Trace {
+    match Trace::load(path) {
+        Ok(trace) => trace,
+        Err(e) => {
+            eprintln!("Failed to read the trace {}: {}", path, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn load_storage(path: &str) -> Vec<StorageEntry> {
+    let storage = std::fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|json| serde_json::from_str(&json).map_err(|e| e.to_string()));
+    match storage {
+        Ok(storage) => storage,
+        Err(e) => {
+            eprintln!("Failed to read the storage {}: {}", path, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn to_json<T: Serialize>(value: &T) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+/// The wasm binaries at `path`, searching directories recursively
+fn wasm_files(path: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(path)?
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .collect();
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() {
+            wasm_files(&entry, files)?;
+        } else if entry.extension().is_some_and(|ext| ext == "wasm") {
+            files.push(entry);
+        }
+    }
+    Ok(())
+}
+
+fn index_references(args: &ArgMatches) {
+    let db_path = args.value_of("db").unwrap();
+    let mut db = if Path::new(db_path).exists() {
+        match SimilarityDb::load(db_path) {
+            Ok(db) => db,
+            Err(e) => {
+                eprintln!("Failed to read the database {}: {}", db_path, e);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        SimilarityDb::default()
+    };
+
+    let mut files = Vec::new();
+    for path in args.values_of("paths").into_iter().flatten() {
+        if let Err(e) = wasm_files(Path::new(path), &mut files) {
+            eprintln!("Failed to read {}: {}", path, e);
+            std::process::exit(1);
+        }
+    }
+    for file in &files {
+        let wasm = match wasm::Instance::from_file(file) {
+            Ok(wasm) => Rc::new(wasm),
+            Err(e) => {
+                eprintln!("Skipping {}: {:?}", file.display(), e);
+                continue;
+            }
+        };
+        let contract = file.file_stem().map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+        let added = identify::index_contract(&mut db, &wasm, &contract);
+        println!("{}: {} functions added", file.display(), added);
+    }
+    if let Err(e) = db.write_to_file(db_path) {
+        eprintln!("Failed to write the database to {}: {}", db_path, e);
+        std::process::exit(1);
+    }
+}
+
+fn identify_functions(args: &ArgMatches) {
+    let db_path = args.value_of("db").unwrap();
+    let db = match SimilarityDb::load(db_path) {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("Failed to read the database {}: {}", db_path, e);
+            std::process::exit(1);
+        }
+    };
+    let wasm = wasm::Instance::load_file(args.value_of("file").unwrap());
+    let identified = identify::identify(&wasm, &db, parse_number(args, "max-distance"));
+
+    if args.value_of("format") == Some("json") {
+        match serde_json::to_string_pretty(&identified) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("Failed to serialize the result: {}", e),
+        }
+        return;
+    }
+    for identification in &identified {
+        println!(
+            "{:>5}  {:<20}  {:<60}  {:>3}  {}",
+            identification.func_index,
+            identification.name,
+            identification.label,
+            identification.distance,
+            identification.contract
+        );
+    }
+}
+
+fn cluster_contracts(args: &ArgMatches) {
+    let dir = Path::new(args.value_of("dir").unwrap());
+    let mut files = Vec::new();
+    if let Err(e) = wasm_files(dir, &mut files) {
+        eprintln!("Failed to read {}: {}", dir.display(), e);
+        std::process::exit(1);
+    }
+    let mut contracts = Vec::new();
+    for file in &files {
+        let wasm = match wasm::Instance::from_file(file) {
+            Ok(wasm) => Rc::new(wasm),
+            Err(e) => {
+                eprintln!("Skipping {}: {:?}", file.display(), e);
+                continue;
+            }
+        };
+        let name = file.strip_prefix(dir).unwrap_or(file).display().to_string();
+        contracts.push(ContractFingerprints::new(&name, &wasm));
+    }
+    let report = cluster::cluster(&contracts, parse_number(args, "max-distance"));
+
+    if args.value_of("format") == Some("json") {
+        match serde_json::to_string_pretty(&report) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("Failed to serialize the report: {}", e),
+        }
+        return;
+    }
+    for (i, cluster) in report.clusters.iter().enumerate() {
+        println!("Cluster {}: {}", i + 1, cluster.contracts.join(", "));
+        let mut contract = None;
+        for divergence in &cluster.divergences {
+            if contract != Some(&divergence.contract) {
+                println!("  {}", divergence.contract);
+                contract = Some(&divergence.contract);
+            }
+            match divergence.distance {
+                Some(distance) => println!("    {} {} (distance {})", divergence.kind, divergence.function, distance),
+                None => println!("    {} {}", divergence.kind, divergence.function),
+            }
+        }
+        if cluster.divergences.is_empty() {
+            println!("  identical exported functions");
+        }
+    }
+    if !report.singletons.is_empty() {
+        println!("Unclustered: {}", report.singletons.join(", "));
+    }
+}
+
+fn diff_contracts(args: &ArgMatches) {
+    let old = wasm::Instance::load_file(args.value_of("old").unwrap());
+    let new = wasm::Instance::load_file(args.value_of("new").unwrap());
+    let options = diff::Options {
+        bodies: !args.is_present("no-bodies"),
+    };
+    let report = diff::compare(&old, &new, &options);
+
+    if args.value_of("format") == Some("json") {
+        match serde_json::to_string_pretty(&report) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("Failed to serialize the report: {}", e),
+        }
+        return;
+    }
+
+    let count = |kind: ChangeKind| report.functions.iter().filter(|change| change.kind == kind).count();
+    println!(
+        "Functions: {} changed, {} added, {} removed, {} unchanged",
+        count(ChangeKind::Changed),
+        count(ChangeKind::Added),
+        count(ChangeKind::Removed),
+        report.unchanged
+    );
+    for change in &report.functions {
+        match (&change.old, &change.new) {
+            (Some(old), Some(new)) if old.name == new.name => print!("{} {}", change.kind, new.signature),
+            (Some(old), Some(new)) => print!("{} {} -> {}", change.kind, old.signature, new.signature),
+            (Some(function), None) | (None, Some(function)) => print!("{} {}", change.kind, function.signature),
+            (None, None) => continue,
+        }
+        match change.matched_by {
+            Some(kind) => println!("  (matched by {})", kind),
+            None => println!(),
+        }
+        if let Some(diff) = &change.diff {
+            print!("{}", diff);
+        }
+    }
+    if !report.types.is_empty() {
+        println!("Types:");
+        for change in &report.types {
+            println!("{} {}", change.kind, change.name);
+            print!("{}", change.diff);
+        }
+    }
+}
+
+fn diff_test(args: &ArgMatches) {
+    let file_path = args.value_of("file").unwrap();
+    let original = match std::fs::read(file_path) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", file_path, e);
+            std::process::exit(1);
+        }
+    };
+    let wasm = wasm::Instance::load_file(file_path);
+    let functions = selected_names(args, &wasm);
+    let project_dir = match args.value_of("source") {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            let dir = args.value_of("work-dir").map_or_else(
+                || std::env::temp_dir().join(format!("auditor-difftest-{}", std::process::id())),
+                PathBuf::from,
+            );
+            let sdk_version = wasm.module().build_info().sdk_version.as_deref();
+            if let Err(e) = project::write_project(&dir, &project::reconstruct(&wasm), sdk_version) {
+                eprintln!("Failed to write the reconstruction to {}: {}", dir.display(), e);
+                std::process::exit(1);
+            }
+            dir
+        }
+    };
+    eprintln!("Building {}", project_dir.display());
+    let reconstruction = match project::build_project(&project_dir) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let options = difftest::Options {
+        cases: parse_number(args, "cases"),
+        seed: parse_number(args, "seed"),
+        functions,
+    };
+    let report = match difftest::compare(&original, &reconstruction, &options) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    if args.value_of("format") == Some("json") {
+        match serde_json::to_string_pretty(&report) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("Failed to serialize the report: {}", e),
+        }
+    } else {
+        for function in &report.functions {
+            println!(
+                "{}: {} case(s), {} trapped in both, {} divergence(s)",
+                function.name,
+                function.cases,
+                function.traps,
+                function.divergences.len()
+            );
+            for divergence in &function.divergences {
+                println!(
+                    "  case {}: {}({}) differs in {}: {} vs {}",
+                    divergence.case,
+                    function.name,
+                    divergence.args.iter().map(to_json).collect::<Vec<_>>().join(", "),
+                    divergence.kind,
+                    divergence.original,
+                    divergence.reconstruction
+                );
+            }
+        }
+    }
+    if report.divergences() > 0 {
+        std::process::exit(2);
+    }
+}
+
+/// Names of the functions matched by the `--func` selectors, empty if there are none
+fn selected_names(args: &ArgMatches, wasm: &wasm::Instance) -> Vec<String> {
+    let selectors: Vec<FuncSelector> = args.values_of("func").into_iter().flatten().map(FuncSelector::new).collect();
+    if selectors.is_empty() {
+        return Vec::new();
+    }
+    match select::select_functions(wasm.module(), &selectors) {
+        Ok(indices) => indices
+            .into_iter()
+            .map(|index| wasm.module().func(index).name().to_string())
+            .collect(),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn fuzz_contract(args: &ArgMatches) {
+    let file_path = args.value_of("file").unwrap();
+    let code = match std::fs::read(file_path) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", file_path, e);
+            std::process::exit(1);
+        }
+    };
+    let wasm = wasm::Instance::load_file(file_path);
+    let options = fuzz::Options {
+        functions: selected_names(args, &wasm),
+        runs: parse_number(args, "runs"),
+        seed: parse_number(args, "seed"),
+        storage: args.value_of("storage").map(load_storage).unwrap_or_default(),
+        corpus: args.value_of("corpus").map(Corpus::new),
+    };
+    let report = match fuzz::fuzz(&wasm, &code, &options) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    if args.value_of("format") == Some("json") {
+        match serde_json::to_string_pretty(&report) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("Failed to serialize the report: {}", e),
+        }
+    } else {
+        for function in &report.functions {
+            println!(
+                "{}: {} run(s), {} block(s) covered, {} input(s) in the corpus",
+                function.name, function.runs, function.covered, function.corpus
+            );
+        }
+        println!("Covered {} of {} blocks", report.covered, report.blocks);
+        for finding in &report.findings {
+            let call_args: Vec<String> = finding.args.iter().map(to_json).collect();
+            println!("Crash: {}({}): {}", finding.function, call_args.join(", "), finding.error);
+        }
+    }
+    if !report.findings.is_empty() {
+        std::process::exit(2);
+    }
+}
+
+fn decompile(args: &ArgMatches) {
+    let file_path = args.value_of("file").unwrap();
+    let wasm = wasm::Instance::load_file(file_path);
+    let graph_stages: Option<Vec<GraphStage>> = if args.is_present("show-graph") {
+        match args.values_of("show-graph") {
+            Some(values) => Some(values.map(|v| v.parse().unwrap()).collect()),
+            None => Some(GraphStage::ALL.to_vec()),
+        }
+    } else {
+        None
+    };
+    let graph_dir = Path::new(args.value_of("graph-dir").unwrap());
+    let source_map_path = args.value_of("source-map");
+    let trace = args.value_of("trace").map(load_trace);
+    let options = WriterOptions {
+        offsets: args.is_present("offsets") || source_map_path.is_some() || trace.is_some(),
+    };
+
+    let selectors: Vec<FuncSelector> = args
+        .values_of("func")
+        .into_iter()
+        .flatten()
+        .chain(args.value_of("function_name"))
+        .map(FuncSelector::new)
+        .collect();
+
+    let func_indices: Vec<u32> = if !selectors.is_empty() {
+        match select::select_functions(wasm.module(), &selectors) {
+            Ok(indices) => indices,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        wasm.module()
+            .functions()
+            .iter()
+            .enumerate()
+            .filter(|(_, func)| !func.is_imported())
+            .map(|(i, _)| i as u32)
+            .collect()
+    };
+
+    if let Some(stages) = &graph_stages {
+        for &func_index in &func_indices {
+            if let Err(e) = graph::write_graphs(wasm.clone(), func_index, stages, graph_dir) {
+                eprintln!("Failed to write graphs of function {}: {}", func_index, e);
+            }
+        }
+    }
+
+    if let Some(path) = args.value_of("dump-ir") {
+        let functions = ir::collect(&wasm, &func_indices, options);
+        if let Err(e) = ir::dump(&functions, path) {
+            eprintln!("Failed to write the IR to {}: {}", path, e);
+        }
+        if path == "-" {
+            return;
+        }
+    }
+
+    if args.value_of("format") == Some("json") {
+        let output = ModuleOutput::new(wasm.clone(), file_path, &func_indices, options);
+        match serde_json::to_string_pretty(&output) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("Failed to serialize the output: {}", e),
+        }
+        return;
+    }
+
+    let mut source_map = SourceMap::new(file_path);
+    let mut lines = 0;
+
+    for func_index in func_indices {
+        let result = if source_map_path.is_some() || trace.is_some() {
+            wasm.decompile_function_to_string(func_index, options).map(|output| {
+                let output = match &trace {
+                    Some(trace) => trace::annotate(&output, trace),
+                    None => output,
+                };
+                source_map.add_output(wasm.module(), &output, lines);
+                lines += output.lines().count();
+                print!("{}", output);
+            })
+        } else {
+            wasm.decompile_function(func_index, options)
+        };
+        match result {
+            Ok(()) => (),
+            Err(CfgBuildError::NoSuchFunc) => eprintln!("No function with index {}", func_index),
+            Err(CfgBuildError::FuncIsImported) => eprintln!(
+                "Function {} ({}) is imported and can not be decompiled",
+                wasm.module().func(func_index).name(),
+                func_index
+            ),
+        }
+    }
+
+    if let Some(path) = source_map_path {
+        if let Err(e) = source_map.write_to_file(path) {
+            eprintln!("Failed to write source map to {}: {}", path, e);
+        }
     }
 }
@@ -0,0 +1,180 @@
+use parity_wasm::elements::{External, Instruction};
+use serde::Serialize;
+
+use crate::cfg::CfgBuildError;
+use crate::fmt::WriterOptions;
+use crate::soroban::{BuildInfo, FunctionInfo, SpecType};
+use crate::wasm_wrapper::wasm::Instance;
+use crate::wasm_wrapper::wasm_adapter::Module;
+use std::rc::Rc;
+
+/// Machine readable result of decompiling a module, emitted with `--format json`
+#[derive(Debug, Serialize)]
+pub struct ModuleOutput {
+    pub file: String,
+    pub metadata: ModuleMetadata,
+    pub spec_functions: Vec<SpecFunction>,
+    pub spec_types: Vec<SpecType>,
+    pub imports: Vec<ImportOutput>,
+    pub functions: Vec<FunctionOutput>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ModuleMetadata {
+    pub functions: usize,
+    pub imported_functions: usize,
+    pub exports: Vec<String>,
+    pub globals: usize,
+    pub memories: usize,
+    pub custom_sections: Vec<String>,
+    pub build: BuildInfo,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SpecFunction {
+    pub name: String,
+    pub inputs: Vec<SpecParam>,
+    pub output: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SpecParam {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub type_str: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportOutput {
+    pub func_index: u32,
+    pub module: String,
+    pub field: String,
+    /// Host function name resolved via env.json
+    pub name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FunctionOutput {
+    pub index: u32,
+    pub name: String,
+    pub exported: bool,
+    pub signature: String,
+    pub spec_signature: Option<String>,
+    pub code: Option<String>,
+    pub matched_patterns: Vec<String>,
+    pub warnings: Vec<String>,
+    pub stats: FunctionStats,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct FunctionStats {
+    pub blocks: usize,
+    pub loops: usize,
+    pub instructions: usize,
+}
+
+impl From<&FunctionInfo> for SpecFunction {
+    fn from(spec: &FunctionInfo) -> Self {
+        SpecFunction {
+            name: spec.name().to_string(),
+            inputs: spec
+                .inputs()
+                .iter()
+                .map(|param| SpecParam {
+                    name: param.name().to_string(),
+                    type_str: param.type_ident().type_str().to_string(),
+                })
+                .collect(),
+            output: spec.output().map(|o| o.type_ident().type_str().to_string()),
+        }
+    }
+}
+
+impl ModuleMetadata {
+    pub fn new(module: &Module) -> Self {
+        ModuleMetadata {
+            functions: module.functions().len(),
+            imported_functions: module.functions().iter().filter(|f| f.is_imported()).count(),
+            exports: module.exports().iter().map(|e| e.field().to_string()).collect(),
+            globals: module.globals().len(),
+            memories: module.memories().len(),
+            custom_sections: module.custom_sections().iter().map(|s| s.name().to_string()).collect(),
+            build: module.build_info().clone(),
+        }
+    }
+}
+
+impl ModuleOutput {
+    pub fn new(wasm: Rc<Instance>, file: &str, func_indices: &[u32], options: WriterOptions) -> Self {
+        let module = wasm.module();
+
+        let metadata = ModuleMetadata::new(module);
+
+        let imports = module
+            .imports()
+            .iter()
+            .filter(|entry| matches!(entry.external(), External::Function(_)))
+            .zip(module.functions())
+            .enumerate()
+            .map(|(i, (entry, func))| ImportOutput {
+                func_index: i as u32,
+                module: entry.module().to_string(),
+                field: entry.field().to_string(),
+                name: func.name().to_string(),
+            })
+            .collect();
+
+        let functions = func_indices
+            .iter()
+            .map(|&func_index| FunctionOutput::new(wasm.clone(), func_index, options))
+            .collect();
+
+        ModuleOutput {
+            file: file.to_string(),
+            metadata,
+            spec_functions: module.spec_fns().iter().map(SpecFunction::from).collect(),
+            spec_types: module.spec_types().to_vec(),
+            imports,
+            functions,
+        }
+    }
+}
+
+impl FunctionOutput {
+    pub fn new(wasm: Rc<Instance>, func_index: u32, options: WriterOptions) -> Self {
+        let module = wasm.module();
+        let func = module.func(func_index);
+
+        let stats = FunctionStats {
+            instructions: func.instructions().len(),
+            loops: func.instructions().iter().filter(|i| matches!(i, Instruction::Loop(_))).count(),
+            ..FunctionStats::default()
+        };
+
+        let mut output = FunctionOutput {
+            index: func_index,
+            name: func.name().to_string(),
+            exported: module.is_exported(func_index),
+            signature: func.to_string(),
+            spec_signature: func.spec_fn().filter(|_| func.has_spec()).map(|spec| spec.to_string()),
+            code: None,
+            matched_patterns: Vec::new(),
+            warnings: Vec::new(),
+            stats,
+        };
+
+        match wasm.decompile_function_output(func_index, options) {
+            Ok(decompiled) => {
+                output.stats.blocks = decompiled.blocks;
+                output.code = Some(decompiled.code);
+                output.matched_patterns = decompiled.matched_patterns;
+                output.warnings = decompiled.warnings;
+            }
+            Err(CfgBuildError::NoSuchFunc) => output.warnings.push(String::from("No such function")),
+            Err(CfgBuildError::FuncIsImported) => {
+                output.warnings.push(String::from("Function is imported and can not be decompiled"))
+            }
+        }
+        output
+    }
+}
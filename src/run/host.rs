@@ -0,0 +1,699 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use serde_json::{json, Value};
+use soroban_sdk::xdr::{
+    Int128Parts, Int256Parts, ScBytes, ScMap, ScMapEntry, ScString, ScSymbol, ScVal, ScVec, UInt128Parts, UInt256Parts,
+};
+
+use super::val::{self, Objects};
+use super::{Event, Host, HostCall, HostConfig, HostError, MemoryAccess, StorageType};
+use crate::soroban::ModuleFunction;
+
+/// Env.json types of arguments and results that are passed as plain integers instead of `Val`s
+const RAW_TYPES: [&str; 3] = ["i64", "u64", "StorageType"];
+
+/// Whether values of an env.json type are plain integers rather than `Val`s
+pub fn is_raw_type(ty: &str) -> bool {
+    RAW_TYPES.contains(&ty)
+}
+
+/// Ledgers an entry can live at most, the network default
+const MAX_ENTRY_TTL: u32 = 3_110_400;
+
+/// Size of a `(pos, len)` slice of linear memory
+const SLICE_SIZE: u32 = 8;
+const VAL_SIZE: u32 = 8;
+
+/// A host function with the types of its arguments and result from env.json
+#[derive(Debug, Clone)]
+pub struct HostFn {
+    pub module: String,
+    pub name: String,
+    pub args: Vec<String>,
+    pub result: String,
+}
+
+impl HostFn {
+    pub fn new(function: &ModuleFunction) -> Self {
+        let args = function.function["args"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|arg| arg["type"].as_str().unwrap_or_default().to_string())
+            .collect();
+        HostFn {
+            module: function.module_name.clone(),
+            name: function.function_name.clone(),
+            args,
+            result: function.function["return"].as_str().unwrap_or_default().to_string(),
+        }
+    }
+}
+
+fn storage_type(value: i64) -> Result<StorageType, HostError> {
+    match value {
+        0 => Ok(StorageType::Temporary),
+        1 => Ok(StorageType::Persistent),
+        2 => Ok(StorageType::Instance),
+        _ => Err(HostError::new(format!("Invalid storage type {}", value))),
+    }
+}
+
+fn read(memory: &[u8], pos: u32, len: u32) -> Result<&[u8], HostError> {
+    let end = pos.checked_add(len).map(|end| end as usize);
+    match end {
+        Some(end) if end <= memory.len() => Ok(&memory[pos as usize..end]),
+        _ => Err(HostError::new(format!(
+            "Out of bounds memory access at {} with length {}",
+            pos, len
+        ))),
+    }
+}
+
+pub(super) fn write(memory: &mut [u8], pos: u32, bytes: &[u8]) -> Result<(), HostError> {
+    let end = pos.checked_add(bytes.len() as u32).map(|end| end as usize);
+    match end {
+        Some(end) if end <= memory.len() => {
+            memory[pos as usize..end].copy_from_slice(bytes);
+            Ok(())
+        }
+        _ => Err(HostError::new(format!("Out of bounds memory access at {}", pos))),
+    }
+}
+
+fn index(len: usize, i: u32) -> Result<usize, HostError> {
+    match i as usize {
+        i if i < len => Ok(i),
+        _ => Err(HostError::new(format!("Index {} out of bounds for length {}", i, len))),
+    }
+}
+
+fn vec_val(items: Vec<ScVal>) -> Result<ScVal, HostError> {
+    let items = items.try_into().map_err(|_| HostError::new("Vector too long"))?;
+    Ok(ScVal::Vec(Some(ScVec(items))))
+}
+
+/// Maps are kept sorted by key like in the real host
+fn map_val(mut entries: Vec<(ScVal, ScVal)>) -> Result<ScVal, HostError> {
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    let entries: Vec<ScMapEntry> = entries.into_iter().map(|(key, val)| ScMapEntry { key, val }).collect();
+    let entries = entries.try_into().map_err(|_| HostError::new("Map too large"))?;
+    Ok(ScVal::Map(Some(ScMap(entries))))
+}
+
+fn bytes_val(bytes: Vec<u8>) -> Result<ScVal, HostError> {
+    let bytes = bytes.try_into().map_err(|_| HostError::new("Bytes too long"))?;
+    Ok(ScVal::Bytes(ScBytes(bytes)))
+}
+
+fn string_val(bytes: &[u8]) -> Result<ScVal, HostError> {
+    let string = bytes
+        .to_vec()
+        .try_into()
+        .map_err(|_| HostError::new("String too long"))?;
+    Ok(ScVal::String(ScString(string)))
+}
+
+fn symbol_val(bytes: &[u8]) -> Result<ScVal, HostError> {
+    let symbol = bytes
+        .to_vec()
+        .try_into()
+        .map_err(|_| HostError::new("Symbol too long"))?;
+    Ok(ScVal::Symbol(ScSymbol(symbol)))
+}
+
+/// The state of the mock host during one invocation
+#[derive(Debug)]
+pub struct MockHost {
+    config: HostConfig,
+    pub objects: Objects,
+    pub initial_storage: BTreeMap<(StorageType, ScVal), ScVal>,
+    pub storage: BTreeMap<(StorageType, ScVal), ScVal>,
+    pub events: Vec<Event>,
+    /// Messages passed to `log_from_linear_memory`
+    pub logs: Vec<String>,
+    pub trace: Vec<HostCall>,
+    /// The value passed to `fail_with_error`
+    pub failure: Option<ScVal>,
+    /// Linear memory read by the current host call
+    reads: RefCell<Vec<MemoryAccess>>,
+    /// Linear memory written by the current host call
+    writes: Vec<MemoryAccess>,
+}
+
+impl MockHost {
+    pub fn new(config: HostConfig) -> Self {
+        let storage: BTreeMap<_, _> = config
+            .storage
+            .iter()
+            .map(|entry| ((entry.storage_type, entry.key.clone()), entry.val.clone()))
+            .collect();
+        MockHost {
+            config,
+            objects: Objects::default(),
+            initial_storage: storage.clone(),
+            storage,
+            events: Vec::new(),
+            logs: Vec::new(),
+            trace: Vec::new(),
+            failure: None,
+            reads: RefCell::default(),
+            writes: Vec::new(),
+        }
+    }
+
+    fn trace_value(&self, value: i64, ty: &str) -> Value {
+        if is_raw_type(ty) {
+            return json!(value);
+        }
+        match self.objects.from_val(value as u64) {
+            Ok(value) => serde_json::to_value(value).unwrap_or_default(),
+            Err(_) => json!(format!("0x{:x}", value)),
+        }
+    }
+
+    /// Read from linear memory and remember it for the trace
+    fn read_memory<'m>(&self, memory: &'m [u8], pos: u32, len: u32) -> Result<&'m [u8], HostError> {
+        let bytes = read(memory, pos, len)?;
+        self.reads.borrow_mut().push(MemoryAccess {
+            pos,
+            bytes: bytes.to_vec(),
+        });
+        Ok(bytes)
+    }
+
+    fn read_memory_u64(&self, memory: &[u8], pos: u32) -> Result<u64, HostError> {
+        Ok(u64::from_le_bytes(self.read_memory(memory, pos, 8)?.try_into().unwrap()))
+    }
+
+    /// The strings of `len` `(pos, len)` slices at `slices_pos`
+    fn read_memory_slices<'m>(&self, memory: &'m [u8], slices_pos: u32, len: u32) -> Result<Vec<&'m [u8]>, HostError> {
+        (0..len)
+            .map(|i| {
+                let slice = self.read_memory(memory, slices_pos + i * SLICE_SIZE, SLICE_SIZE)?;
+                let pos = u32::from_le_bytes(slice[..4].try_into().unwrap());
+                let len = u32::from_le_bytes(slice[4..].try_into().unwrap());
+                self.read_memory(memory, pos, len)
+            })
+            .collect()
+    }
+
+    /// Write to linear memory and remember it for the trace
+    fn write(&mut self, memory: &mut [u8], pos: u32, bytes: &[u8]) -> Result<(), HostError> {
+        write(memory, pos, bytes)?;
+        self.writes.push(MemoryAccess {
+            pos,
+            bytes: bytes.to_vec(),
+        });
+        Ok(())
+    }
+
+    fn val(&mut self, value: ScVal) -> Result<i64, HostError> {
+        Ok(self.objects.to_val(&value)? as i64)
+    }
+
+    fn value(&self, val: i64) -> Result<ScVal, HostError> {
+        self.objects.from_val(val as u64)
+    }
+
+    fn u32(&self, val: i64) -> Result<u32, HostError> {
+        val::u32_from_val(val as u64)
+    }
+
+    fn unexpected<T>(&self, val: i64, expected: &str) -> Result<T, HostError> {
+        Err(HostError::new(format!(
+            "Expected {}, got {:?}",
+            expected,
+            self.value(val)
+        )))
+    }
+
+    fn vec(&self, val: i64) -> Result<Vec<ScVal>, HostError> {
+        match self.value(val)? {
+            ScVal::Vec(Some(items)) => Ok(items.0.to_vec()),
+            ScVal::Vec(None) => Ok(Vec::new()),
+            _ => self.unexpected(val, "a vector"),
+        }
+    }
+
+    fn map(&self, val: i64) -> Result<Vec<(ScVal, ScVal)>, HostError> {
+        match self.value(val)? {
+            ScVal::Map(Some(entries)) => Ok(entries.0.iter().map(|e| (e.key.clone(), e.val.clone())).collect()),
+            ScVal::Map(None) => Ok(Vec::new()),
+            _ => self.unexpected(val, "a map"),
+        }
+    }
+
+    /// The content of a bytes, string or symbol value
+    fn bytes(&self, val: i64) -> Result<Vec<u8>, HostError> {
+        match self.value(val)? {
+            ScVal::Bytes(bytes) => Ok(bytes.0.to_vec()),
+            ScVal::String(string) => Ok(string.0.to_vec()),
+            ScVal::Symbol(symbol) => Ok(symbol.0.to_vec()),
+            _ => self.unexpected(val, "bytes"),
+        }
+    }
+
+    fn u64(&self, val: i64) -> Result<u64, HostError> {
+        match self.value(val)? {
+            ScVal::U64(v) => Ok(v),
+            ScVal::Timepoint(v) => Ok(v.0),
+            ScVal::Duration(v) => Ok(v.0),
+            _ => self.unexpected(val, "a u64"),
+        }
+    }
+
+    fn u128(&self, val: i64) -> Result<UInt128Parts, HostError> {
+        match self.value(val)? {
+            ScVal::U128(parts) => Ok(parts),
+            _ => self.unexpected(val, "a u128"),
+        }
+    }
+
+    fn i128(&self, val: i64) -> Result<Int128Parts, HostError> {
+        match self.value(val)? {
+            ScVal::I128(parts) => Ok(parts),
+            _ => self.unexpected(val, "an i128"),
+        }
+    }
+
+    fn u256(&self, val: i64) -> Result<UInt256Parts, HostError> {
+        match self.value(val)? {
+            ScVal::U256(parts) => Ok(parts),
+            _ => self.unexpected(val, "a u256"),
+        }
+    }
+
+    fn i256(&self, val: i64) -> Result<Int256Parts, HostError> {
+        match self.value(val)? {
+            ScVal::I256(parts) => Ok(parts),
+            _ => self.unexpected(val, "an i256"),
+        }
+    }
+
+    fn require_auth(&self, val: i64) -> Result<i64, HostError> {
+        match self.value(val)? {
+            ScVal::Address(address) if self.config.auth.is_authorized(&address) => Ok(val::VOID_VAL as i64),
+            ScVal::Address(address) => Err(HostError::new(format!("{} is not authorized", address))),
+            _ => self.unexpected(val, "an address"),
+        }
+    }
+
+    fn storage_key(&self, key: i64, ty: i64) -> Result<(StorageType, ScVal), HostError> {
+        Ok((storage_type(ty)?, self.value(key)?))
+    }
+
+    fn dispatch(&mut self, name: &str, memory: &mut [u8], args: &[i64]) -> Result<i64, HostError> {
+        let arg = |i: usize| args.get(i).copied().unwrap_or_default();
+        let void = Ok(val::VOID_VAL as i64);
+        match name {
+            // context
+            "log_from_linear_memory" => {
+                let message = self.read_memory(memory, self.u32(arg(0))?, self.u32(arg(1))?)?;
+                self.logs.push(String::from_utf8_lossy(message).into_owned());
+                void
+            }
+            "obj_cmp" => Ok(self.value(arg(0))?.cmp(&self.value(arg(1))?) as i64),
+            "contract_event" => {
+                let topics = self.vec(arg(0))?;
+                let data = self.value(arg(1))?;
+                self.events.push(Event { topics, data });
+                void
+            }
+            "get_ledger_version" => self.val(ScVal::U32(20)),
+            "get_ledger_sequence" => self.val(ScVal::U32(self.config.ledger_sequence)),
+            "get_ledger_timestamp" => self.val(ScVal::U64(self.config.ledger_timestamp)),
+            "get_ledger_network_id" => self.val(bytes_val(vec![0; 32])?),
+            "get_current_contract_address" => self.val(ScVal::Address(self.config.contract.clone())),
+            "get_max_live_until_ledger" => {
+                let max_live_until = self.config.ledger_sequence.saturating_add(MAX_ENTRY_TTL - 1);
+                self.val(ScVal::U32(max_live_until))
+            }
+            "fail_with_error" => {
+                let error = self.value(arg(0))?;
+                let message = format!("Contract failed with {:?}", error);
+                self.failure = Some(error);
+                Err(HostError::contract_panic(message))
+            }
+
+            // int
+            "obj_from_u64" => self.val(ScVal::U64(arg(0) as u64)),
+            "obj_from_i64" => self.val(ScVal::I64(arg(0))),
+            "timepoint_obj_from_u64" => self.val(ScVal::Timepoint((arg(0) as u64).into())),
+            "duration_obj_from_u64" => self.val(ScVal::Duration((arg(0) as u64).into())),
+            "obj_to_u64" | "timepoint_obj_to_u64" | "duration_obj_to_u64" => Ok(self.u64(arg(0))? as i64),
+            "obj_to_i64" => match self.value(arg(0))? {
+                ScVal::I64(v) => Ok(v),
+                _ => self.unexpected(arg(0), "an i64"),
+            },
+            "obj_from_u128_pieces" => self.val(ScVal::U128(UInt128Parts {
+                hi: arg(0) as u64,
+                lo: arg(1) as u64,
+            })),
+            "obj_to_u128_lo64" => Ok(self.u128(arg(0))?.lo as i64),
+            "obj_to_u128_hi64" => Ok(self.u128(arg(0))?.hi as i64),
+            "obj_from_i128_pieces" => self.val(ScVal::I128(Int128Parts {
+                hi: arg(0),
+                lo: arg(1) as u64,
+            })),
+            "obj_to_i128_lo64" => Ok(self.i128(arg(0))?.lo as i64),
+            "obj_to_i128_hi64" => Ok(self.i128(arg(0))?.hi),
+            "obj_from_u256_pieces" => self.val(ScVal::U256(UInt256Parts {
+                hi_hi: arg(0) as u64,
+                hi_lo: arg(1) as u64,
+                lo_hi: arg(2) as u64,
+                lo_lo: arg(3) as u64,
+            })),
+            "obj_to_u256_hi_hi" => Ok(self.u256(arg(0))?.hi_hi as i64),
+            "obj_to_u256_hi_lo" => Ok(self.u256(arg(0))?.hi_lo as i64),
+            "obj_to_u256_lo_hi" => Ok(self.u256(arg(0))?.lo_hi as i64),
+            "obj_to_u256_lo_lo" => Ok(self.u256(arg(0))?.lo_lo as i64),
+            "obj_from_i256_pieces" => self.val(ScVal::I256(Int256Parts {
+                hi_hi: arg(0),
+                hi_lo: arg(1) as u64,
+                lo_hi: arg(2) as u64,
+                lo_lo: arg(3) as u64,
+            })),
+            "obj_to_i256_hi_hi" => Ok(self.i256(arg(0))?.hi_hi),
+            "obj_to_i256_hi_lo" => Ok(self.i256(arg(0))?.hi_lo as i64),
+            "obj_to_i256_lo_hi" => Ok(self.i256(arg(0))?.lo_hi as i64),
+            "obj_to_i256_lo_lo" => Ok(self.i256(arg(0))?.lo_lo as i64),
+
+            // map
+            "map_new" => self.val(map_val(Vec::new())?),
+            "map_put" => {
+                let key = self.value(arg(1))?;
+                let mut entries = self.map(arg(0))?;
+                entries.retain(|(k, _)| *k != key);
+                entries.push((key, self.value(arg(2))?));
+                self.val(map_val(entries)?)
+            }
+            "map_get" => {
+                let key = self.value(arg(1))?;
+                match self.map(arg(0))?.into_iter().find(|(k, _)| *k == key) {
+                    Some((_, value)) => self.val(value),
+                    None => Err(HostError::new(format!("Map has no key {:?}", key))),
+                }
+            }
+            "map_del" => {
+                let key = self.value(arg(1))?;
+                let mut entries = self.map(arg(0))?;
+                let len = entries.len();
+                entries.retain(|(k, _)| *k != key);
+                if entries.len() == len {
+                    return Err(HostError::new(format!("Map has no key {:?}", key)));
+                }
+                self.val(map_val(entries)?)
+            }
+            "map_len" => self.val(ScVal::U32(self.map(arg(0))?.len() as u32)),
+            "map_has" => {
+                let key = self.value(arg(1))?;
+                Ok(val::bool_val(self.map(arg(0))?.iter().any(|(k, _)| *k == key)) as i64)
+            }
+            "map_key_by_pos" | "map_val_by_pos" => {
+                let mut entries = self.map(arg(0))?;
+                let i = index(entries.len(), self.u32(arg(1))?)?;
+                let (key, value) = entries.swap_remove(i);
+                self.val(if name == "map_key_by_pos" { key } else { value })
+            }
+            "map_keys" => self.val(vec_val(self.map(arg(0))?.into_iter().map(|(k, _)| k).collect())?),
+            "map_values" => self.val(vec_val(self.map(arg(0))?.into_iter().map(|(_, v)| v).collect())?),
+            "map_new_from_linear_memory" => {
+                let len = self.u32(arg(2))?;
+                let keys = self.read_memory_slices(memory, self.u32(arg(0))?, len)?;
+                let vals_pos = self.u32(arg(1))?;
+                let entries = keys
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, key)| {
+                        Ok((
+                            symbol_val(key)?,
+                            self.value(self.read_memory_u64(memory, vals_pos + i as u32 * VAL_SIZE)? as i64)?,
+                        ))
+                    })
+                    .collect::<Result<Vec<_>, HostError>>()?;
+                self.val(map_val(entries)?)
+            }
+            "map_unpack_to_linear_memory" => {
+                let entries = self.map(arg(0))?;
+                let len = self.u32(arg(3))?;
+                let keys: Vec<Vec<u8>> = self.read_memory_slices(memory, self.u32(arg(1))?, len)?
+                    .into_iter()
+                    .map(<[u8]>::to_vec)
+                    .collect();
+                if keys.len() != entries.len() {
+                    return Err(HostError::new("Map size differs from the number of keys"));
+                }
+                let vals_pos = self.u32(arg(2))?;
+                for (i, key) in keys.iter().enumerate() {
+                    let key = symbol_val(key)?;
+                    let value = match entries.iter().find(|(k, _)| *k == key) {
+                        Some((_, value)) => value.clone(),
+                        None => return Err(HostError::new(format!("Map has no key {:?}", key))),
+                    };
+                    let val = self.val(value)? as u64;
+                    self.write(memory, vals_pos + i as u32 * VAL_SIZE, &val.to_le_bytes())?;
+                }
+                void
+            }
+
+            // vec
+            "vec_new" => self.val(vec_val(Vec::new())?),
+            "vec_put" | "vec_insert" => {
+                let mut items = self.vec(arg(0))?;
+                let i = self.u32(arg(1))?;
+                let value = self.value(arg(2))?;
+                if name == "vec_put" {
+                    let i = index(items.len(), i)?;
+                    items[i] = value;
+                } else {
+                    items.insert(index(items.len() + 1, i)?, value);
+                }
+                self.val(vec_val(items)?)
+            }
+            "vec_get" => {
+                let mut items = self.vec(arg(0))?;
+                let i = index(items.len(), self.u32(arg(1))?)?;
+                self.val(items.swap_remove(i))
+            }
+            "vec_del" => {
+                let mut items = self.vec(arg(0))?;
+                items.remove(index(items.len(), self.u32(arg(1))?)?);
+                self.val(vec_val(items)?)
+            }
+            "vec_len" => self.val(ScVal::U32(self.vec(arg(0))?.len() as u32)),
+            "vec_push_front" | "vec_push_back" => {
+                let mut items = self.vec(arg(0))?;
+                let value = self.value(arg(1))?;
+                if name == "vec_push_front" {
+                    items.insert(0, value);
+                } else {
+                    items.push(value);
+                }
+                self.val(vec_val(items)?)
+            }
+            "vec_pop_front" | "vec_pop_back" => {
+                let mut items = self.vec(arg(0))?;
+                index(items.len(), 0)?;
+                if name == "vec_pop_front" {
+                    items.remove(0);
+                } else {
+                    items.pop();
+                }
+                self.val(vec_val(items)?)
+            }
+            "vec_front" | "vec_back" => {
+                let items = self.vec(arg(0))?;
+                let item = if name == "vec_front" {
+                    items.first()
+                } else {
+                    items.last()
+                };
+                match item {
+                    Some(item) => self.val(item.clone()),
+                    None => Err(HostError::new("Vector is empty")),
+                }
+            }
+            "vec_append" => {
+                let mut items = self.vec(arg(0))?;
+                items.extend(self.vec(arg(1))?);
+                self.val(vec_val(items)?)
+            }
+            "vec_slice" => {
+                let items = self.vec(arg(0))?;
+                let (start, end) = (self.u32(arg(1))? as usize, self.u32(arg(2))? as usize);
+                match items.get(start..end) {
+                    Some(slice) => self.val(vec_val(slice.to_vec())?),
+                    None => Err(HostError::new(format!("Slice {}..{} out of bounds", start, end))),
+                }
+            }
+            "vec_first_index_of" | "vec_last_index_of" => {
+                let items = self.vec(arg(0))?;
+                let value = self.value(arg(1))?;
+                let position = if name == "vec_first_index_of" {
+                    items.iter().position(|item| *item == value)
+                } else {
+                    items.iter().rposition(|item| *item == value)
+                };
+                match position {
+                    Some(i) => self.val(ScVal::U32(i as u32)),
+                    None => void,
+                }
+            }
+            "vec_new_from_linear_memory" => {
+                let vals_pos = self.u32(arg(0))?;
+                let items = (0..self.u32(arg(1))?)
+                    .map(|i| self.value(self.read_memory_u64(memory, vals_pos + i * VAL_SIZE)? as i64))
+                    .collect::<Result<Vec<_>, HostError>>()?;
+                self.val(vec_val(items)?)
+            }
+            "vec_unpack_to_linear_memory" => {
+                let items = self.vec(arg(0))?;
+                if items.len() != self.u32(arg(2))? as usize {
+                    return Err(HostError::new("Vector length differs from the requested length"));
+                }
+                let vals_pos = self.u32(arg(1))?;
+                for (i, item) in items.into_iter().enumerate() {
+                    let val = self.val(item)? as u64;
+                    self.write(memory, vals_pos + i as u32 * VAL_SIZE, &val.to_le_bytes())?;
+                }
+                void
+            }
+
+            // ledger
+            "put_contract_data" => {
+                let key = self.storage_key(arg(0), arg(2))?;
+                let value = self.value(arg(1))?;
+                self.storage.insert(key, value);
+                void
+            }
+            "has_contract_data" => {
+                let key = self.storage_key(arg(0), arg(1))?;
+                Ok(val::bool_val(self.storage.contains_key(&key)) as i64)
+            }
+            "get_contract_data" => {
+                let key = self.storage_key(arg(0), arg(1))?;
+                match self.storage.get(&key) {
+                    Some(value) => self.val(value.clone()),
+                    None => Err(HostError::new(format!("No {:?} storage entry for {:?}", key.0, key.1))),
+                }
+            }
+            "del_contract_data" => {
+                let key = self.storage_key(arg(0), arg(1))?;
+                self.storage.remove(&key);
+                void
+            }
+            "extend_contract_data_ttl" => {
+                let key = self.storage_key(arg(0), arg(1))?;
+                if !self.storage.contains_key(&key) {
+                    return Err(HostError::new(format!("No {:?} storage entry for {:?}", key.0, key.1)));
+                }
+                void
+            }
+            "extend_current_contract_instance_and_code_ttl" | "extend_contract_instance_and_code_ttl" => void,
+
+            // buf
+            "bytes_new" => self.val(bytes_val(Vec::new())?),
+            "bytes_new_from_linear_memory" => {
+                let bytes = self.read_memory(memory, self.u32(arg(0))?, self.u32(arg(1))?)?.to_vec();
+                self.val(bytes_val(bytes)?)
+            }
+            "string_new_from_linear_memory" => {
+                let value = string_val(self.read_memory(memory, self.u32(arg(0))?, self.u32(arg(1))?)?)?;
+                self.val(value)
+            }
+            "symbol_new_from_linear_memory" => {
+                let value = symbol_val(self.read_memory(memory, self.u32(arg(0))?, self.u32(arg(1))?)?)?;
+                self.val(value)
+            }
+            "bytes_copy_to_linear_memory" | "string_copy_to_linear_memory" | "symbol_copy_to_linear_memory" => {
+                let bytes = self.bytes(arg(0))?;
+                let (pos, len) = (self.u32(arg(1))?, self.u32(arg(3))?);
+                let bytes = read(&bytes, pos, len)?;
+                self.write(memory, self.u32(arg(2))?, bytes)?;
+                void
+            }
+            "bytes_copy_from_linear_memory" => {
+                let mut bytes = self.bytes(arg(0))?;
+                let pos = self.u32(arg(1))? as usize;
+                let copied = self.read_memory(memory, self.u32(arg(2))?, self.u32(arg(3))?)?;
+                if pos > bytes.len() {
+                    return Err(HostError::new(format!("Index {} out of bounds", pos)));
+                }
+                bytes.resize(bytes.len().max(pos + copied.len()), 0);
+                bytes[pos..pos + copied.len()].copy_from_slice(copied);
+                self.val(bytes_val(bytes)?)
+            }
+            "bytes_len" | "string_len" | "symbol_len" => self.val(ScVal::U32(self.bytes(arg(0))?.len() as u32)),
+            "bytes_get" => {
+                let bytes = self.bytes(arg(0))?;
+                let i = index(bytes.len(), self.u32(arg(1))?)?;
+                self.val(ScVal::U32(u32::from(bytes[i])))
+            }
+            "bytes_push" => {
+                let mut bytes = self.bytes(arg(0))?;
+                bytes.push(self.u32(arg(1))? as u8);
+                self.val(bytes_val(bytes)?)
+            }
+            "bytes_append" => {
+                let mut bytes = self.bytes(arg(0))?;
+                bytes.extend(self.bytes(arg(1))?);
+                self.val(bytes_val(bytes)?)
+            }
+            "bytes_slice" => {
+                let bytes = self.bytes(arg(0))?;
+                let (start, end) = (self.u32(arg(1))?, self.u32(arg(2))?);
+                let slice = read(&bytes, start, end.saturating_sub(start))?.to_vec();
+                self.val(bytes_val(slice)?)
+            }
+            "symbol_index_in_linear_memory" => {
+                let symbol = self.bytes(arg(0))?;
+                let slices = self.read_memory_slices(memory, self.u32(arg(1))?, self.u32(arg(2))?)?;
+                match slices.iter().position(|slice| *slice == symbol.as_slice()) {
+                    Some(i) => self.val(ScVal::U32(i as u32)),
+                    None => Err(HostError::new(format!(
+                        "Symbol {} not found",
+                        String::from_utf8_lossy(&symbol)
+                    ))),
+                }
+            }
+
+            // address
+            "require_auth" | "require_auth_for_args" => self.require_auth(arg(0)),
+
+            _ => Err(HostError::unsupported(format!(
+                "Host function {} is not supported by the mock host",
+                name
+            ))),
+        }
+    }
+}
+
+impl Host for MockHost {
+    /// Call a host function and record it in the trace
+    fn call(&mut self, host_fn: &HostFn, memory: &mut [u8], args: &[i64], site: Option<u32>) -> Result<i64, HostError> {
+        let trace_args = args
+            .iter()
+            .zip(&host_fn.args)
+            .map(|(arg, ty)| self.trace_value(*arg, ty))
+            .collect();
+        let result = self.dispatch(&host_fn.name, memory, args);
+        let trace_result = result
+            .as_ref()
+            .ok()
+            .map(|result| self.trace_value(*result, &host_fn.result));
+        self.trace.push(HostCall {
+            module: host_fn.module.clone(),
+            function: host_fn.name.clone(),
+            site,
+            args: trace_args,
+            result: trace_result,
+            raw_args: args.to_vec(),
+            raw_result: result.as_ref().ok().copied(),
+            memory_reads: self.reads.take(),
+            memory_writes: std::mem::take(&mut self.writes),
+        });
+        result
+    }
+}
@@ -0,0 +1,377 @@
+//! Random arguments of the types declared in a contract spec.
+//!
+//! Values are biased towards the edge cases contracts branch on: zero, one, the limits of
+//! integers and empty collections. Addresses come from a small pool so that a sequence of calls
+//! keeps referring to the same accounts. Existing arguments can be mutated and shrunk, which the
+//! fuzzer uses to explore around interesting inputs and to minimize crashes.
+
+use std::collections::HashMap;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use soroban_sdk::xdr::{
+    AccountId, Duration, Int128Parts, Int256Parts, PublicKey, ScAddress, ScBytes, ScError, ScMap, ScMapEntry,
+    ScSpecEntry, ScSpecFunctionV0, ScSpecTypeDef, ScSpecUdtUnionCaseV0, ScString, ScSymbol, ScVal, ScVec, TimePoint,
+    UInt128Parts, UInt256Parts, Uint256,
+};
+
+/// Nesting depth after which collections are empty and options are `None`
+const MAX_DEPTH: u32 = 3;
+const MAX_LEN: usize = 3;
+const ADDRESSES: u8 = 3;
+const SYMBOL_CHARS: &[u8] = b"_0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+pub struct InputGenerator {
+    types: HashMap<String, ScSpecEntry>,
+    rng: StdRng,
+}
+
+impl InputGenerator {
+    pub fn new(entries: &[ScSpecEntry], seed: u64) -> Self {
+        let types = entries
+            .iter()
+            .filter_map(|entry| {
+                let name = match entry {
+                    ScSpecEntry::FunctionV0(_) => return None,
+                    ScSpecEntry::UdtStructV0(s) => s.name.to_utf8_string_lossy(),
+                    ScSpecEntry::UdtUnionV0(u) => u.name.to_utf8_string_lossy(),
+                    ScSpecEntry::UdtEnumV0(e) => e.name.to_utf8_string_lossy(),
+                    ScSpecEntry::UdtErrorEnumV0(e) => e.name.to_utf8_string_lossy(),
+                };
+                Some((name, entry.clone()))
+            })
+            .collect();
+        InputGenerator {
+            types,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn args(&mut self, func: &ScSpecFunctionV0) -> Vec<ScVal> {
+        func.inputs.iter().map(|input| self.value(&input.type_, 0)).collect()
+    }
+
+    /// Change one of the arguments
+    pub fn mutate(&mut self, func: &ScSpecFunctionV0, args: &[ScVal]) -> Vec<ScVal> {
+        let mut args = args.to_vec();
+        if args.len() != func.inputs.len() || args.is_empty() {
+            return self.args(func);
+        }
+        let i = self.rng.gen_range(0..args.len());
+        args[i] = self.mutate_value(&func.inputs[i].type_, &args[i], 0);
+        args
+    }
+
+    fn mutate_value(&mut self, ty: &ScSpecTypeDef, value: &ScVal, depth: u32) -> ScVal {
+        if self.one_in(4) {
+            return self.value(ty, depth);
+        }
+        if let Some(n) = int_value(value) {
+            let n = match self.rng.gen_range(0..5) {
+                0 => n.wrapping_add(1),
+                1 => n.wrapping_sub(1),
+                2 => n.wrapping_mul(2),
+                3 => n / 2,
+                _ => n ^ 1 << self.rng.gen_range(0..64),
+            };
+            return with_int_value(value, n).unwrap_or_else(|| value.clone());
+        }
+        match (ty, value) {
+            (ScSpecTypeDef::Vec(vec), ScVal::Vec(Some(items))) => {
+                let mut items = items.to_vec();
+                match self.rng.gen_range(0..3) {
+                    0 if !items.is_empty() => {
+                        items.remove(self.rng.gen_range(0..items.len()));
+                    }
+                    1 if !items.is_empty() => {
+                        let i = self.rng.gen_range(0..items.len());
+                        items[i] = self.mutate_value(&vec.element_type, &items[i], depth + 1);
+                    }
+                    _ => items.push(self.value(&vec.element_type, depth + 1)),
+                }
+                vec_val(items)
+            }
+            (ScSpecTypeDef::Option(option), value) if *value != ScVal::Void => {
+                self.mutate_value(&option.value_type, value, depth + 1)
+            }
+            _ => self.value(ty, depth),
+        }
+    }
+
+    /// One in `n` chance
+    fn one_in(&mut self, n: u32) -> bool {
+        self.rng.gen_ratio(1, n)
+    }
+
+    fn unsigned(&mut self, max: u64) -> u64 {
+        match self.rng.gen_range(0..8) {
+            0 => 0,
+            1 => 1,
+            2 => max,
+            _ => self.rng.gen_range(0..1000).min(max),
+        }
+    }
+
+    fn signed(&mut self, min: i64, max: i64) -> i64 {
+        match self.rng.gen_range(0..8) {
+            0 => 0,
+            1 => -1,
+            2 => max,
+            3 => min,
+            _ => self.rng.gen_range(-1000..1000),
+        }
+    }
+
+    fn u128(&mut self) -> u128 {
+        if self.one_in(8) {
+            u128::MAX
+        } else {
+            u128::from(self.unsigned(u64::MAX))
+        }
+    }
+
+    fn i128(&mut self) -> i128 {
+        match self.rng.gen_range(0..8) {
+            0 => i128::MAX,
+            1 => i128::MIN,
+            _ => i128::from(self.signed(i64::MIN, i64::MAX)),
+        }
+    }
+
+    fn len(&mut self, depth: u32) -> usize {
+        if depth >= MAX_DEPTH {
+            0
+        } else {
+            self.rng.gen_range(0..=MAX_LEN)
+        }
+    }
+
+    fn bytes(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.rng.gen()).collect()
+    }
+
+    fn symbol(&mut self) -> String {
+        let len = self.rng.gen_range(1..=9);
+        (0..len)
+            .map(|_| SYMBOL_CHARS[self.rng.gen_range(0..SYMBOL_CHARS.len())] as char)
+            .collect()
+    }
+
+    fn value(&mut self, ty: &ScSpecTypeDef, depth: u32) -> ScVal {
+        match ty {
+            ScSpecTypeDef::Val => ScVal::U32(self.unsigned(u64::from(u32::MAX)) as u32),
+            ScSpecTypeDef::Bool => ScVal::Bool(self.rng.gen()),
+            ScSpecTypeDef::Void => ScVal::Void,
+            ScSpecTypeDef::Error => ScVal::Error(ScError::Contract(self.rng.gen_range(0..16))),
+            ScSpecTypeDef::U32 => ScVal::U32(self.unsigned(u64::from(u32::MAX)) as u32),
+            ScSpecTypeDef::I32 => ScVal::I32(self.signed(i64::from(i32::MIN), i64::from(i32::MAX)) as i32),
+            ScSpecTypeDef::U64 => ScVal::U64(self.unsigned(u64::MAX)),
+            ScSpecTypeDef::I64 => ScVal::I64(self.signed(i64::MIN, i64::MAX)),
+            ScSpecTypeDef::Timepoint => ScVal::Timepoint(TimePoint(self.unsigned(u64::MAX))),
+            ScSpecTypeDef::Duration => ScVal::Duration(Duration(self.unsigned(u64::MAX))),
+            ScSpecTypeDef::U128 => {
+                let value = self.u128();
+                ScVal::U128(UInt128Parts {
+                    hi: (value >> 64) as u64,
+                    lo: value as u64,
+                })
+            }
+            ScSpecTypeDef::I128 => {
+                let value = self.i128();
+                ScVal::I128(Int128Parts {
+                    hi: (value >> 64) as i64,
+                    lo: value as u64,
+                })
+            }
+            ScSpecTypeDef::U256 => ScVal::U256(UInt256Parts {
+                hi_hi: 0,
+                hi_lo: 0,
+                lo_hi: 0,
+                lo_lo: self.unsigned(u64::MAX),
+            }),
+            ScSpecTypeDef::I256 => {
+                let value = self.signed(i64::MIN, i64::MAX);
+                let sign = (value >> 63) as u64;
+                ScVal::I256(Int256Parts {
+                    hi_hi: value >> 63,
+                    hi_lo: sign,
+                    lo_hi: sign,
+                    lo_lo: value as u64,
+                })
+            }
+            ScSpecTypeDef::Bytes => {
+                let len = self.rng.gen_range(0..=8);
+                ScVal::Bytes(ScBytes(self.bytes(len).try_into().unwrap()))
+            }
+            ScSpecTypeDef::BytesN(bytes) => ScVal::Bytes(ScBytes(self.bytes(bytes.n as usize).try_into().unwrap())),
+            ScSpecTypeDef::String => {
+                let string = if self.one_in(4) { String::new() } else { self.symbol() };
+                ScVal::String(ScString(string.try_into().unwrap()))
+            }
+            ScSpecTypeDef::Symbol => ScVal::Symbol(symbol(&self.symbol())),
+            ScSpecTypeDef::Address => ScVal::Address(account(self.rng.gen_range(1..=ADDRESSES))),
+            ScSpecTypeDef::Option(option) => {
+                if depth >= MAX_DEPTH || self.one_in(4) {
+                    ScVal::Void
+                } else {
+                    self.value(&option.value_type, depth + 1)
+                }
+            }
+            // Results only appear as outputs, an input gets the ok value
+            ScSpecTypeDef::Result(result) => self.value(&result.ok_type, depth + 1),
+            ScSpecTypeDef::Vec(vec) => {
+                let len = self.len(depth);
+                let items = (0..len).map(|_| self.value(&vec.element_type, depth + 1)).collect();
+                vec_val(items)
+            }
+            ScSpecTypeDef::Map(map) => {
+                let len = self.len(depth);
+                let entries = (0..len)
+                    .map(|_| {
+                        let key = self.value(&map.key_type, depth + 1);
+                        (key, self.value(&map.value_type, depth + 1))
+                    })
+                    .collect();
+                map_val(entries)
+            }
+            ScSpecTypeDef::Tuple(tuple) => {
+                vec_val(tuple.value_types.iter().map(|ty| self.value(ty, depth + 1)).collect())
+            }
+            ScSpecTypeDef::Udt(udt) => self.udt(&udt.name.to_utf8_string_lossy(), depth),
+        }
+    }
+
+    /// User defined types are encoded the way `#[contracttype]` does it
+    fn udt(&mut self, name: &str, depth: u32) -> ScVal {
+        let entry = match self.types.get(name) {
+            Some(entry) => entry.clone(),
+            None => return ScVal::Void,
+        };
+        match entry {
+            ScSpecEntry::UdtStructV0(s) => {
+                let is_tuple = s
+                    .fields
+                    .iter()
+                    .all(|field| field.name.to_utf8_string_lossy().parse::<u32>().is_ok());
+                let fields = s.fields.iter().map(|field| {
+                    let name = field.name.to_utf8_string_lossy();
+                    (name, self.value(&field.type_, depth + 1))
+                });
+                if is_tuple {
+                    vec_val(fields.map(|(_, value)| value).collect())
+                } else {
+                    map_val(
+                        fields
+                            .map(|(name, value)| (ScVal::Symbol(symbol(&name)), value))
+                            .collect(),
+                    )
+                }
+            }
+            ScSpecEntry::UdtUnionV0(u) if !u.cases.is_empty() => match &u.cases[self.rng.gen_range(0..u.cases.len())] {
+                ScSpecUdtUnionCaseV0::VoidV0(case) => {
+                    vec_val(vec![ScVal::Symbol(symbol(&case.name.to_utf8_string_lossy()))])
+                }
+                ScSpecUdtUnionCaseV0::TupleV0(case) => {
+                    let mut items = vec![ScVal::Symbol(symbol(&case.name.to_utf8_string_lossy()))];
+                    items.extend(case.type_.iter().map(|ty| self.value(ty, depth + 1)));
+                    vec_val(items)
+                }
+            },
+            ScSpecEntry::UdtEnumV0(e) if !e.cases.is_empty() => {
+                ScVal::U32(e.cases[self.rng.gen_range(0..e.cases.len())].value)
+            }
+            ScSpecEntry::UdtErrorEnumV0(e) if !e.cases.is_empty() => {
+                let code = e.cases[self.rng.gen_range(0..e.cases.len())].value;
+                ScVal::Error(ScError::Contract(code))
+            }
+            _ => ScVal::Void,
+        }
+    }
+}
+
+/// Smaller values of the same type to minimize an input with
+pub fn shrink(value: &ScVal) -> Vec<ScVal> {
+    if let Some(n) = int_value(value) {
+        return [0, n / 2]
+            .into_iter()
+            .filter(|smaller| *smaller != n)
+            .filter_map(|smaller| with_int_value(value, smaller))
+            .collect();
+    }
+    match value {
+        ScVal::Vec(Some(items)) => (0..items.len())
+            .map(|i| {
+                let mut items = items.to_vec();
+                items.remove(i);
+                vec_val(items)
+            })
+            .collect(),
+        ScVal::Map(Some(entries)) => (0..entries.len())
+            .map(|i| {
+                let mut entries = entries.to_vec();
+                entries.remove(i);
+                ScVal::Map(Some(ScMap(entries.try_into().unwrap())))
+            })
+            .collect(),
+        ScVal::String(string) if !string.0.is_empty() => {
+            let half = string.0.len() / 2;
+            vec![ScVal::String(ScString(string.0[..half].to_vec().try_into().unwrap()))]
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn int_value(value: &ScVal) -> Option<i128> {
+    Some(match value {
+        ScVal::U32(v) => i128::from(*v),
+        ScVal::I32(v) => i128::from(*v),
+        ScVal::U64(v) => i128::from(*v),
+        ScVal::I64(v) => i128::from(*v),
+        ScVal::U128(UInt128Parts { hi, lo }) => i128::try_from(u128::from(*hi) << 64 | u128::from(*lo)).ok()?,
+        ScVal::I128(Int128Parts { hi, lo }) => i128::from(*hi) << 64 | i128::from(*lo),
+        _ => return None,
+    })
+}
+
+/// A value of the same type as `value` holding `n`, if `n` is in its range
+fn with_int_value(value: &ScVal, n: i128) -> Option<ScVal> {
+    Some(match value {
+        ScVal::U32(_) => ScVal::U32(n.try_into().ok()?),
+        ScVal::I32(_) => ScVal::I32(n.try_into().ok()?),
+        ScVal::U64(_) => ScVal::U64(n.try_into().ok()?),
+        ScVal::I64(_) => ScVal::I64(n.try_into().ok()?),
+        ScVal::U128(_) => {
+            let n = u128::try_from(n).ok()?;
+            ScVal::U128(UInt128Parts {
+                hi: (n >> 64) as u64,
+                lo: n as u64,
+            })
+        }
+        ScVal::I128(_) => ScVal::I128(Int128Parts {
+            hi: (n >> 64) as i64,
+            lo: n as u64,
+        }),
+        _ => return None,
+    })
+}
+
+/// The `n`th account of the address pool
+fn account(n: u8) -> ScAddress {
+    ScAddress::Account(AccountId(PublicKey::PublicKeyTypeEd25519(Uint256([n; 32]))))
+}
+
+fn symbol(name: &str) -> ScSymbol {
+    ScSymbol(name.try_into().unwrap())
+}
+
+fn vec_val(items: Vec<ScVal>) -> ScVal {
+    ScVal::Vec(Some(ScVec(items.try_into().unwrap())))
+}
+
+/// Maps need sorted, unique keys
+fn map_val(mut entries: Vec<(ScVal, ScVal)>) -> ScVal {
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    entries.dedup_by(|a, b| a.0 == b.0);
+    let entries: Vec<ScMapEntry> = entries.into_iter().map(|(key, val)| ScMapEntry { key, val }).collect();
+    ScVal::Map(Some(ScMap(entries.try_into().unwrap())))
+}
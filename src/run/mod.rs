@@ -0,0 +1,410 @@
+//! Concrete execution of contract functions with a mock host.
+//!
+//! The contract runs in `soroban-wasmi` with its imports bound to `MockHost`, which implements
+//! the env.json host functions the SDK commonly uses on top of plain Rust data structures:
+//! storage is a map, events are recorded and `require_auth` follows an `AuthPolicy`.
+//! Host functions that would need the network or other contracts trap.
+
+pub mod host;
+pub mod inputs;
+pub mod trace;
+pub mod val;
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use soroban_sdk::xdr::{Hash, ScAddress, ScVal};
+use soroban_wasmi::core::Trap;
+use soroban_wasmi::{Engine, Extern, ExternType, Linker, Module, Store, Value};
+
+use crate::soroban::host::StorageType;
+use crate::soroban::{env_common_modules_for, take_common_module, BuildInfo};
+pub use host::{HostFn, MockHost};
+
+/// The host functions a contract is linked against
+pub trait Host {
+    /// Call the host function `host_fn` from the instruction at the code offset `site`, if the
+    /// binary records call sites
+    fn call(&mut self, host_fn: &HostFn, memory: &mut [u8], args: &[i64], site: Option<u32>) -> Result<i64, HostError>;
+}
+
+/// Prefix of exported i64 globals whose values are returned as `RunResult::coverage`,
+/// numbered from 0
+pub const COVERAGE_GLOBAL: &str = "__coverage_";
+
+/// Why a host function trapped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum HostErrorKind {
+    /// The host rejected the call, e.g. an invalid argument, a missing entry or a failed auth
+    HostTrap,
+    /// The contract called `fail_with_error`
+    ContractPanic,
+    /// The mock host does not implement the host function
+    Unsupported,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostError {
+    kind: HostErrorKind,
+    message: String,
+}
+
+impl HostError {
+    pub fn new(message: impl Into<String>) -> Self {
+        HostError {
+            kind: HostErrorKind::HostTrap,
+            message: message.into(),
+        }
+    }
+
+    pub fn contract_panic(message: impl Into<String>) -> Self {
+        HostError {
+            kind: HostErrorKind::ContractPanic,
+            message: message.into(),
+        }
+    }
+
+    pub fn unsupported(message: impl Into<String>) -> Self {
+        HostError {
+            kind: HostErrorKind::Unsupported,
+            message: message.into(),
+        }
+    }
+
+    pub fn kind(&self) -> HostErrorKind {
+        self.kind
+    }
+}
+
+impl fmt::Display for HostError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Host errors unwind through wasmi as traps and are recovered with `Trap::downcast_ref`
+impl soroban_wasmi::core::HostError for HostError {}
+
+#[derive(Debug)]
+pub enum RunError {
+    Wasm(soroban_wasmi::Error),
+    /// env.json could not be read
+    Env(String),
+    UnknownImport(String, String),
+    NoSuchFunc(String),
+    InvalidArg(HostError),
+}
+
+impl fmt::Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Wasm(error) => write!(f, "{}", error),
+            Self::Env(error) => write!(f, "Failed to read env.json: {}", error),
+            Self::UnknownImport(module, name) => write!(f, "Unknown host function {}.{}", module, name),
+            Self::NoSuchFunc(name) => write!(f, "The contract does not export a function `{}`", name),
+            Self::InvalidArg(error) => write!(f, "Invalid argument: {}", error),
+        }
+    }
+}
+
+impl From<soroban_wasmi::Error> for RunError {
+    fn from(error: soroban_wasmi::Error) -> Self {
+        Self::Wasm(error)
+    }
+}
+
+impl From<soroban_wasmi::errors::LinkerError> for RunError {
+    fn from(error: soroban_wasmi::errors::LinkerError) -> Self {
+        Self::Wasm(error.into())
+    }
+}
+
+/// Which addresses `require_auth` accepts
+#[derive(Debug, Clone, Default)]
+pub enum AuthPolicy {
+    #[default]
+    AllowAll,
+    Only(Vec<ScAddress>),
+}
+
+impl AuthPolicy {
+    pub fn is_authorized(&self, address: &ScAddress) -> bool {
+        match self {
+            Self::AllowAll => true,
+            Self::Only(addresses) => addresses.contains(address),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageEntry {
+    pub storage_type: StorageType,
+    pub key: ScVal,
+    pub val: ScVal,
+}
+
+#[derive(Debug, Clone)]
+pub struct HostConfig {
+    pub auth: AuthPolicy,
+    /// Address of the contract itself
+    pub contract: ScAddress,
+    pub ledger_sequence: u32,
+    pub ledger_timestamp: u64,
+    /// Storage before the call
+    pub storage: Vec<StorageEntry>,
+}
+
+impl Default for HostConfig {
+    fn default() -> Self {
+        HostConfig {
+            auth: AuthPolicy::default(),
+            contract: ScAddress::Contract(Hash([0; 32])),
+            ledger_sequence: 1,
+            ledger_timestamp: 0,
+            storage: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Event {
+    pub topics: Vec<ScVal>,
+    pub data: ScVal,
+}
+
+/// A call of the contract to the host. Arguments and results that are `Val`s are shown as
+/// `ScVal`s, plain integers as numbers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HostCall {
+    pub module: String,
+    pub function: String,
+    /// Code offset of the call instruction, if the binary records call sites
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub site: Option<u32>,
+    pub args: Vec<serde_json::Value>,
+    /// `None` if the call trapped
+    pub result: Option<serde_json::Value>,
+    /// The arguments as passed by the contract
+    pub raw_args: Vec<i64>,
+    pub raw_result: Option<i64>,
+    /// What the host read from linear memory during the call
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub memory_reads: Vec<MemoryAccess>,
+    /// What the host wrote to linear memory during the call
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub memory_writes: Vec<MemoryAccess>,
+}
+
+/// Bytes of linear memory at `pos`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryAccess {
+    pub pos: u32,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StorageChange {
+    pub storage_type: StorageType,
+    pub key: ScVal,
+    pub before: Option<ScVal>,
+    pub after: Option<ScVal>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RunResult {
+    pub result: Option<ScVal>,
+    /// Why the call trapped
+    pub error: Option<String>,
+    /// Kind of the host error the call trapped with, `None` if the contract itself trapped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host_error: Option<HostErrorKind>,
+    /// The error the contract explicitly failed with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure: Option<ScVal>,
+    pub storage: Vec<StorageChange>,
+    pub events: Vec<Event>,
+    /// Messages the contract logged, only debug builds log
+    pub logs: Vec<String>,
+    pub trace: Vec<HostCall>,
+    /// Values of the `COVERAGE_GLOBAL` globals of instrumented binaries
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub coverage: Vec<u64>,
+    /// The returned `Val`
+    #[serde(skip)]
+    pub raw_result: Option<i64>,
+}
+
+fn storage_diff(
+    before: &BTreeMap<(StorageType, ScVal), ScVal>,
+    after: &BTreeMap<(StorageType, ScVal), ScVal>,
+) -> Vec<StorageChange> {
+    let mut keys: Vec<&(StorageType, ScVal)> = before.keys().chain(after.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    keys.into_iter()
+        .filter(|key| before.get(key) != after.get(key))
+        .map(|key| StorageChange {
+            storage_type: key.0,
+            key: key.1.clone(),
+            before: before.get(key).cloned(),
+            after: after.get(key).cloned(),
+        })
+        .collect()
+}
+
+/// Bind every imported host function of the module to `H`
+fn link<H: Host + 'static>(engine: &Engine, module: &Module, sdk_version: Option<&str>) -> Result<Linker<H>, RunError> {
+    let env_modules = env_common_modules_for(sdk_version).map_err(|e| RunError::Env(e.to_string()))?;
+    let mut linker = Linker::<H>::new(engine);
+    for import in module.imports() {
+        let ty = match import.ty() {
+            ExternType::Func(ty) => ty.clone(),
+            _ => continue,
+        };
+        let function = take_common_module(&env_modules, import.module(), import.name())
+            .map_err(|_| RunError::UnknownImport(import.module().to_string(), import.name().to_string()))?;
+        let host_fn = HostFn::new(&function);
+        linker.func_new(
+            import.module(),
+            import.name(),
+            ty,
+            move |mut caller, params, results| {
+                // every env.json argument is a `Val` or a plain 64 bit integer
+                let args = params
+                    .iter()
+                    .map(|p| {
+                        let message = || format!("Argument {:?} of {} is not an i64", p, host_fn.name);
+                        p.i64().ok_or_else(|| HostError::new(message()))
+                    })
+                    .collect::<Result<Vec<i64>, HostError>>()
+                    .map_err(Trap::from)?;
+                let site = caller
+                    .get_export(trace::CALL_SITE_GLOBAL)
+                    .and_then(Extern::into_global)
+                    .and_then(|global| global.get(&caller).i32())
+                    .map(|site| site as u32);
+                let memory = caller.get_export("memory").and_then(Extern::into_memory);
+                let result = match memory {
+                    Some(memory) => {
+                        let (memory, host) = memory.data_and_store_mut(&mut caller);
+                        host.call(&host_fn, memory, &args, site)
+                    }
+                    None => caller.data_mut().call(&host_fn, &mut [], &args, site),
+                };
+                let result = result.map_err(Trap::from)?;
+                if let Some(slot) = results.first_mut() {
+                    *slot = Value::I64(result);
+                }
+                Ok(())
+            },
+        )?;
+    }
+    Ok(linker)
+}
+
+/// A finished call of an exported function
+struct Invocation<H> {
+    host: H,
+    /// The returned `Val`, `None` for functions without result, or why the call trapped
+    outcome: Result<Option<i64>, soroban_wasmi::Error>,
+    coverage: Vec<u64>,
+}
+
+/// Kind of the host error a call trapped with
+fn host_error_kind(error: &soroban_wasmi::Error) -> Option<HostErrorKind> {
+    match error {
+        soroban_wasmi::Error::Trap(trap) => trap.downcast_ref::<HostError>().map(HostError::kind),
+        _ => None,
+    }
+}
+
+/// A contract compiled and linked against the host `H` once, to be called any number of times
+pub struct Contract<H: Host + 'static = MockHost> {
+    engine: Engine,
+    module: Module,
+    linker: Linker<H>,
+}
+
+impl<H: Host + 'static> Contract<H> {
+    pub fn new(code: &[u8]) -> Result<Self, RunError> {
+        let engine = Engine::default();
+        let module = Module::new(&engine, code)?;
+        let build_info = BuildInfo::from_wasm(code).unwrap_or_default();
+        let linker = link(&engine, &module, build_info.sdk_version.as_deref())?;
+        Ok(Contract { engine, module, linker })
+    }
+
+    /// Call the exported function `func` with the `Val`s `args` on `host`
+    fn invoke(&self, func: &str, host: H, args: &[i64]) -> Result<Invocation<H>, RunError> {
+        let mut store = Store::new(&self.engine, host);
+        let instance = self.linker.instantiate(&mut store, &self.module)?.start(&mut store)?;
+        let func = instance
+            .get_func(&store, func)
+            .ok_or_else(|| RunError::NoSuchFunc(func.to_string()))?;
+
+        let inputs: Vec<Value> = args.iter().map(|arg| Value::I64(*arg)).collect();
+        let mut outputs = vec![Value::I64(0); func.ty(&store).results().len()];
+        let outcome = func
+            .call(&mut store, &inputs, &mut outputs)
+            .map(|()| outputs.first().and_then(Value::i64));
+
+        let coverage = (0..)
+            .map_while(|i| instance.get_export(&store, &format!("{}{}", COVERAGE_GLOBAL, i)))
+            .filter_map(|export| export.into_global())
+            .filter_map(|global| global.get(&store).i64())
+            .map(|bits| bits as u64)
+            .collect();
+        Ok(Invocation {
+            host: store.into_data(),
+            outcome,
+            coverage,
+        })
+    }
+}
+
+impl Contract<MockHost> {
+    /// Call the exported function `func` with the mock host
+    pub fn run(&self, func: &str, args: &[ScVal], config: HostConfig) -> Result<RunResult, RunError> {
+        let mut host = MockHost::new(config);
+        let args = args
+            .iter()
+            .map(|arg| Ok(host.objects.to_val(arg)? as i64))
+            .collect::<Result<Vec<_>, HostError>>()
+            .map_err(RunError::InvalidArg)?;
+        let Invocation {
+            host,
+            outcome,
+            coverage,
+        } = self.invoke(func, host, &args)?;
+
+        let raw_result = outcome.as_ref().ok().copied().flatten();
+        let host_error = outcome.as_ref().err().and_then(host_error_kind);
+        let (result, error) = match outcome {
+            Ok(Some(val)) => match host.objects.from_val(val as u64) {
+                Ok(value) => (Some(value), None),
+                Err(e) => (None, Some(e.to_string())),
+            },
+            Ok(None) => (Some(ScVal::Void), None),
+            Err(e) => (None, Some(e.to_string())),
+        };
+        Ok(RunResult {
+            result,
+            error,
+            host_error,
+            failure: host.failure,
+            storage: storage_diff(&host.initial_storage, &host.storage),
+            events: host.events,
+            logs: host.logs,
+            trace: host.trace,
+            coverage,
+            raw_result,
+        })
+    }
+}
+
+/// Call the exported function `func` of the contract `code` with the mock host. Use a [`Contract`]
+/// to call a binary more than once.
+pub fn run(code: &[u8], func: &str, args: &[ScVal], config: HostConfig) -> Result<RunResult, RunError> {
+    Contract::new(code)?.run(func, args, config)
+}
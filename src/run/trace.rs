@@ -0,0 +1,286 @@
+//! Recording and replaying the host calls of a contract run.
+//!
+//! A trace holds every call the contract made to the mock host with its raw `Val`s and the linear
+//! memory the host read and wrote. Replaying it runs the contract against the recorded results instead of
+//! a host, so an incident can be reproduced without the original storage and checked to issue the
+//! same calls. Binaries instrumented with `instrument` also record the code offset of each call,
+//! which lets `annotate` show the observed values next to the decompiled call sites.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+
+use parity_wasm::elements::{
+    ExportEntry, ExportSection, GlobalEntry, GlobalSection, GlobalType, InitExpr, Instruction, Internal, Section,
+    ValueType,
+};
+use serde::{Deserialize, Serialize};
+use soroban_sdk::xdr::ScVal;
+
+use super::host::{self, HostFn};
+use super::val::Objects;
+use super::{Contract, Host, HostCall, HostError, Invocation, MemoryAccess, RunError, RunResult};
+use crate::source_map::parse_offset;
+use crate::wasm_wrapper::wasm::Instance;
+
+/// Exported i32 global that holds the code offset of the host call in progress
+pub const CALL_SITE_GLOBAL: &str = "__call_site";
+
+/// Observations shown per call site, the rest are counted
+const MAX_OBSERVATIONS: usize = 3;
+
+/// Make `code`, the binary `wasm` was loaded from, store the code offset of every call to an
+/// imported function in `CALL_SITE_GLOBAL` before the call
+pub fn instrument(wasm: &Instance, code: &[u8]) -> Result<Vec<u8>, parity_wasm::elements::Error> {
+    let module = wasm.module();
+    let mut binary: parity_wasm::elements::Module = parity_wasm::deserialize_buffer(code)?;
+    let global = module.globals().len() as u32;
+    let imported_funcs = module.functions().iter().filter(|func| func.is_imported()).count();
+
+    if let Some(code_section) = binary.code_section_mut() {
+        for (i, body) in code_section.bodies_mut().iter_mut().enumerate() {
+            let func = module.func((imported_funcs + i) as u32);
+            let instructions = body.code_mut().elements_mut();
+            let mut recorded = Vec::with_capacity(instructions.len());
+            for (instr_index, instr) in instructions.drain(..).enumerate() {
+                if let (Instruction::Call(callee), Some(offset)) = (&instr, func.instr_offset(instr_index)) {
+                    if (*callee as usize) < imported_funcs {
+                        recorded.push(Instruction::I32Const(offset as i32));
+                        recorded.push(Instruction::SetGlobal(global));
+                    }
+                }
+                recorded.push(instr);
+            }
+            *instructions = recorded;
+        }
+    }
+
+    let entry = GlobalEntry::new(
+        GlobalType::new(ValueType::I32, true),
+        InitExpr::new(vec![Instruction::I32Const(-1), Instruction::End]),
+    );
+    match binary.global_section_mut() {
+        Some(section) => section.entries_mut().push(entry),
+        None => binary.insert_section(Section::Global(GlobalSection::with_entries(vec![entry])))?,
+    }
+    let export = ExportEntry::new(CALL_SITE_GLOBAL.to_string(), Internal::Global(global));
+    match binary.export_section_mut() {
+        Some(section) => section.entries_mut().push(export),
+        None => binary.insert_section(Section::Export(ExportSection::with_entries(vec![export])))?,
+    }
+    parity_wasm::serialize(binary)
+}
+
+/// The host calls of one contract invocation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Trace {
+    pub function: String,
+    pub args: Vec<ScVal>,
+    pub calls: Vec<HostCall>,
+    /// Messages the contract logged, missing in traces recorded before logs were captured
+    #[serde(default)]
+    pub logs: Vec<String>,
+    pub result: Option<ScVal>,
+    /// The returned `Val`, `None` if the call trapped or returns nothing
+    pub raw_result: Option<i64>,
+    /// Why the call trapped
+    pub error: Option<String>,
+}
+
+impl Trace {
+    pub fn new(function: &str, args: &[ScVal], result: &RunResult) -> Self {
+        Trace {
+            function: function.to_string(),
+            args: args.to_vec(),
+            calls: result.trace.clone(),
+            logs: result.logs.clone(),
+            result: result.result.clone(),
+            raw_result: result.raw_result,
+            error: result.error.clone(),
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Trace, Box<dyn Error>> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    pub fn write_to_file(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+}
+
+/// A host call issued during replay
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ReplayedCall {
+    pub module: String,
+    pub function: String,
+    pub raw_args: Vec<i64>,
+    /// Linear memory where the recorded call read from, as it is now
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub memory_reads: Vec<MemoryAccess>,
+}
+
+/// The first point where the replayed calls differ from the trace
+#[derive(Debug, Clone, Serialize)]
+pub struct Mismatch {
+    pub index: usize,
+    /// `None` if the trace has no more calls
+    pub expected: Option<HostCall>,
+    /// `None` if the contract returned without issuing the expected call
+    pub actual: Option<ReplayedCall>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayResult {
+    /// Calls that matched the trace
+    pub calls: usize,
+    pub mismatch: Option<Mismatch>,
+    /// Whether the contract returned the recorded value, or trapped like it did
+    pub result_matches: bool,
+    /// Why the replayed call trapped
+    pub error: Option<String>,
+}
+
+impl ReplayResult {
+    pub fn is_faithful(&self) -> bool {
+        self.mismatch.is_none() && self.result_matches
+    }
+}
+
+/// Answers host calls with the recorded results
+struct ReplayHost {
+    calls: Vec<HostCall>,
+    next: usize,
+    mismatch: Option<Mismatch>,
+}
+
+impl Host for ReplayHost {
+    fn call(
+        &mut self,
+        host_fn: &HostFn,
+        memory: &mut [u8],
+        args: &[i64],
+        _site: Option<u32>,
+    ) -> Result<i64, HostError> {
+        let expected = self.calls.get(self.next);
+        let memory_reads = expected.map_or_else(Vec::new, |call| {
+            call.memory_reads
+                .iter()
+                .map(|read| MemoryAccess {
+                    pos: read.pos,
+                    bytes: memory
+                        .get(read.pos as usize..)
+                        .map(|rest| rest.iter().take(read.bytes.len()).copied().collect())
+                        .unwrap_or_default(),
+                })
+                .collect()
+        });
+        let matches = expected.is_some_and(|call| {
+            call.module == host_fn.module
+                && call.function == host_fn.name
+                && call.raw_args == args
+                && call.memory_reads == memory_reads
+        });
+        if !matches {
+            self.mismatch = Some(Mismatch {
+                index: self.next,
+                expected: expected.cloned(),
+                actual: Some(ReplayedCall {
+                    module: host_fn.module.clone(),
+                    function: host_fn.name.clone(),
+                    raw_args: args.to_vec(),
+                    memory_reads,
+                }),
+            });
+            return Err(HostError::new("The call differs from the trace"));
+        }
+        let call = &self.calls[self.next];
+        self.next += 1;
+        for write in &call.memory_writes {
+            host::write(memory, write.pos, &write.bytes)?;
+        }
+        call.raw_result
+            .ok_or_else(|| HostError::new("The recorded call trapped"))
+    }
+}
+
+/// Run the traced function of the contract `code` against the calls of `trace`
+pub fn replay(code: &[u8], trace: &Trace) -> Result<ReplayResult, RunError> {
+    let mut objects = Objects::default();
+    let args = trace
+        .args
+        .iter()
+        .map(|arg| Ok(objects.to_val(arg)? as i64))
+        .collect::<Result<Vec<_>, HostError>>()
+        .map_err(RunError::InvalidArg)?;
+    let host = ReplayHost {
+        calls: trace.calls.clone(),
+        next: 0,
+        mismatch: None,
+    };
+    let Invocation { host, outcome, .. } = Contract::new(code)?.invoke(&trace.function, host, &args)?;
+
+    let mut mismatch = host.mismatch;
+    if mismatch.is_none() && host.next < host.calls.len() && outcome.is_ok() {
+        mismatch = Some(Mismatch {
+            index: host.next,
+            expected: host.calls.get(host.next).cloned(),
+            actual: None,
+        });
+    }
+    let result_matches = match &outcome {
+        Ok(raw_result) => trace.error.is_none() && *raw_result == trace.raw_result,
+        Err(_) => trace.error.is_some(),
+    };
+    Ok(ReplayResult {
+        calls: host.next,
+        mismatch,
+        result_matches,
+        error: outcome.err().map(|e| e.to_string()),
+    })
+}
+
+fn describe(call: &HostCall) -> String {
+    let args: Vec<String> = call.args.iter().map(|arg| arg.to_string()).collect();
+    match &call.result {
+        Some(result) => format!("{}({}) -> {}", call.function, args.join(", "), result),
+        None => format!("{}({}) trapped", call.function, args.join(", ")),
+    }
+}
+
+/// Add the values observed in `trace` after the offset annotations of the decompiled `code` that
+/// belong to a recorded call site
+pub fn annotate(code: &str, trace: &Trace) -> String {
+    let mut sites: BTreeMap<u32, Vec<&HostCall>> = BTreeMap::new();
+    for call in &trace.calls {
+        if let Some(site) = call.site {
+            sites.entry(site).or_default().push(call);
+        }
+    }
+
+    let mut annotated = String::with_capacity(code.len());
+    for line in code.lines() {
+        annotated.push_str(line);
+        annotated.push('\n');
+        let calls = match parse_offset(line).and_then(|offset| sites.get(&offset)) {
+            Some(calls) => calls,
+            None => continue,
+        };
+        let indent = &line[..line.len() - line.trim_start().len()];
+        for call in calls.iter().take(MAX_OBSERVATIONS) {
+            annotated.push_str(&format!("{}// observed: {}\n", indent, describe(call)));
+        }
+        if calls.len() > MAX_OBSERVATIONS {
+            annotated.push_str(&format!(
+                "{}// observed {} more times\n",
+                indent,
+                calls.len() - MAX_OBSERVATIONS
+            ));
+        }
+    }
+    annotated
+}
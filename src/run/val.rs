@@ -0,0 +1,298 @@
+//! Conversion between the 64 bit `Val`s seen by the contract and `ScVal`s.
+//!
+//! A `Val` has its tag in the low 8 bits. Small values are stored in the remaining 56 bits,
+//! everything else is an object whose handle is stored in the upper 32 bits.
+
+use soroban_sdk::xdr::{Int128Parts, Int256Parts, ScError, ScErrorCode, ScSymbol, ScVal, UInt128Parts, UInt256Parts};
+
+use super::HostError;
+
+pub const FALSE: u8 = 0;
+pub const TRUE: u8 = 1;
+pub const VOID: u8 = 2;
+pub const ERROR: u8 = 3;
+pub const U32: u8 = 4;
+pub const I32: u8 = 5;
+pub const U64_SMALL: u8 = 6;
+pub const I64_SMALL: u8 = 7;
+pub const TIMEPOINT_SMALL: u8 = 8;
+pub const DURATION_SMALL: u8 = 9;
+pub const U128_SMALL: u8 = 10;
+pub const I128_SMALL: u8 = 11;
+pub const U256_SMALL: u8 = 12;
+pub const I256_SMALL: u8 = 13;
+pub const SYMBOL_SMALL: u8 = 14;
+pub const U64_OBJECT: u8 = 64;
+pub const I64_OBJECT: u8 = 65;
+pub const TIMEPOINT_OBJECT: u8 = 66;
+pub const DURATION_OBJECT: u8 = 67;
+pub const U128_OBJECT: u8 = 68;
+pub const I128_OBJECT: u8 = 69;
+pub const U256_OBJECT: u8 = 70;
+pub const I256_OBJECT: u8 = 71;
+pub const BYTES_OBJECT: u8 = 72;
+pub const STRING_OBJECT: u8 = 73;
+pub const SYMBOL_OBJECT: u8 = 74;
+pub const VEC_OBJECT: u8 = 75;
+pub const MAP_OBJECT: u8 = 76;
+pub const ADDRESS_OBJECT: u8 = 77;
+
+pub const VOID_VAL: u64 = VOID as u64;
+
+/// Small values have 56 bits
+const SMALL_BITS: u32 = 56;
+const MAX_SMALL_SYMBOL_LEN: usize = 9;
+
+const fn tag(val: u64) -> u8 {
+    val as u8
+}
+
+/// Whether the low 8 bits of `val` are the tag of a small value or an object
+pub const fn has_tag(val: u64) -> bool {
+    matches!(tag(val), FALSE..=SYMBOL_SMALL | U64_OBJECT..=ADDRESS_OBJECT)
+}
+
+const fn major(val: u64) -> u32 {
+    (val >> 32) as u32
+}
+
+const fn minor(val: u64) -> u32 {
+    ((val >> 8) & 0xff_ffff) as u32
+}
+
+const fn from_major_minor(major: u32, minor: u32, tag: u8) -> u64 {
+    (major as u64) << 32 | (minor as u64) << 8 | tag as u64
+}
+
+const fn small(body: u64, tag: u8) -> u64 {
+    body << 8 | tag as u64
+}
+
+fn fits_small_u64(value: u64) -> bool {
+    value >> SMALL_BITS == 0
+}
+
+fn fits_small_i64(value: i64) -> bool {
+    (value << 8) >> 8 == value
+}
+
+fn symbol_char_code(c: u8) -> Option<u64> {
+    Some(match c {
+        b'_' => 1,
+        b'0'..=b'9' => u64::from(c - b'0') + 2,
+        b'A'..=b'Z' => u64::from(c - b'A') + 12,
+        b'a'..=b'z' => u64::from(c - b'a') + 38,
+        _ => return None,
+    })
+}
+
+fn symbol_code_char(code: u64) -> Option<char> {
+    Some(match code {
+        1 => '_',
+        2..=11 => (b'0' + (code - 2) as u8) as char,
+        12..=37 => (b'A' + (code - 12) as u8) as char,
+        38..=63 => (b'a' + (code - 38) as u8) as char,
+        _ => return None,
+    })
+}
+
+/// Six bits per character, the first character in the highest bits
+fn small_symbol(name: &[u8]) -> Option<u64> {
+    if name.len() > MAX_SMALL_SYMBOL_LEN {
+        return None;
+    }
+    name.iter()
+        .try_fold(0, |body, c| Some(body << 6 | symbol_char_code(*c)?))
+}
+
+fn small_symbol_name(mut body: u64) -> Option<String> {
+    let mut name = Vec::new();
+    while body != 0 {
+        name.push(symbol_code_char(body & 0x3f)?);
+        body >>= 6;
+    }
+    Some(name.into_iter().rev().collect())
+}
+
+fn error_type_and_code(error: &ScError) -> (u32, u32) {
+    match error {
+        ScError::Contract(code) => (0, *code),
+        ScError::WasmVm(code) => (1, *code as u32),
+        ScError::Context(code) => (2, *code as u32),
+        ScError::Storage(code) => (3, *code as u32),
+        ScError::Object(code) => (4, *code as u32),
+        ScError::Crypto(code) => (5, *code as u32),
+        ScError::Events(code) => (6, *code as u32),
+        ScError::Budget(code) => (7, *code as u32),
+        ScError::Value(code) => (8, *code as u32),
+        ScError::Auth(code) => (9, *code as u32),
+    }
+}
+
+fn error_from_type_and_code(ty: u32, code: u32) -> Option<ScError> {
+    if ty == 0 {
+        return Some(ScError::Contract(code));
+    }
+    let code = ScErrorCode::try_from(code as i32).ok()?;
+    Some(match ty {
+        1 => ScError::WasmVm(code),
+        2 => ScError::Context(code),
+        3 => ScError::Storage(code),
+        4 => ScError::Object(code),
+        5 => ScError::Crypto(code),
+        6 => ScError::Events(code),
+        7 => ScError::Budget(code),
+        8 => ScError::Value(code),
+        9 => ScError::Auth(code),
+        _ => return None,
+    })
+}
+
+/// The tag of objects holding values of this kind
+fn object_tag(value: &ScVal) -> Option<u8> {
+    Some(match value {
+        ScVal::U64(_) => U64_OBJECT,
+        ScVal::I64(_) => I64_OBJECT,
+        ScVal::Timepoint(_) => TIMEPOINT_OBJECT,
+        ScVal::Duration(_) => DURATION_OBJECT,
+        ScVal::U128(_) => U128_OBJECT,
+        ScVal::I128(_) => I128_OBJECT,
+        ScVal::U256(_) => U256_OBJECT,
+        ScVal::I256(_) => I256_OBJECT,
+        ScVal::Bytes(_) => BYTES_OBJECT,
+        ScVal::String(_) => STRING_OBJECT,
+        ScVal::Symbol(_) => SYMBOL_OBJECT,
+        ScVal::Vec(_) => VEC_OBJECT,
+        ScVal::Map(_) => MAP_OBJECT,
+        ScVal::Address(_) => ADDRESS_OBJECT,
+        _ => return None,
+    })
+}
+
+/// The host objects the contract holds handles to
+#[derive(Debug, Default)]
+pub struct Objects {
+    objects: Vec<ScVal>,
+}
+
+impl Objects {
+    fn add(&mut self, value: ScVal, tag: u8) -> u64 {
+        self.objects.push(value);
+        from_major_minor(self.objects.len() as u32 - 1, 0, tag)
+    }
+
+    /// The `Val` of a value, new objects are added for values that do not fit into 56 bits
+    pub fn to_val(&mut self, value: &ScVal) -> Result<u64, HostError> {
+        let val = match value {
+            ScVal::Bool(false) => u64::from(FALSE),
+            ScVal::Bool(true) => u64::from(TRUE),
+            ScVal::Void => VOID_VAL,
+            ScVal::Error(error) => {
+                let (ty, code) = error_type_and_code(error);
+                from_major_minor(code, ty, ERROR)
+            }
+            ScVal::U32(v) => from_major_minor(*v, 0, U32),
+            ScVal::I32(v) => from_major_minor(*v as u32, 0, I32),
+            ScVal::U64(v) if fits_small_u64(*v) => small(*v, U64_SMALL),
+            ScVal::I64(v) if fits_small_i64(*v) => small(*v as u64, I64_SMALL),
+            ScVal::Timepoint(v) if fits_small_u64(v.0) => small(v.0, TIMEPOINT_SMALL),
+            ScVal::Duration(v) if fits_small_u64(v.0) => small(v.0, DURATION_SMALL),
+            ScVal::U128(UInt128Parts { hi: 0, lo }) if fits_small_u64(*lo) => small(*lo, U128_SMALL),
+            ScVal::I128(Int128Parts { hi, lo }) if *hi == (*lo as i64) >> 63 && fits_small_i64(*lo as i64) => {
+                small(*lo, I128_SMALL)
+            }
+            ScVal::U256(UInt256Parts {
+                hi_hi: 0,
+                hi_lo: 0,
+                lo_hi: 0,
+                lo_lo,
+            }) if fits_small_u64(*lo_lo) => small(*lo_lo, U256_SMALL),
+            ScVal::Symbol(symbol) => match small_symbol(symbol.0.as_slice()) {
+                Some(body) => small(body, SYMBOL_SMALL),
+                None => self.add(value.clone(), SYMBOL_OBJECT),
+            },
+            _ => match object_tag(value) {
+                Some(tag) => self.add(value.clone(), tag),
+                None => return Err(HostError::new(format!("{:?} can not be passed to a contract", value))),
+            },
+        };
+        Ok(val)
+    }
+
+    pub fn from_val(&self, val: u64) -> Result<ScVal, HostError> {
+        let body = val >> 8;
+        let signed_body = (val as i64) >> 8;
+        let value = match tag(val) {
+            FALSE => ScVal::Bool(false),
+            TRUE => ScVal::Bool(true),
+            VOID => ScVal::Void,
+            ERROR => ScVal::Error(
+                error_from_type_and_code(minor(val), major(val))
+                    .ok_or_else(|| HostError::new(format!("Invalid error value 0x{:x}", val)))?,
+            ),
+            U32 => ScVal::U32(major(val)),
+            I32 => ScVal::I32(major(val) as i32),
+            U64_SMALL => ScVal::U64(body),
+            I64_SMALL => ScVal::I64(signed_body),
+            TIMEPOINT_SMALL => ScVal::Timepoint(body.into()),
+            DURATION_SMALL => ScVal::Duration(body.into()),
+            U128_SMALL => ScVal::U128(UInt128Parts { hi: 0, lo: body }),
+            I128_SMALL => ScVal::I128(Int128Parts {
+                hi: signed_body >> 63,
+                lo: signed_body as u64,
+            }),
+            U256_SMALL => ScVal::U256(UInt256Parts {
+                hi_hi: 0,
+                hi_lo: 0,
+                lo_hi: 0,
+                lo_lo: body,
+            }),
+            I256_SMALL => {
+                let sign = (signed_body >> 63) as u64;
+                ScVal::I256(Int256Parts {
+                    hi_hi: signed_body >> 63,
+                    hi_lo: sign,
+                    lo_hi: sign,
+                    lo_lo: signed_body as u64,
+                })
+            }
+            SYMBOL_SMALL => {
+                let name = small_symbol_name(body)
+                    .ok_or_else(|| HostError::new(format!("Invalid symbol value 0x{:x}", val)))?;
+                ScVal::Symbol(ScSymbol(name.as_str().try_into().unwrap()))
+            }
+            _ => return self.get(val).cloned(),
+        };
+        Ok(value)
+    }
+
+    /// The value of an object
+    pub fn get(&self, val: u64) -> Result<&ScVal, HostError> {
+        let value = self
+            .objects
+            .get(major(val) as usize)
+            .filter(|value| object_tag(value) == Some(tag(val)));
+        value.ok_or_else(|| HostError::new(format!("Invalid object handle 0x{:x}", val)))
+    }
+}
+
+/// The `Val` of a `u32` that is passed as a plain number
+pub const fn u32_val(value: u32) -> u64 {
+    from_major_minor(value, 0, U32)
+}
+
+/// The number in a `U32Val`
+pub fn u32_from_val(val: u64) -> Result<u32, HostError> {
+    match tag(val) {
+        U32 => Ok(major(val)),
+        _ => Err(HostError::new(format!("Expected a U32Val, got 0x{:x}", val))),
+    }
+}
+
+pub const fn bool_val(value: bool) -> u64 {
+    if value {
+        TRUE as u64
+    } else {
+        FALSE as u64
+    }
+}
@@ -0,0 +1,73 @@
+use regex::Regex;
+use std::fmt;
+
+use crate::wasm_wrapper::wasm_adapter::Module;
+
+/// Selects functions of a module on the command line.
+/// A selector is matched against the function names first, then parsed as an index
+/// and finally used as a regex that has to match the whole name.
+#[derive(Debug)]
+pub struct FuncSelector {
+    text: String,
+}
+
+#[derive(Debug)]
+pub enum SelectError {
+    NoMatch(String),
+    InvalidRegex(String, regex::Error),
+}
+
+impl fmt::Display for SelectError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NoMatch(selector) => write!(f, "No function matches `{}`", selector),
+            Self::InvalidRegex(selector, error) => write!(f, "Invalid function selector `{}`: {}", selector, error),
+        }
+    }
+}
+
+impl FuncSelector {
+    pub fn new(text: &str) -> Self {
+        FuncSelector { text: text.to_string() }
+    }
+
+    /// Return the indices of all functions matched by the selector
+    pub fn matches(&self, module: &Module) -> Result<Vec<u32>, SelectError> {
+        let functions = module.functions();
+
+        let by_name: Vec<u32> = (0..functions.len() as u32)
+            .filter(|&i| functions[i as usize].name() == self.text)
+            .collect();
+        if !by_name.is_empty() {
+            return Ok(by_name);
+        }
+
+        if let Ok(index) = self.text.parse::<u32>() {
+            return match module.get_func(index) {
+                Some(_) => Ok(vec![index]),
+                None => Err(SelectError::NoMatch(self.text.clone())),
+            };
+        }
+
+        let regex = Regex::new(&format!("^(?:{})$", self.text))
+            .map_err(|e| SelectError::InvalidRegex(self.text.clone(), e))?;
+        let by_regex: Vec<u32> = (0..functions.len() as u32)
+            .filter(|&i| regex.is_match(functions[i as usize].name()))
+            .collect();
+        if by_regex.is_empty() {
+            return Err(SelectError::NoMatch(self.text.clone()));
+        }
+        Ok(by_regex)
+    }
+}
+
+/// Resolve all selectors, keeping the order of the module and dropping duplicates
+pub fn select_functions(module: &Module, selectors: &[FuncSelector]) -> Result<Vec<u32>, SelectError> {
+    let mut selected = Vec::new();
+    for selector in selectors {
+        selected.extend(selector.matches(module)?);
+    }
+    selected.sort_unstable();
+    selected.dedup();
+    Ok(selected)
+}
@@ -0,0 +1,274 @@
+//! The soroban-sdk and rustc versions a contract was built with.
+//!
+//! The SDK writes both into the `contractmetav0` custom section. Optimizers and strip tools often
+//! drop custom sections, in that case the SDK version is inferred from the internal helpers the
+//! SDK compiles into every contract, whose code changes between releases. The fingerprints of the
+//! helpers of each release are kept in `sdk_versions.toml`.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read};
+use std::path::{Path, PathBuf};
+
+use parity_wasm::elements::{self as pwasm, External, ImportCountType, Internal};
+use serde::{Deserialize, Serialize};
+use soroban_sdk::xdr::{self, Limited, Limits, ReadXdr, ScMetaEntry};
+
+use super::normalize::{fnv1a, Segments, FNV_OFFSET};
+
+pub const META_SECTION: &str = "contractmetav0";
+pub const SDK_VERSION_KEY: &str = "rssdkver";
+pub const RUSTC_VERSION_KEY: &str = "rsver";
+/// The bundled table of helper fingerprints per SDK version
+pub const SDK_TABLE_FILE: &str = "sdk_versions.toml";
+/// Fewest helpers that have to match before a version is inferred
+pub const MIN_MATCHES: usize = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MetaEntry {
+    pub key: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VersionSource {
+    /// Read from the `contractmetav0` section
+    Metadata,
+    /// Inferred from the fingerprints of the SDK helpers
+    Fingerprints,
+    #[default]
+    Unknown,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct BuildInfo {
+    /// soroban-sdk version without the commit, e.g. `20.5.0`
+    pub sdk_version: Option<String>,
+    /// Commit of the soroban-sdk repository, only recorded in the metadata
+    pub sdk_commit: Option<String>,
+    pub rustc_version: Option<String>,
+    pub source: VersionSource,
+    /// Number of helpers that matched the inferred version
+    #[serde(skip_serializing_if = "is_zero")]
+    pub matched_helpers: usize,
+    /// All entries of the `contractmetav0` section
+    pub meta: Vec<MetaEntry>,
+}
+
+fn is_zero(count: &usize) -> bool {
+    *count == 0
+}
+
+/// Helper fingerprints of one SDK release
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SdkFingerprints {
+    pub sdk: String,
+    pub helpers: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SdkTable {
+    pub versions: Vec<SdkFingerprints>,
+}
+
+impl SdkTable {
+    pub fn load(path: impl AsRef<Path>) -> Result<SdkTable, Box<dyn Error>> {
+        let mut content = String::new();
+        BufReader::new(File::open(path)?).read_to_string(&mut content)?;
+        Ok(toml::from_str(&content)?)
+    }
+
+    /// Build the table from the helpers of contracts with a known SDK version. Helpers found in
+    /// contracts of several versions do not tell the versions apart and are left out.
+    pub fn from_contracts<'a>(contracts: impl IntoIterator<Item = (&'a str, Vec<String>)>) -> SdkTable {
+        let mut found_in: BTreeMap<String, BTreeSet<&'a str>> = BTreeMap::new();
+        for (version, helpers) in contracts {
+            for helper in helpers {
+                found_in.entry(helper).or_default().insert(version);
+            }
+        }
+        let mut versions: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+        for (helper, found_in) in found_in {
+            if let [version] = Vec::from_iter(found_in).as_slice() {
+                versions.entry(*version).or_default().push(helper);
+            }
+        }
+        SdkTable {
+            versions: versions
+                .into_iter()
+                .map(|(sdk, helpers)| SdkFingerprints {
+                    sdk: sdk.to_string(),
+                    helpers,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl BuildInfo {
+    /// Read the versions from the metadata of `module`, or infer the SDK version from its
+    /// helpers with the bundled table
+    pub fn from_module(module: &pwasm::Module) -> BuildInfo {
+        let info = BuildInfo::from_meta(module);
+        if info.sdk_version.is_some() {
+            return info;
+        }
+        match SdkTable::load(SDK_TABLE_FILE) {
+            Ok(table) => info.infer(module, &table),
+            Err(_) => info,
+        }
+    }
+
+    /// Parse a wasm binary and read its build info
+    pub fn from_wasm(code: &[u8]) -> Result<BuildInfo, pwasm::Error> {
+        let module: pwasm::Module = pwasm::deserialize_buffer(code)?;
+        Ok(BuildInfo::from_module(&module))
+    }
+
+    /// The versions recorded in the `contractmetav0` sections of `module`
+    pub fn from_meta(module: &pwasm::Module) -> BuildInfo {
+        let meta: Vec<MetaEntry> = module
+            .custom_sections()
+            .filter(|section| section.name() == META_SECTION)
+            .filter_map(|section| parse_contract_meta(section.payload()).ok())
+            .flatten()
+            .collect();
+        let value = |key: &str| {
+            meta.iter()
+                .find(|entry| entry.key == key)
+                .map(|entry| entry.value.clone())
+        };
+        let (sdk_version, sdk_commit) = match value(SDK_VERSION_KEY) {
+            Some(version) => match version.split_once('#') {
+                Some((version, commit)) => (Some(version.to_string()), Some(commit.to_string())),
+                None => (Some(version), None),
+            },
+            None => (None, None),
+        };
+        BuildInfo {
+            source: if sdk_version.is_some() {
+                VersionSource::Metadata
+            } else {
+                VersionSource::Unknown
+            },
+            sdk_version,
+            sdk_commit,
+            rustc_version: value(RUSTC_VERSION_KEY),
+            matched_helpers: 0,
+            meta,
+        }
+    }
+
+    /// Fill in the SDK version with the release whose helpers match `module` best
+    pub fn infer(mut self, module: &pwasm::Module, table: &SdkTable) -> BuildInfo {
+        if let Some((version, matched)) = infer_sdk_version(&helper_fingerprints(module), table) {
+            self.sdk_version = Some(version);
+            self.source = VersionSource::Fingerprints;
+            self.matched_helpers = matched;
+        }
+        self
+    }
+}
+
+/// Decode the entries of a `contractmetav0` section
+pub fn parse_contract_meta(data: &[u8]) -> Result<Vec<MetaEntry>, xdr::Error> {
+    let mut reader = Limited::new(
+        Cursor::new(data),
+        Limits {
+            depth: 500,
+            len: 0x1000000,
+        },
+    );
+    ScMetaEntry::read_xdr_iter(&mut reader)
+        .map(|entry| {
+            let ScMetaEntry::ScMetaV0(entry) = entry?;
+            Ok(MetaEntry {
+                key: entry.key.to_utf8_string_lossy(),
+                value: entry.val.to_utf8_string_lossy(),
+            })
+        })
+        .collect()
+}
+
+/// Hashes of the normalized code of every local function that is not exported
+pub fn helper_fingerprints(module: &pwasm::Module) -> Vec<String> {
+    let imports: Vec<String> = module
+        .import_section()
+        .map(|section| {
+            section
+                .entries()
+                .iter()
+                .filter(|entry| matches!(entry.external(), External::Function(_)))
+                .map(|entry| format!("{}.{}", entry.module(), entry.field()))
+                .collect()
+        })
+        .unwrap_or_default();
+    let imported = module.import_count(ImportCountType::Function);
+    let exported: Vec<usize> = module
+        .export_section()
+        .map(|section| {
+            section
+                .entries()
+                .iter()
+                .filter_map(|export| match export.internal() {
+                    Internal::Function(index) => Some(*index as usize),
+                    _ => None,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let callee = |index: u32| {
+        imports
+            .get(index as usize)
+            .cloned()
+            .unwrap_or_else(|| String::from("local"))
+    };
+    let segments = Segments::from_parity(module);
+    let bodies = module.code_section().map_or(&[][..], |section| section.bodies());
+    let mut fingerprints: Vec<String> = bodies
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !exported.contains(&(imported + i)))
+        .map(|(_, body)| {
+            let code = segments.normalize(body.code().elements(), callee);
+            format!("{:016x}", fnv1a(code.join("\n").as_bytes(), FNV_OFFSET))
+        })
+        .collect();
+    fingerprints.sort();
+    fingerprints.dedup();
+    fingerprints
+}
+
+/// The release with the most matching helpers and the number of matches, `None` if fewer than
+/// `MIN_MATCHES` match or two releases match equally well
+pub fn infer_sdk_version(helpers: &[String], table: &SdkTable) -> Option<(String, usize)> {
+    let mut counts: Vec<(&str, usize)> = table
+        .versions
+        .iter()
+        .map(|version| {
+            let matched = version.helpers.iter().filter(|helper| helpers.contains(helper)).count();
+            (version.sdk.as_str(), matched)
+        })
+        .filter(|(_, matched)| *matched >= MIN_MATCHES)
+        .collect();
+    counts.sort_by_key(|(_, matched)| std::cmp::Reverse(*matched));
+    match counts.as_slice() {
+        [(version, matched)] => Some((version.to_string(), *matched)),
+        [(version, matched), (_, next), ..] if matched > next => Some((version.to_string(), *matched)),
+        _ => None,
+    }
+}
+
+/// The most specific variant of `name` for the SDK version that exists, e.g. `env-20.5.json`
+/// for `env.json` and `20.5.0`, falling back to `name` itself
+pub fn versioned_file(name: &str, sdk_version: Option<&str>) -> PathBuf {
+    let (stem, extension) = name.rsplit_once('.').unwrap_or((name, ""));
+    let parts: Vec<&str> = sdk_version.map_or(Vec::new(), |version| version.split('.').collect());
+    (1..=parts.len())
+        .rev()
+        .map(|len| PathBuf::from(format!("{}-{}.{}", stem, parts[..len].join("."), extension)))
+        .find(|path| path.exists())
+        .unwrap_or_else(|| PathBuf::from(name))
+}
@@ -1,6 +1,7 @@
 use std::{
     fs::File,
-    io::BufReader
+    io::BufReader,
+    path::Path
 };
 
 use serde_json::Value;
@@ -12,8 +13,10 @@ pub struct ModuleFunction {
     pub function: Value,
 }
 
-pub fn read_env_common_from_file() -> Result<Value, Box<dyn std::error::Error>> {
-    let file = File::open("env.json")?;
+use super::build_info::versioned_file;
+
+pub fn read_env_common_from_file<P: AsRef<Path>>(path: P) -> Result<Value, Box<dyn std::error::Error>> {
+    let file = File::open(path)?;
     let reader = BufReader::new(file);
     let u = serde_json::from_reader(reader)?;
 
@@ -21,7 +24,13 @@ pub fn read_env_common_from_file() -> Result<Value, Box<dyn std::error::Error>>
 }
 
 pub fn env_common_modules_result() -> Result<Vec<Value>, Box<dyn std::error::Error>> {
-    let common_env_imports = read_env_common_from_file()?;
+    env_common_modules_for(None)
+}
+
+/// The host modules of the env.json table of the SDK version, e.g. `env-20.json` for 20.5.0 if
+/// there is one
+pub fn env_common_modules_for(sdk_version: Option<&str>) -> Result<Vec<Value>, Box<dyn std::error::Error>> {
+    let common_env_imports = read_env_common_from_file(versioned_file("env.json", sdk_version))?;
     if let Some(modules) = common_env_imports.get("modules").and_then(Value::as_array) {
         let cloned_modules: Vec<Value> = modules.clone();
         return Ok(cloned_modules);
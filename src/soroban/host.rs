@@ -0,0 +1,117 @@
+use serde::{Deserialize, Serialize};
+use soroban_sdk::Val;
+
+use crate::ssa::Expr;
+use crate::wasm_wrapper::wasm_adapter::Module;
+
+pub const PUT_CONTRACT_DATA: &str = "put_contract_data";
+pub const DEL_CONTRACT_DATA: &str = "del_contract_data";
+pub const GET_CONTRACT_DATA: &str = "get_contract_data";
+pub const HAS_CONTRACT_DATA: &str = "has_contract_data";
+pub const REQUIRE_AUTH: &str = "require_auth";
+pub const REQUIRE_AUTH_FOR_ARGS: &str = "require_auth_for_args";
+pub const EXTEND_CONTRACT_DATA_TTL: &str = "extend_contract_data_ttl";
+pub const EXTEND_INSTANCE_TTL: &str = "extend_current_contract_instance_and_code_ttl";
+pub const UPDATE_CONTRACT_WASM: &str = "update_current_contract_wasm";
+pub const CALL: &str = "call";
+pub const TRY_CALL: &str = "try_call";
+pub const OBJ_CMP: &str = "obj_cmp";
+pub const CONTRACT_EVENT: &str = "contract_event";
+
+/// The spec type of `Address` parameters
+pub const ADDRESS_TYPE: &str = "soroban_sdk::Address";
+
+/// The env.json name of the host function `func_index` refers to, e.g. `put_contract_data`.
+/// Returns `None` for functions defined in the module.
+pub fn host_fn(module: &Module, func_index: u32) -> Option<&str> {
+    let func = module.get_func(func_index)?;
+    if !func.is_imported() {
+        return None;
+    }
+    let name = func.name();
+    Some(name.rsplit('.').next().unwrap_or(name))
+}
+
+pub fn is_storage_write(name: &str) -> bool {
+    name == PUT_CONTRACT_DATA || name == DEL_CONTRACT_DATA
+}
+
+pub fn is_require_auth(name: &str) -> bool {
+    name == REQUIRE_AUTH || name == REQUIRE_AUTH_FOR_ARGS
+}
+
+pub fn is_contract_call(name: &str) -> bool {
+    name == CALL || name == TRY_CALL
+}
+
+/// Host functions whose effect outlives the call: storage, auth, events, TTLs, upgrades and calls
+/// of other contracts
+pub fn has_side_effects(name: &str) -> bool {
+    is_storage_write(name)
+        || is_require_auth(name)
+        || is_contract_call(name)
+        || [
+            CONTRACT_EVENT,
+            EXTEND_CONTRACT_DATA_TTL,
+            EXTEND_INSTANCE_TTL,
+            UPDATE_CONTRACT_WASM,
+        ]
+        .contains(&name)
+}
+
+/// The `StorageType` argument of the storage host functions, passed as a plain integer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageType {
+    Temporary,
+    Persistent,
+    Instance,
+}
+
+impl StorageType {
+    pub fn from_expr(expr: &Expr) -> Option<Self> {
+        match expr {
+            Expr::I64Const(0) => Some(StorageType::Temporary),
+            Expr::I64Const(1) => Some(StorageType::Persistent),
+            Expr::I64Const(2) => Some(StorageType::Instance),
+            _ => None,
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            StorageType::Temporary => "temporary",
+            StorageType::Persistent => "persistent",
+            StorageType::Instance => "instance",
+        }
+    }
+}
+
+/// The payload of a small `Val` constant with the given tag, e.g. `transfer` for `Symbol(transfer)`
+fn val_const(expr: &Expr, tag: &str) -> Option<String> {
+    let value = match expr {
+        Expr::I64Const(value) => *value,
+        _ => return None,
+    };
+    let val = Val::from_payload(value);
+    if !val.is_good() {
+        return None;
+    }
+    let debug = format!("{:?}", val);
+    debug
+        .strip_prefix(tag)
+        .and_then(|s| s.strip_prefix('('))
+        .and_then(|s| s.strip_suffix(')'))
+        .filter(|s| !s.starts_with("obj#"))
+        .map(str::to_string)
+}
+
+/// The name of a small symbol constant like `Symbol(transfer)`
+pub fn symbol(expr: &Expr) -> Option<String> {
+    val_const(expr, "Symbol")
+}
+
+/// The value of a `U32Val` constant
+pub fn u32_val(expr: &Expr) -> Option<u32> {
+    val_const(expr, "U32")?.parse().ok()
+}
@@ -1,9 +1,16 @@
 mod specs_generate;
 mod common_env;
+pub mod build_info;
+pub mod host;
+pub mod normalize;
+pub mod pattern;
 pub mod sdk_linker;
 
 pub use specs_generate::read_contract_specs;
+pub use specs_generate::read_contract_types;
+pub use specs_generate::SpecType;
 pub use specs_generate::find_function_specs;
 pub use specs_generate::FunctionInfo;
-pub use common_env::{env_common_modules_result, take_common_module};
-pub use sdk_linker::search_for_patterns;
+pub use build_info::{BuildInfo, VersionSource};
+pub use common_env::{env_common_modules_for, env_common_modules_result, take_common_module, ModuleFunction};
+pub use sdk_linker::{link_patterns, load_patterns, parse_patterns, PatternMatches};
@@ -0,0 +1,146 @@
+//! Normalization of contract code for hashes and comparisons that stay stable when a contract is
+//! rebuilt: addresses, local function indices and large constants move with unrelated changes.
+
+use std::hash::Hasher;
+use std::ops::Range;
+use std::sync::OnceLock;
+
+use parity_wasm::elements::{self as pwasm, Instruction};
+use regex::Regex;
+
+use crate::run::val;
+use crate::wasm_wrapper::wasm_adapter::{InitExpr, Module};
+
+/// 64 bit FNV-1a, stable across platforms and compiler versions unlike `DefaultHasher`
+pub(crate) fn fnv1a(bytes: &[u8], mut hash: u64) -> u64 {
+    for byte in bytes {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+pub(crate) const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// FNV-1a as a `Hasher`
+pub(crate) struct Fnv(pub(crate) u64);
+
+impl Hasher for Fnv {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.0 = fnv1a(bytes, self.0);
+    }
+}
+
+static FUNC_NAME: OnceLock<Regex> = OnceLock::new();
+static LARGE_CONST: OnceLock<Regex> = OnceLock::new();
+
+/// Decompiled code without the layout, local function indices and large constants
+pub(crate) fn normalize_code(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let func_name = FUNC_NAME.get_or_init(|| Regex::new(r"\bfunc_\d+\b").unwrap());
+    let large_const = LARGE_CONST.get_or_init(|| Regex::new(r"\b\d{6,}\b").unwrap());
+    let text = func_name.replace_all(&text, "func_");
+    large_const.replace_all(&text, "N").into_owned()
+}
+
+/// Where the data and element segments are placed. Constants that point into them are addresses,
+/// which move whenever unrelated code or data changes.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Segments {
+    data: Vec<Range<u64>>,
+    elements: Vec<Range<u64>>,
+}
+
+/// Start of a segment placed at a constant offset
+fn segment_start(offset: &[Instruction]) -> Option<u64> {
+    match offset {
+        [Instruction::I32Const(offset), Instruction::End] => Some(u64::from(*offset as u32)),
+        _ => None,
+    }
+}
+
+impl Segments {
+    pub(crate) fn new(module: &Module) -> Self {
+        let start = |offset: &InitExpr| match offset {
+            InitExpr::I32Const(offset) => Some(u64::from(*offset as u32)),
+            _ => None,
+        };
+        Segments {
+            data: module
+                .memory_inits()
+                .iter()
+                .filter_map(|init| start(init.offset()).map(|start| start..start + init.data().len() as u64))
+                .collect(),
+            elements: module
+                .table_inits()
+                .iter()
+                .filter_map(|init| start(init.offset()).map(|start| start..start + init.entries().len() as u64))
+                .collect(),
+        }
+    }
+
+    pub(crate) fn from_parity(module: &pwasm::Module) -> Self {
+        let data = module.data_section().map_or(&[][..], |section| section.entries());
+        let elements = module.elements_section().map_or(&[][..], |section| section.entries());
+        Segments {
+            data: data
+                .iter()
+                .filter_map(|segment| {
+                    let start = segment_start(segment.offset().as_ref()?.code())?;
+                    Some(start..start + segment.value().len() as u64)
+                })
+                .collect(),
+            elements: elements
+                .iter()
+                .filter_map(|segment| {
+                    let start = segment_start(segment.offset().as_ref()?.code())?;
+                    Some(start..start + segment.members().len() as u64)
+                })
+                .collect(),
+        }
+    }
+
+    fn is_data(&self, value: u64) -> bool {
+        self.data.iter().any(|range| range.contains(&value))
+    }
+
+    /// Whether the `i32` constant `value` points into a data segment
+    pub(crate) fn is_address32(&self, value: u32) -> bool {
+        self.is_data(u64::from(value))
+    }
+
+    /// Whether the `i64` constant `value` points into a data segment and is no tagged `Val`
+    pub(crate) fn is_address64(&self, value: u64) -> bool {
+        self.is_data(value) && !val::has_tag(value)
+    }
+
+    /// Whether `value` is a table slot filled by an element segment
+    pub(crate) fn is_element(&self, value: u32) -> bool {
+        self.elements.iter().any(|range| range.contains(&u64::from(value)))
+    }
+
+    /// The instructions with callees replaced by `callee` and addresses left out. An `i32` constant
+    /// is an address if it points into a data segment or is the table slot of a `call_indirect`,
+    /// an `i64` constant if it points into a data segment and is no tagged `Val`.
+    pub(crate) fn normalize(&self, code: &[Instruction], callee: impl Fn(u32) -> String) -> Vec<String> {
+        code.iter()
+            .enumerate()
+            .map(|(i, instr)| match instr {
+                Instruction::Call(index) => format!("Call({})", callee(*index)),
+                Instruction::I32Const(value) if self.is_address32(*value as u32) => String::from("I32Const(N)"),
+                Instruction::I32Const(value)
+                    if matches!(code.get(i + 1), Some(Instruction::CallIndirect(..)))
+                        && self.is_element(*value as u32) =>
+                {
+                    String::from("I32Const(N)")
+                }
+                Instruction::I64Const(value) if self.is_address64(*value as u64) => String::from("I64Const(N)"),
+                instr => format!("{:?}", instr),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,594 @@
+//! Matching of patterns against structured code.
+
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::mem::discriminant;
+use std::ops::Range;
+
+use soroban_sdk::Val;
+
+use super::{Capture, Captures, ExprPattern, Pattern, Replacement, StmtPattern};
+use crate::soroban::host;
+use crate::ssa::cond::{CmpOp, MappedExpr};
+use crate::ssa::expr::val_text;
+use crate::ssa::{Cond, Expr, LoopKind, Stmt, Var};
+use crate::wasm_wrapper::wasm_adapter::{Function, Instruction, Module};
+
+/// Matches patterns against the code of one function, names are resolved like they are printed
+pub struct Matcher<'a> {
+    module: &'a Module,
+    func: &'a Function,
+}
+
+impl<'a> Matcher<'a> {
+    pub fn new(module: &'a Module, func: &'a Function) -> Self {
+        Matcher { module, func }
+    }
+
+    /// The first run of statements of `code` that the pattern matches and its captures, nested
+    /// blocks are not searched
+    pub fn find(&self, pattern: &Pattern, code: &[Stmt]) -> Option<(Range<usize>, Captures)> {
+        let (indices, stmts): (Vec<usize>, Vec<&Stmt>) =
+            code.iter().enumerate().filter(|(_, stmt)| !is_marker(stmt)).unzip();
+        (0..stmts.len()).find_map(|start| {
+            let (len, captures) = self.seq(&pattern.body, &stmts[start..], Captures::new(), false)?;
+            if len == 0 {
+                return None;
+            }
+            Some((indices[start]..indices[start + len - 1] + 1, captures))
+        })
+    }
+
+    /// Replace every match of the pattern in `code` and its nested blocks, the names of the
+    /// matched patterns are added to `matched`
+    pub fn replace(&self, pattern: &Pattern, mut code: Vec<Stmt>, matched: &mut Vec<String>) -> Vec<Stmt> {
+        let mut result = Vec::new();
+        while let Some((range, captures)) = self.find(pattern, &code) {
+            let rest = code.split_off(range.end);
+            code.truncate(range.start);
+            result.extend(code.into_iter().map(|stmt| self.replace_nested(pattern, stmt, matched)));
+            result.push(Stmt::Replaced(Box::new(Replacement {
+                pattern: pattern.name.clone(),
+                template: pattern.template.clone(),
+                captures,
+            })));
+            matched.push(pattern.name.clone());
+            code = rest;
+        }
+        result.extend(code.into_iter().map(|stmt| self.replace_nested(pattern, stmt, matched)));
+        result
+    }
+
+    fn replace_nested(&self, pattern: &Pattern, stmt: Stmt, matched: &mut Vec<String>) -> Stmt {
+        match stmt {
+            Stmt::If(cond, body) => Stmt::If(cond, self.replace(pattern, body, matched)),
+            Stmt::IfElse(cond, then, otherwise) => Stmt::IfElse(
+                cond,
+                self.replace(pattern, then, matched),
+                self.replace(pattern, otherwise, matched),
+            ),
+            Stmt::While(cond, body, kind) => Stmt::While(cond, self.replace(pattern, body, matched), kind),
+            Stmt::ForLoop(var, init, cond, post, body) => {
+                Stmt::ForLoop(var, init, cond, post, self.replace(pattern, body, matched))
+            }
+            Stmt::Seq(code) => Stmt::Seq(self.replace(pattern, code, matched)),
+            Stmt::SwitchCase(expr, cases, default) => Stmt::SwitchCase(
+                expr,
+                cases
+                    .into_iter()
+                    .map(|(values, stmt)| (values, self.replace_nested(pattern, stmt, matched)))
+                    .collect(),
+                default.map(|stmt| Box::new(self.replace_nested(pattern, *stmt, matched))),
+            ),
+            stmt => stmt,
+        }
+    }
+
+    /// Match the patterns against the start of `stmts`, or all of them if `to_end` is set.
+    /// Returns the number of matched statements.
+    fn seq(
+        &self,
+        patterns: &[StmtPattern],
+        stmts: &[&Stmt],
+        captures: Captures,
+        to_end: bool,
+    ) -> Option<(usize, Captures)> {
+        let (pattern, rest) = match patterns.split_first() {
+            Some(split) => split,
+            None if to_end && !stmts.is_empty() => return None,
+            None => return Some((0, captures)),
+        };
+        if let StmtPattern::Rest(name) = pattern {
+            let capture = |captures: Captures, stmts: &[&Stmt]| match name {
+                Some(name) => bind(
+                    captures,
+                    name,
+                    Capture::Stmts(stmts.iter().map(|stmt| (*stmt).clone()).collect()),
+                ),
+                None => Some(captures),
+            };
+            // Statements skipped by `..` are dropped from the output, which is only fine if they
+            // have no side effects
+            let skippable = match name {
+                Some(_) => stmts.len(),
+                None => stmts
+                    .iter()
+                    .position(|stmt| self.has_side_effects(stmt))
+                    .unwrap_or(stmts.len()),
+            };
+            // A trailing `..` extends to the end of the block, otherwise it is as short as possible
+            if rest.is_empty() {
+                if skippable < stmts.len() {
+                    return None;
+                }
+                return Some((stmts.len(), capture(captures, stmts)?));
+            }
+            return (0..=skippable).find_map(|skipped| {
+                let captures = capture(captures.clone(), &stmts[..skipped])?;
+                let (len, captures) = self.seq(rest, &stmts[skipped..], captures, to_end)?;
+                Some((skipped + len, captures))
+            });
+        }
+        let (first, others) = stmts.split_first()?;
+        let captures = self.stmt(pattern, first, captures)?;
+        let (len, captures) = self.seq(rest, others, captures, to_end)?;
+        Some((len + 1, captures))
+    }
+
+    /// Match a whole block
+    fn block(&self, patterns: &[StmtPattern], code: &[Stmt], captures: Captures) -> Option<Captures> {
+        let stmts: Vec<&Stmt> = code.iter().filter(|stmt| !is_marker(stmt)).collect();
+        self.seq(patterns, &stmts, captures, true).map(|(_, captures)| captures)
+    }
+
+    fn stmt(&self, pattern: &StmtPattern, stmt: &Stmt, captures: Captures) -> Option<Captures> {
+        match (pattern, stmt) {
+            (StmtPattern::Any, _) => Some(captures),
+            (StmtPattern::Expr(pattern), Stmt::Expr(expr) | Stmt::Return(expr)) => self.expr(pattern, expr, captures),
+            (StmtPattern::Return(None), Stmt::ReturnVoid) => Some(captures),
+            (StmtPattern::Return(Some(pattern)), Stmt::Return(expr)) => self.expr(pattern, expr, captures),
+            (StmtPattern::Assign(name, pattern), Stmt::SetLocal(var, expr)) => {
+                let captures = self.expr(pattern, expr, captures)?;
+                match name {
+                    Some(name) => bind(captures, name, Capture::Expr(Expr::GetLocal(*var))),
+                    None => Some(captures),
+                }
+            }
+            (StmtPattern::Store(name, target_pattern, value_pattern), stmt) => {
+                let (store, target, value) = store_parts(stmt)?;
+                if store != *name {
+                    return None;
+                }
+                let captures = self.expr(target_pattern, target, captures)?;
+                self.expr(value_pattern, value, captures)
+            }
+            (StmtPattern::If(cond_pattern, then_pattern, otherwise_pattern), Stmt::If(cond, then)) => {
+                let captures = self.cond(cond_pattern, cond, captures)?;
+                let captures = self.block(then_pattern, then, captures)?;
+                match otherwise_pattern {
+                    Some(otherwise_pattern) => self.block(otherwise_pattern, &[], captures),
+                    None => Some(captures),
+                }
+            }
+            (StmtPattern::If(cond_pattern, then_pattern, otherwise_pattern), Stmt::IfElse(cond, then, otherwise)) => {
+                let captures = self.cond(cond_pattern, cond, captures)?;
+                let captures = self.block(then_pattern, then, captures)?;
+                self.block(otherwise_pattern.as_deref().unwrap_or_default(), otherwise, captures)
+            }
+            (StmtPattern::While(cond_pattern, body_pattern), Stmt::While(cond, body, LoopKind::While)) => {
+                let captures = self.cond(cond_pattern, cond, captures)?;
+                self.block(body_pattern, body, captures)
+            }
+            (StmtPattern::Break, Stmt::Break) | (StmtPattern::Unreachable, Stmt::Unreachable) => Some(captures),
+            _ => None,
+        }
+    }
+
+    fn cond(&self, pattern: &ExprPattern, cond: &Cond, captures: Captures) -> Option<Captures> {
+        match (pattern, cond) {
+            (ExprPattern::Any, _) => return Some(captures),
+            (ExprPattern::Capture(name, None), _) => {
+                let capture = match cond {
+                    Cond::Expr(MappedExpr::Expr(expr)) => Capture::Expr((**expr).clone()),
+                    _ => Capture::Cond(cond.clone()),
+                };
+                return bind(captures, name, capture);
+            }
+            (ExprPattern::Name(name), Cond::True) if name == "true" => return Some(captures),
+            (ExprPattern::Name(name), Cond::False) if name == "false" => return Some(captures),
+            _ => (),
+        }
+        let structural = match (pattern, cond) {
+            (ExprPattern::Op("!", args), Cond::Not(cond)) => self.cond(&args[0], cond, captures.clone()),
+            (ExprPattern::Op("&&", args), Cond::And(a, b)) | (ExprPattern::Op("||", args), Cond::Or(a, b)) => self
+                .cond(&args[0], a, captures.clone())
+                .and_then(|captures| self.cond(&args[1], b, captures)),
+            (ExprPattern::Op(op, args), Cond::Cmp(a, cmp, b)) if args.len() == 2 && cmp_matches(op, *cmp) => self
+                .mapped(&args[0], a, captures.clone())
+                .and_then(|captures| self.mapped(&args[1], b, captures)),
+            (_, Cond::Expr(expr)) => self.mapped(pattern, expr, captures.clone()),
+            _ => None,
+        };
+        if structural.is_some() {
+            return structural;
+        }
+        // Negated comparisons are printed inverted
+        match cond {
+            Cond::Not(inner) => match &**inner {
+                Cond::Expr(MappedExpr::Expr(expr)) if expr.can_invert() => {
+                    self.expr(pattern, &(**expr).clone().invert(), captures)
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn mapped(&self, pattern: &ExprPattern, expr: &MappedExpr, captures: Captures) -> Option<Captures> {
+        match (pattern, expr) {
+            (_, MappedExpr::Expr(expr)) => self.expr(pattern, expr, captures),
+            (_, MappedExpr::Const(value)) => self.expr(pattern, &Expr::I32Const(*value), captures),
+            (ExprPattern::Any, MappedExpr::Mapped(_)) => Some(captures),
+            (ExprPattern::Capture(name, None), MappedExpr::Mapped(_)) => {
+                bind(captures, name, Capture::Cond(Cond::Expr(expr.clone())))
+            }
+            _ => None,
+        }
+    }
+
+    fn expr(&self, pattern: &ExprPattern, expr: &Expr, captures: Captures) -> Option<Captures> {
+        match pattern {
+            ExprPattern::Any => Some(captures),
+            ExprPattern::Capture(name, ty) => {
+                if ty.as_ref().is_some_and(|ty| !self.has_type(expr, ty)) {
+                    return None;
+                }
+                bind(captures, name, Capture::Expr(expr.clone()))
+            }
+            ExprPattern::Int(value) => {
+                let matches = match expr {
+                    Expr::I32Const(c) => *c as i32 as i64 == *value || *c as i64 == *value,
+                    Expr::I64Const(c) => *c as i64 == *value,
+                    _ => false,
+                };
+                matches.then_some(captures)
+            }
+            ExprPattern::Name(name) => self.is_named(expr, name).then_some(captures),
+            ExprPattern::Call(name, patterns, rest) => {
+                let args = match expr {
+                    Expr::Call(index, args) if self.module.func(*index).name() == name => args,
+                    _ => return None,
+                };
+                if args.len() < patterns.len() || (!rest && args.len() != patterns.len()) {
+                    return None;
+                }
+                self.exprs(patterns, args.iter(), captures)
+            }
+            ExprPattern::Op("!", patterns) => match expr {
+                Expr::I32Eqz(arg) | Expr::I64Eqz(arg) => self.expr(&patterns[0], arg, captures),
+                _ => None,
+            },
+            ExprPattern::Op(op, patterns) => {
+                let expr = printed_form(expr);
+                if op_name(&expr) != Some(*op) {
+                    return None;
+                }
+                let args = expr.children();
+                if args.len() != patterns.len() {
+                    return None;
+                }
+                self.exprs(patterns, args.into_iter(), captures)
+            }
+        }
+    }
+
+    fn exprs<'e>(
+        &self,
+        patterns: &[ExprPattern],
+        exprs: impl Iterator<Item = &'e Expr>,
+        captures: Captures,
+    ) -> Option<Captures> {
+        patterns
+            .iter()
+            .zip(exprs)
+            .try_fold(captures, |captures, (pattern, expr)| self.expr(pattern, expr, captures))
+    }
+
+    /// Whether the statement calls a host function with side effects, directly or through the
+    /// functions of the module
+    fn has_side_effects(&self, stmt: &Stmt) -> bool {
+        let block = |code: &[Stmt]| code.iter().any(|stmt| self.has_side_effects(stmt));
+        match stmt {
+            Stmt::Expr(expr)
+            | Stmt::Return(expr)
+            | Stmt::Branch(expr)
+            | Stmt::SetLocal(_, expr)
+            | Stmt::SetGlobal(_, expr) => self.expr_has_side_effects(expr),
+            Stmt::While(cond, body, _) => self.cond_has_side_effects(cond) || block(body),
+            Stmt::ForLoop(_, init, cond, step, body) => {
+                init.iter().any(|init| self.expr_has_side_effects(init))
+                    || self.cond_has_side_effects(cond)
+                    || self.expr_has_side_effects(step)
+                    || block(body)
+            }
+            Stmt::Seq(code) => block(code),
+            Stmt::If(cond, body) => self.cond_has_side_effects(cond) || block(body),
+            Stmt::IfElse(cond, then, otherwise) => self.cond_has_side_effects(cond) || block(then) || block(otherwise),
+            Stmt::SwitchCase(expr, cases, default) => {
+                self.mapped_has_side_effects(expr)
+                    || cases.iter().any(|(_, case)| self.has_side_effects(case))
+                    || default.as_deref().is_some_and(|default| self.has_side_effects(default))
+            }
+            Stmt::Replaced(replacement) => replacement.captures.values().any(|capture| match capture {
+                Capture::Expr(expr) => self.expr_has_side_effects(expr),
+                Capture::Cond(cond) => self.cond_has_side_effects(cond),
+                Capture::Stmts(code) => block(code),
+            }),
+            stmt => store_parts(stmt).is_some_and(|(_, target, value)| {
+                self.expr_has_side_effects(target) || self.expr_has_side_effects(value)
+            }),
+        }
+    }
+
+    fn cond_has_side_effects(&self, cond: &Cond) -> bool {
+        match cond {
+            Cond::True | Cond::False => false,
+            Cond::Not(cond) => self.cond_has_side_effects(cond),
+            Cond::And(a, b) | Cond::Or(a, b) => self.cond_has_side_effects(a) || self.cond_has_side_effects(b),
+            Cond::Cmp(a, _, b) => self.mapped_has_side_effects(a) || self.mapped_has_side_effects(b),
+            Cond::Expr(expr) => self.mapped_has_side_effects(expr),
+        }
+    }
+
+    fn mapped_has_side_effects(&self, expr: &MappedExpr) -> bool {
+        matches!(expr, MappedExpr::Expr(expr) if self.expr_has_side_effects(expr))
+    }
+
+    fn expr_has_side_effects(&self, expr: &Expr) -> bool {
+        let call = match expr {
+            Expr::Call(index, _) => self.reaches_side_effects(*index, &mut HashSet::new()),
+            Expr::CallIndirect(..) => self.table_reaches_side_effects(&mut HashSet::new()),
+            _ => false,
+        };
+        call || expr
+            .children()
+            .into_iter()
+            .any(|child| self.expr_has_side_effects(child))
+    }
+
+    /// Whether the function is a host function with side effects or calls one
+    fn reaches_side_effects(&self, func_index: u32, visited: &mut HashSet<u32>) -> bool {
+        if !visited.insert(func_index) {
+            return false;
+        }
+        if let Some(name) = host::host_fn(self.module, func_index) {
+            return host::has_side_effects(name);
+        }
+        let instructions = match self.module.get_func(func_index) {
+            Some(func) => func.instructions(),
+            None => return false,
+        };
+        instructions.iter().any(|instr| match instr {
+            Instruction::Call(callee) => self.reaches_side_effects(*callee, visited),
+            Instruction::CallIndirect(..) => self.table_reaches_side_effects(visited),
+            _ => false,
+        })
+    }
+
+    /// Whether any function of the tables reaches a host function with side effects
+    fn table_reaches_side_effects(&self, visited: &mut HashSet<u32>) -> bool {
+        self.module
+            .table_inits()
+            .iter()
+            .flat_map(|init| init.entries())
+            .any(|&func_index| self.reaches_side_effects(func_index, visited))
+    }
+
+    /// Whether the value is a constant of the `Val` tag or a parameter of the spec type
+    fn has_type(&self, expr: &Expr, ty: &str) -> bool {
+        match expr {
+            Expr::I64Const(value) => {
+                let val = Val::from_payload(*value);
+                let text = format!("{:?}", val);
+                val.is_good()
+                    && text
+                        .strip_prefix(ty)
+                        .is_some_and(|rest| rest.is_empty() || rest.starts_with('('))
+            }
+            Expr::GetLocal(var) => self
+                .param(var)
+                .is_some_and(|(_, param_type)| param_type.strip_prefix("soroban_sdk::").unwrap_or(param_type) == ty),
+            _ => false,
+        }
+    }
+
+    /// Name and type of a parameter described by the contract spec
+    fn param(&self, var: &Var) -> Option<(&str, &str)> {
+        if var.index >= self.func.param_count() {
+            return None;
+        }
+        let param = self.func.spec_fn()?.inputs().get(var.index as usize)?;
+        Some((param.name(), param.type_ident().type_str()))
+    }
+
+    fn is_named(&self, expr: &Expr, name: &str) -> bool {
+        let letter = |index: u32| (index as u8 + b'a') as char;
+        match expr {
+            Expr::True => name == "true",
+            Expr::GetLocal(var) => match self.param(var) {
+                Some((param, _)) => param == name,
+                None if var.index < self.func.param_count() => name == format!("arg_{}", letter(var.index)),
+                None => name == format!("var_{}", letter(var.index)),
+            },
+            Expr::GetGlobal(index) => name == format!("global_{}", letter(*index)),
+            Expr::I64Const(value) => {
+                let text = val_text(*value);
+                text.strip_prefix(name)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('('))
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Statements that do not show up in the output
+fn is_marker(stmt: &Stmt) -> bool {
+    matches!(stmt, Stmt::Offset(_) | Stmt::Nop)
+}
+
+fn bind(mut captures: Captures, name: &str, value: Capture) -> Option<Captures> {
+    match captures.get(name) {
+        Some(bound) => same_capture(bound, &value).then_some(captures),
+        None => {
+            captures.insert(name.to_string(), value);
+            Some(captures)
+        }
+    }
+}
+
+fn same_capture(a: &Capture, b: &Capture) -> bool {
+    match (a, b) {
+        (Capture::Expr(a), Capture::Expr(b)) => same_expr(a, b),
+        (Capture::Cond(a), Capture::Cond(b)) => a == b,
+        (Capture::Stmts(_), Capture::Stmts(_)) => true,
+        _ => false,
+    }
+}
+
+/// Structural equality where variables are compared by index only, since the same variable has
+/// different SSA subscripts at different uses
+fn same_expr(a: &Expr, b: &Expr) -> bool {
+    match (a, b) {
+        (Expr::GetLocal(a), Expr::GetLocal(b)) => a.index == b.index,
+        (Expr::Call(a, _), Expr::Call(b, _)) if a != b => false,
+        (Expr::CallIndirect(_, _, a), Expr::CallIndirect(_, _, b)) if a != b => false,
+        _ if discriminant(a) != discriminant(b) => false,
+        _ => {
+            let (a_children, b_children) = (a.children(), b.children());
+            if a_children.is_empty() && b_children.is_empty() {
+                return a == b;
+            }
+            a_children.len() == b_children.len() && a_children.iter().zip(b_children).all(|(a, b)| same_expr(a, b))
+        }
+    }
+}
+
+fn cmp_matches(op: &str, cmp: CmpOp) -> bool {
+    cmp.to_string() == op
+}
+
+/// The expression as it is printed: `x == 0` for the eqz instructions and `x - 1` for the addition
+/// of a negative constant
+fn printed_form(expr: &Expr) -> Cow<'_, Expr> {
+    match expr {
+        Expr::I32Eqz(arg) => Cow::Owned(Expr::I32Eq(arg.clone(), Box::new(Expr::I32Const(0)))),
+        Expr::I64Eqz(arg) => Cow::Owned(Expr::I64Eq(arg.clone(), Box::new(Expr::I64Const(0)))),
+        Expr::I32Add(a, b) => match **b {
+            Expr::I32Const(value) if (value as i32) < 0 => Cow::Owned(Expr::I32Sub(
+                a.clone(),
+                Box::new(Expr::I32Const((value as i32).wrapping_neg() as u32)),
+            )),
+            _ => Cow::Borrowed(expr),
+        },
+        Expr::I64Add(a, b) => match **b {
+            Expr::I64Const(value) if (value as i64) < 0 => Cow::Owned(Expr::I64Sub(
+                a.clone(),
+                Box::new(Expr::I64Const((value as i64).wrapping_neg() as u64)),
+            )),
+            _ => Cow::Borrowed(expr),
+        },
+        _ => Cow::Borrowed(expr),
+    }
+}
+
+/// The operator or built-in function an expression is printed with
+fn op_name(expr: &Expr) -> Option<&'static str> {
+    use Expr::*;
+    let name = match expr {
+        MemorySize => "memory_size",
+        MemoryGrow(_) => "grow_memory",
+        I32Load(_) => "load_i32",
+        I64Load(_) => "load_i64",
+        F32Load(_) => "load_f32",
+        F64Load(_) => "load_f64",
+        I32Load8S(_) => "load_8s_i32",
+        I32Load8U(_) => "load_8u_i32",
+        I32Load16S(_) => "load_16s_i32",
+        I32Load16U(_) => "load_16u_i32",
+        I64Load8S(_) => "load_8s_i64",
+        I64Load8U(_) => "load_8u_i64",
+        I64Load16S(_) => "load_16s_i64",
+        I64Load16U(_) => "load_16u_i64",
+        I64Load32S(_) => "load_32s_i64",
+        I64Load32U(_) => "load_32u_i64",
+
+        I32Eq(..) | I64Eq(..) | F32Eq(..) | F64Eq(..) => "==",
+        I32Ne(..) | I64Ne(..) | F32Ne(..) | F64Ne(..) => "!=",
+        I32LtS(..) | I64LtS(..) => "<s",
+        I32LtU(..) | I64LtU(..) => "<u",
+        I32GtS(..) | I64GtS(..) => ">s",
+        I32GtU(..) | I64GtU(..) => ">u",
+        I32LeS(..) | I64LeS(..) => "<=s",
+        I32LeU(..) | I64LeU(..) => "<=u",
+        I32GeS(..) | I64GeS(..) => ">=s",
+        I32GeU(..) | I64GeU(..) => ">=u",
+        F32Lt(..) | F64Lt(..) => "<",
+        F32Gt(..) | F64Gt(..) => ">",
+        F32Le(..) | F64Le(..) => "<=",
+        F32Ge(..) | F64Ge(..) => ">=",
+
+        I32Clz(_) | I64Clz(_) => "clz",
+        I32Ctz(_) | I64Ctz(_) => "ctz",
+        I32Popcnt(_) | I64Popcnt(_) => "popcnt",
+        I32Neg(_) | I64Neg(_) | F32Neg(_) | F64Neg(_) => "neg",
+        I32Add(..) | I64Add(..) | F32Add(..) | F64Add(..) => "+",
+        I32Sub(..) | I64Sub(..) | F32Sub(..) | F64Sub(..) => "-",
+        I32Mul(..) | I64Mul(..) | F32Mul(..) | F64Mul(..) => "*",
+        I32DivS(..) | I64DivS(..) => "/s",
+        I32DivU(..) | I64DivU(..) => "/u",
+        F32Div(..) | F64Div(..) => "/",
+        I32RemS(..) | I64RemS(..) => "rem_s",
+        I32RemU(..) | I64RemU(..) => "rem_u",
+        I32And(..) | I64And(..) => "&",
+        I32Or(..) | I64Or(..) => "|",
+        I32Xor(..) | I64Xor(..) => "^",
+        I32Shl(..) | I64Shl(..) => "<<",
+        I32ShrS(..) | I64ShrS(..) => ">>s",
+        I32ShrU(..) | I64ShrU(..) => ">>u",
+        I32Rotl(..) | I64Rotl(..) => "rotl",
+        I32Rotr(..) | I64Rotr(..) => "rotr",
+
+        F32Abs(_) | F64Abs(_) => "abs",
+        F32Ceil(_) | F64Ceil(_) => "ceil",
+        F32Floor(_) | F64Floor(_) => "floor",
+        F32Trunc(_) | F64Trunc(_) => "trunc",
+        F32Nearest(_) | F64Nearest(_) => "nearest",
+        F32Sqrt(_) | F64Sqrt(_) => "sqrt",
+        F32Min(..) | F64Min(..) => "min",
+        F32Max(..) | F64Max(..) => "max",
+        F32Copysign(..) | F64Copysign(..) => "copysign",
+
+        I32WrapI64(_) => "wrap_i32",
+        I64ExtendSI32(_) => "extend_s_i64",
+        I64ExtendUI32(_) => "extend_u_i64",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// Name as printed, target and value of a store
+fn store_parts(stmt: &Stmt) -> Option<(&'static str, &Expr, &Expr)> {
+    let (name, target, value) = match stmt {
+        Stmt::I32Store(target, value) => ("store_i32", target, value),
+        Stmt::I64Store(target, value) => ("store_i64", target, value),
+        Stmt::F32Store(target, value) => ("store_f32", target, value),
+        Stmt::F64Store(target, value) => ("store_f64", target, value),
+        Stmt::I32Store8(target, value) => ("store8_i32", target, value),
+        Stmt::I32Store16(target, value) => ("store16_i32", target, value),
+        Stmt::I64Store8(target, value) => ("store8_i64", target, value),
+        Stmt::I64Store16(target, value) => ("store16_i64", target, value),
+        Stmt::I64Store32(target, value) => ("store32_i32", target, value),
+        _ => return None,
+    };
+    Some((name, target, value))
+}
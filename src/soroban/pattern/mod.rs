@@ -0,0 +1,198 @@
+//! Patterns over the structured IR that replace known SDK code with the source it was compiled
+//! from.
+//!
+//! Patterns are written like the decompiled output, so a pattern usually starts as a copy of the
+//! code it should replace:
+//!
+//! ```text
+//! $found = ledger.has_contract_data($key: Symbol, Void)
+//! if $found {
+//!     ..
+//!     $value = load_i64($entry + 16)
+//!     $value
+//! }
+//! panic!()
+//! ```
+//!
+//! * `$x` captures an expression, condition or assigned variable, every use of `$x` has to match
+//!   the same value. `$x: Address` only matches values of that type: constants of the `Val` tag
+//!   and parameters with that spec type.
+//! * `_` matches any expression, or any statement on its own.
+//! * `..` matches any sequence of statements, `$body..` also captures it. A trailing `..` extends
+//!   the match to the end of the block. Statements are only dropped from the output if they
+//!   have no side effects, so `..` does not skip storage writes, auth checks, events or contract
+//!   calls unless the template prints them as `$body`.
+//! * Other names are host functions like `ledger.get_contract_data`, the built-in operations of
+//!   the output like `load_i64`, parameter names and `Val` constants like `Void` or `Map`.
+//!
+//! A pattern matches a run of statements of any block. The run is printed with the replacement
+//! template of the pattern, with `$x` substituted by the captured value.
+
+mod matching;
+mod parse;
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::fmt::CodeWriter;
+use crate::ssa::{Cond, Expr, Stmt};
+
+pub use matching::Matcher;
+
+/// A value bound to a metavariable
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Capture {
+    Expr(Expr),
+    Cond(Cond),
+    Stmts(Vec<Stmt>),
+}
+
+pub type Captures = BTreeMap<String, Capture>;
+
+/// Statements matched by a pattern, printed with its template
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Replacement {
+    pub pattern: String,
+    pub template: String,
+    pub captures: Captures,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ExprPattern {
+    /// `_`
+    Any,
+    /// `$x` or `$x: Type`
+    Capture(String, Option<String>),
+    Int(i64),
+    /// A parameter, local, global or `Val` constant
+    Name(String),
+    /// Call of a function by name, `true` if further arguments are ignored
+    Call(String, Vec<ExprPattern>, bool),
+    /// An operator or built-in function as printed, e.g. `+`, `load_i64` or `!`
+    Op(&'static str, Vec<ExprPattern>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum StmtPattern {
+    /// `_` on its own
+    Any,
+    /// `..` or `$x..`
+    Rest(Option<String>),
+    Expr(ExprPattern),
+    Return(Option<ExprPattern>),
+    /// The assigned variable is captured unless it is `_`
+    Assign(Option<String>, ExprPattern),
+    Store(&'static str, ExprPattern, ExprPattern),
+    If(ExprPattern, Vec<StmtPattern>, Option<Vec<StmtPattern>>),
+    While(ExprPattern, Vec<StmtPattern>),
+    Break,
+    Unreachable,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    pub name: String,
+    pub template: String,
+    body: Vec<StmtPattern>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatternError {
+    /// Line of the pattern source, 1-based
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for PatternError {}
+
+impl Pattern {
+    /// Parse the pattern `source`, every metavariable of `template` has to be captured by it
+    pub fn parse(name: &str, source: &str, template: &str) -> Result<Pattern, PatternError> {
+        let mut body = parse::parse(source)?;
+        if body.iter().all(|stmt| matches!(stmt, StmtPattern::Rest(_))) {
+            return Err(PatternError {
+                line: 1,
+                message: String::from("the pattern matches any code"),
+            });
+        }
+        let mut captured = Vec::new();
+        parse::captured_names(&body, &mut captured);
+        let mut printed = Vec::new();
+        for part in template_parts(template) {
+            if let TemplatePart::Capture(capture) = part {
+                if !captured.iter().any(|name| name == capture) {
+                    return Err(PatternError {
+                        line: 1,
+                        message: format!("the template uses `${}`, which the pattern does not capture", capture),
+                    });
+                }
+                printed.push(capture);
+            }
+        }
+        parse::forget_sequences(&mut body, &printed);
+        Ok(Pattern {
+            name: name.to_string(),
+            template: template.to_string(),
+            body,
+        })
+    }
+}
+
+enum TemplatePart<'a> {
+    Text(&'a str),
+    Capture(&'a str),
+}
+
+fn template_parts(template: &str) -> Vec<TemplatePart<'_>> {
+    let is_name = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut parts = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('$') {
+        let len = rest[start + 1..]
+            .find(|c| !is_name(c))
+            .unwrap_or(rest.len() - start - 1);
+        if len == 0 {
+            parts.push(TemplatePart::Text(&rest[..=start]));
+        } else {
+            parts.push(TemplatePart::Text(&rest[..start]));
+            parts.push(TemplatePart::Capture(&rest[start + 1..start + 1 + len]));
+        }
+        rest = &rest[start + 1 + len..];
+    }
+    parts.push(TemplatePart::Text(rest));
+    parts
+}
+
+impl crate::fmt::CodeDisplay for Replacement {
+    fn fmt_code(&self, f: &mut CodeWriter) {
+        for (i, line) in self.template.lines().enumerate() {
+            if i > 0 {
+                f.newline();
+            }
+            for part in template_parts(line) {
+                match part {
+                    TemplatePart::Text(text) => write!(f, "{}", text),
+                    TemplatePart::Capture(name) => match self.captures.get(name) {
+                        Some(Capture::Expr(expr)) => f.write(expr),
+                        Some(Capture::Cond(cond)) => f.write(cond),
+                        Some(Capture::Stmts(code)) => {
+                            f.indent();
+                            f.write(&code[..]);
+                            f.dedent();
+                        }
+                        None => write!(f, "${}", name),
+                    },
+                }
+            }
+        }
+    }
+}
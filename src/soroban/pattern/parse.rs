@@ -0,0 +1,510 @@
+//! Parser of the pattern language.
+
+use super::{ExprPattern, PatternError, StmtPattern};
+
+/// Operators, longest first so that `>>s` is not read as `>` and `>s`
+const PUNCTUATION: &[&str] = &[
+    ">>s", ">>u", "<=s", "<=u", ">=s", ">=u", "..", "<<", "==", "!=", "&&", "||", "<s", "<u", ">s", ">u", "/s", "/u",
+    "<=", ">=", "+", "-", "*", "/", "&", "|", "^", "!", "<", ">", "(", ")", "{", "}", ",", ";", "=", ":",
+];
+
+/// Built-in functions of the output and the number of their arguments
+const BUILTINS: &[(&str, usize)] = &[
+    ("load_i32", 1),
+    ("load_i64", 1),
+    ("load_f32", 1),
+    ("load_f64", 1),
+    ("load_8s_i32", 1),
+    ("load_8u_i32", 1),
+    ("load_16s_i32", 1),
+    ("load_16u_i32", 1),
+    ("load_8s_i64", 1),
+    ("load_8u_i64", 1),
+    ("load_16s_i64", 1),
+    ("load_16u_i64", 1),
+    ("load_32s_i64", 1),
+    ("load_32u_i64", 1),
+    ("memory_size", 0),
+    ("grow_memory", 1),
+    ("clz", 1),
+    ("ctz", 1),
+    ("popcnt", 1),
+    ("abs", 1),
+    ("ceil", 1),
+    ("floor", 1),
+    ("trunc", 1),
+    ("nearest", 1),
+    ("sqrt", 1),
+    ("wrap_i32", 1),
+    ("extend_s_i64", 1),
+    ("extend_u_i64", 1),
+    ("rem_s", 2),
+    ("rem_u", 2),
+    ("rotl", 2),
+    ("rotr", 2),
+    ("min", 2),
+    ("max", 2),
+    ("copysign", 2),
+];
+
+/// Store statements as printed
+const STORES: &[&str] = &[
+    "store_i32",
+    "store_i64",
+    "store_f32",
+    "store_f64",
+    "store8_i32",
+    "store16_i32",
+    "store8_i64",
+    "store16_i64",
+    "store32_i32",
+];
+
+/// Binary operators from the loosest to the tightest binding
+const BINARY_LEVELS: &[&[&str]] = &[
+    &["||"],
+    &["&&"],
+    &[
+        "==", "!=", "<s", "<u", ">s", ">u", "<=s", "<=u", ">=s", ">=u", "<", ">", "<=", ">=",
+    ],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["<<", ">>s", ">>u"],
+    &["+", "-"],
+    &["*", "/s", "/u", "/"],
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Name(String),
+    Meta(String),
+    Int(i64),
+    Punct(&'static str),
+}
+
+struct Lexer;
+
+impl Lexer {
+    fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, PatternError> {
+        let mut tokens = Vec::new();
+        for (i, line) in source.lines().enumerate() {
+            let line_number = i + 1;
+            let error = |message: String| PatternError {
+                line: line_number,
+                message,
+            };
+            let chars: Vec<char> = line.chars().collect();
+            let mut pos = 0;
+            while pos < chars.len() {
+                let c = chars[pos];
+                let rest: String = chars[pos..].iter().collect();
+                if c.is_whitespace() {
+                    pos += 1;
+                } else if rest.starts_with("//") {
+                    break;
+                } else if c == '$' {
+                    let len = name_len(&chars[pos + 1..], false);
+                    if len == 0 {
+                        return Err(error(String::from("expected a name after `$`")));
+                    }
+                    tokens.push((Token::Meta(chars[pos + 1..pos + 1 + len].iter().collect()), line_number));
+                    pos += 1 + len;
+                } else if c.is_ascii_digit() {
+                    let len = chars[pos..]
+                        .iter()
+                        .take_while(|c| c.is_ascii_alphanumeric() || **c == '_')
+                        .count();
+                    let text: String = chars[pos..pos + len].iter().filter(|c| **c != '_').collect();
+                    let value = match text.strip_prefix("0x") {
+                        Some(hex) => u64::from_str_radix(hex, 16).map(|value| value as i64),
+                        None => text.parse::<u64>().map(|value| value as i64),
+                    };
+                    let value = value.map_err(|_| error(format!("invalid number `{}`", text)))?;
+                    tokens.push((Token::Int(value), line_number));
+                    pos += len;
+                } else if c.is_alphabetic() || c == '_' {
+                    let len = name_len(&chars[pos..], true);
+                    tokens.push((Token::Name(chars[pos..pos + len].iter().collect()), line_number));
+                    pos += len;
+                } else {
+                    // Signed and unsigned operators like `<s` are followed by a space in the output
+                    let punct = PUNCTUATION.iter().find(|punct| {
+                        rest.starts_with(*punct)
+                            && !(punct.ends_with(['s', 'u'])
+                                && rest[punct.len()..].starts_with(|c: char| c.is_alphanumeric() || c == '_'))
+                    });
+                    match punct {
+                        Some(punct) => {
+                            tokens.push((Token::Punct(punct), line_number));
+                            pos += punct.len();
+                        }
+                        None => return Err(error(format!("unexpected character `{}`", c))),
+                    }
+                }
+            }
+        }
+        Ok(tokens)
+    }
+}
+
+/// Length of the name at the start of `chars`, function names may contain dots
+fn name_len(chars: &[char], dotted: bool) -> usize {
+    let mut len = 0;
+    while len < chars.len() {
+        let c = chars[len];
+        let dot = dotted && c == '.' && chars.get(len + 1).is_some_and(|c| c.is_alphabetic() || *c == '_');
+        if !(c.is_alphanumeric() || c == '_' || dot) {
+            break;
+        }
+        len += 1;
+    }
+    len
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.pos + offset).map(|(token, _)| token)
+    }
+
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or_else(|| self.tokens.last())
+            .map_or(1, |(_, line)| *line)
+    }
+
+    fn error<T>(&self, message: String) -> Result<T, PatternError> {
+        Err(PatternError {
+            line: self.line(),
+            message,
+        })
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.peek().cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        if self.peek() == Some(&Token::Punct(punct_str(punct))) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<(), PatternError> {
+        if self.eat(punct) {
+            Ok(())
+        } else {
+            self.error(format!("expected `{}`, found {}", punct, describe(self.peek())))
+        }
+    }
+
+    fn is_name(&self, name: &str) -> bool {
+        matches!(self.peek(), Some(Token::Name(n)) if n == name)
+    }
+
+    fn statements(&mut self, in_block: bool) -> Result<Vec<StmtPattern>, PatternError> {
+        let mut stmts = Vec::new();
+        loop {
+            while self.eat(";") {}
+            match self.peek() {
+                None if in_block => return self.error(String::from("expected `}`")),
+                None => return Ok(stmts),
+                Some(Token::Punct("}")) if in_block => {
+                    self.pos += 1;
+                    return Ok(stmts);
+                }
+                _ => stmts.push(self.statement()?),
+            }
+        }
+    }
+
+    fn block(&mut self) -> Result<Vec<StmtPattern>, PatternError> {
+        self.expect("{")?;
+        self.statements(true)
+    }
+
+    fn statement(&mut self) -> Result<StmtPattern, PatternError> {
+        match (self.peek().cloned(), self.peek_at(1).cloned()) {
+            (Some(Token::Punct("..")), _) => {
+                self.pos += 1;
+                Ok(StmtPattern::Rest(None))
+            }
+            (Some(Token::Meta(name)), Some(Token::Punct(".."))) => {
+                self.pos += 2;
+                Ok(StmtPattern::Rest(Some(name)))
+            }
+            (Some(Token::Meta(name)), Some(Token::Punct("="))) => {
+                self.pos += 2;
+                Ok(StmtPattern::Assign(Some(name), self.expr()?))
+            }
+            (Some(Token::Name(name)), Some(Token::Punct("="))) if name == "_" => {
+                self.pos += 2;
+                Ok(StmtPattern::Assign(None, self.expr()?))
+            }
+            (Some(Token::Name(name)), next) => match name.as_str() {
+                "_" if matches!(next, None | Some(Token::Punct(";")) | Some(Token::Punct("}"))) => {
+                    self.pos += 1;
+                    Ok(StmtPattern::Any)
+                }
+                "if" => {
+                    self.pos += 1;
+                    let cond = self.expr()?;
+                    let then = self.block()?;
+                    if !self.is_name("else") {
+                        return Ok(StmtPattern::If(cond, then, None));
+                    }
+                    self.pos += 1;
+                    let otherwise = if self.is_name("if") {
+                        vec![self.statement()?]
+                    } else {
+                        self.block()?
+                    };
+                    Ok(StmtPattern::If(cond, then, Some(otherwise)))
+                }
+                "while" => {
+                    self.pos += 1;
+                    let cond = self.expr()?;
+                    Ok(StmtPattern::While(cond, self.block()?))
+                }
+                "return" => {
+                    self.pos += 1;
+                    match self.peek() {
+                        None | Some(Token::Punct(";")) | Some(Token::Punct("}")) => Ok(StmtPattern::Return(None)),
+                        _ => Ok(StmtPattern::Return(Some(self.expr()?))),
+                    }
+                }
+                "break" => {
+                    self.pos += 1;
+                    Ok(StmtPattern::Break)
+                }
+                "panic" if next == Some(Token::Punct("!")) => {
+                    self.pos += 2;
+                    self.expect("(")?;
+                    self.expect(")")?;
+                    Ok(StmtPattern::Unreachable)
+                }
+                store if STORES.contains(&store) => {
+                    let store = STORES.iter().find(|s| **s == store).unwrap();
+                    self.pos += 1;
+                    self.expect("(")?;
+                    let target = self.expr()?;
+                    self.expect(",")?;
+                    let value = self.expr()?;
+                    self.expect(")")?;
+                    Ok(StmtPattern::Store(store, target, value))
+                }
+                _ => Ok(StmtPattern::Expr(self.expr()?)),
+            },
+            _ => Ok(StmtPattern::Expr(self.expr()?)),
+        }
+    }
+
+    fn expr(&mut self) -> Result<ExprPattern, PatternError> {
+        self.binary(0)
+    }
+
+    fn binary(&mut self, level: usize) -> Result<ExprPattern, PatternError> {
+        if level == BINARY_LEVELS.len() {
+            return self.unary();
+        }
+        let mut left = self.binary(level + 1)?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Punct(op)) if BINARY_LEVELS[level].contains(op) => *op,
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            let right = self.binary(level + 1)?;
+            left = ExprPattern::Op(op, vec![left, right]);
+        }
+    }
+
+    fn unary(&mut self) -> Result<ExprPattern, PatternError> {
+        if self.eat("!") {
+            return Ok(ExprPattern::Op("!", vec![self.unary()?]));
+        }
+        if self.eat("-") {
+            return match self.unary()? {
+                ExprPattern::Int(value) => Ok(ExprPattern::Int(value.wrapping_neg())),
+                expr => Ok(ExprPattern::Op("neg", vec![expr])),
+            };
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<ExprPattern, PatternError> {
+        match self.next() {
+            Some(Token::Int(value)) => Ok(ExprPattern::Int(value)),
+            Some(Token::Meta(name)) => {
+                if !self.eat(":") {
+                    return Ok(ExprPattern::Capture(name, None));
+                }
+                match self.next() {
+                    Some(Token::Name(ty)) => Ok(ExprPattern::Capture(name, Some(ty))),
+                    token => self.error(format!(
+                        "expected a type after `${}:`, found {}",
+                        name,
+                        describe(token.as_ref())
+                    )),
+                }
+            }
+            Some(Token::Punct("(")) => {
+                let expr = self.expr()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Some(Token::Name(name)) if name == "_" => Ok(ExprPattern::Any),
+            Some(Token::Name(name)) => {
+                if !self.eat("(") {
+                    return Ok(ExprPattern::Name(name));
+                }
+                let (args, rest) = self.arguments()?;
+                match BUILTINS.iter().find(|(builtin, _)| *builtin == name) {
+                    Some((builtin, arity)) if *arity == args.len() && !rest => Ok(ExprPattern::Op(builtin, args)),
+                    Some((builtin, arity)) => self.error(format!("`{}` takes {} arguments", builtin, arity)),
+                    None => Ok(ExprPattern::Call(name, args, rest)),
+                }
+            }
+            token => self.error(format!("expected an expression, found {}", describe(token.as_ref()))),
+        }
+    }
+
+    /// Call arguments after the `(`, a final `..` ignores the remaining arguments
+    fn arguments(&mut self) -> Result<(Vec<ExprPattern>, bool), PatternError> {
+        let mut args = Vec::new();
+        if self.eat(")") {
+            return Ok((args, false));
+        }
+        loop {
+            if self.eat("..") {
+                self.expect(")")?;
+                return Ok((args, true));
+            }
+            args.push(self.expr()?);
+            if self.eat(")") {
+                return Ok((args, false));
+            }
+            self.expect(",")?;
+        }
+    }
+}
+
+fn punct_str(punct: &str) -> &'static str {
+    PUNCTUATION.iter().find(|p| **p == punct).copied().unwrap_or("")
+}
+
+fn describe(token: Option<&Token>) -> String {
+    match token {
+        None => String::from("the end of the pattern"),
+        Some(Token::Name(name)) => format!("`{}`", name),
+        Some(Token::Meta(name)) => format!("`${}`", name),
+        Some(Token::Int(value)) => format!("`{}`", value),
+        Some(Token::Punct(punct)) => format!("`{}`", punct),
+    }
+}
+
+pub(super) fn parse(source: &str) -> Result<Vec<StmtPattern>, PatternError> {
+    let mut parser = Parser {
+        tokens: Lexer::tokenize(source)?,
+        pos: 0,
+    };
+    let body = parser.statements(false)?;
+    let mut sequences = Vec::new();
+    check_sequences(&body, &mut sequences)?;
+    Ok(body)
+}
+
+/// Captured statement sequences can only be used once, they are not compared
+fn check_sequences(body: &[StmtPattern], names: &mut Vec<String>) -> Result<(), PatternError> {
+    for stmt in body {
+        match stmt {
+            StmtPattern::Rest(Some(name)) if names.contains(name) => {
+                return Err(PatternError {
+                    line: 1,
+                    message: format!("the sequence `${}..` is captured twice", name),
+                })
+            }
+            StmtPattern::Rest(Some(name)) => names.push(name.clone()),
+            StmtPattern::If(_, then, otherwise) => {
+                check_sequences(then, names)?;
+                check_sequences(otherwise.as_deref().unwrap_or_default(), names)?;
+            }
+            StmtPattern::While(_, body) => check_sequences(body, names)?,
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+/// Turn the captured sequences that are not in `printed` into `..`, they are dropped from the
+/// output like skipped statements
+pub(super) fn forget_sequences(body: &mut [StmtPattern], printed: &[&str]) {
+    for stmt in body {
+        match stmt {
+            StmtPattern::Rest(name) if name.as_deref().is_some_and(|name| !printed.contains(&name)) => *name = None,
+            StmtPattern::If(_, then, otherwise) => {
+                forget_sequences(then, printed);
+                forget_sequences(otherwise.as_deref_mut().unwrap_or_default(), printed);
+            }
+            StmtPattern::While(_, body) => forget_sequences(body, printed),
+            _ => (),
+        }
+    }
+}
+
+/// Names of all metavariables of the pattern
+pub(super) fn captured_names(body: &[StmtPattern], names: &mut Vec<String>) {
+    fn expr_names(expr: &ExprPattern, names: &mut Vec<String>) {
+        match expr {
+            ExprPattern::Capture(name, _) => names.push(name.clone()),
+            ExprPattern::Call(_, args, _) | ExprPattern::Op(_, args) => {
+                args.iter().for_each(|arg| expr_names(arg, names))
+            }
+            ExprPattern::Any | ExprPattern::Int(_) | ExprPattern::Name(_) => (),
+        }
+    }
+    for stmt in body {
+        match stmt {
+            StmtPattern::Rest(Some(name)) | StmtPattern::Assign(Some(name), _) => names.push(name.clone()),
+            _ => (),
+        }
+        match stmt {
+            StmtPattern::Expr(expr) | StmtPattern::Return(Some(expr)) | StmtPattern::Assign(_, expr) => {
+                expr_names(expr, names)
+            }
+            StmtPattern::Store(_, target, value) => {
+                expr_names(target, names);
+                expr_names(value, names);
+            }
+            StmtPattern::If(cond, then, otherwise) => {
+                expr_names(cond, names);
+                captured_names(then, names);
+                captured_names(otherwise.as_deref().unwrap_or_default(), names);
+            }
+            StmtPattern::While(cond, body) => {
+                expr_names(cond, names);
+                captured_names(body, names);
+            }
+            StmtPattern::Any
+            | StmtPattern::Rest(_)
+            | StmtPattern::Return(None)
+            | StmtPattern::Break
+            | StmtPattern::Unreachable => (),
+        }
+    }
+}
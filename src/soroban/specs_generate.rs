@@ -4,13 +4,14 @@ use soroban_spec_rust::types::generate_union;
 use soroban_spec_rust::types::generate_struct;
 use parity_wasm::elements::ValueType;
 use crate::wasm_wrapper::wasm_adapter::ExtendedValueType;
-use std::io::Read;
+use std::io::{self, Read};
 use soroban_sdk::xdr::ScSpecEntry;
 use std::fs::File;
 use std::fmt;
 use soroban_spec::read::from_wasm;
 // use soroban_spec_rust::types::{generate_enum, generate_error_enum, generate_struct, generate_union};
 use soroban_sdk::xdr::ScSpecTypeDef;
+use serde::Serialize;
 
 // Updated struct to represent function parameters with extended types
 #[derive(Clone, Eq, PartialEq, Debug, Hash)]
@@ -101,12 +102,10 @@ impl fmt::Display for FunctionInfo {
             .map(|param| format!("{}: {}", param.name, param.type_ident))
             .collect();
 
-        let output_str = match &self.output {
-            Some(return_type) => format!("{}", return_type.type_ident),
-            None => "".to_string(),
-        };
-
-        write!(f, "fn {}({}) {}", self.name, inputs_str.join(", "), output_str)
+        match &self.output {
+            Some(return_type) => write!(f, "fn {}({}) -> {}", self.name, inputs_str.join(", "), return_type.type_ident),
+            None => write!(f, "fn {}({})", self.name, inputs_str.join(", ")),
+        }
     }
 }
 
@@ -119,11 +118,44 @@ pub fn find_function_specs(spec_fns_result: &Vec<FunctionInfo>, function_name_to
     None
 }
 
+/// A user defined type of the contract spec together with its generated Rust definition
+#[derive(Clone, PartialEq, Eq, Debug, Hash, Serialize)]
+pub struct SpecType {
+    pub name: String,
+    pub kind: &'static str,
+    pub definition: String,
+}
+
+pub fn read_contract_types<P: AsRef<::std::path::Path>>(file_path: P) -> std::io::Result<Vec<SpecType>> {
+    let mut file = File::open(file_path)?;
+    let mut buffer = Vec::new();
+    file.read_to_end(&mut buffer)?;
+    let entries = from_wasm(&buffer).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+    let mut spec_types = Vec::new();
+
+    for s in entries.iter() {
+        let (name, kind, definition) = match s {
+            ScSpecEntry::FunctionV0(_) => continue,
+            ScSpecEntry::UdtStructV0(s) => (s.name.to_utf8_string(), "struct", generate_struct(s)),
+            ScSpecEntry::UdtUnionV0(u) => (u.name.to_utf8_string(), "union", generate_union(u)),
+            ScSpecEntry::UdtEnumV0(e) => (e.name.to_utf8_string(), "enum", generate_enum(e)),
+            ScSpecEntry::UdtErrorEnumV0(e) => (e.name.to_utf8_string(), "error_enum", generate_error_enum(e)),
+        };
+        spec_types.push(SpecType {
+            name: name.unwrap_or_default(),
+            kind,
+            definition: definition.to_string(),
+        });
+    }
+
+    Ok(spec_types)
+}
+
 pub fn read_contract_specs<P: AsRef<::std::path::Path>>(file_path: P) -> std::io::Result<Vec<FunctionInfo>> {
     let mut file = File::open(file_path)?;
     let mut buffer = Vec::new();
     file.read_to_end(&mut buffer)?;
-    let entries = from_wasm(&buffer).unwrap();
+    let entries = from_wasm(&buffer).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
     let mut spec_fns = Vec::new();
     let mut spec_structs = Vec::new();
     let mut spec_unions = Vec::new();
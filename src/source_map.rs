@@ -0,0 +1,75 @@
+use crate::wasm_wrapper::wasm_adapter::Module;
+use serde::Serialize;
+use std::error::Error;
+use std::fs::File;
+use std::io::BufWriter;
+
+const OFFSET_MARKER: &str = "// @0x";
+
+/// The offset of an annotation line written with `WriterOptions::offsets`
+pub fn parse_offset(line: &str) -> Option<u32> {
+    let hex = line.trim().strip_prefix(OFFSET_MARKER)?;
+    u32::from_str_radix(hex.trim(), 16).ok()
+}
+
+/// Links a line of decompiled output to the wasm instruction it originates from
+#[derive(Debug, Clone, Serialize)]
+pub struct Mapping {
+    /// Line in the decompiled output, starting at 1
+    pub line: usize,
+    /// Name of the function containing the instruction
+    pub function: String,
+    pub func_index: u32,
+    /// Index of the instruction inside the function body
+    pub instr: u32,
+    /// Byte offset of the instruction relative to the start of the code section
+    pub offset: u32,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct SourceMap {
+    pub file: String,
+    pub mappings: Vec<Mapping>,
+}
+
+impl SourceMap {
+    pub fn new(file: &str) -> SourceMap {
+        SourceMap {
+            file: file.to_string(),
+            mappings: Vec::new(),
+        }
+    }
+
+    /// Collect the offset annotations of decompiled code printed after `lines_before` other lines.
+    /// Every annotation maps the statement below it, skipping other comments such as trace observations.
+    pub fn add_output(&mut self, module: &Module, output: &str, lines_before: usize) {
+        let mut pending = Vec::new();
+        for (i, line) in output.lines().enumerate() {
+            if let Some(offset) = parse_offset(line) {
+                pending.push(offset);
+                continue;
+            }
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+            for offset in pending.drain(..) {
+                if let Some((func_index, instr)) = module.find_instr(offset) {
+                    self.mappings.push(Mapping {
+                        line: lines_before + i + 1,
+                        function: module.get_func(func_index).map(|f| f.name().to_string()).unwrap_or_default(),
+                        func_index,
+                        instr,
+                        offset,
+                    });
+                }
+            }
+        }
+    }
+
+    pub fn write_to_file(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+}
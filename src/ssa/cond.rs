@@ -1,6 +1,7 @@
 use std::collections::{HashMap, HashSet};
 use std::ops::Not;
 
+use serde::{Deserialize, Serialize};
 use z3::ast::Ast;
 
 use crate::analysis::used_vars;
@@ -13,7 +14,7 @@ thread_local! {
     static Z3_CTX: z3::Context = z3::Context::new(&z3::Config::new());
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum MappedExpr {
     Expr(Box<Expr>),
     Const(u32),
@@ -91,7 +92,7 @@ impl fmt::CodeDisplay for MappedExpr {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum CmpOp {
     Eq,
     Neq,
@@ -138,7 +139,7 @@ impl std::fmt::Display for CmpOp {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Cond {
     True,
     False,
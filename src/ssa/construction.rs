@@ -273,6 +273,7 @@ impl SSATransformer {
             Unreachable => (),
             ReturnVoid => (),
             Nop => (),
+            Offset(_) => (),
             While(..) => unreachable!(),
             ForLoop(..) => unreachable!(),
             Break => unreachable!(),
@@ -280,6 +281,7 @@ impl SSATransformer {
             IfElse(..) => unreachable!(),
             SwitchCase(..) => unreachable!(),
             Seq(..) => unreachable!(),
+            Replaced(..) => unreachable!(),
         }
     }
 
@@ -9,6 +9,7 @@ pub fn transform_out_of_ssa(cfg: &mut Cfg) {
             Stmt::Nop => false,
             _ => true,
         });
+        remove_stale_offsets(&mut node.code);
 
         for stmt in node.code.iter_mut() {
             remove_subscripts_in_stmt(stmt);
@@ -16,6 +17,24 @@ pub fn transform_out_of_ssa(cfg: &mut Cfg) {
     }
 }
 
+/// Remove offset markers that no longer precede a statement,
+/// e.g. because the statement was propagated or turned into a branch condition
+fn remove_stale_offsets(code: &mut Vec<Stmt>) {
+    let mut i = 0;
+    while i < code.len() {
+        if let Stmt::Offset(_) = code[i] {
+            match code.get(i + 1) {
+                None | Some(Stmt::Offset(_)) | Some(Stmt::Branch(_)) => {
+                    code.remove(i);
+                    continue;
+                }
+                _ => (),
+            }
+        }
+        i += 1;
+    }
+}
+
 fn remove_subscripts_in_stmt(stmt: &mut Stmt) {
     use Stmt::*;
     match stmt {
@@ -40,6 +59,7 @@ fn remove_subscripts_in_stmt(stmt: &mut Stmt) {
         Unreachable => (),
         ReturnVoid => (),
         Nop => (),
+        Offset(_) => (),
         Phi(..) => unreachable!(),
         While(..) => unreachable!(),
         ForLoop(..) => unreachable!(),
@@ -48,6 +68,7 @@ fn remove_subscripts_in_stmt(stmt: &mut Stmt) {
         IfElse(..) => unreachable!(),
         SwitchCase(..) => unreachable!(),
         Seq(..) => unreachable!(),
+        Replaced(..) => unreachable!(),
     }
 }
 
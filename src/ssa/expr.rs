@@ -2,8 +2,10 @@ use std::fmt::Formatter;
 use std::fmt::Display;
 use crate::ssa::Stmt;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use crate::soroban::FunctionInfo;
 use std::collections::HashMap;
+use std::iter;
 
 use soroban_sdk::Val;
 
@@ -13,14 +15,14 @@ use crate::wasm_wrapper::wasm_adapter::{ValueType, self};
 
 use super::Var;
 
-const DAY_IN_LEDGERS: u32 = 17280;
+pub(crate) const DAY_IN_LEDGERS: u32 = 17280;
 const INSTANCE_BUMP_AMOUNT: u32 = 7 * DAY_IN_LEDGERS;
 const INSTANCE_LIFETIME_THRESHOLD: u32 = INSTANCE_BUMP_AMOUNT - DAY_IN_LEDGERS;
 const BALANCE_BUMP_AMOUNT: u32 = 30 * DAY_IN_LEDGERS;
 const BALANCE_LIFETIME_THRESHOLD: u32 = BALANCE_BUMP_AMOUNT - DAY_IN_LEDGERS;
 
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Expr {
     True,
 
@@ -194,6 +196,155 @@ pub enum Expr {
 }
 
 impl Expr {
+    /// The direct sub expressions in evaluation order
+    pub fn children(&self) -> Vec<&Expr> {
+        use Expr::*;
+        match self {
+            True => Vec::new(),
+            Select(cond, true_expr, false_expr) => vec![cond, true_expr, false_expr],
+
+            Call(_, args) => args.iter().collect(),
+            CallIndirect(index, args, _) => iter::once(&**index).chain(args.iter()).collect(),
+
+            MemorySize => Vec::new(),
+            MemoryGrow(expr) => vec![expr],
+            I32Load(expr) | I64Load(expr) | F32Load(expr) | F64Load(expr) | I32Load8S(expr) | I32Load8U(expr)
+            | I32Load16S(expr) | I32Load16U(expr) | I64Load8S(expr) | I64Load8U(expr) | I64Load16S(expr)
+            | I64Load16U(expr) | I64Load32S(expr) | I64Load32U(expr) => vec![expr],
+
+            GetLocal(_) | GetGlobal(_) => Vec::new(),
+
+            I32Const(_) | I64Const(_) | F32Const(_) | F64Const(_) => Vec::new(),
+
+            I32Eqz(expr)
+            | I64Eqz(expr)
+            | I32Clz(expr)
+            | I32Ctz(expr)
+            | I32Popcnt(expr)
+            | I32Neg(expr)
+            | I64Clz(expr)
+            | I64Ctz(expr)
+            | I64Popcnt(expr)
+            | I64Neg(expr)
+            | F32Abs(expr)
+            | F32Neg(expr)
+            | F32Ceil(expr)
+            | F32Floor(expr)
+            | F32Trunc(expr)
+            | F32Nearest(expr)
+            | F32Sqrt(expr)
+            | F64Abs(expr)
+            | F64Neg(expr)
+            | F64Ceil(expr)
+            | F64Floor(expr)
+            | F64Trunc(expr)
+            | F64Nearest(expr)
+            | F64Sqrt(expr)
+            | I32WrapI64(expr)
+            | I32TruncSF32(expr)
+            | I32TruncUF32(expr)
+            | I32TruncSF64(expr)
+            | I32TruncUF64(expr)
+            | I64ExtendSI32(expr)
+            | I64ExtendUI32(expr)
+            | I64TruncSF32(expr)
+            | I64TruncUF32(expr)
+            | I64TruncSF64(expr)
+            | I64TruncUF64(expr)
+            | F32ConvertSI32(expr)
+            | F32ConvertUI32(expr)
+            | F32ConvertSI64(expr)
+            | F32ConvertUI64(expr)
+            | F32DemoteF64(expr)
+            | F64ConvertSI32(expr)
+            | F64ConvertUI32(expr)
+            | F64ConvertSI64(expr)
+            | F64ConvertUI64(expr)
+            | F64PromoteF32(expr)
+            | I32ReinterpretF32(expr)
+            | I64ReinterpretF64(expr)
+            | F32ReinterpretI32(expr)
+            | F64ReinterpretI64(expr) => vec![expr],
+
+            I32Eq(left, right)
+            | I32Ne(left, right)
+            | I32LtS(left, right)
+            | I32LtU(left, right)
+            | I32GtS(left, right)
+            | I32GtU(left, right)
+            | I32LeS(left, right)
+            | I32LeU(left, right)
+            | I32GeS(left, right)
+            | I32GeU(left, right)
+            | I64Eq(left, right)
+            | I64Ne(left, right)
+            | I64LtS(left, right)
+            | I64LtU(left, right)
+            | I64GtS(left, right)
+            | I64GtU(left, right)
+            | I64LeS(left, right)
+            | I64LeU(left, right)
+            | I64GeS(left, right)
+            | I64GeU(left, right)
+            | F32Eq(left, right)
+            | F32Ne(left, right)
+            | F32Lt(left, right)
+            | F32Gt(left, right)
+            | F32Le(left, right)
+            | F32Ge(left, right)
+            | F64Eq(left, right)
+            | F64Ne(left, right)
+            | F64Lt(left, right)
+            | F64Gt(left, right)
+            | F64Le(left, right)
+            | F64Ge(left, right)
+            | I32Add(left, right)
+            | I32Sub(left, right)
+            | I32Mul(left, right)
+            | I32DivS(left, right)
+            | I32DivU(left, right)
+            | I32RemS(left, right)
+            | I32RemU(left, right)
+            | I32And(left, right)
+            | I32Or(left, right)
+            | I32Xor(left, right)
+            | I32Shl(left, right)
+            | I32ShrS(left, right)
+            | I32ShrU(left, right)
+            | I32Rotl(left, right)
+            | I32Rotr(left, right)
+            | I64Add(left, right)
+            | I64Sub(left, right)
+            | I64Mul(left, right)
+            | I64DivS(left, right)
+            | I64DivU(left, right)
+            | I64RemS(left, right)
+            | I64RemU(left, right)
+            | I64And(left, right)
+            | I64Or(left, right)
+            | I64Xor(left, right)
+            | I64Shl(left, right)
+            | I64ShrS(left, right)
+            | I64ShrU(left, right)
+            | I64Rotl(left, right)
+            | I64Rotr(left, right)
+            | F32Add(left, right)
+            | F32Sub(left, right)
+            | F32Mul(left, right)
+            | F32Div(left, right)
+            | F32Min(left, right)
+            | F32Max(left, right)
+            | F32Copysign(left, right)
+            | F64Add(left, right)
+            | F64Sub(left, right)
+            | F64Mul(left, right)
+            | F64Div(left, right)
+            | F64Min(left, right)
+            | F64Max(left, right)
+            | F64Copysign(left, right) => vec![left, right],
+        }
+    }
+
     pub fn complexity(&self) -> u32 {
         use Expr::*;
         match self {
@@ -673,6 +824,32 @@ pub fn write_call(f: &mut fmt::CodeWriter, index: u32, args: &[Expr]) -> Option<
     }
 }
 
+/// An i64 constant as printed, tagged `Val`s like `Void` or `Address(obj#0)` by their value
+pub(crate) fn val_text(value: u64) -> String {
+    let v = Val::from_payload(value);
+    if v.is_good() {
+        let mut t = format!("{:?}", v);
+        let re = Regex::new(r"\((?<value>\w+)\)").unwrap();
+        if let Some(captures) = re.captures(t.as_str()) {
+            if let Some(value) = captures.get(1) {
+                t = format!("{}", value.as_str());
+            } 
+        } 
+        if t == format!("{}", INSTANCE_LIFETIME_THRESHOLD) {
+            t = "INSTANCE_LIFETIME_THRESHOLD".to_string();
+        } else if t == format!("{}", BALANCE_BUMP_AMOUNT) {
+            t = "BALANCE_BUMP_AMOUNT".to_string();
+        } else if t == format!("{}", BALANCE_LIFETIME_THRESHOLD) {
+            t = "BALANCE_LIFETIME_THRESHOLD".to_string();
+        } else if t == format!("{}", INSTANCE_BUMP_AMOUNT) {
+            t = "INSTANCE_BUMP_AMOUNT".to_string();
+        } 
+        t
+    } else {
+        format!("{}", value as i64)
+    }
+}
+
 impl Display for Expr {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -775,32 +952,7 @@ impl fmt::CodeDisplay for Expr {
             }
             Expr::GetGlobal(index) => write!(f, "global_{}", (*index as u8 +b'a') as char),
             Expr::I32Const(val) => write!(f, "{}", *val as i32),
-            Expr::I64Const(val) => {
-                let value = *val;
-                let v = Val::from_payload(value);
-                if v.is_good() {
-                    let mut t = format!("{:?}", v);
-                    let re = Regex::new(r"\((?<value>\w+)\)").unwrap();
-                    if let Some(captures) = re.captures(t.as_str()) {
-                        if let Some(value) = captures.get(1) {
-                            t = format!("{}", value.as_str());
-                        } 
-                    } 
-                    if t == format!("{}", INSTANCE_LIFETIME_THRESHOLD) {
-                        t = "INSTANCE_LIFETIME_THRESHOLD".to_string();
-                    } else if t == format!("{}", BALANCE_BUMP_AMOUNT) {
-                        t = "BALANCE_BUMP_AMOUNT".to_string();
-                    } else if t == format!("{}", BALANCE_LIFETIME_THRESHOLD) {
-                        t = "BALANCE_LIFETIME_THRESHOLD".to_string();
-                    } else if t == format!("{}", INSTANCE_BUMP_AMOUNT) {
-                        t = "INSTANCE_BUMP_AMOUNT".to_string();
-                    } 
-
-                    write!(f, "{}", t)
-                } else {
-                    write!(f, "{}", *val as i64)
-                }
-            },
+            Expr::I64Const(val) => write!(f, "{}", val_text(*val)),
             Expr::F32Const(val) => write!(f, "{}", f32::from_bits(*val)),
             Expr::F64Const(val) => write!(f, "{}", f64::from_bits(*val)),
 
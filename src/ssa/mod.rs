@@ -2,6 +2,7 @@ pub mod cond;
 mod construction;
 mod deconstruction;
 pub mod expr;
+pub mod smt;
 pub mod stmt;
 mod value_space;
 
@@ -9,10 +10,12 @@ pub use cond::Cond;
 pub use construction::transform_to_ssa;
 pub use deconstruction::transform_out_of_ssa;
 pub use expr::Expr;
-pub use stmt::{LoopKind, Stmt};
+pub use stmt::{LoopKind, SourcePos, Stmt};
 pub use value_space::ValueSpace;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Var {
     pub index: u32,
     pub subscript: u32,
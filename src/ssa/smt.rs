@@ -0,0 +1,263 @@
+//! Translation of expressions and conditions into Z3 bit-vector terms.
+//!
+//! Locals are bit-vector constants named after their index and version, memory loads and calls
+//! are uninterpreted functions of the memory version, so that two loads of the same address
+//! are equal unless memory may have changed in between. Floating point values and operations
+//! without a bit-vector counterpart become fresh unconstrained constants.
+
+use std::collections::HashMap;
+
+use z3::ast::{Ast, Bool, Dynamic, BV};
+use z3::{Context, FuncDecl, Sort};
+
+use super::cond::{CmpOp, MappedExpr};
+use super::{Cond, Expr};
+use crate::wasm_wrapper::wasm_adapter::{Module, ValueType};
+
+pub struct Translator<'ctx, 'm> {
+    ctx: &'ctx Context,
+    module: &'m Module,
+    /// Versions of the locals seen by the next translated expression, missing locals have version 0
+    pub versions: HashMap<u32, u32>,
+    /// Version of linear memory and host state seen by the next translated expression
+    pub memory: u32,
+    fresh: u32,
+}
+
+/// The width of the value of an expression, if it follows from the expression itself
+pub fn width_of(expr: &Expr) -> Option<u32> {
+    use Expr::*;
+    match expr {
+        I64Load(_) | I64Load8S(_) | I64Load8U(_) | I64Load16S(_) | I64Load16U(_) | I64Load32S(_)
+        | I64Load32U(_) | I64Const(_) | I64Clz(_) | I64Ctz(_) | I64Popcnt(_) | I64Neg(_) | I64Add(..)
+        | I64Sub(..) | I64Mul(..) | I64DivS(..) | I64DivU(..) | I64RemS(..) | I64RemU(..) | I64And(..)
+        | I64Or(..) | I64Xor(..) | I64Shl(..) | I64ShrS(..) | I64ShrU(..) | I64Rotl(..) | I64Rotr(..)
+        | I64ExtendSI32(_) | I64ExtendUI32(_) | I64TruncSF32(_) | I64TruncUF32(_) | I64TruncSF64(_)
+        | I64TruncUF64(_) | I64ReinterpretF64(_) => Some(64),
+        GetLocal(_) | GetGlobal(_) | Call(..) | CallIndirect(..) => None,
+        Select(_, a, b) => width_of(a).or_else(|| width_of(b)),
+        _ => Some(32),
+    }
+}
+
+const fn value_width(value_type: ValueType) -> u32 {
+    match value_type {
+        ValueType::I32 | ValueType::F32 => 32,
+        _ => 64,
+    }
+}
+
+impl<'ctx, 'm> Translator<'ctx, 'm> {
+    pub fn new(ctx: &'ctx Context, module: &'m Module) -> Self {
+        Translator {
+            ctx,
+            module,
+            versions: HashMap::new(),
+            memory: 0,
+            fresh: 0,
+        }
+    }
+
+    /// A local at its current version. Reads of any width are taken from one 64 bit symbol,
+    /// so a 32 bit read of a local is the low half of its 64 bit value.
+    pub fn local(&self, index: u32, width: u32) -> BV<'ctx> {
+        let version = self.versions.get(&index).copied().unwrap_or(0);
+        let value = BV::new_const(self.ctx, format!("var_{}@{}", index, version), 64);
+        if width < 64 {
+            value.extract(width - 1, 0)
+        } else {
+            value
+        }
+    }
+
+    fn fresh(&mut self, width: u32) -> BV<'ctx> {
+        self.fresh += 1;
+        BV::new_const(self.ctx, format!("fresh_{}", self.fresh), width)
+    }
+
+    fn constant(&self, value: u64, width: u32) -> BV<'ctx> {
+        BV::from_u64(self.ctx, value, width)
+    }
+
+    fn flag(&self, cond: Bool<'ctx>) -> BV<'ctx> {
+        cond.ite(&self.constant(1, 32), &self.constant(0, 32))
+    }
+
+    fn load(&mut self, addr: &Expr, width: u32) -> BV<'ctx> {
+        let addr = self.bv(addr, 32);
+        let name = format!("load{}@{}", width, self.memory);
+        let decl = FuncDecl::new(self.ctx, name, &[&Sort::bitvector(self.ctx, 32)], &Sort::bitvector(self.ctx, width));
+        decl.apply(&[&Dynamic::from(addr)]).as_bv().unwrap()
+    }
+
+    fn call(&mut self, func_index: u32, args: &[Expr], width: u32) -> BV<'ctx> {
+        let params: Vec<u32> = match self.module.get_func(func_index) {
+            Some(func) => func.params().iter().map(|t| value_width(*t)).collect(),
+            None => return self.fresh(width),
+        };
+        let args: Vec<Dynamic<'ctx>> = args
+            .iter()
+            .zip(&params)
+            .map(|(arg, width)| Dynamic::from(self.bv(arg, *width)))
+            .collect();
+        let domain: Vec<Sort<'ctx>> = params.iter().map(|width| Sort::bitvector(self.ctx, *width)).collect();
+        let name = format!("func_{}_{}@{}", func_index, width, self.memory);
+        let decl = FuncDecl::new(
+            self.ctx,
+            name,
+            &domain.iter().collect::<Vec<_>>(),
+            &Sort::bitvector(self.ctx, width),
+        );
+        decl.apply(&args.iter().collect::<Vec<_>>()).as_bv().unwrap()
+    }
+
+    /// wasm masks the shift amount with the width of the value
+    fn shift_amount(&mut self, expr: &Expr, width: u32) -> BV<'ctx> {
+        self.bv(expr, width).bvand(&self.constant(u64::from(width - 1), width))
+    }
+
+    /// Translate an integer expression. `width` is used for expressions whose width cannot be
+    /// derived from the expression itself, like locals and call results.
+    pub fn bv(&mut self, expr: &Expr, width: u32) -> BV<'ctx> {
+        use Expr::*;
+        let width = width_of(expr).unwrap_or(width);
+        match expr {
+            True => self.constant(1, width),
+            Select(cond, a, b) => {
+                let cond = self.bool(cond);
+                let a = self.bv(a, width);
+                let b = self.bv(b, width);
+                cond.ite(&a, &b)
+            }
+            Call(func_index, args) => self.call(*func_index, args, width),
+
+            I32Load(addr) => self.load(addr, 32),
+            I64Load(addr) => self.load(addr, 64),
+            I32Load8S(addr) => self.load(addr, 8).sign_ext(24),
+            I32Load8U(addr) => self.load(addr, 8).zero_ext(24),
+            I32Load16S(addr) => self.load(addr, 16).sign_ext(16),
+            I32Load16U(addr) => self.load(addr, 16).zero_ext(16),
+            I64Load8S(addr) => self.load(addr, 8).sign_ext(56),
+            I64Load8U(addr) => self.load(addr, 8).zero_ext(56),
+            I64Load16S(addr) => self.load(addr, 16).sign_ext(48),
+            I64Load16U(addr) => self.load(addr, 16).zero_ext(48),
+            I64Load32S(addr) => self.load(addr, 32).sign_ext(32),
+            I64Load32U(addr) => self.load(addr, 32).zero_ext(32),
+
+            GetLocal(var) => self.local(var.index, width),
+            GetGlobal(index) => BV::new_const(self.ctx, format!("global_{}@{}", index, self.memory), width),
+            I32Const(value) => self.constant(u64::from(*value), 32),
+            I64Const(value) => self.constant(*value, 64),
+
+            I32Eqz(..) | I32Eq(..) | I32Ne(..) | I32LtS(..) | I32LtU(..) | I32GtS(..) | I32GtU(..) | I32LeS(..)
+            | I32LeU(..) | I32GeS(..) | I32GeU(..) | I64Eqz(..) | I64Eq(..) | I64Ne(..) | I64LtS(..) | I64LtU(..)
+            | I64GtS(..) | I64GtU(..) | I64LeS(..) | I64LeU(..) | I64GeS(..) | I64GeU(..) => {
+                let cond = self.bool(expr);
+                self.flag(cond)
+            }
+
+            I32Neg(a) | I64Neg(a) => self.bv(a, width).bvneg(),
+            I32Add(a, b) | I64Add(a, b) => self.bv(a, width).bvadd(&self.bv(b, width)),
+            I32Sub(a, b) | I64Sub(a, b) => self.bv(a, width).bvsub(&self.bv(b, width)),
+            I32Mul(a, b) | I64Mul(a, b) => self.bv(a, width).bvmul(&self.bv(b, width)),
+            I32DivS(a, b) | I64DivS(a, b) => self.bv(a, width).bvsdiv(&self.bv(b, width)),
+            I32DivU(a, b) | I64DivU(a, b) => self.bv(a, width).bvudiv(&self.bv(b, width)),
+            I32RemS(a, b) | I64RemS(a, b) => self.bv(a, width).bvsrem(&self.bv(b, width)),
+            I32RemU(a, b) | I64RemU(a, b) => self.bv(a, width).bvurem(&self.bv(b, width)),
+            I32And(a, b) | I64And(a, b) => self.bv(a, width).bvand(&self.bv(b, width)),
+            I32Or(a, b) | I64Or(a, b) => self.bv(a, width).bvor(&self.bv(b, width)),
+            I32Xor(a, b) | I64Xor(a, b) => self.bv(a, width).bvxor(&self.bv(b, width)),
+            I32Shl(a, b) | I64Shl(a, b) => self.bv(a, width).bvshl(&self.shift_amount(b, width)),
+            I32ShrS(a, b) | I64ShrS(a, b) => self.bv(a, width).bvashr(&self.shift_amount(b, width)),
+            I32ShrU(a, b) | I64ShrU(a, b) => self.bv(a, width).bvlshr(&self.shift_amount(b, width)),
+            I32Rotl(a, b) | I64Rotl(a, b) => self.bv(a, width).bvrotl(&self.shift_amount(b, width)),
+            I32Rotr(a, b) | I64Rotr(a, b) => self.bv(a, width).bvrotr(&self.shift_amount(b, width)),
+
+            I32WrapI64(a) => self.bv(a, 64).extract(31, 0),
+            I64ExtendSI32(a) => self.bv(a, 32).sign_ext(32),
+            I64ExtendUI32(a) => self.bv(a, 32).zero_ext(32),
+
+            _ => self.fresh(width),
+        }
+    }
+
+    /// Translate an expression used as a condition, i.e. compared with zero
+    pub fn bool(&mut self, expr: &Expr) -> Bool<'ctx> {
+        use Expr::*;
+        let (a, b, cmp, signed) = match expr {
+            I32Eq(a, b) | I64Eq(a, b) => (a, b, CmpOp::Eq, false),
+            I32Ne(a, b) | I64Ne(a, b) => (a, b, CmpOp::Neq, false),
+            I32LtS(a, b) | I64LtS(a, b) => (a, b, CmpOp::Lt, true),
+            I32LtU(a, b) | I64LtU(a, b) => (a, b, CmpOp::Lt, false),
+            I32GtS(a, b) | I64GtS(a, b) => (a, b, CmpOp::Gt, true),
+            I32GtU(a, b) | I64GtU(a, b) => (a, b, CmpOp::Gt, false),
+            I32LeS(a, b) | I64LeS(a, b) => (a, b, CmpOp::Leq, true),
+            I32LeU(a, b) | I64LeU(a, b) => (a, b, CmpOp::Leq, false),
+            I32GeS(a, b) | I64GeS(a, b) => (a, b, CmpOp::Geq, true),
+            I32GeU(a, b) | I64GeU(a, b) => (a, b, CmpOp::Geq, false),
+            I32Eqz(a) => return self.bv(a, 32)._eq(&self.constant(0, 32)),
+            I64Eqz(a) => return self.bv(a, 64)._eq(&self.constant(0, 64)),
+            _ => {
+                let value = self.bv(expr, 32);
+                let zero = self.constant(0, value.get_size());
+                return value._eq(&zero).not();
+            }
+        };
+        let is_i64 = matches!(
+            expr,
+            I64Eq(..) | I64Ne(..) | I64LtS(..) | I64LtU(..) | I64GtS(..) | I64GtU(..) | I64LeS(..) | I64LeU(..)
+                | I64GeS(..) | I64GeU(..)
+        );
+        let width = if is_i64 { 64 } else { 32 };
+        let a = self.bv(a, width);
+        let b = self.bv(b, width);
+        compare(&a, cmp, &b, signed)
+    }
+
+    fn mapped(&mut self, expr: &MappedExpr, width: u32) -> BV<'ctx> {
+        match expr {
+            MappedExpr::Expr(expr) => self.bv(expr, width),
+            MappedExpr::Const(value) => self.constant(u64::from(*value), width),
+            MappedExpr::Mapped(index) => BV::new_const(self.ctx, format!("mapped_{}", index), width),
+        }
+    }
+
+    /// Translate a condition, comparisons of `Cond::Cmp` are unsigned like in `Cond::simplify`
+    pub fn cond(&mut self, cond: &Cond) -> Bool<'ctx> {
+        match cond {
+            Cond::True => Bool::from_bool(self.ctx, true),
+            Cond::False => Bool::from_bool(self.ctx, false),
+            Cond::Not(cond) => self.cond(cond).not(),
+            Cond::And(a, b) => self.cond(a).and(&[&self.cond(b)]),
+            Cond::Or(a, b) => self.cond(a).or(&[&self.cond(b)]),
+            Cond::Cmp(a, cmp, b) => {
+                let width_of_mapped = |expr: &MappedExpr| match expr {
+                    MappedExpr::Expr(expr) => width_of(expr),
+                    _ => None,
+                };
+                let width = width_of_mapped(a).or_else(|| width_of_mapped(b)).unwrap_or(32);
+                let a = self.mapped(a, width);
+                let b = self.mapped(b, width);
+                compare(&a, *cmp, &b, false)
+            }
+            Cond::Expr(MappedExpr::Expr(expr)) => self.bool(expr),
+            Cond::Expr(MappedExpr::Const(value)) => Bool::from_bool(self.ctx, *value != 0),
+            Cond::Expr(MappedExpr::Mapped(index)) => Bool::new_const(self.ctx, format!("mapped_{}", index)),
+        }
+    }
+}
+
+fn compare<'ctx>(a: &BV<'ctx>, cmp: CmpOp, b: &BV<'ctx>, signed: bool) -> Bool<'ctx> {
+    match (cmp, signed) {
+        (CmpOp::Eq, _) => a._eq(b),
+        (CmpOp::Neq, _) => a._eq(b).not(),
+        (CmpOp::Lt, false) => a.bvult(b),
+        (CmpOp::Lt, true) => a.bvslt(b),
+        (CmpOp::Gt, false) => a.bvugt(b),
+        (CmpOp::Gt, true) => a.bvsgt(b),
+        (CmpOp::Leq, false) => a.bvule(b),
+        (CmpOp::Leq, true) => a.bvsle(b),
+        (CmpOp::Geq, false) => a.bvuge(b),
+        (CmpOp::Geq, true) => a.bvsge(b),
+    }
+}
@@ -1,15 +1,26 @@
+use crate::soroban::pattern::Replacement;
 use crate::soroban::FunctionInfo;
 use crate::fmt;
+use serde::{Deserialize, Serialize};
 
 use super::{cond::MappedExpr, Cond, Expr, ValueSpace, Var};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum LoopKind {
     While,
     DoWhile,
 }
 
-#[derive(Debug, Clone)]
+/// Location of the wasm instruction a statement was produced from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct SourcePos {
+    /// Function relative instruction index
+    pub instr: u32,
+    /// Byte offset relative to the start of the code section
+    pub offset: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Stmt {
     Unreachable,
     Expr(Expr),
@@ -20,6 +31,8 @@ pub enum Stmt {
     Break,
     Seq(Vec<Stmt>),
     Nop,
+    /// Marks the origin of the following statements
+    Offset(SourcePos),
 
     Branch(Expr),
     Phi(Var, Vec<u32>),
@@ -41,6 +54,9 @@ pub enum Stmt {
     I64Store8(Expr, Expr),
     I64Store16(Expr, Expr),
     I64Store32(Expr, Expr),
+
+    /// Statements matched by an SDK pattern, printed with its template
+    Replaced(Box<Replacement>),
 }
 
 impl Stmt {
@@ -66,6 +82,7 @@ impl Stmt {
             ReturnVoid => 1,
             Branch(expr) => 1 + expr.complexity(),
             Phi(..) => 0,
+            Offset(..) => 0,
             SetLocal(_, expr) | SetGlobal(_, expr) => 1 + expr.complexity(),
             I32Store(location, value)
             | I64Store(location, value)
@@ -77,6 +94,7 @@ impl Stmt {
             | I64Store16(location, value)
             | I64Store32(location, value) => 1 + location.complexity() + value.complexity(),
             Unreachable => 1,
+            Replaced(..) => 1,
             While(..) => unreachable!(),
             ForLoop(..) => unreachable!(),
             Break => unreachable!(),
@@ -238,6 +256,7 @@ impl fmt::CodeDisplay for Stmt {
     fn fmt_code(&self, f: &mut fmt::CodeWriter) {
         match self {
             Stmt::Nop => return,
+            Stmt::Offset(_) if !f.options().offsets => return,
             Stmt::Seq(code) => {
                 f.write(&code[..]);
                 return;
@@ -297,6 +316,7 @@ impl fmt::CodeDisplay for Stmt {
             Stmt::Break => {
                 f.write("break;");
             }
+            Stmt::Offset(pos) => write!(f, "// @{:#x}", pos.offset),
             Stmt::Seq(..) => unreachable!(),
             Stmt::Nop => unreachable!(),
             Stmt::Branch(cond) => f.write(cond),
@@ -401,6 +421,7 @@ impl fmt::CodeDisplay for Stmt {
             Stmt::I64Store8(target, expr) => write_store(f, "store8_i64", target, expr),
             Stmt::I64Store16(target, expr) => write_store(f, "store16_i64", target, expr),
             Stmt::I64Store32(target, expr) => write_store(f, "store32_i32", target, expr),
+            Stmt::Replaced(replacement) => f.write(replacement),
         }
     }
 }
@@ -1,6 +1,8 @@
+use serde::{Deserialize, Serialize};
+
 use super::cond::CmpOp;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ValueSpace(pub Vec<(u32, u32)>);
 
 impl ValueSpace {
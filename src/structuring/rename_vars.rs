@@ -112,10 +112,12 @@ impl<'a> Renamer<'a> {
             }
             Seq(body) => self.rename(body),
             Nop => (),
+            Offset(_) => (),
             Break => (),
             ReturnVoid => (),
             Unreachable => (),
             Phi(..) => unreachable!(),
+            Replaced(..) => unreachable!(),
         }
     }
 
@@ -0,0 +1,487 @@
+//! Symbolic execution of a function over its SSA form.
+//!
+//! Every path through the CFG is explored depth first, loops are unrolled a bounded number
+//! of times. Locals and globals are Z3 bit-vectors, linear memory is an array from 32 bit
+//! addresses to bytes. Calls are uninterpreted functions of their arguments and of the host
+//! state, which changes with every call; the `i64` results of host functions are constrained
+//! to carry a valid `Val` tag unless env.json types them as plain integers, like the `u64` of
+//! `obj_to_u64`. Paths whose branch conditions contradict each other are cut off.
+
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use z3::ast::{Array, Ast, Bool, Dynamic, BV};
+use z3::{Context, FuncDecl, SatResult, Solver, Sort};
+
+use crate::analysis::used_vars;
+use crate::cfg::{Cfg, CfgBuildError, EdgeType, NodeId};
+use crate::run::host::{is_raw_type, HostFn};
+use crate::soroban::{env_common_modules_for, take_common_module};
+use crate::ssa::smt::{self, Translator};
+use crate::ssa::{self, Expr, Stmt};
+use crate::wasm_wrapper::wasm;
+use crate::wasm_wrapper::wasm_adapter::{External, InitExpr, Module, ValueType};
+
+/// Data segments beyond this size are left out of the initial memory to keep queries small
+const MAX_DATA_BYTES: usize = 16 * 1024;
+
+/// Tags of `Val`s that fit into the 64 bits, from `False` to `SymbolSmall`
+const SMALL_TAGS: (u64, u64) = (0, 14);
+/// Tags of `Val`s that refer to host objects, from `U64Object` to `AddressObject`
+const OBJECT_TAGS: (u64, u64) = (64, 77);
+
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    /// How often a path may take the same back edge
+    pub max_unroll: u32,
+    /// Exploration stops after this many paths
+    pub max_paths: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_unroll: 2,
+            max_paths: 1024,
+        }
+    }
+}
+
+/// Arguments that make a function reach an `unreachable` instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnreachableInput {
+    pub node: NodeId,
+    /// Byte offset of the instruction relative to the start of the code section
+    pub offset: Option<u32>,
+    /// Value of each parameter, `i32` parameters are zero extended
+    pub args: Vec<u64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Exploration {
+    /// One input per reachable `unreachable` statement, in the order they were found
+    pub unreachable: Vec<UnreachableInput>,
+    pub paths: usize,
+    /// A limit was hit, so some paths were not explored
+    pub truncated: bool,
+}
+
+/// The symbolic values of one path
+#[derive(Clone)]
+struct State<'ctx> {
+    /// Version and width of the Z3 constant of each SSA variable, keyed by index and subscript.
+    /// Variables in loops are defined again in every iteration and get a new version each time.
+    vars: HashMap<(u32, u32), (u32, u32)>,
+    /// Subscript of the latest definition of each local, selects the incoming value of a phi
+    last_def: HashMap<u32, u32>,
+    globals: HashMap<u32, BV<'ctx>>,
+    memory: Array<'ctx>,
+    /// Version of the host state seen by calls
+    host: u32,
+    back_edges: HashMap<(NodeId, NodeId), u32>,
+}
+
+struct Executor<'ctx, 'm> {
+    ctx: &'ctx Context,
+    module: &'m Module,
+    func_index: u32,
+    cfg: &'m Cfg,
+    solver: Solver<'ctx>,
+    translator: Translator<'ctx, 'm>,
+    limits: Limits,
+    /// Imported functions whose result is a plain integer rather than a `Val`
+    raw_results: HashSet<u32>,
+    next_version: u32,
+    next_memory: u32,
+    found: HashSet<(NodeId, usize)>,
+    result: Exploration,
+}
+
+const fn value_width(value_type: ValueType) -> u32 {
+    match value_type {
+        ValueType::I32 | ValueType::F32 => 32,
+        _ => 64,
+    }
+}
+
+/// The memory access width in bits and signedness of a load
+fn load_kind(expr: &Expr) -> Option<(&Expr, u32, bool)> {
+    use Expr::*;
+    Some(match expr {
+        I32Load(addr) | I64Load32U(addr) => (addr, 32, false),
+        I64Load(addr) => (addr, 64, false),
+        I32Load8S(addr) | I64Load8S(addr) => (addr, 8, true),
+        I32Load8U(addr) | I64Load8U(addr) => (addr, 8, false),
+        I32Load16S(addr) | I64Load16S(addr) => (addr, 16, true),
+        I32Load16U(addr) | I64Load16U(addr) => (addr, 16, false),
+        I64Load32S(addr) => (addr, 32, true),
+        _ => return None,
+    })
+}
+
+/// The address, value and access width in bits of an integer store
+fn store_kind(stmt: &Stmt) -> Option<(&Expr, &Expr, u32)> {
+    use Stmt::*;
+    Some(match stmt {
+        I32Store(addr, value) | I64Store32(addr, value) => (addr, value, 32),
+        I64Store(addr, value) => (addr, value, 64),
+        I32Store8(addr, value) | I64Store8(addr, value) => (addr, value, 8),
+        I32Store16(addr, value) | I64Store16(addr, value) => (addr, value, 16),
+        _ => return None,
+    })
+}
+
+impl<'ctx, 'm> Executor<'ctx, 'm> {
+    fn width(&self, state: &State<'ctx>, expr: &Expr) -> u32 {
+        let params = self.module.func(self.func_index).params();
+        smt::width_of(expr).unwrap_or_else(|| match expr {
+            Expr::GetLocal(var) => match state.vars.get(&(var.index, var.subscript)) {
+                Some((_, width)) => *width,
+                None => params.get(var.index as usize).map_or(64, |t| value_width(*t)),
+            },
+            Expr::GetGlobal(index) => self
+                .module
+                .globals()
+                .get(*index as usize)
+                .map_or(64, |g| value_width(g.value_type())),
+            Expr::Call(index, _) => self
+                .module
+                .get_func(*index)
+                .and_then(|func| func.return_type())
+                .map_or(64, value_width),
+            _ => 64,
+        })
+    }
+
+    fn address(&self, addr: &BV<'ctx>, offset: u32) -> BV<'ctx> {
+        addr.bvadd(&BV::from_u64(self.ctx, u64::from(offset), 32))
+    }
+
+    fn fresh_memory(&mut self) -> Array<'ctx> {
+        self.next_memory += 1;
+        let addr = Sort::bitvector(self.ctx, 32);
+        let byte = Sort::bitvector(self.ctx, 8);
+        Array::new_const(self.ctx, format!("memory@{}", self.next_memory), &addr, &byte)
+    }
+
+    /// Little endian load of `bits` from memory
+    fn load(&self, state: &State<'ctx>, addr: &BV<'ctx>, bits: u32) -> BV<'ctx> {
+        let byte = |i: u32| state.memory.select(&self.address(addr, i)).as_bv().unwrap();
+        (1..bits / 8).fold(byte(0), |value, i| byte(i).concat(&value))
+    }
+
+    fn store(&self, state: &mut State<'ctx>, addr: &BV<'ctx>, value: &BV<'ctx>, bits: u32) {
+        for i in 0..bits / 8 {
+            let byte = value.extract(i * 8 + 7, i * 8);
+            state.memory = state.memory.store(&self.address(addr, i), &byte);
+        }
+    }
+
+    /// A `Val` has its tag in the low 8 bits
+    fn valid_tag(&self, value: &BV<'ctx>) -> Bool<'ctx> {
+        let tag = value.extract(7, 0);
+        let in_range = |(lo, hi): (u64, u64)| {
+            tag.bvuge(&BV::from_u64(self.ctx, lo, 8))
+                .and(&[&tag.bvule(&BV::from_u64(self.ctx, hi, 8))])
+        };
+        in_range(SMALL_TAGS).or(&[&in_range(OBJECT_TAGS)])
+    }
+
+    fn call(&mut self, state: &mut State<'ctx>, func_index: u32, args: &[Expr], width: u32) -> BV<'ctx> {
+        let func = self.module.func(func_index);
+        let params: Vec<u32> = func.params().iter().map(|t| value_width(*t)).collect();
+        let args: Vec<Dynamic<'ctx>> = args
+            .iter()
+            .zip(&params)
+            .map(|(arg, width)| Dynamic::from(self.value(state, arg, *width)))
+            .collect();
+        let domain: Vec<Sort<'ctx>> = params.iter().map(|width| Sort::bitvector(self.ctx, *width)).collect();
+        let decl = FuncDecl::new(
+            self.ctx,
+            format!("{}@{}", func.name(), state.host),
+            &domain.iter().collect::<Vec<_>>(),
+            &Sort::bitvector(self.ctx, width),
+        );
+        let result = decl.apply(&args.iter().collect::<Vec<_>>()).as_bv().unwrap();
+        state.host += 1;
+
+        if func.is_imported() {
+            if width == 64 && !self.raw_results.contains(&func_index) {
+                self.solver.assert(&self.valid_tag(&result));
+            }
+            // e.g. `bytes_copy_to_linear_memory`, other host functions leave memory alone
+            if func.name().ends_with("to_linear_memory") {
+                state.memory = self.fresh_memory();
+            }
+        } else {
+            state.memory = self.fresh_memory();
+        }
+        result
+    }
+
+    /// The value of an expression. Loads, globals and calls only occur at the top level
+    /// of an expression before propagation, nested ones are left to the translator.
+    fn value(&mut self, state: &mut State<'ctx>, expr: &Expr, width: u32) -> BV<'ctx> {
+        if let Some((addr, bits, signed)) = load_kind(expr) {
+            let addr = self.value(state, addr, 32);
+            let value = self.load(state, &addr, bits);
+            let width = smt::width_of(expr).unwrap();
+            return match (width - bits, signed) {
+                (0, _) => value,
+                (ext, true) => value.sign_ext(ext),
+                (ext, false) => value.zero_ext(ext),
+            };
+        }
+        match expr {
+            Expr::GetGlobal(index) => match state.globals.get(index) {
+                Some(value) => value.clone(),
+                None => BV::new_const(self.ctx, format!("global_{}", index), width),
+            },
+            Expr::Call(func_index, args) if self.module.get_func(*func_index).is_some() => {
+                self.call(state, *func_index, args, width)
+            }
+            _ => {
+                self.translator.versions = used_vars::find(expr)
+                    .into_iter()
+                    .map(|var| {
+                        (
+                            var.index,
+                            state.vars.get(&(var.index, var.subscript)).map_or(0, |v| v.0),
+                        )
+                    })
+                    .collect();
+                self.translator.bv(expr, width)
+            }
+        }
+    }
+
+    fn assign(&mut self, state: &mut State<'ctx>, index: u32, subscript: u32, value: &BV<'ctx>) {
+        self.next_version += 1;
+        let width = value.get_size();
+        state.vars.insert((index, subscript), (self.next_version, width));
+        state.last_def.insert(index, subscript);
+        self.translator.versions.insert(index, self.next_version);
+        self.solver.assert(&self.translator.local(index, width)._eq(value));
+    }
+
+    fn is_sat(&self) -> bool {
+        self.solver.check() != SatResult::Unsat
+    }
+
+    fn args(&self) -> Vec<u64> {
+        let model = match self.solver.get_model() {
+            Some(model) => model,
+            None => return Vec::new(),
+        };
+        let translator = Translator::new(self.ctx, self.module);
+        self.module
+            .func(self.func_index)
+            .params()
+            .iter()
+            .enumerate()
+            .map(|(i, t)| {
+                let param = translator.local(i as u32, value_width(*t));
+                model.eval(&param, true).and_then(|v| v.as_u64()).unwrap_or(0)
+            })
+            .collect()
+    }
+
+    fn explore(&mut self, node: NodeId, mut state: State<'ctx>) {
+        if self.result.paths >= self.limits.max_paths {
+            self.result.truncated = true;
+            return;
+        }
+        self.solver.push();
+        let mut branch = None;
+        let mut offset = None;
+        let cfg = self.cfg;
+        for (i, stmt) in cfg.nodes[node].code.iter().enumerate() {
+            match stmt {
+                Stmt::Offset(pos) => offset = Some(pos.offset),
+                Stmt::SetLocal(var, expr) => {
+                    let width = self.width(&state, expr);
+                    let value = self.value(&mut state, expr, width);
+                    self.assign(&mut state, var.index, var.subscript, &value);
+                }
+                Stmt::Phi(var, args) => {
+                    // the incoming value is the one defined last on this path
+                    let incoming = state
+                        .last_def
+                        .get(&var.index)
+                        .filter(|subscript| args.contains(subscript))
+                        .and_then(|subscript| state.vars.get(&(var.index, *subscript)))
+                        .copied();
+                    if let Some(local) = incoming {
+                        state.vars.insert((var.index, var.subscript), local);
+                    }
+                    state.last_def.insert(var.index, var.subscript);
+                }
+                Stmt::SetGlobal(index, expr) => {
+                    let width = self.width(&state, expr);
+                    let value = self.value(&mut state, expr, width);
+                    state.globals.insert(*index, value);
+                }
+                Stmt::Expr(expr) => {
+                    let width = self.width(&state, expr);
+                    self.value(&mut state, expr, width);
+                }
+                Stmt::Branch(expr) => branch = Some(self.value(&mut state, expr, 32)),
+                Stmt::Unreachable => {
+                    self.result.paths += 1;
+                    if !self.found.contains(&(node, i)) && self.solver.check() == SatResult::Sat {
+                        self.found.insert((node, i));
+                        let args = self.args();
+                        self.result.unreachable.push(UnreachableInput { node, offset, args });
+                    }
+                    self.solver.pop(1);
+                    return;
+                }
+                Stmt::Return(_) | Stmt::ReturnVoid => {
+                    self.result.paths += 1;
+                    self.solver.pop(1);
+                    return;
+                }
+                _ => {
+                    if let Some((addr, value, bits)) = store_kind(stmt) {
+                        let addr = self.value(&mut state, addr, 32);
+                        let width = self.width(&state, value);
+                        let value = self.value(&mut state, value, width);
+                        self.store(&mut state, &addr, &value, bits);
+                    }
+                }
+            }
+        }
+
+        let edges = &cfg.nodes[node].next;
+        if edges.is_empty() {
+            self.result.paths += 1;
+        }
+        for edge in edges {
+            let mut next = state.clone();
+            if edge.back_edge {
+                let count = next.back_edges.entry((node, edge.node)).or_default();
+                if *count >= self.limits.max_unroll {
+                    self.result.truncated = true;
+                    continue;
+                }
+                *count += 1;
+            }
+            let cond = match (&branch, edge.cond.edge_type) {
+                (Some(value), edge_type) => {
+                    let constant = |v: u32| BV::from_u64(self.ctx, u64::from(v), value.get_size());
+                    match edge_type {
+                        EdgeType::Unconditional => None,
+                        EdgeType::Conditional(true) => Some(value._eq(&constant(0)).not()),
+                        EdgeType::Conditional(false) => Some(value._eq(&constant(0))),
+                        EdgeType::CaseRange(start, end) => {
+                            Some(value.bvuge(&constant(start)).and(&[&value.bvult(&constant(end))]))
+                        }
+                        EdgeType::Default(min) => Some(value.bvuge(&constant(min))),
+                    }
+                }
+                (None, _) => None,
+            };
+            self.solver.push();
+            if let Some(cond) = &cond {
+                self.solver.assert(cond);
+            }
+            if cond.is_none() || self.is_sat() {
+                self.explore(edge.node, next);
+            }
+            self.solver.pop(1);
+        }
+        self.solver.pop(1);
+    }
+}
+
+/// The imported functions whose env.json result type is a plain integer, like `obj_cmp`
+/// or `obj_to_i128_lo64`
+pub fn raw_results(module: &Module) -> HashSet<u32> {
+    let env_modules = env_common_modules_for(module.build_info().sdk_version.as_deref()).unwrap_or_default();
+    module
+        .imports()
+        .iter()
+        .filter(|entry| matches!(entry.external(), External::Function(_)))
+        .enumerate()
+        .filter(|(_, entry)| {
+            take_common_module(&env_modules, entry.module(), entry.field())
+                .map_or(false, |function| is_raw_type(&HostFn::new(&function).result))
+        })
+        .map(|(i, _)| i as u32)
+        .collect()
+}
+
+/// Find inputs for which the function reaches an `unreachable` instruction,
+/// e.g. a failed assertion or a `panic!`
+pub fn find_unreachable(
+    wasm: Rc<wasm::Instance>,
+    func_index: u32,
+    limits: Limits,
+) -> Result<Exploration, CfgBuildError> {
+    let mut cfg = Cfg::build_with_offsets(wasm.clone(), func_index)?;
+    ssa::transform_to_ssa(&mut cfg);
+    let module = wasm.module();
+
+    let ctx = Context::new(&z3::Config::new());
+    let mut executor = Executor {
+        ctx: &ctx,
+        module,
+        func_index,
+        cfg: &cfg,
+        solver: Solver::new(&ctx),
+        translator: Translator::new(&ctx, module),
+        limits,
+        raw_results: raw_results(module),
+        next_version: 0,
+        next_memory: 0,
+        found: HashSet::new(),
+        result: Exploration::default(),
+    };
+
+    let mut memory = executor.fresh_memory();
+    let mut data_bytes = 0;
+    for init in module.memory_inits() {
+        data_bytes += init.data().len();
+        if let (InitExpr::I32Const(offset), true) = (init.offset(), data_bytes <= MAX_DATA_BYTES) {
+            for (i, byte) in init.data().iter().enumerate() {
+                let addr = BV::from_u64(&ctx, u64::from(*offset as u32) + i as u64, 32);
+                memory = memory.store(&addr, &BV::from_u64(&ctx, u64::from(*byte), 8));
+            }
+        }
+    }
+    let globals = module
+        .globals()
+        .iter()
+        .enumerate()
+        .filter_map(|(i, global)| {
+            let value = match global.init_expr() {
+                InitExpr::I32Const(value) => BV::from_u64(&ctx, u64::from(*value as u32), 32),
+                InitExpr::I64Const(value) => BV::from_u64(&ctx, *value as u64, 64),
+                _ => return None,
+            };
+            Some((i as u32, value))
+        })
+        .collect();
+
+    // the parameters of contract functions are `Val`s
+    let func = module.func(func_index);
+    if func.has_spec() {
+        for (i, param) in func.params().iter().enumerate() {
+            if *param == ValueType::I64 {
+                let tag = executor.valid_tag(&executor.translator.local(i as u32, 64));
+                executor.solver.assert(&tag);
+            }
+        }
+    }
+
+    let state = State {
+        vars: HashMap::new(),
+        last_def: HashMap::new(),
+        globals,
+        memory,
+        host: 0,
+        back_edges: HashMap::new(),
+    };
+    executor.explore(0, state);
+    Ok(executor.result)
+}
@@ -1,7 +1,7 @@
-use crate::ssa::Expr;
+use crate::ssa::{Expr, Stmt};
 use super::wasm_adapter::{InitExpr, LoadError, Module};
 use crate::cfg::CfgBuildError;
-use crate::fmt::CodeWriter;
+use crate::fmt::{CodeWriter, WriterOptions};
 use crate::soroban::FunctionInfo;
 use std::rc::Rc;
 
@@ -16,6 +16,16 @@ pub struct Table {
     pub elements: Vec<TableElement>,
 }
 
+/// Decompiled code of a function together with what was found while producing it
+#[derive(Clone, Debug, Default)]
+pub struct DecompiledFunction {
+    pub code: String,
+    pub matched_patterns: Vec<String>,
+    pub warnings: Vec<String>,
+    /// Basic blocks of the CFG of the function
+    pub blocks: usize,
+}
+
 #[derive(PartialEq, Clone, Debug)]
 pub struct Instance {
     module: Module,
@@ -50,13 +60,50 @@ impl Instance {
         &self.module().spec_fns()
     }
 
-    pub fn decompile_function(&self, func_index: u32) -> Result<(), CfgBuildError> {
-        let mut printer = CodeWriter::printer(Rc::new(self.clone()), func_index);
+    pub fn decompile_function(&self, func_index: u32, options: WriterOptions) -> Result<(), CfgBuildError> {
+        let mut printer = CodeWriter::printer(Rc::new(self.clone()), func_index).with_options(options);
         let empty_args: &[Expr] = &[];
-        let code = printer.decompile_func(func_index as u32, false, empty_args).unwrap();
+        let code = printer.decompile_func(func_index as u32, false, empty_args)?;
         printer.write_func(&code, false);
+        for warning in printer.warnings() {
+            eprintln!("{}", warning);
+        }
         Ok(())
     }
+
+    pub fn decompile_function_to_string(&self, func_index: u32, options: WriterOptions) -> Result<String, CfgBuildError> {
+        let decompiled = self.decompile_function_output(func_index, options)?;
+        for warning in &decompiled.warnings {
+            eprintln!("{}", warning);
+        }
+        Ok(decompiled.code)
+    }
+
+    /// Decompile a function into its structured IR without printing it
+    pub fn decompile_function_ir(&self, func_index: u32, options: WriterOptions) -> Result<Vec<Stmt>, CfgBuildError> {
+        let mut formatter = CodeWriter::formatter(Rc::new(self.clone()), func_index).with_options(options);
+        let empty_args: &[Expr] = &[];
+        formatter.decompile_func(func_index, false, empty_args)
+    }
+
+    pub fn decompile_function_output(
+        &self,
+        func_index: u32,
+        options: WriterOptions,
+    ) -> Result<DecompiledFunction, CfgBuildError> {
+        let mut formatter = CodeWriter::formatter(Rc::new(self.clone()), func_index).with_options(options);
+        let empty_args: &[Expr] = &[];
+        let code = formatter.decompile_func(func_index as u32, false, empty_args)?;
+        formatter.write_func(&code, false);
+        let matched_patterns = formatter.matched_patterns().to_vec();
+        let warnings = formatter.warnings().to_vec();
+        Ok(DecompiledFunction {
+            blocks: formatter.blocks(),
+            code: formatter.get_output(),
+            matched_patterns,
+            warnings,
+        })
+    }
 }
 
 fn init_tables(module: &Module) -> Vec<Table> {
@@ -1,10 +1,14 @@
 use crate::soroban;
 use crate::soroban::take_common_module;
-use crate::soroban::env_common_modules_result;
+use crate::soroban::env_common_modules_for;
+use crate::soroban::BuildInfo;
 use crate::soroban::FunctionInfo;
+use crate::soroban::SpecType;
+use crate::soroban::pattern::Pattern;
 use core::convert::{TryFrom, TryInto};
 use core::fmt;
 use core::iter::{self, FromIterator};
+use std::rc::Rc;
 
 use parity_wasm::elements as pwasm;
 use serde_json::Value;
@@ -127,6 +131,7 @@ pub struct Function {
     is_imported: bool,
     locals: Vec<ExtendedValueType>,
     instructions: Vec<Instruction>,
+    instr_offsets: Vec<u32>,
     spec_fn: FunctionInfo 
 }
 
@@ -136,6 +141,7 @@ impl Function {
         func_type: FunctionType,
         locals: Vec<ExtendedValueType>,
         instructions: Vec<Instruction>,
+        instr_offsets: Vec<u32>,
         spec_fn: FunctionInfo
     ) -> Self {
         Function {
@@ -144,6 +150,7 @@ impl Function {
             is_imported: false,
             locals,
             instructions,
+            instr_offsets,
             spec_fn,
         }
     }
@@ -155,6 +162,7 @@ impl Function {
             is_imported: true,
             locals: Vec::new(),
             instructions: Vec::new(),
+            instr_offsets: Vec::new(),
             spec_fn 
         }
     }
@@ -186,9 +194,20 @@ impl Function {
     pub fn instructions(&self) -> &[Instruction] {
         &self.instructions
     }
+    /// Byte offsets of the instructions relative to the start of the code section
+    pub fn instr_offsets(&self) -> &[u32] {
+        &self.instr_offsets
+    }
+    pub fn instr_offset(&self, instr: usize) -> Option<u32> {
+        self.instr_offsets.get(instr).copied()
+    }
     pub fn spec_fn(&self) -> Option<&FunctionInfo> {
         Some(&self.spec_fn)
     }
+    /// Whether the contract spec describes this function
+    pub fn has_spec(&self) -> bool {
+        self.spec_fn != FunctionInfo::default()
+    }
 }
 
 impl fmt::Display for Function {
@@ -359,19 +378,38 @@ pub struct Module {
     exports: Vec<ExportEntry>,
     start_func: Option<u32>,
     custom_sections: Vec<CustomSection>,
-    spec_fns: Vec<FunctionInfo>
+    spec_fns: Vec<FunctionInfo>,
+    spec_types: Vec<SpecType>,
+    build_info: BuildInfo,
+    /// The SDK patterns of the contract's SDK version, or why they could not be loaded
+    patterns: Rc<Result<Vec<Pattern>, String>>,
 }
 
 impl Module {
     pub fn from_file<P: AsRef<::std::path::Path>>(path: P) -> Result<Self, LoadError> {
-        let module = parity_wasm::deserialize_file(&path)?;
-        let common_modules = env_common_modules_result().unwrap();
-        let spec_fns_result = soroban::read_contract_specs(&path).map_err(|_err| LoadError::ValidationError(wasmi_validation::Error("Hello".to_string())))?;
+        let bytes = std::fs::read(&path).map_err(|err| SerializationError::HeapOther(err.to_string()))?;
+        let instr_offsets = read_instr_offsets(&bytes);
+        let module = parity_wasm::deserialize_buffer(&bytes)?;
+        let build_info = BuildInfo::from_module(&module);
+        let common_modules = env_common_modules_for(build_info.sdk_version.as_deref()).unwrap();
+        let spec_fns_result = soroban::read_contract_specs(&path).map_err(|err| LoadError::ValidationError(wasmi_validation::Error(err.to_string())))?;
+        let spec_types = soroban::read_contract_types(&path).unwrap_or_default();
         wasmi_validation::validate_module::<wasmi_validation::PlainValidator>(&module)?;
-        Ok(Module::from_parity_module(module, common_modules, spec_fns_result))
-    }
-
-    fn from_parity_module(module: pwasm::Module, common_modules: Vec<Value>, spec_fns_result: Vec<FunctionInfo>) -> Self {
+        let patterns = soroban::load_patterns(build_info.sdk_version.as_deref()).map_err(|err| err.to_string());
+        let mut module =
+            Module::from_parity_module(module, common_modules, spec_fns_result, spec_types, build_info, instr_offsets);
+        module.patterns = Rc::new(patterns);
+        Ok(module)
+    }
+
+    fn from_parity_module(
+        module: pwasm::Module,
+        common_modules: Vec<Value>,
+        spec_fns_result: Vec<FunctionInfo>,
+        spec_types: Vec<SpecType>,
+        build_info: BuildInfo,
+        instr_offsets: Vec<Vec<u32>>,
+    ) -> Self {
         // TODO: What happens when multiple functions have the same name?
         let mut module = match module.parse_names() {
             Ok(module) => module,
@@ -418,7 +456,7 @@ impl Module {
         }
 
         handle_global_section(&mut globals, &module);
-        handle_function_section(&mut functions, &module, &types);
+        handle_function_section(&mut functions, &module, &types, instr_offsets);
         handle_table_section(&mut tables, &mut module);
         handle_memory_section(&mut memories, &mut module);
 
@@ -465,7 +503,10 @@ impl Module {
             exports,
             start_func: module.start_section(),
             custom_sections: Vec::from_iter(module.custom_sections().cloned()),
-            spec_fns: spec_fns_result
+            spec_fns: spec_fns_result,
+            spec_types,
+            build_info,
+            patterns: Rc::new(Ok(Vec::new())),
         }
     }
 
@@ -502,6 +543,11 @@ impl Module {
     pub fn exports(&self) -> &[ExportEntry] {
         &self.exports
     }
+    pub fn is_exported(&self, func_index: u32) -> bool {
+        self.exports
+            .iter()
+            .any(|export| matches!(export.internal(), Internal::Function(index) if *index == func_index))
+    }
     pub const fn start_func(&self) -> Option<u32> {
         self.start_func
     }
@@ -511,6 +557,28 @@ impl Module {
     pub fn spec_fns(&self) -> &Vec<FunctionInfo> {
         &self.spec_fns
     }
+    pub fn spec_types(&self) -> &[SpecType] {
+        &self.spec_types
+    }
+    /// The soroban-sdk and rustc versions the contract was built with
+    pub fn build_info(&self) -> &BuildInfo {
+        &self.build_info
+    }
+    /// The SDK patterns of the contract's SDK version, loaded once with the module
+    pub fn patterns(&self) -> Result<&[Pattern], &str> {
+        match &*self.patterns {
+            Ok(patterns) => Ok(patterns),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Find the function and instruction index of a code section byte offset
+    pub fn find_instr(&self, offset: u32) -> Option<(u32, u32)> {
+        self.functions.iter().enumerate().find_map(|(i, func)| {
+            let instr = func.instr_offsets.binary_search(&offset).ok()?;
+            Some((i as u32, instr as u32))
+        })
+    }
 }
 
 fn get_types(module: &mut pwasm::Module) -> Vec<FunctionType> {
@@ -541,10 +609,11 @@ fn handle_function_section(
     functions: &mut Vec<Function>,
     module: &pwasm::Module,
     types: &[FunctionType],
+    mut instr_offsets: Vec<Vec<u32>>,
 ) {
     if let Some(func_sec) = module.function_section() {
         let func_bodies = module.code_section().map(|sec| sec.bodies()).unwrap_or(&[]);
-        for (type_ref, body) in func_sec.entries().iter().zip(func_bodies.iter()) {
+        for (i, (type_ref, body)) in func_sec.entries().iter().zip(func_bodies.iter()).enumerate() {
             let type_ref = type_ref.type_ref();
             let name = format!("func_{}", functions.len());
             let func_type = types[type_ref as usize].clone();
@@ -558,11 +627,133 @@ fn handle_function_section(
                 .collect();
 
             let instructions = body.code().elements().to_vec();
-            functions.push(Function::new(name, func_type, locals, instructions, FunctionInfo::default()));
+            let mut offsets = instr_offsets.get_mut(i).map(take).unwrap_or_default();
+            if offsets.len() != instructions.len() {
+                offsets.clear();
+            }
+            functions.push(Function::new(name, func_type, locals, instructions, offsets, FunctionInfo::default()));
         }
     }
 }
 
+/// Read the offsets of all instructions of the code section, relative to the start of the section content.
+/// They are taken from the raw module because the encoding of immediates isn't always canonical,
+/// e.g. call targets padded to 5 bytes. Bodies that can't be decoded get no offsets.
+fn read_instr_offsets(bytes: &[u8]) -> Vec<Vec<u32>> {
+    let mut pos = 8;
+    while pos < bytes.len() {
+        let id = bytes[pos];
+        pos += 1;
+        let size = match read_var_u32(bytes, &mut pos) {
+            Some(size) => size as usize,
+            None => break,
+        };
+        let section = match bytes.get(pos..pos + size) {
+            Some(section) => section,
+            None => break,
+        };
+        if id == 10 {
+            return read_code_section(section);
+        }
+        pos += size;
+    }
+    Vec::new()
+}
+
+fn read_code_section(section: &[u8]) -> Vec<Vec<u32>> {
+    let mut pos = 0;
+    let count = read_var_u32(section, &mut pos).unwrap_or(0);
+    let mut bodies = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let size = match read_var_u32(section, &mut pos) {
+            Some(size) => size as usize,
+            None => break,
+        };
+        let end = (pos + size).min(section.len());
+        bodies.push(read_body_offsets(section, pos, end).unwrap_or_default());
+        pos = end;
+    }
+    bodies
+}
+
+fn read_body_offsets(section: &[u8], mut pos: usize, end: usize) -> Option<Vec<u32>> {
+    let body = &section[..end];
+    for _ in 0..read_var_u32(body, &mut pos)? {
+        read_var_u32(body, &mut pos)?;
+        pos += 1;
+    }
+    let mut offsets = Vec::new();
+    while pos < end {
+        offsets.push(pos as u32);
+        pos = skip_instr(body, pos)?;
+    }
+    (pos == end).then_some(offsets)
+}
+
+/// The position after the instruction at `pos`
+fn skip_instr(body: &[u8], mut pos: usize) -> Option<usize> {
+    let opcode = *body.get(pos)?;
+    pos += 1;
+    match opcode {
+        // block types and the reserved memory index byte
+        0x02..=0x04 | 0x3f | 0x40 => pos += 1,
+        0x0c | 0x0d | 0x10 | 0x20..=0x24 | 0x41 | 0x42 => skip_var(body, &mut pos)?,
+        0x0e => {
+            for _ in 0..=read_var_u32(body, &mut pos)? {
+                skip_var(body, &mut pos)?;
+            }
+        }
+        0x11 => {
+            skip_var(body, &mut pos)?;
+            pos += 1;
+        }
+        0x28..=0x3e => {
+            skip_var(body, &mut pos)?;
+            skip_var(body, &mut pos)?;
+        }
+        0x43 => pos += 4,
+        0x44 => pos += 8,
+        0xfc => match read_var_u32(body, &mut pos)? {
+            0..=7 => (),
+            8 => {
+                skip_var(body, &mut pos)?;
+                pos += 1;
+            }
+            9 | 13 | 15..=17 => skip_var(body, &mut pos)?,
+            10 | 12 | 14 => {
+                skip_var(body, &mut pos)?;
+                skip_var(body, &mut pos)?;
+            }
+            11 => pos += 1,
+            _ => return None,
+        },
+        _ => (),
+    }
+    Some(pos)
+}
+
+fn read_var_u32(bytes: &[u8], pos: &mut usize) -> Option<u32> {
+    let mut value = 0u32;
+    for shift in (0..35).step_by(7) {
+        let byte = *bytes.get(*pos)?;
+        *pos += 1;
+        value |= ((byte & 0x7f) as u32) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Skip a signed or unsigned LEB128 number
+fn skip_var(bytes: &[u8], pos: &mut usize) -> Option<()> {
+    while *bytes.get(*pos)? & 0x80 != 0 {
+        *pos += 1;
+    }
+    *pos += 1;
+    Some(())
+}
+
 fn handle_table_section(tables: &mut Vec<Table>, module: &mut pwasm::Module) {
     if let Some(table_sec) = module.table_section_mut() {
         tables.extend(table_sec.entries_mut().drain(..).map(|table_type| Table {
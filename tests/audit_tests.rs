@@ -0,0 +1,822 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use auditor::audit::keys::KeyTracker;
+use auditor::audit::{self, baseline, report, AuditContext, AuditRule, Finding, RuleFilter, RuleRegistry, Severity};
+use auditor::ssa::cond::MappedExpr;
+use auditor::ssa::{Cond, Expr, LoopKind, SourcePos, Stmt, Var};
+use auditor::wasm_wrapper::wasm;
+use parity_wasm::elements::{
+    External, FunctionType, ImportCountType, ImportEntry, Instruction, Internal, Module, Type, ValueType,
+};
+
+const TOKEN: &str = "tests/soroban_token_contract.wasm";
+const CONTRACT: &str = "tests/contract.wasm";
+const INSTANCE: u64 = 2;
+const PERSISTENT: u64 = 1;
+
+fn function(wasm: &wasm::Instance, name: &str) -> u32 {
+    let module = wasm.module();
+    (0..module.functions().len() as u32)
+        .find(|i| module.func(*i).name().ends_with(name))
+        .unwrap()
+}
+
+/// The `Val` of a small symbol
+fn symbol(name: &str) -> u64 {
+    let body = name.bytes().fold(0u64, |body, c| {
+        let code = match c {
+            b'_' => 1,
+            b'0'..=b'9' => 2 + c - b'0',
+            b'A'..=b'Z' => 12 + c - b'A',
+            _ => 38 + c - b'a',
+        };
+        body << 6 | code as u64
+    });
+    body << 8 | 14
+}
+
+fn var(index: u32) -> Expr {
+    Expr::GetLocal(Var::new(index, 0))
+}
+
+fn call(wasm: &wasm::Instance, name: &str, args: Vec<Expr>) -> Expr {
+    Expr::Call(function(wasm, name), args)
+}
+
+fn put(wasm: &wasm::Instance, key: &str, value: Expr, storage: u64) -> Stmt {
+    Stmt::Expr(call(
+        wasm,
+        "put_contract_data",
+        vec![Expr::I64Const(symbol(key)), value, Expr::I64Const(storage)],
+    ))
+}
+
+fn require_auth(wasm: &wasm::Instance, address: Expr) -> Stmt {
+    Stmt::Expr(call(wasm, "require_auth", vec![address]))
+}
+
+/// Run one rule on hand-written code of the contract functions
+fn run(wasm: &Rc<wasm::Instance>, rule: &str, code: Vec<(&str, Vec<Stmt>)>) -> Vec<Finding> {
+    let code: HashMap<u32, Vec<Stmt>> = code
+        .into_iter()
+        .map(|(name, code)| (function(&wasm, name), code))
+        .collect();
+    let ctx = AuditContext::with_code(Rc::clone(wasm), code);
+    let filter = RuleFilter {
+        enabled: vec![rule.to_string()],
+        ..RuleFilter::default()
+    };
+    RuleRegistry::with_builtin_rules().run(&ctx, &filter)
+}
+
+fn reported<'a>(findings: &'a [Finding], function: &str) -> Vec<&'a Finding> {
+    findings.iter().filter(|finding| finding.function == function).collect()
+}
+
+struct EveryFunction;
+
+impl AuditRule for EveryFunction {
+    fn id(&self) -> &'static str {
+        "every-function"
+    }
+
+    fn description(&self) -> &'static str {
+        "Reports every spec function that has code"
+    }
+
+    fn check(&self, ctx: &AuditContext) -> Vec<Finding> {
+        ctx.spec_functions()
+            .into_iter()
+            .filter(|func_index| ctx.code(*func_index).is_some())
+            .map(|func_index| ctx.finding(self.id(), Severity::Low, func_index, "found".to_string()))
+            .collect()
+    }
+}
+
+#[test]
+fn test_rule_registry() {
+    let wasm = wasm::Instance::load_file(TOKEN);
+    let code = HashMap::from([(function(&wasm, "mint"), vec![Stmt::ReturnVoid])]);
+    let ctx = AuditContext::with_code(Rc::clone(&wasm), code);
+
+    let mut registry = RuleRegistry::with_builtin_rules();
+    let builtin = registry.rules().len();
+    registry.register(Box::new(EveryFunction));
+    registry.register(Box::new(EveryFunction));
+    assert_eq!(registry.rules().len(), builtin + 1);
+
+    let filter = RuleFilter {
+        enabled: vec!["every-function".to_string()],
+        ..RuleFilter::default()
+    };
+    let findings = registry.run(&ctx, &filter);
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].function, "mint");
+    assert_eq!(findings[0].location.func_index, function(&wasm, "mint"));
+
+    let filter = RuleFilter {
+        min_severity: Severity::Medium,
+        ..filter
+    };
+    assert!(registry.run(&ctx, &filter).is_empty());
+
+    let filter = RuleFilter {
+        disabled: vec!["no-such-rule".to_string()],
+        ..RuleFilter::default()
+    };
+    assert!(registry.check_filter(&filter).is_err());
+}
+
+#[test]
+fn test_missing_auth() {
+    let wasm = wasm::Instance::load_file(TOKEN);
+    let write = || put(&wasm, "Balance", var(1), PERSISTENT);
+
+    let findings = run(&wasm, "missing-auth", vec![("mint", vec![write()])]);
+    assert_eq!(reported(&findings, "mint").len(), 1, "{:?}", findings);
+    assert!(findings[0].message.contains("put_contract_data"));
+
+    let findings = run(
+        &wasm,
+        "missing-auth",
+        vec![("mint", vec![require_auth(&wasm, var(0)), write()])],
+    );
+    assert!(findings.is_empty(), "{:?}", findings);
+
+    // `amount` is not an address, and neither is a value computed from an address
+    let findings = run(
+        &wasm,
+        "missing-auth",
+        vec![("mint", vec![require_auth(&wasm, var(1)), write()])],
+    );
+    assert_eq!(reported(&findings, "mint").len(), 1);
+    let mixed = Expr::I64Or(Box::new(var(0)), Box::new(var(1)));
+    let findings = run(
+        &wasm,
+        "missing-auth",
+        vec![("mint", vec![require_auth(&wasm, mixed), write()])],
+    );
+    assert_eq!(reported(&findings, "mint").len(), 1);
+}
+
+#[test]
+fn test_missing_auth_stored_address() {
+    let wasm = wasm::Instance::load_file(TOKEN);
+    let stored = |key: &str| {
+        vec![
+            Stmt::SetLocal(
+                Var::new(5, 1),
+                call(
+                    &wasm,
+                    "get_contract_data",
+                    vec![Expr::I64Const(symbol(key)), Expr::I64Const(INSTANCE)],
+                ),
+            ),
+            require_auth(&wasm, Expr::GetLocal(Var::new(5, 1))),
+            put(&wasm, "Balance", var(1), PERSISTENT),
+        ]
+    };
+    // `set_admin` writes its `Address` parameter under `Admin`
+    let set_admin = vec![require_auth(&wasm, var(0)), put(&wasm, "Admin", var(0), INSTANCE)];
+
+    let findings = run(
+        &wasm,
+        "missing-auth",
+        vec![("set_admin", set_admin.clone()), ("mint", stored("Admin"))],
+    );
+    assert!(findings.is_empty(), "{:?}", findings);
+
+    // Nothing writes an address under `Name`
+    let findings = run(
+        &wasm,
+        "missing-auth",
+        vec![("set_admin", set_admin), ("mint", stored("Name"))],
+    );
+    assert_eq!(reported(&findings, "mint").len(), 1, "{:?}", findings);
+}
+
+/// A `U32Val` pointer to `var + offset` as passed to host functions
+fn pointer(index: u32, offset: u32) -> Expr {
+    let address = Expr::I32Add(Box::new(var(index)), Box::new(Expr::I32Const(offset)));
+    let shifted = Expr::I64Shl(
+        Box::new(Expr::I64ExtendUI32(Box::new(address))),
+        Box::new(Expr::I64Const(32)),
+    );
+    Expr::I64Or(Box::new(shifted), Box::new(Expr::I64Const(4)))
+}
+
+#[test]
+fn test_key_classes() {
+    let mut keys = KeyTracker::default();
+    assert_eq!(
+        keys.class_of(&Expr::I64Const(symbol("Admin"))),
+        Some("Admin".to_string())
+    );
+    assert_eq!(keys.class_of(&var(0)), None);
+
+    keys.assign(Var::new(4, 1), &Expr::I64Const(symbol("Balance")));
+    assert_eq!(keys.class_of(&var(4)), Some("Balance".to_string()));
+
+    // `DataKey::Admin` is built in memory and passed by pointer
+    let store = Stmt::I64Store(
+        Expr::I32Add(Box::new(var(3)), Box::new(Expr::I32Const(8))),
+        Expr::I64Const(symbol("Admin")),
+    );
+    keys.store(&store);
+    let key = Expr::Call(5, vec![pointer(3, 8), Expr::I64Const(4294967300)]);
+    assert_eq!(keys.class_of(&key), Some("Admin".to_string()));
+
+    // A key built from other memory has no class, even right after a symbol was stored
+    let other = Expr::Call(5, vec![pointer(6, 8), Expr::I64Const(4294967300)]);
+    assert_eq!(keys.class_of(&other), None);
+
+    let mut joined = keys.clone();
+    joined.join(&KeyTracker::default());
+    assert_eq!(joined.class_of(&key), None);
+
+    keys.assign(Var::new(3, 2), &var(0));
+    assert_eq!(keys.class_of(&key), None);
+}
+
+#[test]
+fn test_unprotected_admin() {
+    let wasm = wasm::Instance::load_file(TOKEN);
+    let write_admin = || put(&wasm, "Admin", var(0), INSTANCE);
+    let stored_admin = || {
+        vec![
+            Stmt::SetLocal(
+                Var::new(5, 1),
+                call(
+                    &wasm,
+                    "get_contract_data",
+                    vec![Expr::I64Const(symbol("Admin")), Expr::I64Const(INSTANCE)],
+                ),
+            ),
+            require_auth(&wasm, Expr::GetLocal(Var::new(5, 1))),
+        ]
+    };
+
+    let findings = run(&wasm, "unprotected-admin", vec![("set_admin", vec![write_admin()])]);
+    assert_eq!(findings.len(), 1, "{:?}", findings);
+    assert_eq!(findings[0].severity, Severity::High);
+    assert!(findings[0].message.contains("`Admin`"), "{}", findings[0].message);
+
+    // Authorizing the new admin passed in is not authorizing the stored one
+    let findings = run(
+        &wasm,
+        "unprotected-admin",
+        vec![("set_admin", vec![require_auth(&wasm, var(0)), write_admin()])],
+    );
+    assert_eq!(findings.len(), 1, "{:?}", findings);
+
+    // Guarded writes are not reported at all
+    let mut guarded = stored_admin();
+    guarded.push(write_admin());
+    let findings = run(&wasm, "unprotected-admin", vec![("set_admin", guarded)]);
+    assert!(findings.is_empty(), "{:?}", findings);
+
+    // Other keys are not admin keys
+    let findings = run(
+        &wasm,
+        "unprotected-admin",
+        vec![("mint", vec![put(&wasm, "Balance", var(1), PERSISTENT)])],
+    );
+    assert!(findings.is_empty(), "{:?}", findings);
+}
+
+fn set(index: u32, expr: Expr) -> Stmt {
+    Stmt::SetLocal(Var::new(index, 1), expr)
+}
+
+fn local(index: u32) -> Expr {
+    Expr::GetLocal(Var::new(index, 1))
+}
+
+fn cond(expr: Expr) -> Cond {
+    Cond::Expr(MappedExpr::Expr(Box::new(expr)))
+}
+
+#[test]
+fn test_unchecked_arithmetic() {
+    let wasm = wasm::Instance::load_file(TOKEN);
+    let add = || set(5, Expr::I64Add(Box::new(var(1)), Box::new(Expr::I64Const(10))));
+    let store = || put(&wasm, "Balance", local(5), PERSISTENT);
+    let is_zero = || cond(Expr::I64Eqz(Box::new(local(5))));
+
+    let findings = run(&wasm, "unchecked-arithmetic", vec![("mint", vec![add(), store()])]);
+    assert_eq!(findings.len(), 1, "{:?}", findings);
+    assert!(findings[0].message.contains("stored"), "{}", findings[0].message);
+
+    // The other side of an overflow check panics, also when the flag is computed into a local
+    let panics = || vec![Stmt::Expr(call(&wasm, "contract_event", vec![])), Stmt::Unreachable];
+    let xor = |a: Expr, b: Expr| Box::new(Expr::I64Xor(Box::new(a), Box::new(b)));
+    let overflow_flag = || {
+        let sign = Expr::I64And(xor(local(5), var(1)), xor(local(5), Expr::I64Const(10)));
+        Expr::I64LtS(Box::new(sign), Box::new(Expr::I64Const(0)))
+    };
+    let code = vec![add(), Stmt::If(cond(overflow_flag()), panics()), store()];
+    let findings = run(&wasm, "unchecked-arithmetic", vec![("mint", code)]);
+    assert!(findings.is_empty(), "{:?}", findings);
+    let code = vec![
+        add(),
+        set(6, overflow_flag()),
+        Stmt::If(cond(local(6)), panics()),
+        store(),
+    ];
+    let findings = run(&wasm, "unchecked-arithmetic", vec![("mint", code)]);
+    assert!(findings.is_empty(), "{:?}", findings);
+
+    // A panic on another condition of the result is not, like `amount + fee == 0`
+    let code = vec![add(), Stmt::If(is_zero(), panics()), store()];
+    let findings = run(&wasm, "unchecked-arithmetic", vec![("mint", code)]);
+    assert_eq!(findings.len(), 1, "{:?}", findings);
+
+    // A branch that doesn't trap is not a check
+    let code = vec![add(), Stmt::If(is_zero(), vec![Stmt::Nop]), store()];
+    let findings = run(&wasm, "unchecked-arithmetic", vec![("mint", code)]);
+    assert_eq!(findings.len(), 1, "{:?}", findings);
+
+    // Comparing the result with an operand is
+    let overflowed = cond(Expr::I64LtU(Box::new(local(5)), Box::new(var(1))));
+    let code = vec![add(), Stmt::If(overflowed, vec![Stmt::ReturnVoid]), store()];
+    let findings = run(&wasm, "unchecked-arithmetic", vec![("mint", code)]);
+    assert!(findings.is_empty(), "{:?}", findings);
+}
+
+#[test]
+fn test_unchecked_arithmetic_stored_amount() {
+    let wasm = wasm::Instance::load_file(TOKEN);
+    let increment = |key: &str| {
+        vec![
+            set(
+                6,
+                call(
+                    &wasm,
+                    "get_contract_data",
+                    vec![Expr::I64Const(symbol(key)), Expr::I64Const(PERSISTENT)],
+                ),
+            ),
+            set(5, Expr::I64Add(Box::new(local(6)), Box::new(Expr::I64Const(10)))),
+            put(&wasm, "Total", local(5), PERSISTENT),
+        ]
+    };
+    // `burn` writes its amount under `Balance`
+    let burn = vec![put(&wasm, "Balance", var(1), PERSISTENT)];
+
+    let findings = run(
+        &wasm,
+        "unchecked-arithmetic",
+        vec![("burn", burn.clone()), ("name", increment("Balance"))],
+    );
+    assert_eq!(reported(&findings, "name").len(), 1, "{:?}", findings);
+
+    let findings = run(
+        &wasm,
+        "unchecked-arithmetic",
+        vec![("burn", burn), ("name", increment("Name"))],
+    );
+    assert!(reported(&findings, "name").is_empty(), "{:?}", findings);
+}
+
+/// The token contract importing `call` and `obj_cmp` after its own imports,
+/// with the indices of its functions shifted past them
+fn calling_contract() -> Rc<wasm::Instance> {
+    let mut module: Module = parity_wasm::deserialize_file(TOKEN).unwrap();
+    let imported = module.import_count(ImportCountType::Function) as u32;
+    let types = module.type_section_mut().unwrap().types_mut();
+    let mut type_of = |params: usize| {
+        let ty = Type::Function(FunctionType::new(vec![ValueType::I64; params], Some(ValueType::I64)));
+        let index = types.iter().position(|t| *t == ty).unwrap_or_else(|| {
+            types.push(ty);
+            types.len() - 1
+        });
+        index as u32
+    };
+    let added = [("d", "_", type_of(3)), ("x", "0", type_of(2))];
+    let entries = module.import_section_mut().unwrap().entries_mut();
+    for (module, field, ty) in added {
+        entries.push(ImportEntry::new(
+            module.to_string(),
+            field.to_string(),
+            External::Function(ty),
+        ));
+    }
+
+    let shift = |index: &mut u32| {
+        if *index >= imported {
+            *index += added.len() as u32;
+        }
+    };
+    for body in module.code_section_mut().unwrap().bodies_mut() {
+        for instruction in body.code_mut().elements_mut() {
+            if let Instruction::Call(index) = instruction {
+                shift(index);
+            }
+        }
+    }
+    for export in module.export_section_mut().unwrap().entries_mut() {
+        if let Internal::Function(index) = export.internal_mut() {
+            shift(index);
+        }
+    }
+    if let Some(elements) = module.elements_section_mut() {
+        elements
+            .entries_mut()
+            .iter_mut()
+            .flat_map(|e| e.members_mut())
+            .for_each(shift);
+    }
+    if let Some(mut start) = module.start_section() {
+        shift(&mut start);
+        module.set_start_section(start);
+    }
+
+    let path = std::env::temp_dir().join(format!("auditor-audit-call-{}.wasm", std::process::id()));
+    parity_wasm::serialize_to_file(&path, module).unwrap();
+    let instance = wasm::Instance::load_file(&path);
+    std::fs::remove_file(&path).unwrap();
+    instance
+}
+
+#[test]
+fn test_external_call() {
+    let wasm = calling_contract();
+    let invoke = |contract: Expr| {
+        Stmt::Expr(call(
+            &wasm,
+            ".call",
+            vec![contract, Expr::I64Const(symbol("transfer")), Expr::I64Const(2)],
+        ))
+    };
+    let unvalidated = |findings: &[Finding]| findings.iter().filter(|f| f.severity == Severity::High).count();
+    let compare = |other: Expr| {
+        vec![
+            set(5, call(&wasm, "obj_cmp", vec![var(1), other])),
+            Stmt::If(
+                cond(Expr::I64Ne(Box::new(local(5)), Box::new(Expr::I64Const(0)))),
+                vec![Stmt::Unreachable],
+            ),
+        ]
+    };
+    let stored = || {
+        call(
+            &wasm,
+            "get_contract_data",
+            vec![Expr::I64Const(symbol("Pool")), Expr::I64Const(INSTANCE)],
+        )
+    };
+
+    let findings = run(&wasm, "external-call", vec![("transfer", vec![invoke(var(1))])]);
+    assert_eq!(unvalidated(&findings), 1, "{:?}", findings);
+
+    // Comparing or looking up the address is not a validation by itself
+    let code = vec![set(5, call(&wasm, "obj_cmp", vec![var(1), stored()])), invoke(var(1))];
+    assert_eq!(unvalidated(&run(&wasm, "external-call", vec![("transfer", code)])), 1);
+    let code = vec![
+        Stmt::Expr(call(
+            &wasm,
+            "has_contract_data",
+            vec![var(1), Expr::I64Const(PERSISTENT)],
+        )),
+        invoke(var(1)),
+    ];
+    assert_eq!(unvalidated(&run(&wasm, "external-call", vec![("transfer", code)])), 1);
+
+    // Passing a check against a stored address is, a check against another argument is not
+    let mut code = compare(stored());
+    code.push(invoke(var(1)));
+    assert_eq!(unvalidated(&run(&wasm, "external-call", vec![("transfer", code)])), 0);
+    let mut code = compare(var(0));
+    code.push(invoke(var(1)));
+    assert_eq!(unvalidated(&run(&wasm, "external-call", vec![("transfer", code)])), 1);
+}
+
+#[test]
+fn test_write_after_external_call() {
+    let wasm = calling_contract();
+    let pool = call(
+        &wasm,
+        "get_contract_data",
+        vec![Expr::I64Const(symbol("Pool")), Expr::I64Const(INSTANCE)],
+    );
+    let invoke = Stmt::Expr(call(
+        &wasm,
+        ".call",
+        vec![pool, Expr::I64Const(symbol("swap")), Expr::I64Const(2)],
+    ));
+    let write = put(&wasm, "Balance", var(2), PERSISTENT);
+
+    let findings = run(
+        &wasm,
+        "external-call",
+        vec![("transfer", vec![invoke.clone(), write.clone()])],
+    );
+    assert_eq!(findings.len(), 1, "{:?}", findings);
+    assert_eq!(findings[0].severity, Severity::Medium);
+    assert!(findings[0].message.contains("`Balance`"), "{}", findings[0].message);
+
+    let findings = run(&wasm, "external-call", vec![("transfer", vec![write, invoke])]);
+    assert!(findings.is_empty(), "{:?}", findings);
+}
+
+fn less(a: Expr, b: Expr) -> Cond {
+    cond(Expr::I32LtU(Box::new(a), Box::new(b)))
+}
+
+fn and(a: Cond, b: Cond) -> Cond {
+    Cond::And(Box::new(a), Box::new(b))
+}
+
+#[test]
+fn test_unbounded_loop() {
+    let wasm = wasm::Instance::load_file(CONTRACT);
+    let i = || local(7);
+    let len = || local(6);
+    let step = || set(7, Expr::I32Add(Box::new(i()), Box::new(Expr::I32Const(1))));
+    let read = || {
+        vec![
+            set(
+                5,
+                call(
+                    &wasm,
+                    "get_contract_data",
+                    vec![Expr::I64Const(symbol("Items")), Expr::I64Const(PERSISTENT)],
+                ),
+            ),
+            set(7, Expr::I32Const(0)),
+        ]
+    };
+    let looping = |cond: Cond| {
+        let mut code = read();
+        code.push(set(6, call(&wasm, "vec_len", vec![local(5)])));
+        code.push(Stmt::While(cond, vec![step()], LoopKind::While));
+        code
+    };
+    let unbounded = |code: Vec<Stmt>| run(&wasm, "unbounded-loop", vec![("decimals", code)]);
+
+    let findings = unbounded(looping(less(i(), len())));
+    assert_eq!(findings.len(), 1, "{:?}", findings);
+    assert!(findings[0].message.contains("`Items`"), "{}", findings[0].message);
+
+    assert!(unbounded(looping(and(less(i(), len()), less(i(), Expr::I32Const(10))))).is_empty());
+    let either = Cond::Or(Box::new(less(i(), len())), Box::new(less(i(), Expr::I32Const(10))));
+    assert_eq!(unbounded(looping(either)).len(), 1);
+    // Only a bound on the induction variable counts
+    let other = and(less(i(), len()), less(local(8), Expr::I32Const(10)));
+    assert_eq!(unbounded(looping(other)).len(), 1);
+
+    // A branch after the loop with the same condition is not its exit
+    let mut code = read();
+    code.push(set(6, local(8)));
+    code.push(Stmt::While(less(i(), len()), vec![step()], LoopKind::While));
+    code.push(set(6, call(&wasm, "vec_len", vec![local(5)])));
+    code.push(Stmt::If(less(i(), len()), vec![Stmt::Nop]));
+    assert!(unbounded(code).is_empty());
+
+    // The length is only known inside the body
+    let mut code = read();
+    let body = vec![
+        set(6, call(&wasm, "vec_len", vec![local(5)])),
+        Stmt::If(cond(Expr::I32GeU(Box::new(i()), Box::new(len()))), vec![Stmt::Break]),
+        step(),
+    ];
+    code.push(Stmt::While(Cond::True, body.clone(), LoopKind::While));
+    assert_eq!(unbounded(code).len(), 1);
+
+    let mut code = read();
+    let mut bounded = body;
+    bounded.insert(
+        0,
+        Stmt::If(
+            cond(Expr::I32GeU(Box::new(i()), Box::new(Expr::I32Const(100)))),
+            vec![Stmt::Break],
+        ),
+    );
+    code.push(Stmt::While(Cond::True, bounded, LoopKind::While));
+    assert!(unbounded(code).is_empty());
+}
+
+/// The offset annotation before a statement
+fn at(offset: u32) -> Stmt {
+    Stmt::Offset(SourcePos { instr: 0, offset })
+}
+
+#[test]
+fn test_finding_locations() {
+    let wasm = wasm::Instance::load_file(TOKEN);
+    let offset = |findings: &[Finding], function: &str| {
+        let found = reported(findings, function);
+        assert_eq!(found.len(), 1, "{:?}", findings);
+        (found[0].location.function.clone(), found[0].location.offset)
+    };
+    let write = || put(&wasm, "Balance", var(1), PERSISTENT);
+
+    let code = vec![at(0x10), Stmt::Nop, at(0x20), write()];
+    let findings = run(&wasm, "missing-auth", vec![("mint", code)]);
+    assert_eq!(offset(&findings, "mint"), ("mint".to_string(), Some(0x20)));
+
+    // A write in a callee points into the callee
+    let code = vec![at(0x30), Stmt::Expr(call(&wasm, "name", vec![var(0), var(1)]))];
+    let findings = run(
+        &wasm,
+        "missing-auth",
+        vec![("mint", code), ("name", vec![at(0x40), write()])],
+    );
+    assert_eq!(offset(&findings, "mint"), ("name".to_string(), Some(0x40)));
+
+    let findings = run(&wasm, "missing-ttl-extension", vec![("mint", vec![at(0x50), write()])]);
+    assert_eq!(offset(&findings, "mint").1, Some(0x50));
+
+    let code = vec![at(0x60), put(&wasm, "Admin", var(0), INSTANCE)];
+    let findings = run(&wasm, "unprotected-admin", vec![("set_admin", code)]);
+    assert_eq!(offset(&findings, "set_admin").1, Some(0x60));
+
+    // Overflows point to the arithmetic rather than to the write
+    let code = vec![
+        at(0x70),
+        set(5, Expr::I64Add(Box::new(var(1)), Box::new(Expr::I64Const(10)))),
+        at(0x80),
+        put(&wasm, "Balance", local(5), PERSISTENT),
+    ];
+    let findings = run(&wasm, "unchecked-arithmetic", vec![("mint", code)]);
+    assert_eq!(offset(&findings, "mint").1, Some(0x70));
+
+    let wasm = calling_contract();
+    let pool = call(
+        &wasm,
+        "get_contract_data",
+        vec![Expr::I64Const(symbol("Pool")), Expr::I64Const(INSTANCE)],
+    );
+    let code = vec![
+        at(0x90),
+        Stmt::Expr(call(
+            &wasm,
+            ".call",
+            vec![pool, Expr::I64Const(symbol("swap")), Expr::I64Const(2)],
+        )),
+        at(0xa0),
+        put(&wasm, "Balance", var(2), PERSISTENT),
+    ];
+    let findings = run(&wasm, "external-call", vec![("transfer", code)]);
+    assert_eq!(offset(&findings, "transfer").1, Some(0xa0));
+}
+
+#[test]
+fn test_reports() {
+    let wasm = wasm::Instance::load_file(TOKEN);
+    let mut code = Vec::new();
+    for i in 0..60 {
+        code.push(at(0x100 + i * 4));
+        code.push(set(10 + i, Expr::I64Const(1000 + i as u64)));
+    }
+    code.insert(100, at(0x800));
+    code.insert(101, put(&wasm, "Balance", var(1), PERSISTENT));
+    let ctx = AuditContext::with_code(Rc::clone(&wasm), HashMap::from([(function(&wasm, "mint"), code)]));
+    let registry = RuleRegistry::with_builtin_rules();
+    let filter = RuleFilter {
+        enabled: vec!["missing-auth".to_string()],
+        ..RuleFilter::default()
+    };
+    let findings = registry.run(&ctx, &filter);
+    assert_eq!(findings.len(), 1, "{:?}", findings);
+
+    let sarif = report::to_sarif(registry.rules(), &findings, TOKEN);
+    let location = &sarif["runs"][0]["results"][0]["locations"][0]["logicalLocations"][0];
+    assert_eq!(location["properties"]["wasmOffset"], 0x800);
+
+    // The snippet is cut around the write, not at the start of the function
+    let markdown = report::to_markdown(&ctx, &findings, TOKEN);
+    let marked = markdown.lines().find(|line| line.ends_with("// <- finding")).unwrap();
+    assert!(marked.contains("put_contract_data"), "{}", marked);
+    assert!(markdown.contains("1059"), "{}", markdown);
+    assert!(!markdown.contains(" 1000"), "{}", markdown);
+    assert!(!markdown.contains("// @0x"), "{}", markdown);
+}
+
+#[test]
+fn test_baseline_fingerprints() {
+    let wasm = wasm::Instance::load_file(TOKEN);
+    let fingerprints = |mint: Vec<Stmt>, name: Vec<Stmt>| {
+        let code = HashMap::from([(function(&wasm, "mint"), mint), (function(&wasm, "name"), name)]);
+        let ctx = AuditContext::with_code(Rc::clone(&wasm), code);
+        let filter = RuleFilter {
+            enabled: vec!["missing-auth".to_string()],
+            ..RuleFilter::default()
+        };
+        let findings = RuleRegistry::with_builtin_rules().run(&ctx, &filter);
+        let findings = reported(&findings, "mint");
+        assert_eq!(findings.len(), 1, "{:?}", findings);
+        baseline::fingerprint(&ctx, findings[0])
+    };
+    // `mint` passes a memory address to the host before calling `name`, which does the write
+    let mint = |address: u64, offset: u32| {
+        vec![
+            at(offset),
+            Stmt::Expr(call(&wasm, "get_ledger_sequence", vec![Expr::I64Const(address)])),
+            at(offset + 8),
+            Stmt::Expr(call(&wasm, "name", vec![var(0), var(1)])),
+        ]
+    };
+    let name = |offset: u32, key: &str| vec![at(offset), put(&wasm, key, var(1), PERSISTENT)];
+
+    let original = fingerprints(mint(1048576, 0x100), name(0x200, "Balance"));
+    // Unrelated code moved: other data addresses and offsets
+    assert_eq!(original, fingerprints(mint(1048640, 0x180), name(0x280, "Balance")));
+    // The finding's code changed
+    assert_ne!(original, fingerprints(mint(1048576, 0x100), name(0x200, "Allowance")));
+
+    // The same when the write is in `mint` itself
+    let direct = |address: u64, offset: u32| {
+        let mut code = mint(address, offset);
+        code[3] = put(&wasm, "Balance", var(1), PERSISTENT);
+        code
+    };
+    let original = fingerprints(direct(1048576, 0x100), Vec::new());
+    assert_eq!(original, fingerprints(direct(1048640, 0x180), Vec::new()));
+    assert_ne!(original, fingerprints(direct(1000, 0x100), Vec::new()));
+}
+
+/// Needs Z3 to check the branch conditions of the paths
+#[test]
+fn test_missing_auth_feasibility() {
+    let wasm = wasm::Instance::load_file(TOKEN);
+    let write = || put(&wasm, "Balance", var(1), PERSISTENT);
+    let i = || local(5);
+    let is_five = || cond(Expr::I32Eq(Box::new(i()), Box::new(Expr::I32Const(5))));
+    let below_ten = || less(i(), Expr::I32Const(10));
+
+    // The write happens in the sixth iteration, not in the first one
+    let code = vec![
+        set(5, Expr::I32Const(0)),
+        Stmt::While(
+            below_ten(),
+            vec![
+                Stmt::If(is_five(), vec![write()]),
+                set(5, Expr::I32Add(Box::new(i()), Box::new(Expr::I32Const(1)))),
+            ],
+            LoopKind::While,
+        ),
+    ];
+    let findings = run(&wasm, "missing-auth", vec![("mint", code)]);
+    assert_eq!(reported(&findings, "mint").len(), 1, "{:?}", findings);
+
+    let code = vec![Stmt::ForLoop(
+        Var::new(5, 1),
+        Some(Expr::I32Const(0)),
+        below_ten(),
+        Expr::I32Add(Box::new(i()), Box::new(Expr::I32Const(1))),
+        vec![Stmt::If(is_five(), vec![write()])],
+    )];
+    let findings = run(&wasm, "missing-auth", vec![("mint", code)]);
+    assert_eq!(reported(&findings, "mint").len(), 1, "{:?}", findings);
+
+    // A 32 bit read of a local is the low half of the 64 bit value assigned to it
+    let guarded = |value: u32| {
+        vec![
+            set(5, Expr::I64Const(1 << 32 | 5)),
+            Stmt::If(
+                cond(Expr::I32Eq(Box::new(i()), Box::new(Expr::I32Const(value)))),
+                vec![write()],
+            ),
+        ]
+    };
+    let findings = run(&wasm, "missing-auth", vec![("mint", guarded(5))]);
+    assert_eq!(reported(&findings, "mint").len(), 1, "{:?}", findings);
+    let findings = run(&wasm, "missing-auth", vec![("mint", guarded(6))]);
+    assert!(findings.is_empty(), "{:?}", findings);
+}
+
+/// Needs Z3 to decompile the contract
+#[test]
+fn test_audit_token() {
+    let findings = audit::audit(wasm::Instance::load_file(TOKEN));
+    let mut reported: Vec<(&str, &str)> = findings
+        .iter()
+        .map(|finding| (finding.rule_id.as_str(), finding.function.as_str()))
+        .collect();
+    reported.sort_unstable();
+
+    // Only the one-time initialization stores the admin and the metadata without authorization
+    // and without extending the instance, everything else authorizes, checks its arithmetic,
+    // extends its entries and neither calls contracts nor loops
+    assert_eq!(
+        reported,
+        vec![
+            ("missing-auth", "initialize"),
+            ("missing-ttl-extension", "initialize"),
+            ("missing-ttl-extension", "initialize"),
+            ("unprotected-admin", "initialize"),
+        ],
+        "{:#?}",
+        findings
+    );
+    let mut entries: Vec<&str> = findings
+        .iter()
+        .filter(|finding| finding.rule_id == "missing-ttl-extension")
+        .map(|finding| finding.message.as_str())
+        .collect();
+    entries.sort_unstable();
+    assert!(entries[0].contains("`Admin`"), "{}", entries[0]);
+    assert!(entries[1].contains("`METADATA`"), "{}", entries[1]);
+}
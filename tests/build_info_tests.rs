@@ -0,0 +1,113 @@
+use auditor::soroban::build_info::{self, BuildInfo, SdkFingerprints, SdkTable, VersionSource};
+use parity_wasm::elements::{Module, Section};
+
+fn load(path: &str) -> Module {
+    parity_wasm::deserialize_file(path).unwrap()
+}
+
+fn table(versions: &[(&str, &[&str])]) -> SdkTable {
+    SdkTable {
+        versions: versions
+            .iter()
+            .map(|(sdk, helpers)| SdkFingerprints {
+                sdk: sdk.to_string(),
+                helpers: helpers.iter().map(|helper| helper.to_string()).collect(),
+            })
+            .collect(),
+    }
+}
+
+#[test]
+fn test_metadata() {
+    let info = BuildInfo::from_module(&load("tests/hello.wasm"));
+    assert_eq!(info.source, VersionSource::Metadata);
+    assert_eq!(info.sdk_version.as_deref(), Some("20.5.0"));
+    assert_eq!(
+        info.sdk_commit.as_deref(),
+        Some("9e2c3022b4355b224a7a814e13ba51761eeb14bb")
+    );
+    assert_eq!(info.rustc_version.as_deref(), Some("1.74.0"));
+
+    // Contracts of other SDKs record other keys
+    let info = BuildInfo::from_meta(&load("tests/contract_test.wasm"));
+    assert_eq!(info.source, VersionSource::Unknown);
+    assert_eq!(info.sdk_version, None);
+    assert!(info
+        .meta
+        .iter()
+        .any(|entry| entry.key == "assdkver" && entry.value == "1.0.0"));
+}
+
+#[test]
+fn test_stripped_metadata() {
+    let mut module = load("tests/soroban_token_contract.wasm");
+    module
+        .sections_mut()
+        .retain(|section| !matches!(section, Section::Custom(_) | Section::Name(_)));
+    assert_eq!(BuildInfo::from_meta(&module).sdk_version, None);
+
+    let table = SdkTable::load(build_info::SDK_TABLE_FILE).unwrap();
+    let info = BuildInfo::default().infer(&module, &table);
+    assert_eq!(info.source, VersionSource::Fingerprints);
+    assert_eq!(info.sdk_version.as_deref(), Some("20.3.1"));
+    assert!(info.matched_helpers >= build_info::MIN_MATCHES);
+}
+
+#[test]
+fn test_infer_sdk_version() {
+    let helpers = vec![String::from("a"), String::from("b"), String::from("c")];
+    let found = build_info::infer_sdk_version(&helpers, &table(&[("20.0.0", &["a", "x"]), ("20.1.0", &["b", "c"])]));
+    assert_eq!(found, Some((String::from("20.1.0"), 2)));
+    // Equally good matches are ambiguous
+    let tied = table(&[("20.0.0", &["a", "b"]), ("20.1.0", &["b", "c"])]);
+    assert_eq!(build_info::infer_sdk_version(&helpers, &tied), None);
+    assert_eq!(
+        build_info::infer_sdk_version(&helpers, &table(&[("20.0.0", &["a"])])),
+        None
+    );
+}
+
+#[test]
+fn test_table_from_contracts() {
+    let contracts = vec![
+        ("20.0.0", vec![String::from("shared"), String::from("old")]),
+        ("20.1.0", vec![String::from("shared"), String::from("new")]),
+        ("20.1.0", vec![String::from("new")]),
+    ];
+    let table = SdkTable::from_contracts(contracts);
+    let versions: Vec<(&str, &[String])> = table
+        .versions
+        .iter()
+        .map(|version| (version.sdk.as_str(), version.helpers.as_slice()))
+        .collect();
+    assert_eq!(
+        versions,
+        vec![
+            ("20.0.0", &[String::from("old")][..]),
+            ("20.1.0", &[String::from("new")][..])
+        ]
+    );
+}
+
+#[test]
+fn test_versioned_file() {
+    let dir = std::env::temp_dir().join(format!("auditor-versions-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let name = dir.join("env.json");
+    let name = name.to_str().unwrap();
+    assert_eq!(build_info::versioned_file(name, Some("20.5.0")), dir.join("env.json"));
+
+    std::fs::write(dir.join("env-20.json"), "{}").unwrap();
+    assert_eq!(
+        build_info::versioned_file(name, Some("20.5.0")),
+        dir.join("env-20.json")
+    );
+    std::fs::write(dir.join("env-20.5.json"), "{}").unwrap();
+    assert_eq!(
+        build_info::versioned_file(name, Some("20.5.0")),
+        dir.join("env-20.5.json")
+    );
+    assert_eq!(build_info::versioned_file(name, Some("21.0.0")), dir.join("env.json"));
+    assert_eq!(build_info::versioned_file(name, None), dir.join("env.json"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,87 @@
+use std::collections::BTreeMap;
+
+use auditor::cluster::{self, ContractFingerprints, DivergenceKind};
+use auditor::identify::{Fingerprint, MAX_DISTANCE};
+
+fn contract(name: &str, functions: &[(&str, &str)]) -> ContractFingerprints {
+    ContractFingerprints {
+        contract: name.to_string(),
+        functions: functions
+            .iter()
+            .map(|(function, structure)| {
+                let fingerprint = Fingerprint {
+                    tlsh: None,
+                    structure: structure.to_string(),
+                };
+                (function.to_string(), fingerprint)
+            })
+            .collect::<BTreeMap<_, _>>(),
+    }
+}
+
+#[test]
+fn test_cluster_forks() {
+    let token = [("balance", "01"), ("burn", "02"), ("mint", "03"), ("transfer", "04")];
+    let contracts = vec![
+        contract("token", &token),
+        contract(
+            "changed",
+            &[("balance", "01"), ("burn", "02"), ("mint", "03"), ("transfer", "05")],
+        ),
+        contract("no_burn", &[("balance", "01"), ("mint", "03"), ("transfer", "04")]),
+        contract(
+            "extended",
+            &[
+                ("balance", "01"),
+                ("burn", "02"),
+                ("mint", "03"),
+                ("transfer", "04"),
+                ("pause", "06"),
+            ],
+        ),
+        contract("amm", &[("swap", "11"), ("deposit", "12")]),
+    ];
+    let report = cluster::cluster(&contracts, MAX_DISTANCE);
+    assert_eq!(report.singletons, vec!["amm"]);
+    assert_eq!(report.clusters.len(), 1);
+
+    let cluster = &report.clusters[0];
+    assert_eq!(cluster.contracts, vec!["token", "changed", "no_burn", "extended"]);
+    let divergences: Vec<(&str, &str, DivergenceKind)> = cluster
+        .divergences
+        .iter()
+        .map(|divergence| {
+            (
+                divergence.contract.as_str(),
+                divergence.function.as_str(),
+                divergence.kind,
+            )
+        })
+        .collect();
+    assert_eq!(
+        divergences,
+        vec![
+            ("changed", "transfer", DivergenceKind::Changed),
+            ("extended", "pause", DivergenceKind::Added),
+            ("no_burn", "burn", DivergenceKind::Missing),
+        ]
+    );
+
+    let transfer = cluster
+        .functions
+        .iter()
+        .find(|function| function.name == "transfer")
+        .unwrap();
+    assert_eq!(transfer.versions.len(), 2);
+    assert_eq!(transfer.versions[0].fingerprint.structure, "04");
+    assert_eq!(transfer.versions[0].contracts, vec!["token", "no_burn", "extended"]);
+}
+
+#[test]
+fn test_similarity() {
+    let a = contract("a", &[("f", "01"), ("g", "02")]);
+    let b = contract("b", &[("f", "01"), ("g", "03"), ("h", "04")]);
+    assert_eq!(a.similarity(&a, 0), 1.0);
+    assert!((a.similarity(&b, 0) - 1.0 / 3.0).abs() < 1e-9);
+    assert_eq!(a.similarity(&contract("empty", &[]), 0), 0.0);
+}
@@ -0,0 +1,143 @@
+use auditor::diff::text::unified_diff;
+use auditor::diff::{self, ChangeKind, MatchKind, Options};
+use auditor::run::val::SYMBOL_SMALL;
+use auditor::wasm_wrapper::wasm;
+use parity_wasm::elements::{Instruction, Internal, Module};
+
+const TOKEN: &str = "tests/soroban_token_contract.wasm";
+
+fn summary() -> Options {
+    Options { bodies: false }
+}
+
+/// Write a variant of the token contract and load it
+fn variant(name: &str, change: impl FnOnce(&mut Module)) -> std::rc::Rc<wasm::Instance> {
+    let mut module: Module = parity_wasm::deserialize_file(TOKEN).unwrap();
+    change(&mut module);
+    let path = std::env::temp_dir().join(format!("auditor-diff-{}-{}.wasm", name, std::process::id()));
+    parity_wasm::serialize_to_file(&path, module).unwrap();
+    let instance = wasm::Instance::load_file(&path);
+    std::fs::remove_file(&path).unwrap();
+    instance
+}
+
+/// Swap the local functions `a` and `b` and every reference to them
+fn swap_functions(module: &mut Module, a: usize, b: usize) {
+    let imported = module.import_count(parity_wasm::elements::ImportCountType::Function);
+    let remap = |index: &mut u32| {
+        if *index as usize == imported + a {
+            *index = (imported + b) as u32;
+        } else if *index as usize == imported + b {
+            *index = (imported + a) as u32;
+        }
+    };
+    module.function_section_mut().unwrap().entries_mut().swap(a, b);
+    let bodies = module.code_section_mut().unwrap().bodies_mut();
+    bodies.swap(a, b);
+    for body in bodies {
+        for instr in body.code_mut().elements_mut() {
+            if let Instruction::Call(index) = instr {
+                remap(index);
+            }
+        }
+    }
+    if let Some(exports) = module.export_section_mut() {
+        for export in exports.entries_mut() {
+            if let Internal::Function(index) = export.internal_mut() {
+                remap(index);
+            }
+        }
+    }
+    if let Some(elements) = module.elements_section_mut() {
+        for segment in elements.entries_mut() {
+            segment.members_mut().iter_mut().for_each(remap);
+        }
+    }
+}
+
+#[test]
+fn test_identical_contracts() {
+    let old = wasm::Instance::load_file(TOKEN);
+    let new = wasm::Instance::load_file(TOKEN);
+    let report = diff::compare(&old, &new, &summary());
+    assert!(report.is_empty());
+    assert!(report.unchanged > 0);
+}
+
+#[test]
+fn test_reordered_functions_are_not_reported() {
+    let old = wasm::Instance::load_file(TOKEN);
+    let new = variant("reordered", |module| swap_functions(module, 3, 7));
+    let report = diff::compare(&old, &new, &summary());
+    assert!(report.is_empty(), "{:#?}", report.functions);
+    assert_eq!(report.unchanged, diff::compare(&old, &old, &summary()).unchanged);
+}
+
+/// Code of the exported function `name`
+fn export_code<'a>(module: &'a mut Module, name: &str) -> &'a mut Vec<Instruction> {
+    let imported = module.import_count(parity_wasm::elements::ImportCountType::Function);
+    let index = module
+        .export_section()
+        .unwrap()
+        .entries()
+        .iter()
+        .find_map(|export| match export.internal() {
+            Internal::Function(index) if export.field() == name => Some(*index as usize),
+            _ => None,
+        })
+        .unwrap();
+    let body = &mut module.code_section_mut().unwrap().bodies_mut()[index - imported];
+    body.code_mut().elements_mut()
+}
+
+#[test]
+fn test_changed_export() {
+    let old = wasm::Instance::load_file(TOKEN);
+    let new = variant("changed", |module| {
+        // The storage key is a large constant, but a tagged `Val` and no address
+        let key = export_code(module, "decimals")
+            .iter_mut()
+            .find_map(|instr| match instr {
+                Instruction::I64Const(value) if *value as u8 == SYMBOL_SMALL => Some(value),
+                _ => None,
+            })
+            .unwrap();
+        *key += 1 << 8;
+    });
+    let report = diff::compare(&old, &new, &summary());
+    assert_eq!(report.functions.len(), 1, "{:#?}", report.functions);
+    let change = &report.functions[0];
+    assert_eq!(change.kind, ChangeKind::Changed);
+    assert_eq!(change.matched_by, Some(MatchKind::ExportName));
+    assert_eq!(change.new.as_ref().unwrap().name, "decimals");
+}
+
+#[test]
+fn test_moved_addresses_are_not_reported() {
+    let old = wasm::Instance::load_file(TOKEN);
+    let new = variant("moved", |module| {
+        // Points into the data segment at 1048576
+        let address = export_code(module, "approve")
+            .iter_mut()
+            .find_map(|instr| match instr {
+                Instruction::I32Const(value) if *value >= 1_048_576 => Some(value),
+                _ => None,
+            })
+            .unwrap();
+        *address += 1;
+    });
+    let report = diff::compare(&old, &new, &summary());
+    assert!(report.is_empty(), "{:#?}", report.functions);
+}
+
+#[test]
+fn test_unified_diff() {
+    let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\n";
+    let new = "a\nb\nc\nD\ne\nf\ng\nh\ni\nj\n";
+    let diff = unified_diff(old, new, "old", "new", 1);
+    assert_eq!(
+        diff,
+        "--- old\n+++ new\n@@ -3,3 +3,3 @@\n c\n-d\n+D\n e\n@@ -9,1 +9,2 @@\n i\n+j\n"
+    );
+    assert_eq!(unified_diff(old, old, "old", "new", 3), "");
+}
@@ -0,0 +1,129 @@
+use auditor::difftest::{self, project, DivergenceKind, Options};
+use auditor::wasm_wrapper::wasm;
+use std::path::Path;
+
+fn read(path: &str) -> Vec<u8> {
+    std::fs::read(path).unwrap()
+}
+
+#[test]
+fn test_token_matches_itself() {
+    let original = read("tests/soroban_token_contract.wasm");
+    let report = difftest::compare(&original, &original, &Options::default()).unwrap();
+
+    assert!(report.functions.iter().any(|function| function.name == "transfer"));
+    assert!(report.functions.iter().all(|function| function.cases == 16));
+    assert_eq!(report.divergences(), 0, "{:#?}", report);
+    // initialize succeeds once, so later calls run on initialized storage
+    let initialize = report
+        .functions
+        .iter()
+        .find(|function| function.name == "initialize")
+        .unwrap();
+    assert_eq!(initialize.traps, 15);
+}
+
+#[test]
+fn test_user_defined_types_match_themselves() {
+    let original = read("tests/contract.wasm");
+    let options = Options {
+        cases: 4,
+        ..Options::default()
+    };
+    let report = difftest::compare(&original, &original, &options).unwrap();
+    assert!(report.functions.iter().any(|function| function.name == "x_twap"));
+    assert_eq!(report.divergences(), 0);
+}
+
+#[test]
+fn test_token_builds_diverge_on_zero_amounts() {
+    // The optimized build comes from another token version that treats zero amounts differently
+    let original = read("tests/soroban_token_contract.wasm");
+    let optimized = read("tests/soroban_token_contract.optimized.wasm");
+    let report = difftest::compare(&original, &optimized, &Options::default()).unwrap();
+    let diverging: Vec<&str> = report
+        .functions
+        .iter()
+        .filter(|function| !function.divergences.is_empty())
+        .map(|function| function.name.as_str())
+        .collect();
+    assert_eq!(diverging, ["transfer_from", "burn_from"]);
+    let divergence = &report
+        .functions
+        .iter()
+        .find(|function| function.name == "burn_from")
+        .unwrap()
+        .divergences[0];
+    assert_eq!(divergence.kind, DivergenceKind::Storage);
+}
+
+#[test]
+fn test_changed_function_diverges() {
+    let original = read("tests/hello.wasm");
+    let changed = read("tests/hello.optimized.wasm");
+    let options = Options {
+        cases: 2,
+        ..Options::default()
+    };
+    let report = difftest::compare(&original, &changed, &options).unwrap();
+    let hello = &report.functions[0];
+    assert_eq!(hello.name, "hello");
+    assert_eq!(hello.divergences.len(), 2);
+    assert_eq!(hello.divergences[0].kind, DivergenceKind::Trap);
+}
+
+/// Build the cargo project in `dir` and compare it with the original binary
+fn check_source(dir: &str, original: &str) {
+    let reconstruction = project::build_project(Path::new(dir)).unwrap();
+    let report = difftest::compare(&read(original), &reconstruction, &Options::default()).unwrap();
+    assert_eq!(report.divergences(), 0, "{:#?}", report);
+}
+
+#[test]
+#[ignore = "needs cargo with the wasm32-unknown-unknown target"]
+fn test_token_source() {
+    check_source("tests/token", "tests/soroban_token_contract.wasm");
+}
+
+#[test]
+#[ignore = "needs cargo with the wasm32-unknown-unknown target"]
+fn test_hello_source() {
+    check_source("tests/hello", "tests/hello.optimized.wasm");
+}
+
+/// Write the reconstruction of `original` to a temporary cargo project and return its directory
+fn write_reconstruction(original: &str) -> std::path::PathBuf {
+    let wasm = wasm::Instance::load_file(original);
+    let name = Path::new(original).file_stem().unwrap().to_string_lossy();
+    let dir = std::env::temp_dir().join(format!("auditor-reconstruction-{}-{}", name, std::process::id()));
+    let sdk_version = wasm.module().build_info().sdk_version.as_deref();
+    project::write_project(&dir, &project::reconstruct(&wasm), sdk_version).unwrap();
+    dir
+}
+
+#[test]
+fn test_project_uses_sdk_version() {
+    let wasm = wasm::Instance::load_file("tests/soroban_token_contract.wasm");
+    let sdk_version = wasm.module().build_info().sdk_version.clone().unwrap();
+    let dir = std::env::temp_dir().join(format!("auditor-project-{}", std::process::id()));
+    project::write_project(&dir, "#![no_std]\n", Some(&sdk_version)).unwrap();
+    let manifest = std::fs::read_to_string(dir.join("Cargo.toml")).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(
+        manifest.contains(&format!("soroban-sdk = \"={}\"", sdk_version)),
+        "{}",
+        manifest
+    );
+}
+
+#[test]
+#[ignore = "needs cargo with the wasm32-unknown-unknown target"]
+fn test_token_reconstruction_builds() {
+    let dir = write_reconstruction("tests/soroban_token_contract.wasm");
+    let reconstruction = project::build_project(&dir);
+    std::fs::remove_dir_all(&dir).unwrap();
+    let reconstruction = reconstruction.unwrap();
+    let original = read("tests/soroban_token_contract.wasm");
+    let report = difftest::compare(&original, &reconstruction, &Options::default()).unwrap();
+    assert!(report.functions.iter().any(|function| function.name == "transfer"));
+}
@@ -0,0 +1,53 @@
+use auditor::fuzz::corpus::Corpus;
+use auditor::fuzz::{self, Options};
+use auditor::wasm_wrapper::wasm;
+use soroban_sdk::xdr::ScVal;
+
+const TOKEN: &str = "tests/soroban_token_contract.wasm";
+
+#[test]
+fn test_crash_is_minimized() {
+    let wasm = wasm::Instance::load_file(TOKEN);
+    let code = std::fs::read(TOKEN).unwrap();
+    let options = Options {
+        functions: vec![String::from("initialize")],
+        runs: 64,
+        ..Options::default()
+    };
+    let report = fuzz::fuzz(&wasm, &code, &options).unwrap();
+
+    assert!(report.covered > 0 && report.covered < report.blocks);
+    assert_eq!(report.functions.len(), 1);
+    assert!(report.functions[0].corpus > 0);
+    // initialize panics if the decimals do not fit into a u8
+    assert_eq!(report.findings.len(), 1);
+    match report.findings[0].args[1] {
+        ScVal::U32(decimal) => assert!(decimal > u32::from(u8::MAX) && decimal / 2 <= u32::from(u8::MAX)),
+        ref other => panic!("unexpected argument {:?}", other),
+    }
+}
+
+#[test]
+fn test_corpus_is_replayed() {
+    let wasm = wasm::Instance::load_file(TOKEN);
+    let code = std::fs::read(TOKEN).unwrap();
+    let dir = std::env::temp_dir().join(format!("auditor-fuzz-test-{}", std::process::id()));
+    let options = Options {
+        functions: vec![String::from("transfer")],
+        runs: 32,
+        corpus: Some(Corpus::new(&dir)),
+        ..Options::default()
+    };
+    let first = fuzz::fuzz(&wasm, &code, &options).unwrap();
+    let saved = Corpus::new(&dir).load("transfer").unwrap();
+    assert_eq!(saved.len(), first.functions[0].corpus);
+
+    // Replaying the saved inputs alone reaches the same blocks
+    let replay = Options {
+        runs: saved.len(),
+        ..options
+    };
+    let second = fuzz::fuzz(&wasm, &code, &replay).unwrap();
+    assert_eq!(second.covered, first.covered);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
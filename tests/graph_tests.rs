@@ -0,0 +1,71 @@
+use std::path::{Path, PathBuf};
+
+use auditor::graph::{self, GraphStage};
+use auditor::wasm_wrapper::wasm;
+use parity_wasm::elements::{FunctionNameSubsection, Module, NameSection, Section};
+
+const TOKEN: &str = "tests/soroban_token_contract.wasm";
+
+/// The token with every internal function named `helper "<index>"`, so the labels of the
+/// graphs contain quotes
+fn quoted_names(dir: &Path) -> PathBuf {
+    let original = wasm::Instance::load_file(TOKEN);
+    let mut module: Module = parity_wasm::deserialize_file(TOKEN).unwrap();
+    let mut functions = FunctionNameSubsection::default();
+    for i in 0..original.module().functions().len() as u32 {
+        if !original.module().func(i).is_imported() && !original.module().is_exported(i) {
+            functions.names_mut().insert(i, format!("helper \"{}\"", i));
+        }
+    }
+    module
+        .sections_mut()
+        .push(Section::Name(NameSection::new(None, Some(functions), None)));
+    let path = dir.join("token.wasm");
+    parity_wasm::serialize_to_file(&path, module).unwrap();
+    path
+}
+
+/// A `digraph` whose quoted strings are all closed on their line
+fn assert_digraph(dot: &str) {
+    assert!(dot.starts_with("digraph G {\n"), "{}", dot);
+    assert!(dot.trim_end().ends_with('}'), "{}", dot);
+    for line in dot.lines() {
+        let quotes = line.replace("\\\\", "").replace("\\\"", "").matches('"').count();
+        assert_eq!(quotes % 2, 0, "unbalanced quotes in `{}`", line);
+    }
+}
+
+/// Needs Z3 to build the graphs
+#[test]
+fn test_write_graphs() {
+    let dir = std::env::temp_dir().join(format!("auditor-graphs-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let wasm = wasm::Instance::load_file(quoted_names(&dir));
+    let module = wasm.module();
+    let func = (0..module.functions().len() as u32)
+        .find(|i| module.func(*i).name() == "balance")
+        .unwrap();
+
+    let paths = graph::write_graphs(wasm.clone(), func, &GraphStage::ALL, &dir.join("graphs")).unwrap();
+    let names: Vec<String> = paths
+        .iter()
+        .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+        .collect();
+    for stage in ["raw", "domtree", "ssa", "propagated"] {
+        assert!(
+            names.contains(&format!("{}_balance.{}.dot", func, stage)),
+            "{:?}",
+            names
+        );
+    }
+    for path in &paths {
+        assert_digraph(&std::fs::read_to_string(path).unwrap());
+    }
+    let raw = std::fs::read_to_string(dir.join("graphs").join(format!("{}_balance.raw.dot", func))).unwrap();
+    assert!(raw.contains("helper \\\""), "{}", raw);
+
+    // Only the requested stages are written
+    let paths = graph::write_graphs(wasm, func, &[GraphStage::DomTree], &dir.join("domtree")).unwrap();
+    assert_eq!(paths.len(), 1);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
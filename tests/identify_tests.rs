@@ -0,0 +1,93 @@
+use auditor::identify::db::{demangle, Reference, SimilarityDb};
+use auditor::identify::{self, Fingerprint};
+use auditor::wasm_wrapper::wasm;
+use parity_wasm::elements::{FunctionNameSubsection, Module, NameSection, Section};
+
+fn reference(label: &str, structure: &str) -> Reference {
+    Reference {
+        label: label.to_string(),
+        contract: String::from("token"),
+        fingerprint: Fingerprint {
+            tlsh: None,
+            structure: structure.to_string(),
+        },
+    }
+}
+
+#[test]
+fn test_demangle() {
+    assert_eq!(
+        demangle("_ZN11soroban_sdk7address7Address12require_auth17h0123456789abcdefE"),
+        "soroban_sdk::address::Address::require_auth"
+    );
+    assert_eq!(
+        demangle("_ZN4core3ptr42drop_in_place$LT$soroban_sdk..env..Env$GT$17h0123456789abcdefE"),
+        "core::ptr::drop_in_place<soroban_sdk::env::Env>"
+    );
+    assert_eq!(
+        demangle("_ZN55_$LT$soroban_sdk..Val$u20$as$u20$core..clone..Clone$GT$5clone17h0123456789abcdefE"),
+        "<soroban_sdk::Val as core::clone::Clone>::clone"
+    );
+    assert_eq!(demangle("transfer"), "transfer");
+    assert_eq!(demangle("_ZN3foo"), "_ZN3foo");
+}
+
+#[test]
+fn test_database() {
+    let mut db = SimilarityDb::default();
+    assert!(db.add(reference("soroban_sdk::Val::clone", "00000000000000aa")));
+    assert!(!db.add(reference("soroban_sdk::Val::clone", "00000000000000aa")));
+    assert!(db.add(reference("token::transfer", "00000000000000bb")));
+
+    let path = std::env::temp_dir().join(format!("auditor-similarity-{}.json", std::process::id()));
+    let path = path.to_str().unwrap();
+    db.write_to_file(path).unwrap();
+    let loaded = SimilarityDb::load(path).unwrap();
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(loaded.references, db.references);
+
+    let unknown = Fingerprint {
+        tlsh: None,
+        structure: String::from("00000000000000cc"),
+    };
+    assert!(identify::nearest(&loaded, &unknown, identify::MAX_DISTANCE).is_none());
+    let (found, distance) = identify::nearest(&loaded, &reference("", "00000000000000bb").fingerprint, 0).unwrap();
+    assert_eq!(found.label, "token::transfer");
+    assert_eq!(distance, 0);
+}
+
+/// Needs Z3 to decompile the functions
+#[test]
+fn test_index_and_identify() {
+    const TOKEN: &str = "tests/soroban_token_contract.wasm";
+    let original = wasm::Instance::load_file(TOKEN);
+    let internal: Vec<u32> = (0..original.module().functions().len() as u32)
+        .filter(|i| !original.module().func(*i).is_imported() && !original.module().is_exported(*i))
+        .take(3)
+        .collect();
+
+    // The same contract with a name section as the reference
+    let mut module: Module = parity_wasm::deserialize_file(TOKEN).unwrap();
+    let mut functions = FunctionNameSubsection::default();
+    for i in &internal {
+        functions.names_mut().insert(*i, format!("helper_{}", i));
+    }
+    module
+        .sections_mut()
+        .push(Section::Name(NameSection::new(None, Some(functions), None)));
+    let path = std::env::temp_dir().join(format!("auditor-identify-{}.wasm", std::process::id()));
+    parity_wasm::serialize_to_file(&path, module).unwrap();
+    let reference = wasm::Instance::load_file(&path);
+    std::fs::remove_file(&path).unwrap();
+
+    let mut db = SimilarityDb::default();
+    assert!(identify::index_contract(&mut db, &reference, "token") >= internal.len());
+    let identified = identify::identify(&original, &db, identify::MAX_DISTANCE);
+    for i in &internal {
+        let found = identified.iter().find(|found| found.func_index == *i).unwrap();
+        assert_eq!(found.distance, 0);
+        assert_eq!(found.contract, "token");
+        // identical helpers share a structure, so the label may be another one of them
+        assert!(found.label.starts_with("helper_"), "{:?}", found);
+    }
+}
@@ -0,0 +1,37 @@
+use auditor::ir::FunctionIr;
+use auditor::ssa::cond::{CmpOp, MappedExpr};
+use auditor::ssa::{Cond, Expr, LoopKind, Stmt, Var};
+
+#[test]
+fn test_ir_roundtrip() {
+    let var = Var::new(0, 0);
+    let ir = vec![FunctionIr {
+        index: 3,
+        name: "hello".to_string(),
+        code: vec![
+            Stmt::SetLocal(var, Expr::I32Const(0)),
+            Stmt::While(
+                Cond::Cmp(
+                    MappedExpr::Expr(Box::new(Expr::GetLocal(var))),
+                    CmpOp::Lt,
+                    MappedExpr::Const(16),
+                ),
+                vec![Stmt::I64Store(Expr::GetLocal(var), Expr::I64Const(2))],
+                LoopKind::While,
+            ),
+            Stmt::Return(Expr::Call(7, vec![Expr::GetLocal(var)])),
+        ],
+    }];
+
+    let json = serde_json::to_string(&ir).unwrap();
+    let loaded: Vec<FunctionIr> = serde_json::from_str(&json).unwrap();
+    assert_eq!(loaded.len(), 1);
+    assert_eq!(loaded[0].name, "hello");
+    match &loaded[0].code[1] {
+        Stmt::While(Cond::Cmp(_, CmpOp::Lt, _), body, LoopKind::While) => {
+            assert!(matches!(body[0], Stmt::I64Store(Expr::GetLocal(v), Expr::I64Const(2)) if v == var))
+        }
+        other => panic!("unexpected statement {:?}", other),
+    }
+    assert_eq!(serde_json::to_string(&loaded).unwrap(), json);
+}
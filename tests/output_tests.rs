@@ -0,0 +1,83 @@
+use auditor::fmt::WriterOptions;
+use auditor::output::ModuleOutput;
+use auditor::wasm_wrapper::wasm;
+use serde_json::Value;
+
+const TOKEN: &str = "tests/soroban_token_contract.wasm";
+
+fn json_output(wasm: &std::rc::Rc<wasm::Instance>, func_indices: &[u32]) -> Value {
+    let output = ModuleOutput::new(wasm.clone(), TOKEN, func_indices, WriterOptions::default());
+    serde_json::from_str(&serde_json::to_string_pretty(&output).unwrap()).unwrap()
+}
+
+fn index(wasm: &wasm::Instance, name: &str) -> u32 {
+    let module = wasm.module();
+    (0..module.functions().len() as u32)
+        .find(|i| module.func(*i).name() == name)
+        .unwrap()
+}
+
+#[test]
+fn test_module_metadata() {
+    let wasm = wasm::Instance::load_file(TOKEN);
+    let output = json_output(&wasm, &[]);
+
+    assert_eq!(output["file"], TOKEN);
+    let metadata = &output["metadata"];
+    assert_eq!(metadata["functions"], 59);
+    assert_eq!(metadata["imported_functions"], 15);
+    assert_eq!(metadata["globals"], 3);
+    assert_eq!(metadata["memories"], 1);
+    assert!(metadata["exports"]
+        .as_array()
+        .unwrap()
+        .contains(&Value::from("transfer")));
+    assert!(metadata["custom_sections"]
+        .as_array()
+        .unwrap()
+        .contains(&Value::from("contractspecv0")));
+    assert_eq!(metadata["build"]["sdk_version"], "20.3.1");
+    assert_eq!(metadata["build"]["rustc_version"], "1.74.0");
+
+    assert_eq!(output["imports"][1]["name"], "ledger.get_contract_data");
+    assert_eq!(output["spec_functions"][0]["name"], "initialize");
+    assert_eq!(output["spec_functions"][0]["inputs"][0]["type"], "soroban_sdk::Address");
+    assert!(output["functions"].as_array().unwrap().is_empty());
+}
+
+/// Needs Z3 to decompile the functions
+#[test]
+fn test_function_output() {
+    let wasm = wasm::Instance::load_file(TOKEN);
+    let (decimals, balance) = (index(&wasm, "decimals"), index(&wasm, "balance"));
+    let output = json_output(&wasm, &[decimals, balance]);
+    let functions = output["functions"].as_array().unwrap();
+    assert_eq!(functions.len(), 2);
+
+    for (function, func_index) in functions.iter().zip([decimals, balance]) {
+        let func = wasm.module().func(func_index);
+        assert_eq!(function["index"], func_index);
+        assert_eq!(function["name"], func.name());
+        assert_eq!(function["exported"], true);
+        assert!(function["code"]
+            .as_str()
+            .unwrap()
+            .contains(&format!("pub fn {}(", func.name())));
+
+        let stats = &function["stats"];
+        assert_eq!(stats["instructions"], func.instructions().len());
+        assert_eq!(stats["loops"], 0);
+        assert!(stats["blocks"].as_u64().unwrap() > 0);
+
+        // The patterns of the contract's SDK version were loaded
+        let warnings = function["warnings"].as_array().unwrap();
+        assert!(
+            !warnings
+                .iter()
+                .any(|warning| warning.as_str().unwrap().contains("patterns")),
+            "{:?}",
+            warnings
+        );
+    }
+    assert_eq!(functions[0]["matched_patterns"], serde_json::json!(["decimals"]));
+}
@@ -0,0 +1,261 @@
+use std::rc::Rc;
+
+use auditor::fmt::{CodeWriter, WriterOptions};
+use auditor::soroban::pattern::{Capture, Matcher, Pattern};
+use auditor::soroban::{self, sdk_linker};
+use auditor::ssa::cond::MappedExpr;
+use auditor::ssa::{Cond, Expr, Stmt, Var};
+use auditor::wasm_wrapper::wasm;
+
+const TOKEN: &str = "tests/soroban_token_contract.wasm";
+const VOID: u64 = 2;
+
+fn function(wasm: &wasm::Instance, name: &str) -> u32 {
+    let module = wasm.module();
+    (0..module.functions().len() as u32)
+        .find(|i| module.func(*i).name().ends_with(name))
+        .unwrap()
+}
+
+fn var(index: u32) -> Expr {
+    Expr::GetLocal(Var::new(index, 0))
+}
+
+fn set(index: u32, expr: Expr) -> Stmt {
+    Stmt::SetLocal(Var::new(index, 1), expr)
+}
+
+/// A storage read as it comes out of the decompiler, `key` is the first parameter
+fn storage_read(has: u32, get: u32) -> Vec<Stmt> {
+    vec![
+        set(5, Expr::Call(has, vec![var(0), Expr::I64Const(VOID)])),
+        Stmt::If(
+            Cond::Expr(MappedExpr::Expr(Box::new(var(5)))),
+            vec![
+                set(7, Expr::Call(get, vec![var(0), Expr::I64Const(VOID)])),
+                Stmt::Nop,
+                set(
+                    6,
+                    Expr::I64Load(Box::new(Expr::I32Add(Box::new(var(7)), Box::new(Expr::I32Const(16))))),
+                ),
+                Stmt::Return(var(6)),
+            ],
+        ),
+        Stmt::Unreachable,
+    ]
+}
+
+fn storage_source(wasm: &wasm::Instance, key: &str) -> String {
+    let module = wasm.module();
+    let has = module.func(function(wasm, "has_contract_data")).name();
+    let get = module.func(function(wasm, "get_contract_data")).name();
+    format!(
+        "$found = {}($key{}, Void)\nif $found {{\n    $entry = {}($key, _)\n    ..\n    $value = load_i64($entry + 16)\n    $value\n}}\npanic!()",
+        has, key, get
+    )
+}
+
+fn storage_pattern(wasm: &wasm::Instance, key: &str) -> Pattern {
+    Pattern::parse("storage", &storage_source(wasm, key), "$value = storage.get($key)").unwrap()
+}
+
+#[test]
+fn test_parse_errors() {
+    let error = Pattern::parse("p", "$a = load_i64($b)", "$c").unwrap_err();
+    assert!(error.message.contains("`$c`"), "{}", error);
+
+    let error = Pattern::parse("p", "store_i64($a, 1)\nif $a {\n    _\n", "").unwrap_err();
+    assert_eq!(error.line, 3);
+
+    assert!(Pattern::parse("p", "$a = 1 # 2", "").is_err());
+    assert!(Pattern::parse("p", "..", "").is_err());
+    assert!(Pattern::parse("p", "$a..\n_\n$a..", "").is_err());
+    assert!(Pattern::parse("p", "load_i64($a, $b)", "").is_err());
+
+    let patterns = sdk_linker::parse_patterns(&std::fs::read_to_string("patterns.toml").unwrap()).unwrap();
+    assert!(!patterns.is_empty());
+}
+
+#[test]
+fn test_match_storage_read() {
+    let wasm = wasm::Instance::load_file(TOKEN);
+    let func = function(&wasm, "balance");
+    let matcher = Matcher::new(wasm.module(), wasm.module().func(func));
+    let (has, get) = (
+        function(&wasm, "has_contract_data"),
+        function(&wasm, "get_contract_data"),
+    );
+
+    let mut code = vec![Stmt::Nop];
+    code.extend(storage_read(has, get));
+    let (range, captures) = matcher.find(&storage_pattern(&wasm, ""), &code).unwrap();
+    assert_eq!(range, 1..4);
+    assert!(matches!(&captures["key"], Capture::Expr(expr) if *expr == var(0)));
+    assert!(matches!(&captures["value"], Capture::Expr(Expr::GetLocal(v)) if v.index == 6));
+
+    // Every use of a metavariable has to match the same value
+    let mut different_key = storage_read(has, get);
+    if let Stmt::If(_, body) = &mut different_key[1] {
+        body[0] = set(7, Expr::Call(get, vec![var(1), Expr::I64Const(VOID)]));
+    }
+    assert!(matcher.find(&storage_pattern(&wasm, ""), &different_key).is_none());
+
+    // The spec type of `balance(id: Address)` constrains the key
+    assert!(matcher.find(&storage_pattern(&wasm, ": Address"), &code).is_some());
+    assert!(matcher.find(&storage_pattern(&wasm, ": Symbol"), &code).is_none());
+}
+
+#[test]
+fn test_printed_forms() {
+    let wasm = wasm::Instance::load_file(TOKEN);
+    let func = function(&wasm, "balance");
+    let matcher = Matcher::new(wasm.module(), wasm.module().func(func));
+
+    let code = vec![
+        Stmt::I32Store(
+            Expr::I32Add(Box::new(var(3)), Box::new(Expr::I32Const(-8i32 as u32))),
+            Expr::I32Eqz(Box::new(var(0))),
+        ),
+        Stmt::Expr(Expr::I64Const(VOID)),
+    ];
+    let pattern = Pattern::parse("p", "store_i32(var_d - 8, id == 0)\nVoid", "").unwrap();
+    assert!(matcher.find(&pattern, &code).is_some());
+    let pattern = Pattern::parse("p", "store_i32(var_d + 8, _)", "").unwrap();
+    assert!(matcher.find(&pattern, &code).is_none());
+
+    // Negated conditions are matched like they are printed
+    let cond = Cond::Expr(MappedExpr::Expr(Box::new(Expr::I32Eq(
+        Box::new(var(0)),
+        Box::new(Expr::I32Const(1)),
+    ))));
+    let code = vec![Stmt::If(Cond::Not(Box::new(cond)), vec![Stmt::Break])];
+    let pattern = Pattern::parse("p", "if $x != 1 {\n    break\n}", "").unwrap();
+    assert!(matcher.find(&pattern, &code).is_some());
+}
+
+#[test]
+fn test_replace_and_print() {
+    let wasm = wasm::Instance::load_file(TOKEN);
+    let func = function(&wasm, "balance");
+    let (has, get) = (
+        function(&wasm, "has_contract_data"),
+        function(&wasm, "get_contract_data"),
+    );
+    let patterns = vec![storage_pattern(&wasm, "")];
+
+    // Matches are also replaced in nested blocks
+    let code = vec![Stmt::IfElse(
+        Cond::Expr(MappedExpr::Expr(Box::new(var(1)))),
+        storage_read(has, get),
+        vec![Stmt::Return(Expr::I64Const(VOID))],
+    )];
+    let matches = soroban::link_patterns(&patterns, code, wasm.module(), wasm.module().func(func));
+    assert_eq!(matches.matched, vec!["storage"]);
+    let body = match &matches.code[0] {
+        Stmt::IfElse(_, body, _) => body,
+        other => panic!("unexpected statement {:?}", other),
+    };
+    assert_eq!(body.len(), 1);
+    assert!(matches!(&body[0], Stmt::Replaced(replacement) if replacement.pattern == "storage"));
+
+    let mut writer = CodeWriter::formatter(Rc::clone(&wasm), func);
+    writer.write(&matches.code[..]);
+    let output = writer.get_output();
+    assert!(output.contains("var_g = storage.get(id)"), "{}", output);
+    assert!(!output.contains("panic!"), "{}", output);
+}
+
+#[test]
+fn test_skipped_side_effects() {
+    let wasm = wasm::Instance::load_file(TOKEN);
+    let func = function(&wasm, "balance");
+    let matcher = Matcher::new(wasm.module(), wasm.module().func(func));
+    let (has, get) = (
+        function(&wasm, "has_contract_data"),
+        function(&wasm, "get_contract_data"),
+    );
+    let with_call = |callee: u32| {
+        let mut code = storage_read(has, get);
+        if let Stmt::If(_, body) = &mut code[1] {
+            body.insert(
+                2,
+                Stmt::Expr(Expr::Call(callee, vec![var(0), var(1), Expr::I64Const(1)])),
+            );
+        }
+        code
+    };
+
+    // `..` does not drop a storage write, neither a direct one nor one in a called function
+    let put = with_call(function(&wasm, "put_contract_data"));
+    assert!(matcher.find(&storage_pattern(&wasm, ""), &put).is_none());
+    let set_admin = with_call(function(&wasm, "set_admin"));
+    assert!(matcher.find(&storage_pattern(&wasm, ""), &set_admin).is_none());
+
+    // Skipped statements can be kept by printing their capture
+    let source = storage_source(&wasm, "").replace("    ..", "    $skipped..");
+    let printed = Pattern::parse("storage", &source, "$value = storage.get($key)\n$skipped").unwrap();
+    let (_, captures) = matcher.find(&printed, &put).unwrap();
+    assert!(matches!(&captures["skipped"], Capture::Stmts(code) if code.len() == 1));
+    let unprinted = Pattern::parse("storage", &source, "$value = storage.get($key)").unwrap();
+    assert!(matcher.find(&unprinted, &put).is_none());
+}
+
+#[test]
+fn test_auth_pattern() {
+    let wasm = wasm::Instance::load_file(TOKEN);
+    let func = function(&wasm, "transfer_from");
+    let patterns = sdk_linker::parse_patterns(&std::fs::read_to_string("patterns.toml").unwrap()).unwrap();
+    let put = function(&wasm, "put_contract_data");
+
+    // `transfer_from(spender: Address, from: Address, ..)` checks the tags of both addresses
+    let not_address = |index: u32| {
+        Expr::I64Ne(
+            Box::new(Expr::I64And(Box::new(var(index)), Box::new(Expr::I64Const(255)))),
+            Box::new(Expr::I64Const(77)),
+        )
+    };
+    let checks = Expr::I32Or(Box::new(not_address(0)), Box::new(not_address(1)));
+    let write = Stmt::Expr(Expr::Call(put, vec![var(1), var(2), Expr::I64Const(1)]));
+    let code = vec![
+        Stmt::If(
+            Cond::Not(Box::new(Cond::Expr(MappedExpr::Expr(Box::new(checks))))),
+            vec![write],
+        ),
+        Stmt::Unreachable,
+    ];
+    let matches = soroban::link_patterns(&patterns, code, wasm.module(), wasm.module().func(func));
+    assert_eq!(matches.matched, vec!["auth"]);
+
+    // The checked body is printed, side effects included
+    let mut writer = CodeWriter::formatter(Rc::clone(&wasm), func);
+    writer.write(&matches.code[..]);
+    let output = writer.get_output();
+    assert!(output.contains("Address::try_from_val(&env, &from)"), "{}", output);
+    assert!(output.contains("put_contract_data"), "{}", output);
+}
+
+#[test]
+fn test_decompiled_token_patterns() {
+    let wasm = wasm::Instance::load_file(TOKEN);
+    let matched = |name: &str| {
+        let func = function(&wasm, name);
+        let decompiled = wasm.decompile_function_output(func, WriterOptions::default()).unwrap();
+        assert!(
+            !decompiled.warnings.iter().any(|warning| warning.contains("patterns")),
+            "{:?}",
+            decompiled.warnings
+        );
+        decompiled.matched_patterns
+    };
+
+    assert_eq!(matched("decimals"), vec!["decimals"]);
+    assert_eq!(matched("symbol"), vec!["symbol"]);
+    let balance = matched("balance");
+    assert!(
+        !balance
+            .iter()
+            .any(|pattern| pattern == "decimals" || pattern == "symbol"),
+        "{:?}",
+        balance
+    );
+}
@@ -0,0 +1,195 @@
+use auditor::run::val::{self, Objects};
+use auditor::run::{AuthPolicy, Contract, Host, HostConfig, HostErrorKind, HostFn, MockHost};
+use soroban_sdk::xdr::{Hash, Int128Parts, ScAddress, ScError, ScString, ScSymbol, ScVal};
+
+fn host_fn(name: &str) -> HostFn {
+    HostFn {
+        module: String::from("test"),
+        name: name.to_string(),
+        args: Vec::new(),
+        result: String::from("Val"),
+    }
+}
+
+fn call(host: &mut MockHost, name: &str, args: &[i64]) -> i64 {
+    host.call(&host_fn(name), &mut [], args, None).unwrap()
+}
+
+fn symbol(name: &str) -> ScVal {
+    ScVal::Symbol(ScSymbol(name.try_into().unwrap()))
+}
+
+#[test]
+fn test_small_vals() {
+    let mut objects = Objects::default();
+    let cases = [
+        (ScVal::Bool(true), u64::from(val::TRUE)),
+        (ScVal::Void, val::VOID_VAL),
+        (ScVal::U32(7), 7 << 32 | u64::from(val::U32)),
+        (ScVal::I32(-1), 0xffff_ffff << 32 | u64::from(val::I32)),
+        (ScVal::U64(5), 5 << 8 | u64::from(val::U64_SMALL)),
+        (ScVal::I64(-2), (-2i64 << 8) as u64 | u64::from(val::I64_SMALL)),
+        (
+            ScVal::I128(Int128Parts { hi: -1, lo: u64::MAX }),
+            (-1i64 << 8) as u64 | u64::from(val::I128_SMALL),
+        ),
+        // `a` is 38, `b` is 39, 6 bits each
+        (symbol("ab"), (38 << 6 | 39) << 8 | u64::from(val::SYMBOL_SMALL)),
+    ];
+    for (value, expected) in cases {
+        let encoded = objects.to_val(&value).unwrap();
+        assert_eq!(encoded, expected, "{:?}", value);
+        assert_eq!(objects.from_val(encoded).unwrap(), value);
+    }
+    assert_eq!(val::u32_from_val(val::u32_val(9)), Ok(9));
+    assert!(val::u32_from_val(val::VOID_VAL).is_err());
+    // the 6 bit code 0 is no character
+    let invalid_symbol = (38 << 6) << 8 | u64::from(val::SYMBOL_SMALL);
+    assert!(objects.from_val(invalid_symbol).is_err());
+}
+
+#[test]
+fn test_object_vals() {
+    let mut objects = Objects::default();
+    let large = ScVal::U64(1 << 60);
+    let string = ScVal::String(ScString("hello".try_into().unwrap()));
+    let long_symbol = symbol("a_long_symbol");
+
+    let large_val = objects.to_val(&large).unwrap();
+    let string_val = objects.to_val(&string).unwrap();
+    let symbol_val = objects.to_val(&long_symbol).unwrap();
+    assert_eq!(large_val as u8, val::U64_OBJECT);
+    assert_eq!(string_val as u8, val::STRING_OBJECT);
+    assert_eq!(symbol_val as u8, val::SYMBOL_OBJECT);
+    // handles are numbered in the upper 32 bits
+    assert_eq!(large_val >> 32, 0);
+    assert_eq!(string_val >> 32, 1);
+
+    assert_eq!(objects.from_val(large_val).unwrap(), large);
+    assert_eq!(objects.from_val(string_val).unwrap(), string);
+    assert_eq!(objects.from_val(symbol_val).unwrap(), long_symbol);
+    // a handle with the tag of another object type or beyond the objects is invalid
+    assert!(objects.get(string_val & !0xff | u64::from(val::BYTES_OBJECT)).is_err());
+    assert!(objects.get(7 << 32 | u64::from(val::STRING_OBJECT)).is_err());
+}
+
+#[test]
+fn test_map_host_functions() {
+    let mut host = MockHost::new(HostConfig::default());
+    let key = host.objects.to_val(&symbol("key")).unwrap() as i64;
+    let other = host.objects.to_val(&symbol("other")).unwrap() as i64;
+    let one = val::u32_val(1) as i64;
+    let two = val::u32_val(2) as i64;
+
+    let map = call(&mut host, "map_new", &[]);
+    let map = call(&mut host, "map_put", &[map, key, one]);
+    let map = call(&mut host, "map_put", &[map, key, two]);
+    assert_eq!(call(&mut host, "map_len", &[map]), val::u32_val(1) as i64);
+    assert_eq!(call(&mut host, "map_get", &[map, key]), two);
+    assert_eq!(call(&mut host, "map_has", &[map, key]), val::bool_val(true) as i64);
+    assert_eq!(call(&mut host, "map_has", &[map, other]), val::bool_val(false) as i64);
+    assert!(host.call(&host_fn("map_get"), &mut [], &[map, other], None).is_err());
+
+    let map = call(&mut host, "map_del", &[map, key]);
+    assert_eq!(call(&mut host, "map_len", &[map]), val::u32_val(0) as i64);
+    assert!(host.call(&host_fn("map_del"), &mut [], &[map, key], None).is_err());
+    // every call is traced
+    assert_eq!(host.trace.len(), 11);
+}
+
+#[test]
+fn test_vec_host_functions() {
+    let mut host = MockHost::new(HostConfig::default());
+    let item = |i: u32| val::u32_val(i) as i64;
+
+    let vec = call(&mut host, "vec_new", &[]);
+    let vec = call(&mut host, "vec_push_back", &[vec, item(1)]);
+    let vec = call(&mut host, "vec_push_back", &[vec, item(2)]);
+    let vec = call(&mut host, "vec_push_front", &[vec, item(0)]);
+    assert_eq!(call(&mut host, "vec_len", &[vec]), item(3));
+    assert_eq!(call(&mut host, "vec_get", &[vec, item(0)]), item(0));
+    assert_eq!(call(&mut host, "vec_back", &[vec]), item(2));
+
+    let vec = call(&mut host, "vec_put", &[vec, item(1), item(5)]);
+    assert_eq!(call(&mut host, "vec_get", &[vec, item(1)]), item(5));
+    let vec = call(&mut host, "vec_del", &[vec, item(0)]);
+    assert_eq!(call(&mut host, "vec_front", &[vec]), item(5));
+    assert!(host.call(&host_fn("vec_get"), &mut [], &[vec, item(2)], None).is_err());
+
+    let empty = call(&mut host, "vec_new", &[]);
+    assert!(host.call(&host_fn("vec_pop_back"), &mut [], &[empty], None).is_err());
+    // a value that is not a vector
+    assert!(host.call(&host_fn("vec_len"), &mut [], &[item(1)], None).is_err());
+}
+
+#[test]
+fn test_logs_are_recorded() {
+    let mut host = MockHost::new(HostConfig::default());
+    let mut memory = b"..hello".to_vec();
+    let (pos, len) = (val::u32_val(2) as i64, val::u32_val(5) as i64);
+    let empty = val::u32_val(0) as i64;
+    let result = host.call(
+        &host_fn("log_from_linear_memory"),
+        &mut memory,
+        &[pos, len, empty, empty],
+        None,
+    );
+    assert_eq!(result.unwrap(), val::VOID_VAL as i64);
+    assert_eq!(host.logs, vec![String::from("hello")]);
+    assert!(host.events.is_empty());
+}
+
+#[test]
+fn test_max_live_until_ledger() {
+    let mut host = MockHost::new(HostConfig {
+        ledger_sequence: u32::MAX,
+        ..HostConfig::default()
+    });
+    let max = call(&mut host, "get_max_live_until_ledger", &[]);
+    assert_eq!(max, val::u32_val(u32::MAX) as i64);
+}
+
+#[test]
+fn test_host_error_kinds() {
+    let mut host = MockHost::new(HostConfig::default());
+    let error = host.objects.to_val(&ScVal::Error(ScError::Contract(3))).unwrap() as i64;
+    let kind = |host: &mut MockHost, name: &str, args: &[i64]| {
+        host.call(&host_fn(name), &mut [], args, None).unwrap_err().kind()
+    };
+
+    assert_eq!(
+        kind(&mut host, "fail_with_error", &[error]),
+        HostErrorKind::ContractPanic
+    );
+    assert_eq!(kind(&mut host, "no_such_function", &[]), HostErrorKind::Unsupported);
+    let map = call(&mut host, "map_new", &[]);
+    assert_eq!(
+        kind(&mut host, "map_get", &[map, val::u32_val(1) as i64]),
+        HostErrorKind::HostTrap
+    );
+}
+
+#[test]
+fn test_contract_runs_repeatedly() {
+    let code = std::fs::read("tests/soroban_token_contract.wasm").unwrap();
+    let contract: Contract = Contract::new(&code).unwrap();
+    let address = ScVal::Address(ScAddress::Contract(Hash([1; 32])));
+    let amount = ScVal::I128(Int128Parts { hi: 0, lo: 1 });
+    let nobody = || HostConfig {
+        auth: AuthPolicy::Only(Vec::new()),
+        ..HostConfig::default()
+    };
+
+    let balance = contract
+        .run("balance", &[address.clone()], HostConfig::default())
+        .unwrap();
+    assert_eq!(balance.result, Some(ScVal::I128(Int128Parts { hi: 0, lo: 0 })));
+    // the host rejects the missing authorization
+    let burn = contract.run("burn", &[address.clone(), amount], nobody()).unwrap();
+    assert!(burn.error.is_some());
+    assert_eq!(burn.host_error, Some(HostErrorKind::HostTrap));
+    // the contract itself traps without an admin
+    let set_admin = contract.run("set_admin", &[address], nobody()).unwrap();
+    assert!(set_admin.error.is_some());
+    assert_eq!(set_admin.host_error, None);
+}
@@ -0,0 +1,52 @@
+use auditor::select::{self, FuncSelector, SelectError};
+use auditor::wasm_wrapper::wasm;
+
+const TOKEN: &str = "tests/soroban_token_contract.wasm";
+
+fn matches(wasm: &wasm::Instance, selector: &str) -> Result<Vec<u32>, SelectError> {
+    FuncSelector::new(selector).matches(wasm.module())
+}
+
+fn index(wasm: &wasm::Instance, name: &str) -> u32 {
+    let module = wasm.module();
+    (0..module.functions().len() as u32)
+        .find(|i| module.func(*i).name() == name)
+        .unwrap()
+}
+
+#[test]
+fn test_selectors() {
+    let wasm = wasm::Instance::load_file(TOKEN);
+    let (transfer, transfer_from) = (index(&wasm, "transfer"), index(&wasm, "transfer_from"));
+
+    // Names and indices select one function, regexes every function they match
+    assert_eq!(matches(&wasm, "transfer").unwrap(), vec![transfer]);
+    assert_eq!(matches(&wasm, &transfer_from.to_string()).unwrap(), vec![transfer_from]);
+    assert_eq!(matches(&wasm, "transfer.*").unwrap(), vec![transfer, transfer_from]);
+
+    // Regexes have to match the whole name
+    assert!(matches!(matches(&wasm, "ransfer"), Err(SelectError::NoMatch(_))));
+    assert!(matches!(matches(&wasm, "100000"), Err(SelectError::NoMatch(_))));
+    assert!(matches!(matches(&wasm, "("), Err(SelectError::InvalidRegex(..))));
+}
+
+#[test]
+fn test_select_functions() {
+    let wasm = wasm::Instance::load_file(TOKEN);
+    let (balance, transfer, transfer_from) = (
+        index(&wasm, "balance"),
+        index(&wasm, "transfer"),
+        index(&wasm, "transfer_from"),
+    );
+    let selectors: Vec<FuncSelector> = ["transfer_from", "balance", "transfer.*", &balance.to_string()]
+        .iter()
+        .map(|selector| FuncSelector::new(selector))
+        .collect();
+    assert_eq!(
+        select::select_functions(wasm.module(), &selectors).unwrap(),
+        vec![balance, transfer, transfer_from]
+    );
+
+    let selectors = vec![FuncSelector::new("balance"), FuncSelector::new("no_such_function")];
+    assert!(select::select_functions(wasm.module(), &selectors).is_err());
+}
@@ -0,0 +1,89 @@
+use auditor::source_map::{parse_offset, SourceMap};
+use auditor::wasm_wrapper::wasm;
+use auditor::wasm_wrapper::wasm_adapter::Instruction;
+
+const TOKEN: &str = "tests/soroban_token_contract.wasm";
+const TOKEN_OPTIMIZED: &str = "tests/soroban_token_contract.optimized.wasm";
+
+fn read_var_u32(bytes: &[u8], pos: &mut usize) -> u32 {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = bytes[*pos];
+        *pos += 1;
+        value |= ((byte & 0x7f) as u32) << shift;
+        if byte & 0x80 == 0 {
+            return value;
+        }
+        shift += 7;
+    }
+}
+
+/// The code section content of a wasm file
+fn code_section(bytes: &[u8]) -> &[u8] {
+    let mut pos = 8;
+    loop {
+        let id = bytes[pos];
+        pos += 1;
+        let size = read_var_u32(bytes, &mut pos) as usize;
+        if id == 10 {
+            return &bytes[pos..pos + size];
+        }
+        pos += size;
+    }
+}
+
+#[test]
+fn test_instr_offsets() {
+    for path in [TOKEN, TOKEN_OPTIMIZED] {
+        let bytes = std::fs::read(path).unwrap();
+        let code = code_section(&bytes);
+        let wasm = wasm::Instance::load_file(path);
+        let module = wasm.module();
+
+        for (index, func) in module.functions().iter().enumerate().filter(|(_, f)| !f.is_imported()) {
+            let offsets = func.instr_offsets();
+            assert_eq!(offsets.len(), func.instructions().len(), "{} {}", path, func.name());
+            for (instr, (offset, instruction)) in offsets.iter().zip(func.instructions()).enumerate() {
+                let opcode = code[*offset as usize];
+                match instruction {
+                    Instruction::End => assert_eq!(opcode, 0x0b),
+                    Instruction::Call(_) => assert_eq!(opcode, 0x10),
+                    Instruction::I64Const(_) => assert_eq!(opcode, 0x42),
+                    _ => (),
+                }
+                assert_eq!(module.find_instr(*offset), Some((index as u32, instr as u32)));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_source_map_lines() {
+    let wasm = wasm::Instance::load_file(TOKEN);
+    let module = wasm.module();
+    let (index, func) = module
+        .functions()
+        .iter()
+        .enumerate()
+        .find(|(_, f)| !f.is_imported() && f.instructions().len() > 2)
+        .unwrap();
+    let (first, second) = (func.instr_offset(0).unwrap(), func.instr_offset(1).unwrap());
+    assert_eq!(parse_offset(&format!("    // @0x{:x}", first)), Some(first));
+    assert_eq!(parse_offset("    // observed: 1"), None);
+
+    let output = format!(
+        "fn f() {{\n    // @0x{:x}\n    let a = 1;\n    // @0x{:x}\n    // observed: 1\n    g(a);\n}}\n",
+        first, second
+    );
+    let mut source_map = SourceMap::new("test");
+    source_map.add_output(module, &output, 10);
+
+    assert_eq!(source_map.mappings.len(), 2);
+    assert_eq!(source_map.mappings[0].line, 13);
+    assert_eq!(source_map.mappings[0].func_index, index as u32);
+    assert_eq!(source_map.mappings[0].instr, 0);
+    // The trace observation between the annotation and the statement is skipped
+    assert_eq!(source_map.mappings[1].line, 16);
+    assert_eq!(source_map.mappings[1].instr, 1);
+}
@@ -0,0 +1,21 @@
+use auditor::soroban;
+use auditor::wasm_wrapper::wasm;
+
+#[test]
+fn test_read_specs() {
+    let specs = soroban::read_contract_specs("tests/soroban_token_contract.wasm").unwrap();
+    assert!(specs.iter().any(|spec| spec.name() == "balance"));
+    assert!(soroban::read_contract_types("tests/soroban_token_contract.wasm").is_ok());
+}
+
+#[test]
+fn test_missing_spec_section() {
+    // An empty module has no contractspecv0 section
+    let path = std::env::temp_dir().join(format!("auditor-nospec-{}.wasm", std::process::id()));
+    std::fs::write(&path, b"\0asm\x01\0\0\0").unwrap();
+
+    assert!(soroban::read_contract_types(&path).is_err());
+    assert!(soroban::read_contract_specs(&path).is_err());
+    assert!(wasm::Instance::from_file(&path).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
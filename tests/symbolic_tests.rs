@@ -0,0 +1,81 @@
+use std::rc::Rc;
+
+use auditor::symbolic::{self, Limits};
+use auditor::wasm_wrapper::wasm;
+use parity_wasm::elements::{
+    BlockType, Func, FuncBody, FunctionType, ImportCountType, Instruction, Instructions, Module, Type, ValueType,
+};
+
+const TOKEN: &str = "tests/soroban_token_contract.wasm";
+const GET_CONTRACT_DATA: u32 = 1;
+const OBJ_TO_I128_LO64: u32 = 7;
+/// The low byte of a `Val` is its tag, 15 is not a valid one
+const INVALID_TAG: i64 = 15;
+
+/// The token contract with a function `fn(a: i64)` added, returns it with its index
+fn with_function(name: &str, code: Vec<Instruction>) -> (Rc<wasm::Instance>, u32) {
+    let mut module: Module = parity_wasm::deserialize_file(TOKEN).unwrap();
+    let types = module.type_section_mut().unwrap().types_mut();
+    types.push(Type::Function(FunctionType::new(vec![ValueType::I64], None)));
+    let type_ref = types.len() as u32 - 1;
+    let func_index = module.import_count(ImportCountType::Function) as u32
+        + module.function_section().unwrap().entries().len() as u32;
+    module
+        .function_section_mut()
+        .unwrap()
+        .entries_mut()
+        .push(Func::new(type_ref));
+    let mut code = code;
+    code.push(Instruction::End);
+    let body = FuncBody::new(Vec::new(), Instructions::new(code));
+    module.code_section_mut().unwrap().bodies_mut().push(body);
+
+    let path = std::env::temp_dir().join(format!("auditor-symbolic-{}-{}.wasm", name, std::process::id()));
+    parity_wasm::serialize_to_file(&path, module).unwrap();
+    let instance = wasm::Instance::load_file(&path);
+    std::fs::remove_file(&path).unwrap();
+    (instance, func_index)
+}
+
+/// Traps when the result of the call has the low byte `INVALID_TAG`
+fn trap_on_tag(call: Vec<Instruction>) -> Vec<Instruction> {
+    let mut code = call;
+    code.extend([
+        Instruction::I64Const(0xff),
+        Instruction::I64And,
+        Instruction::I64Const(INVALID_TAG),
+        Instruction::I64Eq,
+        Instruction::If(BlockType::NoResult),
+        Instruction::Unreachable,
+        Instruction::End,
+    ]);
+    code
+}
+
+#[test]
+fn test_raw_results() {
+    let wasm = wasm::Instance::load_file(TOKEN);
+    let raw = symbolic::raw_results(wasm.module());
+    assert!(raw.contains(&OBJ_TO_I128_LO64), "{:?}", raw);
+    assert!(!raw.contains(&GET_CONTRACT_DATA), "{:?}", raw);
+}
+
+/// Needs Z3 to explore the paths
+#[test]
+fn test_raw_results_are_not_vals() {
+    // `obj_to_i128_lo64` returns a plain `u64` that can have any low byte
+    let call = vec![Instruction::GetLocal(0), Instruction::Call(OBJ_TO_I128_LO64)];
+    let (wasm, func_index) = with_function("lo64", trap_on_tag(call));
+    let exploration = symbolic::find_unreachable(wasm, func_index, Limits::default()).unwrap();
+    assert_eq!(exploration.unreachable.len(), 1, "{:?}", exploration);
+
+    // `get_contract_data` returns a `Val`, which always has a valid tag
+    let call = vec![
+        Instruction::GetLocal(0),
+        Instruction::I64Const(2),
+        Instruction::Call(GET_CONTRACT_DATA),
+    ];
+    let (wasm, func_index) = with_function("get", trap_on_tag(call));
+    let exploration = symbolic::find_unreachable(wasm, func_index, Limits::default()).unwrap();
+    assert!(exploration.unreachable.is_empty(), "{:?}", exploration);
+}
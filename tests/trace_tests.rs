@@ -0,0 +1,77 @@
+use auditor::run::trace::{self, Trace};
+use auditor::run::{self, HostConfig};
+use auditor::wasm_wrapper::wasm;
+use soroban_sdk::xdr::{Hash, ScAddress, ScString, ScVal};
+
+const TOKEN: &str = "tests/soroban_token_contract.wasm";
+
+fn record(function: &str, args: &[ScVal]) -> Trace {
+    let code = std::fs::read(TOKEN).unwrap();
+    let code = trace::instrument(&wasm::Instance::load_file(TOKEN), &code).unwrap();
+    let result = run::run(&code, function, args, HostConfig::default()).unwrap();
+    Trace::new(function, args, &result)
+}
+
+fn initialize_args() -> Vec<ScVal> {
+    vec![
+        ScVal::Address(ScAddress::Contract(Hash([1; 32]))),
+        ScVal::U32(7),
+        ScVal::String(ScString("Token".try_into().unwrap())),
+        ScVal::String(ScString("TOK".try_into().unwrap())),
+    ]
+}
+
+#[test]
+fn test_replay_matches_recording() {
+    let trace = record("initialize", &initialize_args());
+    assert!(trace.error.is_none());
+    assert!(!trace.calls.is_empty());
+    assert!(trace.calls.iter().all(|call| call.site.is_some()));
+
+    // The trace survives a round trip through JSON and replays on the uninstrumented binary
+    let trace: Trace = serde_json::from_str(&serde_json::to_string(&trace).unwrap()).unwrap();
+    let code = std::fs::read(TOKEN).unwrap();
+    let result = trace::replay(&code, &trace).unwrap();
+    assert!(result.is_faithful(), "{:?}", result);
+    assert_eq!(result.calls, trace.calls.len());
+
+    // Traces recorded before logs were captured still load
+    let mut json = serde_json::to_value(&trace).unwrap();
+    json.as_object_mut().unwrap().remove("logs");
+    let trace: Trace = serde_json::from_value(json).unwrap();
+    assert!(trace.logs.is_empty());
+}
+
+#[test]
+fn test_replay_detects_divergence() {
+    let mut trace = record("initialize", &initialize_args());
+    let code = std::fs::read(TOKEN).unwrap();
+
+    // The contract issues a call the trace does not have
+    let last = trace.calls.pop().unwrap();
+    let result = trace::replay(&code, &trace).unwrap();
+    let mismatch = result.mismatch.unwrap();
+    assert_eq!(mismatch.index, trace.calls.len());
+    assert!(mismatch.expected.is_none());
+    assert_eq!(mismatch.actual.unwrap().function, last.function);
+
+    // Different arguments lead to different host calls
+    trace.calls.push(last);
+    trace.args[1] = ScVal::U32(8);
+    let result = trace::replay(&code, &trace).unwrap();
+    assert!(!result.is_faithful());
+}
+
+#[test]
+fn test_annotate_call_sites() {
+    let trace = record("initialize", &initialize_args());
+    let call = &trace.calls[0];
+    let code = format!("    // @0x{:x}\n    let x = f();\n", call.site.unwrap());
+    let annotated = trace::annotate(&code, &trace);
+    let lines: Vec<&str> = annotated.lines().collect();
+    // Helpers inlined at several places share their call sites
+    let observed = trace.calls.iter().filter(|other| other.site == call.site).count();
+    assert_eq!(lines.len(), 2 + observed.min(3) + usize::from(observed > 3));
+    assert!(lines[1].starts_with(&format!("    // observed: {}(", call.function)));
+    assert_eq!(lines[lines.len() - 1], "    let x = f();");
+}